@@ -78,6 +78,7 @@ pub async fn backfill_operations(db: &PrismaClient, sync: &crate::Manager, insta
 										option_sync_entry!(o.note, note),
 										option_sync_entry!(o.date_created, date_created),
 										option_sync_entry!(o.date_accessed, date_accessed),
+										option_sync_entry!(o.size_in_bytes, size_in_bytes),
 									],
 								),
 							)
@@ -173,6 +174,10 @@ pub async fn backfill_operations(db: &PrismaClient, sync: &crate::Manager, insta
 											fp.size_in_bytes_bytes,
 											size_in_bytes_bytes
 										),
+										option_sync_entry!(
+											fp.size_in_bytes_number,
+											size_in_bytes_number
+										),
 										option_sync_entry!(fp.inode, inode),
 										option_sync_entry!(fp.date_created, date_created),
 										option_sync_entry!(fp.date_modified, date_modified),
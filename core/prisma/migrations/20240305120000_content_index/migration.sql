@@ -0,0 +1,12 @@
+-- CreateVirtualTable
+-- Prisma can't model FTS5 tables, so this one is only ever accessed through raw queries
+CREATE VIRTUAL TABLE "content_index" USING fts5(
+    "object_id" UNINDEXED,
+    "content",
+    tokenize = 'porter unicode61 remove_diacritics 2'
+);
+
+-- CreateTrigger
+CREATE TRIGGER "content_index_object_delete" AFTER DELETE ON "object" BEGIN
+    DELETE FROM "content_index" WHERE "object_id" = OLD."id";
+END;
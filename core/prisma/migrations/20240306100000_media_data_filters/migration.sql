@@ -0,0 +1,16 @@
+-- AlterTable
+ALTER TABLE "media_data" ADD COLUMN "resolution_width" INTEGER;
+ALTER TABLE "media_data" ADD COLUMN "resolution_height" INTEGER;
+ALTER TABLE "media_data" ADD COLUMN "camera_make" TEXT;
+ALTER TABLE "media_data" ADD COLUMN "camera_model" TEXT;
+
+-- Backfill from the serialized metadata we already have
+UPDATE "media_data" SET
+    "resolution_width" = json_extract(CAST("resolution" AS TEXT), '$.width'),
+    "resolution_height" = json_extract(CAST("resolution" AS TEXT), '$.height')
+WHERE "resolution" IS NOT NULL AND json_valid(CAST("resolution" AS TEXT));
+
+UPDATE "media_data" SET
+    "camera_make" = json_extract(CAST("camera_data" AS TEXT), '$.device_make'),
+    "camera_model" = json_extract(CAST("camera_data" AS TEXT), '$.device_model')
+WHERE "camera_data" IS NOT NULL AND json_valid(CAST("camera_data" AS TEXT));
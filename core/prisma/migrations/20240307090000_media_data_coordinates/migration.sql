@@ -0,0 +1,12 @@
+-- AlterTable
+ALTER TABLE "media_data" ADD COLUMN "latitude" REAL;
+ALTER TABLE "media_data" ADD COLUMN "longitude" REAL;
+
+-- Backfill from the serialized locations we already have
+UPDATE "media_data" SET
+    "latitude" = json_extract(CAST("media_location" AS TEXT), '$.latitude'),
+    "longitude" = json_extract(CAST("media_location" AS TEXT), '$.longitude')
+WHERE "media_location" IS NOT NULL AND json_valid(CAST("media_location" AS TEXT));
+
+-- CreateIndex
+CREATE INDEX "media_data_latitude_longitude_idx" ON "media_data"("latitude", "longitude");
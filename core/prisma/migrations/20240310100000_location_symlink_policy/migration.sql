@@ -0,0 +1,2 @@
+-- AlterTable
+ALTER TABLE "location" ADD COLUMN "symlink_policy" INTEGER;
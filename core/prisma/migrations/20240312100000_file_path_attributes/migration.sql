@@ -0,0 +1,8 @@
+-- AlterTable
+ALTER TABLE "file_path" ADD COLUMN "mode" INTEGER;
+ALTER TABLE "file_path" ADD COLUMN "uid" INTEGER;
+ALTER TABLE "file_path" ADD COLUMN "gid" INTEGER;
+ALTER TABLE "file_path" ADD COLUMN "xattrs" TEXT;
+
+-- AlterTable
+ALTER TABLE "location" ADD COLUMN "xattr_allowlist" TEXT;
@@ -0,0 +1,10 @@
+-- DropIndex
+-- Hard links to the same file in a location share its inode, so it can't be unique
+DROP INDEX "file_path_location_id_inode_key";
+
+-- AlterTable
+ALTER TABLE "file_path" ADD COLUMN "device" BLOB;
+ALTER TABLE "file_path" ADD COLUMN "hard_link_of_id" INTEGER;
+
+-- CreateIndex
+CREATE INDEX "file_path_device_inode_idx" ON "file_path"("device", "inode");
@@ -0,0 +1,3 @@
+-- AlterTable
+ALTER TABLE "location" ADD COLUMN "watcher_mode" INTEGER;
+ALTER TABLE "location" ADD COLUMN "watcher_poll_interval" INTEGER;
@@ -0,0 +1,11 @@
+-- CreateTable
+CREATE TABLE "watcher_event" (
+    "id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    "location_id" INTEGER NOT NULL,
+    "paths" BLOB NOT NULL,
+    "date_created" DATETIME NOT NULL,
+    CONSTRAINT "watcher_event_location_id_fkey" FOREIGN KEY ("location_id") REFERENCES "location" ("id") ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- CreateIndex
+CREATE INDEX "watcher_event_location_id_idx" ON "watcher_event"("location_id");
@@ -0,0 +1,12 @@
+-- DropIndex
+DROP INDEX "volume_mount_point_name_key";
+
+-- AlterTable
+ALTER TABLE "volume" ADD COLUMN "identifier" TEXT;
+ALTER TABLE "volume" ADD COLUMN "is_mounted" BOOLEAN NOT NULL DEFAULT false;
+
+-- AlterTable
+ALTER TABLE "location" ADD COLUMN "volume_id" INTEGER REFERENCES "volume" ("id") ON DELETE SET NULL ON UPDATE CASCADE;
+
+-- CreateIndex
+CREATE UNIQUE INDEX "volume_identifier_key" ON "volume"("identifier");
@@ -0,0 +1,3 @@
+-- CreateIndex
+-- Finds the links left pointing to removed file paths without going through every file path
+CREATE INDEX "file_path_hard_link_of_id_idx" ON "file_path"("hard_link_of_id");
@@ -3,7 +3,7 @@ use crate::{
 	invalidate_query,
 	job::Job,
 	library::Library,
-	location::{get_location_path_from_location_id, LocationError},
+	location::{get_location_path_from_location_id, hard_links, LocationError},
 	object::{
 		fs::{
 			copy::FileCopierJobInit, cut::FileCutterJobInit, delete::FileDeleterJobInit,
@@ -173,6 +173,16 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 						.map(|str| str.to_string()))
 				})
 		})
+		.procedure("getHardLinks", {
+			R.with2(library())
+				.query(|(_, library), id: file_path::id::Type| async move {
+					let links = hard_links::find_hard_links(&library.db, id)
+						.await?
+						.ok_or(LocationError::FilePath(FilePathError::IdNotFound(id)))?;
+
+					Ok(links)
+				})
+		})
 		.procedure("setNote", {
 			#[derive(Type, Deserialize)]
 			pub struct SetNoteArgs {
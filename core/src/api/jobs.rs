@@ -303,6 +303,7 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 					Job::new(ObjectValidatorJobInit {
 						location,
 						sub_path: Some(args.path),
+						file_path_ids: None,
 					})
 					.spawn(&node, &library)
 					.await
@@ -254,7 +254,7 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 								return Ok(());
 							};
 
-							scan_location(&node, &library, location)
+							scan_location(&node, &library, location, false)
 								.await
 								.map_err(rspc::Error::from)
 						}))
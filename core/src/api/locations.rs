@@ -1,28 +1,46 @@
 use crate::{
 	invalidate_query,
-	job::StatefulJob,
+	job::{Job, StatefulJob},
+	library::Library,
 	location::{
 		delete_location, find_location,
-		indexer::{rules::IndexerRuleCreateArgs, IndexerJobInit},
+		indexer::{
+			explain::{IndexerRulesExplainArgs, RuleDecision},
+			rules::{
+				rule_set::{IndexerRuleSet, IndexerRuleSetImportArgs},
+				IndexerRule, IndexerRuleCreateArgs, IndexerRuleUpdateArgs, RulePerKind,
+			},
+			IndexerJobInit,
+		},
 		light_scan_location, location_with_indexer_rules,
 		non_indexed::NonIndexedPathItem,
-		relink_location, scan_location, scan_location_sub_path, LocationCreateArgs, LocationError,
+		relink_location,
+		relinker::LocationRelinkerJobInit,
+		scan_location, scan_location_sub_path, LocationCreateArgs, LocationError,
 		LocationUpdateArgs,
 	},
 	object::file_identifier::file_identifier_job::FileIdentifierJobInit,
 	p2p::PeerMetadata,
-	util::AbortOnDrop,
+	util::{unsafe_streamed_query, AbortOnDrop, BatchedStream},
+	Node,
 };
 
 use sd_cache::{CacheNode, Model, Normalise, NormalisedResult, NormalisedResults, Reference};
 use sd_prisma::prisma::{
-	file_path, indexer_rule, indexer_rules_in_location, location, object, SortOrder,
+	file_path, indexer_rule, indexer_rules_in_location, location, object, watcher_event, SortOrder,
 };
+use sd_utils::from_bytes_to_uuid;
 
-use std::path::{Path, PathBuf};
+use std::{
+	collections::HashSet,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
 
+use async_stream::stream;
 use chrono::{DateTime, FixedOffset, Utc};
 use directories::UserDirs;
+use futures::StreamExt;
 use rspc::{self, alpha::AlphaRouter, ErrorCode};
 use serde::{Deserialize, Serialize};
 use specta::Type;
@@ -243,8 +261,13 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 				pub generate_preview_media: Option<bool>,
 				pub sync_preview_media: Option<bool>,
 				pub hidden: Option<bool>,
+				pub symlink_policy: Option<i32>,
+				pub xattr_allowlist: Option<String>,
+				pub watcher_mode: Option<i32>,
+				pub watcher_poll_interval: Option<i32>,
 				pub date_created: Option<DateTime<FixedOffset>>,
 				pub instance_id: Option<i32>,
+				pub volume_id: Option<i32>,
 				pub indexer_rules: Vec<Reference<indexer_rule::Data>>,
 			}
 
@@ -271,8 +294,13 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 						generate_preview_media: value.generate_preview_media,
 						sync_preview_media: value.sync_preview_media,
 						hidden: value.hidden,
+						symlink_policy: value.symlink_policy,
+						xattr_allowlist: value.xattr_allowlist,
+						watcher_mode: value.watcher_mode,
+						watcher_poll_interval: value.watcher_poll_interval,
 						date_created: value.date_created,
 						instance_id: value.instance_id,
+						volume_id: value.volume_id,
 						indexer_rules: value
 							.indexer_rules
 							.into_iter()
@@ -314,7 +342,7 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 				.mutation(|(node, library), args: LocationCreateArgs| async move {
 					if let Some(location) = args.create(&node, &library).await? {
 						let id = Some(location.id);
-						scan_location(&node, &library, location).await?;
+						scan_location(&node, &library, location, false).await?;
 						invalidate_query!(library, "locations.list");
 						Ok(id)
 					} else {
@@ -347,12 +375,22 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 						.map_err(Into::into)
 				})
 		})
+		.procedure("findRelinks", {
+			R.with2(library()).mutation(
+				|(node, library), args: LocationRelinkerJobInit| async move {
+					Job::new(args)
+						.spawn(&node, &library)
+						.await
+						.map_err(Into::into)
+				},
+			)
+		})
 		.procedure("addLibrary", {
 			R.with2(library())
 				.mutation(|(node, library), args: LocationCreateArgs| async move {
 					if let Some(location) = args.add_library(&node, &library).await? {
 						let id = location.id;
-						scan_location(&node, &library, location).await?;
+						scan_location(&node, &library, location, false).await?;
 						invalidate_query!(library, "locations.list");
 						Ok(Some(id))
 					} else {
@@ -365,12 +403,16 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 			pub struct FullRescanArgs {
 				pub location_id: location::id::Type,
 				pub reidentify_objects: bool,
+				/// Walks every directory, even the ones that didn't change since the last scan
+				#[serde(default)]
+				pub force_full: bool,
 			}
 			R.with2(library()).mutation(
 				|(node, library),
 				 FullRescanArgs {
 				     location_id,
 				     reidentify_objects,
+				     force_full,
 				 }| async move {
 					if reidentify_objects {
 						let count = library
@@ -404,6 +446,7 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 							.exec()
 							.await?
 							.ok_or(LocationError::IdNotFound(location_id))?,
+						force_full,
 					)
 					.await
 					.map_err(Into::into)
@@ -500,6 +543,40 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 				}
 			}),
 		)
+		.procedure("state", {
+			#[derive(Type, Serialize)]
+			pub struct LocationState {
+				online: bool,
+				/// Watcher events received but not fully handled yet, which are replayed when the
+				/// location is watched again if the node stops before handling them
+				watcher_backlog: u32,
+			}
+
+			R.with2(library()).query(
+				|(node, library), location_id: location::id::Type| async move {
+					let location = find_location(&library, location_id)
+						.select(location::select!({ pub_id }))
+						.exec()
+						.await?
+						.ok_or(LocationError::IdNotFound(location_id))?;
+
+					let watcher_backlog = library
+						.db
+						.watcher_event()
+						.count(vec![watcher_event::location_id::equals(location_id)])
+						.exec()
+						.await?;
+
+					Ok(LocationState {
+						online: node
+							.locations
+							.is_online(&from_bytes_to_uuid(&location.pub_id))
+							.await,
+						watcher_backlog: u32::try_from(watcher_backlog).unwrap_or(u32::MAX),
+					})
+				},
+			)
+		})
 		.procedure("systemLocations", {
 			R.query(|_, _: ()| async move {
 				UserDirs::new().map(SystemLocations::from).ok_or_else(|| {
@@ -568,6 +645,46 @@ fn mount_indexer_rule_routes() -> AlphaRouter<Ctx> {
 					Ok(())
 				})
 		})
+		.procedure("explain", {
+			#[derive(Serialize, Type, Debug)]
+			struct IndexerRulesExplainItem {
+				decisions: Vec<RuleDecision>,
+				errors: Vec<rspc::Error>,
+			}
+
+			R.with2(library()).subscription(
+				|(_, library), args: IndexerRulesExplainArgs| async move {
+					let mut stream = BatchedStream::new(Box::pin(args.explain(&library).await?));
+
+					Ok(unsafe_streamed_query(stream! {
+						while let Some(results) = stream.next().await {
+							// We optimise for the case of no errors because it should be way more common.
+							let mut decisions = Vec::with_capacity(results.len());
+							let mut errors = Vec::with_capacity(0);
+
+							for result in results {
+								match result {
+									Ok(decision) => decisions.push(decision),
+									Err(e) => errors.push(e.into()),
+								}
+							}
+
+							yield IndexerRulesExplainItem { decisions, errors };
+						}
+					}))
+				},
+			)
+		})
+		// a portable JSON file with these indexer rules, or every indexer rule if empty
+		.procedure("export", {
+			R.with2(library()).query(
+				|(_, library), indexer_rules_ids: Vec<indexer_rule::id::Type>| async move {
+					Ok(IndexerRuleSet::export(&library, indexer_rules_ids)
+						.await?
+						.to_json()?)
+				},
+			)
+		})
 		.procedure("get", {
 			R.with2(library())
 				.query(|(_, library), indexer_rule_id: i32| async move {
@@ -586,6 +703,65 @@ fn mount_indexer_rule_routes() -> AlphaRouter<Ctx> {
 						.map(|i| NormalisedResult::from(i, |i| i.id.to_string()))
 				})
 		})
+		.procedure("import", {
+			R.with2(library()).mutation(
+				|(node, library), args: IndexerRuleSetImportArgs| async move {
+					let location_ids = args.location_ids.clone();
+					let attached_to_locations = !location_ids.is_empty();
+
+					let imported_ids = args.import(&library).await?;
+
+					reload_watchers(&node, &library, imported_ids.clone(), location_ids).await?;
+
+					invalidate_query!(library, "locations.indexer_rules.list");
+					invalidate_query!(library, "locations.indexer_rules.get");
+					if attached_to_locations {
+						invalidate_query!(library, "locations.indexer_rules.listForLocation");
+						invalidate_query!(library, "locations.getWithRules");
+					}
+
+					Ok(imported_ids)
+				},
+			)
+		})
+		// the rules of an indexer rule in the same format they're created and updated with
+		.procedure("parameters", {
+			R.with2(library())
+				.query(|(_, library), indexer_rule_id: i32| async move {
+					let indexer_rule = library
+						.db
+						.indexer_rule()
+						.find_unique(indexer_rule::id::equals(indexer_rule_id))
+						.exec()
+						.await?
+						.ok_or_else(|| {
+							rspc::Error::new(
+								ErrorCode::NotFound,
+								format!("Indexer rule <id={indexer_rule_id}> not found"),
+							)
+						})?;
+
+					Ok(IndexerRule::try_from(indexer_rule)?
+						.rules
+						.iter()
+						.map(RulePerKind::to_parameters)
+						.collect::<Vec<_>>())
+				})
+		})
+		.procedure("update", {
+			R.with2(library())
+				.mutation(|(node, library), args: IndexerRuleUpdateArgs| async move {
+					let indexer_rule_id = args.id;
+					args.update(&library).await?;
+
+					reload_watchers(&node, &library, vec![indexer_rule_id], []).await?;
+
+					invalidate_query!(library, "locations.indexer_rules.list");
+					invalidate_query!(library, "locations.indexer_rules.get");
+
+					Ok(())
+				})
+		})
 		.procedure("list", {
 			R.with2(library()).query(|(_, library), _: ()| async move {
 				let rules = library.db.indexer_rule().find_many(vec![]).exec().await?;
@@ -614,3 +790,39 @@ fn mount_indexer_rule_routes() -> AlphaRouter<Ctx> {
 				})
 		})
 }
+
+/// Watchers hold their own copy of the indexer rules, so the ones of `location_ids`, and of every
+/// location using any of `indexer_rule_ids`, must load them again after they change
+async fn reload_watchers(
+	node: &Node,
+	library: &Arc<Library>,
+	indexer_rule_ids: Vec<indexer_rule::id::Type>,
+	location_ids: impl IntoIterator<Item = location::id::Type>,
+) -> Result<(), rspc::Error> {
+	let location_ids = library
+		.db
+		.indexer_rules_in_location()
+		.find_many(vec![indexer_rules_in_location::indexer_rule_id::in_vec(
+			indexer_rule_ids,
+		)])
+		.select(indexer_rules_in_location::select!({ location_id }))
+		.exec()
+		.await?
+		.into_iter()
+		.map(|rule_in_location| rule_in_location.location_id)
+		.chain(location_ids)
+		.collect::<HashSet<_>>();
+
+	for location_id in location_ids {
+		node.locations
+			.remove(location_id, library.clone())
+			.await
+			.map_err(LocationError::from)?;
+		node.locations
+			.add(location_id, library.clone())
+			.await
+			.map_err(LocationError::from)?;
+	}
+
+	Ok(())
+}
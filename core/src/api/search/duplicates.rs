@@ -0,0 +1,435 @@
+use crate::{
+	api::locations::{file_path_with_object, ExplorerItem},
+	job::{Job, JobManagerError},
+	library::Library,
+	location::LocationError,
+	object::{
+		media::thumbnail::get_indexed_thumb_key, validation::validator_job::ObjectValidatorJobInit,
+	},
+	Node,
+};
+
+use sd_cache::{CacheNode, Normalise, Reference};
+use sd_prisma::prisma::{file_path, location, object, PrismaClient};
+use sd_utils::{chain_optional_iter, db::size_in_bytes_to_db};
+
+use std::{collections::HashMap, sync::Arc};
+
+use itertools::Itertools;
+use prisma_client_rust::{raw, PrismaValue, QueryError};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::MAX_TAKE;
+
+/// How many objects we fetch file paths for in a single query
+const OBJECTS_CHUNK_SIZE: usize = 1000;
+
+#[derive(Deserialize, Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicatesFilters {
+	/// Only objects of these kinds, any kind when empty
+	#[serde(default)]
+	pub kinds: Vec<i32>,
+	/// Only file paths in these locations, all locations when empty
+	#[serde(default)]
+	pub locations: Vec<location::id::Type>,
+	#[serde(default)]
+	#[specta(optional)]
+	#[specta(type = Option<f64>)]
+	pub min_size_in_bytes: Option<u64>,
+}
+
+#[derive(Deserialize, Type, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicatesArgs {
+	#[serde(flatten)]
+	pub filters: DuplicatesFilters,
+	pub take: u8,
+	/// The cursor returned with the previous page
+	#[specta(optional)]
+	pub cursor: Option<u32>,
+	/// `cas_id` only samples big files, so this splits copies by their `integrity_checksum`
+	/// and only reports the ones that really match. Copies without one are left out, so
+	/// `search.verifyDuplicates` must compute them first.
+	#[serde(default)]
+	pub verify_integrity: bool,
+}
+
+#[derive(Serialize, Type, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateGroup {
+	pub object_id: object::id::Type,
+	/// Size of a single copy
+	pub size_in_bytes: f64,
+	/// Space freed by keeping a single copy
+	pub reclaimable_bytes: f64,
+	pub items: Vec<Reference<ExplorerItem>>,
+}
+
+#[derive(Serialize, Type, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Duplicates {
+	/// Groups with the most reclaimable space first
+	pub groups: Vec<DuplicateGroup>,
+	/// Space freed across all groups, not only the ones in this page
+	pub reclaimable_bytes: f64,
+	/// Copies left out when verifying integrity, as they don't have a checksum yet
+	pub unverified: u32,
+	pub nodes: Vec<CacheNode>,
+	/// `None` when there are no more pages
+	pub cursor: Option<u32>,
+}
+
+/// Finds duplicates without touching the files, so it only relies on what was already indexed
+pub async fn find(
+	node: &Node,
+	library: &Library,
+	DuplicatesArgs {
+		filters,
+		take,
+		cursor,
+		verify_integrity,
+	}: DuplicatesArgs,
+) -> Result<Duplicates, rspc::Error> {
+	#[derive(Deserialize)]
+	struct Totals {
+		reclaimable_bytes: f64,
+	}
+
+	#[derive(Deserialize)]
+	struct Unverified {
+		count: i64,
+	}
+
+	let Library { db, .. } = library;
+
+	let skip = cursor.unwrap_or_default();
+	let take = take.clamp(1, MAX_TAKE) as u32;
+
+	let min_size_in_bytes = PrismaValue::Float(size_in_bytes_to_db(
+		filters.min_size_in_bytes.unwrap_or_default(),
+	));
+
+	// Pages must always be cut in the same order, or we would skip or repeat groups
+	let mut groups = db
+		._query_raw::<Group>(raw!(
+			&format!(
+				"{}
+				ORDER BY reclaimable_bytes DESC, object_id, integrity_checksum
+				LIMIT {{}} OFFSET {{}}",
+				groups_query(&filters, verify_integrity)
+			),
+			min_size_in_bytes.clone(),
+			// One more than asked, to know if there's another page
+			PrismaValue::Int(take as i64 + 1),
+			PrismaValue::Int(skip as i64)
+		))
+		.exec()
+		.await?;
+
+	let cursor = (groups.len() > take as usize).then_some(skip + take);
+	groups.truncate(take as usize);
+
+	let reclaimable_bytes = db
+		._query_raw::<Totals>(raw!(
+			&format!(
+				"SELECT COALESCE(SUM(reclaimable_bytes), 0.0) AS reclaimable_bytes FROM ({})",
+				groups_query(&filters, verify_integrity)
+			),
+			min_size_in_bytes.clone()
+		))
+		.exec()
+		.await?
+		.first()
+		.map_or(0.0, |totals| totals.reclaimable_bytes);
+
+	let unverified = if verify_integrity {
+		db._query_raw::<Unverified>(raw!(
+			&format!(
+				"SELECT COUNT(*) AS count
+				FROM file_path f
+				WHERE
+					f.is_dir = FALSE
+					AND f.integrity_checksum IS NULL
+					{}
+					AND f.object_id IN (SELECT object_id FROM ({}))",
+				locations_condition(&filters.locations),
+				groups_query(&filters, false)
+			),
+			min_size_in_bytes
+		))
+		.exec()
+		.await?
+		.first()
+		.map_or(0, |unverified| unverified.count as u32)
+	} else {
+		0
+	};
+
+	let mut file_paths_by_group = find_file_paths_by_group(
+		db,
+		&filters,
+		groups
+			.iter()
+			.map(|group| group.object_id)
+			.unique()
+			.collect(),
+		verify_integrity,
+	)
+	.await?;
+
+	let mut nodes = vec![];
+	let mut duplicate_groups = Vec::with_capacity(groups.len());
+
+	for Group {
+		object_id,
+		integrity_checksum,
+		size_in_bytes,
+		reclaimable_bytes,
+	} in groups
+	{
+		let file_paths = file_paths_by_group
+			.remove(&(object_id, integrity_checksum))
+			.unwrap_or_default();
+
+		let mut items = Vec::with_capacity(file_paths.len());
+
+		for file_path in file_paths {
+			let thumbnail_exists_locally = if let Some(cas_id) = &file_path.cas_id {
+				library
+					.thumbnail_exists(node, cas_id)
+					.await
+					.map_err(LocationError::from)?
+			} else {
+				false
+			};
+
+			items.push(ExplorerItem::Path {
+				thumbnail: file_path
+					.cas_id
+					.as_ref()
+					.filter(|_| thumbnail_exists_locally)
+					.map(|i| get_indexed_thumb_key(i, library.id)),
+				item: file_path,
+			})
+		}
+
+		let (group_nodes, items) = items.normalise(|item| item.id());
+		nodes.extend(group_nodes);
+
+		duplicate_groups.push(DuplicateGroup {
+			object_id,
+			size_in_bytes,
+			reclaimable_bytes,
+			items,
+		});
+	}
+
+	Ok(Duplicates {
+		groups: duplicate_groups,
+		reclaimable_bytes,
+		unverified,
+		nodes,
+		cursor,
+	})
+}
+
+/// Computes the missing checksums of the copies of possible duplicates, so they can be verified
+/// by `search.duplicates`. Only the locations of this instance can be read, so each one of them
+/// with copies to check gets its own validator job.
+pub async fn verify(
+	node: &Arc<Node>,
+	library: &Arc<Library>,
+	filters: DuplicatesFilters,
+) -> Result<(), JobManagerError> {
+	let Library { db, .. } = library.as_ref();
+
+	let instance_id = library.config().await.instance_id;
+
+	let mut file_path_ids_by_location = HashMap::<_, Vec<_>>::new();
+	let object_ids = db
+		._query_raw::<Group>(raw!(
+			&groups_query(&filters, false),
+			PrismaValue::Float(size_in_bytes_to_db(
+				filters.min_size_in_bytes.unwrap_or_default()
+			))
+		))
+		.exec()
+		.await?
+		.into_iter()
+		.map(|group| group.object_id)
+		.collect();
+
+	for file_path in find_file_paths_by_group(db, &filters, object_ids, false)
+		.await?
+		.into_values()
+		.flatten()
+		.filter(|file_path| file_path.integrity_checksum.is_none())
+	{
+		if let Some(location_id) = file_path.location_id {
+			file_path_ids_by_location
+				.entry(location_id)
+				.or_default()
+				.push(file_path.id);
+		}
+	}
+
+	if file_path_ids_by_location.is_empty() {
+		return Ok(());
+	}
+
+	for location in db
+		.location()
+		.find_many(vec![
+			location::id::in_vec(file_path_ids_by_location.keys().copied().collect()),
+			location::instance_id::equals(Some(instance_id)),
+		])
+		.exec()
+		.await?
+	{
+		let file_path_ids = file_path_ids_by_location.remove(&location.id);
+
+		Job::new(ObjectValidatorJobInit {
+			location,
+			sub_path: None,
+			file_path_ids,
+		})
+		.spawn(node, library)
+		.await?;
+	}
+
+	Ok(())
+}
+
+/// A set of copies of the same contents, split by `integrity_checksum` when verifying them
+#[derive(Deserialize)]
+struct Group {
+	object_id: object::id::Type,
+	integrity_checksum: Option<String>,
+	/// Size of a single copy
+	size_in_bytes: f64,
+	reclaimable_bytes: f64,
+}
+
+/// The file paths of these objects, by the [`Group`] they belong to
+async fn find_file_paths_by_group(
+	db: &PrismaClient,
+	filters: &DuplicatesFilters,
+	object_ids: Vec<object::id::Type>,
+	verify_integrity: bool,
+) -> Result<HashMap<(object::id::Type, Option<String>), Vec<file_path_with_object::Data>>, QueryError>
+{
+	let mut file_paths_by_group = HashMap::<_, Vec<_>>::with_capacity(object_ids.len());
+
+	for file_path in db
+		._batch(
+			object_ids
+				.into_iter()
+				.chunks(OBJECTS_CHUNK_SIZE)
+				.into_iter()
+				.map(|object_ids| {
+					db.file_path()
+						.find_many(chain_optional_iter(
+							[
+								file_path::object_id::in_vec(object_ids.collect()),
+								file_path::is_dir::equals(Some(false)),
+							],
+							[(!filters.locations.is_empty()).then(|| {
+								file_path::location_id::in_vec(filters.locations.clone())
+							})],
+						))
+						.include(file_path_with_object::include())
+				})
+				.collect::<Vec<_>>(),
+		)
+		.await?
+		.into_iter()
+		.flatten()
+	{
+		if let Some(object_id) = file_path.object_id {
+			let integrity_checksum = verify_integrity
+				.then(|| file_path.integrity_checksum.clone())
+				.flatten();
+
+			file_paths_by_group
+				.entry((object_id, integrity_checksum))
+				.or_default()
+				.push(file_path);
+		}
+	}
+
+	Ok(file_paths_by_group)
+}
+
+/// Objects are unique by `cas_id`, so any object with more than one copy is a duplicate. Hard
+/// links to the same file share its space, so they only count as a single copy. They can only be
+/// on the same instance, as devices and inodes of other instances may be the same by chance.
+///
+/// Selects every [`Group`], taking the minimum size in bytes as its single parameter. When
+/// verifying integrity, copies are grouped by their checksum and the ones without it are left out.
+fn groups_query(
+	DuplicatesFilters {
+		kinds, locations, ..
+	}: &DuplicatesFilters,
+	verify_integrity: bool,
+) -> String {
+	let copies = "COUNT(DISTINCT CASE
+		WHEN f.device IS NULL OR f.inode IS NULL THEN 'id' || f.id
+		ELSE COALESCE(l.instance_id, '') || ':' || hex(f.device) || hex(f.inode)
+	END)";
+
+	// FIXME: Had to use format! macro because PCR doesn't support IN with Vec for SQLite
+	// These are all numbers, so this is sql injection safe
+	format!(
+		"SELECT
+			f.object_id AS object_id,
+			{checksum} AS integrity_checksum,
+			COALESCE(MAX(f.size_in_bytes_number), 0.0) AS size_in_bytes,
+			COALESCE(MAX(f.size_in_bytes_number), 0.0) * ({copies} - 1) AS reclaimable_bytes
+		FROM file_path f
+		INNER JOIN object o ON o.id = f.object_id
+		LEFT JOIN location l ON l.id = f.location_id
+		WHERE
+			f.is_dir = FALSE
+			AND COALESCE(o.size_in_bytes, 0) >= {{}}
+			{kinds}
+			{locations}
+			{verified}
+		GROUP BY f.object_id{group_by_checksum}
+		HAVING {copies} > 1",
+		checksum = if verify_integrity {
+			"f.integrity_checksum"
+		} else {
+			"NULL"
+		},
+		kinds = if kinds.is_empty() {
+			String::new()
+		} else {
+			format!("AND o.kind IN ({})", join_ids(kinds))
+		},
+		locations = locations_condition(locations),
+		verified = if verify_integrity {
+			"AND f.integrity_checksum IS NOT NULL"
+		} else {
+			""
+		},
+		group_by_checksum = if verify_integrity {
+			", f.integrity_checksum"
+		} else {
+			""
+		},
+	)
+}
+
+fn locations_condition(locations: &[location::id::Type]) -> String {
+	if locations.is_empty() {
+		String::new()
+	} else {
+		format!("AND f.location_id IN ({})", join_ids(locations))
+	}
+}
+
+fn join_ids(ids: &[i32]) -> String {
+	ids.iter().map(ToString::to_string).join(",")
+}
@@ -0,0 +1,120 @@
+use crate::{
+	api::locations::ExplorerItem,
+	library::Library,
+	location::{
+		indexer::rules::{
+			seed::{no_hidden, no_os_protected},
+			IndexerRule,
+		},
+		non_indexed::{self, NonIndexedLocationError, NonIndexedPathItem},
+		symlinks::SymlinkPolicy,
+	},
+	Node,
+};
+
+use sd_prisma::prisma::indexer_rule;
+use sd_utils::chain_optional_iter;
+
+use std::{path::PathBuf, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use futures::Stream;
+use itertools::Either;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use super::utils::{InOrNotIn, Range, TextMatch};
+
+#[derive(Deserialize, Type, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EphemeralSearchArgs {
+	pub path: PathBuf,
+	/// How many levels of directories we walk into, where 1 only searches `path` itself.
+	/// Unlimited when missing.
+	#[specta(optional)]
+	pub max_depth: Option<u32>,
+	#[serde(default)]
+	pub with_hidden_files: bool,
+	#[serde(default)]
+	pub filters: Vec<EphemeralFilterArgs>,
+	/// Applied while walking, in the same way they're applied to locations
+	#[serde(default)]
+	pub indexer_rules_ids: Vec<indexer_rule::id::Type>,
+	/// Symbolic links are skipped when missing
+	#[specta(optional)]
+	pub symlink_policy: Option<SymlinkPolicy>,
+}
+
+#[derive(Serialize, Deserialize, Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub enum EphemeralFilterArgs {
+	Name(TextMatch),
+	Extension(InOrNotIn<String>),
+	Kind(InOrNotIn<i32>),
+	/// Only applies to files, as we don't know the sizes of directories without walking them
+	SizeInBytes(#[specta(type = Range<f64>)] Range<u64>),
+	CreatedAt(Range<DateTime<Utc>>),
+	ModifiedAt(Range<DateTime<Utc>>),
+	Hidden(bool),
+}
+
+impl EphemeralFilterArgs {
+	pub fn matches(&self, item: &NonIndexedPathItem) -> bool {
+		match self {
+			Self::Name(v) => v.matches(&item.name),
+			Self::Extension(v) => v.matches(&item.extension),
+			Self::Kind(v) => v.matches(&item.kind),
+			Self::SizeInBytes(v) => {
+				!item.is_dir
+					&& <[u8; 8]>::try_from(item.size_in_bytes_bytes.as_slice())
+						.map_or(false, |bytes| v.contains(&u64::from_be_bytes(bytes)))
+			}
+			Self::CreatedAt(v) => v.contains(&item.date_created),
+			Self::ModifiedAt(v) => v.contains(&item.date_modified),
+			Self::Hidden(v) => item.hidden == *v,
+		}
+	}
+}
+
+pub async fn search(
+	node: Arc<Node>,
+	library: &Library,
+	EphemeralSearchArgs {
+		path,
+		max_depth,
+		with_hidden_files,
+		filters,
+		indexer_rules_ids,
+		symlink_policy,
+	}: EphemeralSearchArgs,
+) -> Result<
+	impl Stream<Item = Result<ExplorerItem, Either<rspc::Error, NonIndexedLocationError>>> + Send,
+	rspc::Error,
+> {
+	let mut rules = chain_optional_iter(
+		[IndexerRule::from(no_os_protected())],
+		[(!with_hidden_files).then(|| IndexerRule::from(no_hidden()))],
+	);
+
+	if !indexer_rules_ids.is_empty() {
+		for rule in library
+			.db
+			.indexer_rule()
+			.find_many(vec![indexer_rule::id::in_vec(indexer_rules_ids)])
+			.exec()
+			.await?
+		{
+			rules.push(IndexerRule::try_from(rule)?);
+		}
+	}
+
+	Ok(non_indexed::search(
+		path,
+		max_depth,
+		rules,
+		symlink_policy.unwrap_or_default(),
+		move |item| filters.iter().all(|filter| filter.matches(item)),
+		node,
+	)
+	.await?)
+}
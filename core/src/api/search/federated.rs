@@ -0,0 +1,245 @@
+use crate::{
+	api::locations::{file_path_with_object, ExplorerItem},
+	location::LocationError,
+	object::media::thumbnail::get_indexed_thumb_key,
+	Node,
+};
+
+use sd_cache::{CacheNode, Reference};
+use sd_prisma::prisma::{self, file_path};
+
+use std::{cmp::Ordering, collections::HashMap};
+
+use rspc::ErrorCode;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use uuid::Uuid;
+
+use super::{
+	query::{self, SearchQueryError},
+	FilePathOrder, ObjectOrder, SearchFilterArgs, MAX_TAKE,
+};
+
+#[derive(Deserialize, Type, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FederatedSearchArgs {
+	pub take: u8,
+	#[specta(optional)]
+	pub order: Option<FilePathOrder>,
+	/// Applied as is to every library, so filters by id (like locations or tags) rarely
+	/// make sense here, prefer `query` which is resolved against each library
+	#[serde(default)]
+	pub filters: Vec<SearchFilterArgs>,
+	/// A textual query, compiled into more filters and an order when none was given.
+	/// Libraries without the tags or labels it names are skipped
+	#[specta(optional)]
+	pub query: Option<String>,
+	/// The cursor returned with the previous page
+	#[specta(optional)]
+	pub cursor: Option<FederatedCursor>,
+}
+
+/// How many items of each library were already returned in previous pages
+#[derive(Serialize, Deserialize, Type, Debug, Default)]
+pub struct FederatedCursor(HashMap<Uuid, u32>);
+
+#[derive(Serialize, Type, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FederatedSearchItem {
+	pub library_id: Uuid,
+	pub item: Reference<ExplorerItem>,
+}
+
+#[derive(Serialize, Type, Debug)]
+pub struct FederatedSearchData {
+	pub items: Vec<FederatedSearchItem>,
+	pub nodes: Vec<CacheNode>,
+	/// `None` when there are no more pages
+	pub cursor: Option<FederatedCursor>,
+}
+
+/// Searches file paths on every loaded library, merging the results by the requested order.
+///
+/// Each library is paginated with its own offset, and as we fetch a full page from each one,
+/// the merged page is always made of the first items left across all of them.
+pub async fn search(
+	node: &Node,
+	FederatedSearchArgs {
+		take,
+		mut order,
+		filters,
+		query,
+		cursor,
+	}: FederatedSearchArgs,
+) -> Result<FederatedSearchData, rspc::Error> {
+	// Empty pages would never move the cursor forward
+	if take == 0 {
+		return Err(rspc::Error::new(
+			ErrorCode::BadRequest,
+			"Must take at least one item".to_string(),
+		));
+	}
+
+	let take = take.min(MAX_TAKE) as usize;
+	let FederatedCursor(mut offsets) = cursor.unwrap_or_default();
+
+	let mut libraries = node.libraries.get_all().await;
+	// Ties between libraries must always be broken in the same way, or we would skip
+	// or repeat items between pages
+	libraries.sort_by_key(|library| library.id);
+
+	// Syntax errors are the same for every library, so we only parse the query once
+	let parsed = query.as_deref().map(query::parse).transpose()?;
+
+	let mut candidates = Vec::new();
+
+	for (library_idx, library) in libraries.iter().enumerate() {
+		let db = &library.db;
+
+		let mut filters = filters.clone();
+
+		if let Some(parsed) = &parsed {
+			let compiled = match parsed.clone().compile(db).await {
+				Ok(compiled) => compiled,
+				// A library without the tags or labels of the query can't have any matches
+				Err(SearchQueryError::Invalid(errors)) if errors.only_unknown_names() => continue,
+				Err(e) => return Err(e.into()),
+			};
+
+			filters.extend(compiled.filters);
+
+			// The order doesn't depend on the library, so any of them is fine
+			if order.is_none() {
+				order = compiled.order;
+			}
+		}
+
+		if let Some(FilePathOrder::Object(object_order)) = &order {
+			if let ObjectOrder::MediaData(_) = object_order.as_ref() {
+				return Err(rspc::Error::new(
+					ErrorCode::BadRequest,
+					"Can't order by media data when searching all libraries".to_string(),
+				));
+			}
+		}
+
+		let mut params = Vec::new();
+
+		for filter in filters {
+			params.extend(filter.into_file_path_params(db).await?);
+		}
+
+		let mut query = db
+			.file_path()
+			.find_many(params)
+			.skip(offsets.get(&library.id).copied().unwrap_or_default() as i64)
+			.take(take as i64 + 1);
+
+		if let Some(order) = &order {
+			query = query.order_by(order.clone().into_param());
+		}
+
+		candidates.extend(
+			query
+				.order_by(file_path::id::order(prisma::SortOrder::Asc))
+				.include(file_path_with_object::include())
+				.exec()
+				.await?
+				.into_iter()
+				.map(|file_path| (library_idx, file_path)),
+		);
+	}
+
+	candidates.sort_by(|(a_library_idx, a), (b_library_idx, b)| {
+		order
+			.as_ref()
+			.map_or(Ordering::Equal, |order| compare(order, a, b))
+			.then(a_library_idx.cmp(b_library_idx))
+			.then(a.id.cmp(&b.id))
+	});
+
+	let has_more = candidates.len() > take;
+	candidates.truncate(take);
+
+	let mut items = Vec::with_capacity(candidates.len());
+	let mut nodes = Vec::with_capacity(candidates.len());
+
+	for (library_idx, file_path) in candidates {
+		let library = &libraries[library_idx];
+
+		*offsets.entry(library.id).or_default() += 1;
+
+		let thumbnail_exists_locally = if let Some(cas_id) = &file_path.cas_id {
+			library
+				.thumbnail_exists(node, cas_id)
+				.await
+				.map_err(LocationError::from)?
+		} else {
+			false
+		};
+
+		let item = ExplorerItem::Path {
+			thumbnail: file_path
+				.cas_id
+				.as_ref()
+				.filter(|_| thumbnail_exists_locally)
+				.map(|i| get_indexed_thumb_key(i, library.id)),
+			item: file_path,
+		};
+
+		// Ids are only unique inside a library, so they're namespaced to not clash in the cache
+		let id = format!("{}:{}", library.id, item.id());
+		nodes.push(CacheNode::new(id.clone(), item));
+		items.push(FederatedSearchItem {
+			library_id: library.id,
+			item: Reference::new(id),
+		});
+	}
+
+	Ok(FederatedSearchData {
+		items,
+		nodes,
+		cursor: has_more.then_some(FederatedCursor(offsets)),
+	})
+}
+
+/// Compares file paths in the same way the database orders them, with nulls first
+fn compare(
+	order: &FilePathOrder,
+	a: &file_path_with_object::Data,
+	b: &file_path_with_object::Data,
+) -> Ordering {
+	let ordering = match order {
+		FilePathOrder::Name(_) => a.name.cmp(&b.name),
+		FilePathOrder::SizeInBytes(_) => a
+			.size_in_bytes_number
+			.partial_cmp(&b.size_in_bytes_number)
+			.unwrap_or(Ordering::Equal),
+		FilePathOrder::DateCreated(_) => a.date_created.cmp(&b.date_created),
+		FilePathOrder::DateModified(_) => a.date_modified.cmp(&b.date_modified),
+		FilePathOrder::DateIndexed(_) => a.date_indexed.cmp(&b.date_indexed),
+		FilePathOrder::Object(order) => {
+			let (a, b) = (a.object.as_ref(), b.object.as_ref());
+
+			match order.as_ref() {
+				ObjectOrder::DateAccessed(_) => a
+					.and_then(|object| object.date_accessed)
+					.cmp(&b.and_then(|object| object.date_accessed)),
+				ObjectOrder::Kind(_) => a
+					.and_then(|object| object.kind)
+					.cmp(&b.and_then(|object| object.kind)),
+				ObjectOrder::SizeInBytes(_) => a
+					.and_then(|object| object.size_in_bytes)
+					.partial_cmp(&b.and_then(|object| object.size_in_bytes))
+					.unwrap_or(Ordering::Equal),
+				// Rejected before querying, as we don't fetch media data
+				ObjectOrder::MediaData(_) => Ordering::Equal,
+			}
+		}
+	};
+
+	match order.get_sort_order() {
+		prisma::SortOrder::Asc => ordering,
+		prisma::SortOrder::Desc => ordering.reverse(),
+	}
+}
@@ -1,7 +1,11 @@
-use crate::location::LocationError;
+use crate::{location::LocationError, object::content_index};
 
-use sd_file_path_helper::{check_file_path_exists, IsolatedFilePathData};
+use sd_file_path_helper::{
+	attributes::{id_to_db, PERMISSION_BITS},
+	check_file_path_exists, IsolatedFilePathData,
+};
 use sd_prisma::prisma::{self, file_path};
+use sd_utils::db::size_in_bytes_to_db;
 
 use chrono::{DateTime, FixedOffset, Utc};
 use prisma_client_rust::{OrderByQuery, PaginatedQuery, WhereQuery};
@@ -43,7 +47,7 @@ impl FilePathOrder {
 		use file_path::*;
 		match self {
 			Self::Name(_) => name::order(dir),
-			Self::SizeInBytes(_) => size_in_bytes_bytes::order(dir),
+			Self::SizeInBytes(_) => size_in_bytes_number::order(dir),
 			Self::DateCreated(_) => date_created::order(dir),
 			Self::DateModified(_) => date_modified::order(dir),
 			Self::DateIndexed(_) => date_indexed::order(dir),
@@ -69,6 +73,19 @@ pub enum FilePathFilterArgs {
 	ModifiedAt(Range<DateTime<Utc>>),
 	IndexedAt(Range<DateTime<Utc>>),
 	Hidden(bool),
+	/// Applies to directories too, as their sizes are the sum of their contents
+	SizeInBytes(#[specta(type = Range<f64>)] Range<u64>),
+	/// Full-text search over the contents of text, code, config and document files
+	Content(String),
+	/// Paths whose POSIX permission bits include all of the given ones, like `0o111` for
+	/// files everyone can execute
+	Permissions(u32),
+	/// POSIX owner user ids
+	Owner(InOrNotIn<u32>),
+	/// POSIX owner group ids
+	Group(InOrNotIn<u32>),
+	/// Paths with an extended attribute of this name, among the ones in their location's allowlist
+	Xattr(String),
 }
 
 impl FilePathFilterArgs {
@@ -151,6 +168,53 @@ impl FilePathFilterArgs {
 			Self::Hidden(v) => {
 				vec![hidden::equals(Some(v))]
 			}
+			Self::SizeInBytes(v) => {
+				vec![match v {
+					Range::From(v) => size_in_bytes_number::gte(size_in_bytes_to_db(v)),
+					Range::To(v) => size_in_bytes_number::lte(size_in_bytes_to_db(v)),
+				}]
+			}
+			Self::Content(query) => vec![object_id::in_vec(
+				content_index::search(db, &query)
+					.await?
+					.into_iter()
+					.map(|content_match| content_match.object_id)
+					.collect(),
+			)],
+			Self::Permissions(bits) => {
+				let bits = bits & PERMISSION_BITS;
+
+				if bits == 0 {
+					vec![]
+				} else {
+					// Prisma has no bitwise filters, but there are only a few thousand modes anyway
+					vec![mode::in_vec(
+						(0..=PERMISSION_BITS)
+							.filter(|mode| mode & bits == bits)
+							.map(|mode| mode as i32)
+							.collect(),
+					)]
+				}
+			}
+			Self::Owner(v) => v
+				.into_param(
+					|uids| uid::in_vec(uids.into_iter().map(id_to_db).collect()),
+					|uids| uid::not_in_vec(uids.into_iter().map(id_to_db).collect()),
+				)
+				.map(|v| vec![v])
+				.unwrap_or_default(),
+			Self::Group(v) => v
+				.into_param(
+					|gids| gid::in_vec(gids.into_iter().map(id_to_db).collect()),
+					|gids| gid::not_in_vec(gids.into_iter().map(id_to_db).collect()),
+				)
+				.map(|v| vec![v])
+				.unwrap_or_default(),
+			// Names are keys of the JSON object in the `xattrs` column, and any quote inside
+			// the values would be escaped, so this can't match a value
+			Self::Xattr(name) => serde_json::to_string(&name)
+				.map(|name| vec![xattrs::contains(format!("{name}:"))])
+				.unwrap_or_default(),
 		})
 	}
 }
@@ -235,7 +299,7 @@ impl FilePathCursorVariant {
 				query.add_where(prisma::file_path::id::gt(id));
 			}
 			Self::SizeInBytes(order) => {
-				query.add_order_by(prisma::file_path::size_in_bytes_bytes::order(order.into()));
+				query.add_order_by(prisma::file_path::size_in_bytes_number::order(order.into()));
 			}
 			Self::Name(item) => arm!(name, item),
 			Self::DateCreated(item) => {
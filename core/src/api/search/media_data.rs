@@ -1,5 +1,7 @@
-use sd_prisma::prisma::{self, media_data};
+use sd_prisma::prisma::{self, media_data, PrismaClient};
 
+use chrono::{DateTime, Utc};
+use prisma_client_rust::{not, or};
 use serde::{Deserialize, Serialize};
 use specta::Type;
 
@@ -27,3 +29,195 @@ impl MediaDataOrder {
 		}
 	}
 }
+
+#[derive(Serialize, Deserialize, Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub enum MediaDataFilterArgs {
+	CaptureDate(Range<DateTime<Utc>>),
+	Width(Range<i32>),
+	Height(Range<i32>),
+	CameraMake(TextMatch),
+	CameraModel(TextMatch),
+	Artist(TextMatch),
+	Copyright(TextMatch),
+	BoundingBox(BoundingBox),
+	Radius {
+		latitude: f64,
+		longitude: f64,
+		/// In meters
+		radius: f64,
+	},
+}
+
+impl MediaDataFilterArgs {
+	pub async fn into_params(
+		self,
+		db: &PrismaClient,
+	) -> Result<Vec<media_data::WhereParam>, rspc::Error> {
+		use media_data::*;
+
+		Ok(match self {
+			Self::CaptureDate(v) => {
+				vec![
+					not![epoch_time::equals(None)],
+					match v {
+						Range::From(v) => epoch_time::gte(v.timestamp()),
+						Range::To(v) => epoch_time::lte(v.timestamp()),
+					},
+				]
+			}
+			Self::Width(v) => {
+				vec![
+					not![resolution_width::equals(None)],
+					match v {
+						Range::From(v) => resolution_width::gte(v),
+						Range::To(v) => resolution_width::lte(v),
+					},
+				]
+			}
+			Self::Height(v) => {
+				vec![
+					not![resolution_height::equals(None)],
+					match v {
+						Range::From(v) => resolution_height::gte(v),
+						Range::To(v) => resolution_height::lte(v),
+					},
+				]
+			}
+			Self::CameraMake(v) => v
+				.into_param(
+					camera_make::contains,
+					camera_make::starts_with,
+					camera_make::ends_with,
+					|s| camera_make::equals(Some(s)),
+				)
+				.map(|v| vec![v])
+				.unwrap_or_default(),
+			Self::CameraModel(v) => v
+				.into_param(
+					camera_model::contains,
+					camera_model::starts_with,
+					camera_model::ends_with,
+					|s| camera_model::equals(Some(s)),
+				)
+				.map(|v| vec![v])
+				.unwrap_or_default(),
+			Self::Artist(v) => v
+				.into_param(
+					artist::contains,
+					artist::starts_with,
+					artist::ends_with,
+					|s| artist::equals(Some(s)),
+				)
+				.map(|v| vec![v])
+				.unwrap_or_default(),
+			Self::Copyright(v) => v
+				.into_param(
+					copyright::contains,
+					copyright::starts_with,
+					copyright::ends_with,
+					|s| copyright::equals(Some(s)),
+				)
+				.map(|v| vec![v])
+				.unwrap_or_default(),
+			Self::BoundingBox(v) => v.into_params(),
+			Self::Radius {
+				latitude,
+				longitude,
+				radius,
+			} => {
+				// The bounding box narrows it down with the index, then we check the actual distance
+				let object_ids = db
+					.media_data()
+					.find_many(BoundingBox::around(latitude, longitude, radius).into_params())
+					.select(media_data::select!({ object_id latitude longitude }))
+					.exec()
+					.await?
+					.into_iter()
+					.filter(|data| {
+						data.latitude.zip(data.longitude).map_or(false, |point| {
+							haversine_distance((latitude, longitude), point) <= radius
+						})
+					})
+					.map(|data| data.object_id)
+					.collect();
+
+				vec![object_id::in_vec(object_ids)]
+			}
+		})
+	}
+}
+
+const EARTH_RADIUS_IN_METERS: f64 = 6_371_008.8;
+
+/// An area on the map, in degrees. `west` is greater than `east` when the area crosses the antimeridian.
+#[derive(Serialize, Deserialize, Type, Debug, Clone, Copy)]
+pub struct BoundingBox {
+	pub north: f64,
+	pub south: f64,
+	pub east: f64,
+	pub west: f64,
+}
+
+impl BoundingBox {
+	/// The smallest box containing every point within `radius` meters of the center
+	pub fn around(latitude: f64, longitude: f64, radius: f64) -> Self {
+		let delta_latitude = (radius / EARTH_RADIUS_IN_METERS).to_degrees();
+		let north = latitude + delta_latitude;
+		let south = latitude - delta_latitude;
+
+		// Near the poles every longitude is in range
+		if north >= 90.0 || south <= -90.0 {
+			return Self {
+				north: north.min(90.0),
+				south: south.max(-90.0),
+				east: 180.0,
+				west: -180.0,
+			};
+		}
+
+		let delta_longitude = (delta_latitude / latitude.to_radians().cos()).min(180.0);
+
+		let wrap = |longitude: f64| {
+			if longitude > 180.0 {
+				longitude - 360.0
+			} else if longitude < -180.0 {
+				longitude + 360.0
+			} else {
+				longitude
+			}
+		};
+
+		Self {
+			north,
+			south,
+			east: wrap(longitude + delta_longitude),
+			west: wrap(longitude - delta_longitude),
+		}
+	}
+
+	pub fn into_params(self) -> Vec<media_data::WhereParam> {
+		use media_data::*;
+
+		vec![
+			latitude::lte(self.north),
+			latitude::gte(self.south),
+			if self.west <= self.east {
+				prisma_client_rust::and![longitude::gte(self.west), longitude::lte(self.east)]
+			} else {
+				or![longitude::gte(self.west), longitude::lte(self.east)]
+			},
+		]
+	}
+}
+
+/// Great-circle distance in meters between two `(latitude, longitude)` points
+pub fn haversine_distance((lat1, long1): (f64, f64), (lat2, long2): (f64, f64)) -> f64 {
+	let delta_latitude = (lat2 - lat1).to_radians();
+	let delta_longitude = (long2 - long1).to_radians();
+
+	let a = (delta_latitude / 2.0).sin().powi(2)
+		+ lat1.to_radians().cos() * lat2.to_radians().cos() * (delta_longitude / 2.0).sin().powi(2);
+
+	2.0 * EARTH_RADIUS_IN_METERS * a.sqrt().asin()
+}
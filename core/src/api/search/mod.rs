@@ -4,40 +4,57 @@ use crate::{
 		utils::library,
 	},
 	library::Library,
-	location::{non_indexed, LocationError},
-	object::media::thumbnail::get_indexed_thumb_key,
+	location::{non_indexed, symlinks::SymlinkPolicy, LocationError},
+	object::{
+		content_index,
+		media::{perceptual_hash, thumbnail::get_indexed_thumb_key},
+	},
 	util::{unsafe_streamed_query, BatchedStream},
 };
 
 use sd_cache::{CacheNode, Model, Normalise, Reference};
 use sd_prisma::prisma::{self, PrismaClient};
 
-use std::path::PathBuf;
+use std::{
+	collections::{HashMap, HashSet},
+	path::PathBuf,
+};
 
 use async_stream::stream;
-use futures::StreamExt;
+use futures::{Stream, StreamExt};
 use itertools::Either;
+use prisma_client_rust::not;
 use rspc::{alpha::AlphaRouter, ErrorCode};
 use serde::{Deserialize, Serialize};
 use specta::Type;
 
+pub mod duplicates;
+pub mod ephemeral;
+pub mod federated;
 pub mod file_path;
 pub mod media_data;
 pub mod object;
+pub mod query;
 pub mod saved;
 mod utils;
 
-pub use self::{file_path::*, object::*, utils::*};
+pub use self::{file_path::*, media_data::*, object::*, utils::*};
 
 use super::{Ctx, R};
 
 const MAX_TAKE: u8 = 100;
 
+/// How many content matches, as ranked by the content index, we check against the rest of the
+/// filters of a search at once
+const CONTENT_MATCHES_BATCH: i64 = 500;
+
 #[derive(Serialize, Type, Debug)]
 struct SearchData<T: Model> {
 	cursor: Option<Vec<u8>>,
 	items: Vec<Reference<T>>,
 	nodes: Vec<CacheNode>,
+	/// Highlighted excerpts of the matched contents by item id, when searching with a content filter
+	content_snippets: HashMap<i32, String>,
 }
 
 impl<T: Model> Model for SearchData<T> {
@@ -54,6 +71,13 @@ pub enum SearchFilterArgs {
 }
 
 impl SearchFilterArgs {
+	fn content_query(&self) -> Option<&str> {
+		match self {
+			Self::FilePath(FilePathFilterArgs::Content(query)) => Some(query),
+			_ => None,
+		}
+	}
+
 	async fn into_params<T>(
 		self,
 		db: &PrismaClient,
@@ -62,7 +86,7 @@ impl SearchFilterArgs {
 	) -> Result<Vec<T>, rspc::Error> {
 		Ok(match self {
 			Self::FilePath(v) => file_path(v.into_params(db).await?),
-			Self::Object(v) => object(v.into_params()),
+			Self::Object(v) => object(v.into_params(db).await?),
 		})
 	}
 
@@ -83,6 +107,61 @@ impl SearchFilterArgs {
 	}
 }
 
+/// Params of all the `filters` for a file path search
+async fn file_path_params(
+	db: &PrismaClient,
+	filters: &[SearchFilterArgs],
+) -> Result<Vec<prisma::file_path::WhereParam>, rspc::Error> {
+	let mut params = Vec::new();
+
+	for filter in filters {
+		params.extend(filter.clone().into_file_path_params(db).await?);
+	}
+
+	Ok(params)
+}
+
+#[derive(Serialize, Type, Debug)]
+struct EphemeralPathsResultItem {
+	pub entries: Vec<Reference<ExplorerItem>>,
+	pub errors: Vec<rspc::Error>,
+	pub nodes: Vec<CacheNode>,
+}
+
+fn batch_ephemeral_entries(
+	entries: impl Stream<
+			Item = Result<ExplorerItem, Either<rspc::Error, non_indexed::NonIndexedLocationError>>,
+		> + Unpin,
+) -> impl Stream<Item = EphemeralPathsResultItem> {
+	let mut stream = BatchedStream::new(entries);
+
+	stream! {
+		while let Some(result) = stream.next().await {
+			// We optimise for the case of no errors because it should be way more common.
+			let mut entries = Vec::with_capacity(result.len());
+			let mut errors = Vec::with_capacity(0);
+
+			for item in result {
+				match item {
+					Ok(item) => entries.push(item),
+					Err(e) => match e {
+						Either::Left(e) => errors.push(e),
+						Either::Right(e) => errors.push(e.into()),
+					},
+				}
+			}
+
+			let (nodes, entries) = entries.normalise(|item: &ExplorerItem| item.id());
+
+			yield EphemeralPathsResultItem {
+				entries,
+				errors,
+				nodes,
+			};
+		}
+	}
+}
+
 pub fn mount() -> AlphaRouter<Ctx> {
 	R.router()
 		.procedure("ephemeralPaths", {
@@ -102,12 +181,9 @@ pub fn mount() -> AlphaRouter<Ctx> {
 				with_hidden_files: bool,
 				#[specta(optional)]
 				order: Option<EphemeralPathOrder>,
-			}
-			#[derive(Serialize, Type, Debug)]
-			struct EphemeralPathsResultItem {
-				pub entries: Vec<Reference<ExplorerItem>>,
-				pub errors: Vec<rspc::Error>,
-				pub nodes: Vec<CacheNode>,
+				/// Symbolic links are shown by themselves when missing
+				#[specta(optional)]
+				symlink_policy: Option<SymlinkPolicy>,
 			}
 
 			R.with2(library()).subscription(
@@ -116,9 +192,15 @@ pub fn mount() -> AlphaRouter<Ctx> {
 				     path,
 				     with_hidden_files,
 				     order,
+				     symlink_policy,
 				 }| async move {
-					let paths =
-						non_indexed::walk(path, with_hidden_files, node, library, |entries| {
+					let paths = non_indexed::walk(
+						path,
+						with_hidden_files,
+						symlink_policy.unwrap_or(SymlinkPolicy::IndexLink),
+						node,
+						library,
+						|entries| {
 							macro_rules! order_match {
 								($order:ident, [$(($variant:ident, |$i:ident| $func:expr)),+]) => {{
 									match $order {
@@ -150,35 +232,20 @@ pub fn mount() -> AlphaRouter<Ctx> {
 									]
 								)
 							}
-						})
-						.await?;
-
-					let mut stream = BatchedStream::new(paths);
-					Ok(unsafe_streamed_query(stream! {
-						while let Some(result) = stream.next().await {
-							// We optimise for the case of no errors because it should be way more common.
-							let mut entries = Vec::with_capacity(result.len());
-							let mut errors = Vec::with_capacity(0);
-
-							for item in result {
-								match item {
-									Ok(item) => entries.push(item),
-									Err(e) => match e {
-										Either::Left(e) => errors.push(e),
-										Either::Right(e) => errors.push(e.into()),
-									},
-								}
-							}
+						},
+					)
+					.await?;
 
-							let (nodes, entries) = entries.normalise(|item: &ExplorerItem| item.id());
+					Ok(unsafe_streamed_query(batch_ephemeral_entries(paths)))
+				},
+			)
+		})
+		.procedure("ephemeralSearch", {
+			R.with2(library()).subscription(
+				|(node, library), args: ephemeral::EphemeralSearchArgs| async move {
+					let entries = ephemeral::search(node, &library, args).await?;
 
-							yield EphemeralPathsResultItem {
-								entries,
-								errors,
-								nodes,
-							};
-						}
-					}))
+					Ok(unsafe_streamed_query(batch_ephemeral_entries(entries)))
 				},
 			)
 		})
@@ -192,6 +259,9 @@ pub fn mount() -> AlphaRouter<Ctx> {
 				order_and_pagination: Option<file_path::OrderAndPagination>,
 				#[serde(default)]
 				filters: Vec<SearchFilterArgs>,
+				/// A textual query, compiled into more filters and an order when none was given
+				#[specta(optional)]
+				query: Option<String>,
 				#[serde(default = "default_group_directories")]
 				group_directories: bool,
 			}
@@ -204,43 +274,148 @@ pub fn mount() -> AlphaRouter<Ctx> {
 				|(node, library),
 				 FilePathSearchArgs {
 				     take,
-				     order_and_pagination,
-				     filters,
+				     mut order_and_pagination,
+				     mut filters,
+				     query,
 				     group_directories,
 				 }| async move {
 					let Library { db, .. } = library.as_ref();
 
-					let params = {
-						let mut params = Vec::new();
+					if let Some(query) = query {
+						let compiled = query::compile(&query, db).await?;
 
-						for filter in filters {
-							params.extend(filter.into_file_path_params(db).await?);
+						filters.extend(compiled.filters);
+
+						if order_and_pagination.is_none() {
+							order_and_pagination =
+								compiled.order.map(file_path::OrderAndPagination::OrderOnly);
 						}
+					}
 
-						params
-					};
+					let content_queries = filters
+						.iter()
+						.filter_map(SearchFilterArgs::content_query)
+						.map(str::to_string)
+						.collect::<Vec<_>>();
+
+					// Without an explicit order, content searches are sorted by relevance
+					let order_by_rank = !content_queries.is_empty()
+						&& matches!(
+							order_and_pagination,
+							None | Some(file_path::OrderAndPagination::Offset { order: None, .. })
+						);
+
+					let file_paths = if order_by_rank {
+						filters.retain(|filter| filter.content_query().is_none());
+
+						let take = take.unwrap_or(MAX_TAKE) as usize;
+						let mut to_skip = match order_and_pagination {
+							Some(file_path::OrderAndPagination::Offset { offset, .. }) => {
+								offset.max(0) as usize
+							}
+							_ => 0,
+						};
 
-					let mut query = db.file_path().find_many(params);
+						let mut file_paths = Vec::with_capacity(take);
+						let mut ranked_skip = 0;
 
-					if let Some(take) = take {
-						query = query.take(take as i64);
-					}
+						while file_paths.len() < take {
+							let ids = content_index::ranked_file_path_ids(
+								db,
+								&content_queries,
+								ranked_skip,
+								CONTENT_MATCHES_BATCH,
+							)
+							.await?;
 
-					// WARN: this order_by for grouping directories MUST always come before the other order_by
-					if group_directories {
-						query = query
-							.order_by(prisma::file_path::is_dir::order(prisma::SortOrder::Desc));
-					}
+							if ids.is_empty() {
+								break;
+							}
 
-					// WARN: this order_by for sorting data MUST always come after the other order_by
-					if let Some(order_and_pagination) = order_and_pagination {
-						order_and_pagination.apply(&mut query, group_directories)
-					}
+							let batch_len = ids.len() as i64;
+							ranked_skip += batch_len;
+
+							let mut params = file_path_params(db, &filters).await?;
+							params.push(prisma::file_path::id::in_vec(ids.clone()));
+
+							let mut found = db
+								.file_path()
+								.find_many(params)
+								.include(file_path_with_object::include())
+								.exec()
+								.await?
+								.into_iter()
+								.map(|file_path| (file_path.id, file_path))
+								.collect::<HashMap<_, _>>();
+
+							// Keeping the order of the ranked ids
+							for id in ids {
+								let Some(file_path) = found.remove(&id) else {
+									continue;
+								};
+
+								if to_skip > 0 {
+									to_skip -= 1;
+								} else if file_paths.len() < take {
+									file_paths.push(file_path);
+								}
+							}
 
-					let file_paths = query
-						.include(file_path_with_object::include())
-						.exec()
-						.await?;
+							if batch_len < CONTENT_MATCHES_BATCH {
+								break;
+							}
+						}
+
+						file_paths
+					} else {
+						let mut query = db
+							.file_path()
+							.find_many(file_path_params(db, &filters).await?);
+
+						if let Some(take) = take {
+							query = query.take(take as i64);
+						}
+
+						// WARN: this order_by for grouping directories MUST always come before the other order_by
+						if group_directories {
+							query = query.order_by(prisma::file_path::is_dir::order(
+								prisma::SortOrder::Desc,
+							));
+						}
+
+						// WARN: this order_by for sorting data MUST always come after the other order_by
+						if let Some(order_and_pagination) = order_and_pagination {
+							order_and_pagination.apply(&mut query, group_directories)
+						}
+
+						query
+							.include(file_path_with_object::include())
+							.exec()
+							.await?
+					};
+
+					let snippets_by_object = content_index::snippets(
+						db,
+						&content_queries,
+						file_paths
+							.iter()
+							.filter_map(|file_path| file_path.object_id)
+							.collect::<HashSet<_>>(),
+					)
+					.await?
+					.into_iter()
+					.map(|content_snippet| (content_snippet.object_id, content_snippet.snippet))
+					.collect::<HashMap<_, _>>();
+
+					let content_snippets = file_paths
+						.iter()
+						.filter_map(|file_path| {
+							file_path
+								.object_id
+								.and_then(|object_id| snippets_by_object.get(&object_id))
+								.map(|snippet| (file_path.id, snippet.clone()))
+						})
+						.collect();
 
 					let mut items = Vec::with_capacity(file_paths.len());
 
@@ -270,6 +445,7 @@ pub fn mount() -> AlphaRouter<Ctx> {
 						items,
 						cursor: None,
 						nodes,
+						content_snippets,
 					})
 				},
 			)
@@ -281,25 +457,65 @@ pub fn mount() -> AlphaRouter<Ctx> {
 			struct Args {
 				#[specta(default)]
 				filters: Vec<SearchFilterArgs>,
+				#[specta(optional)]
+				query: Option<String>,
 			}
 
 			R.with2(library())
-				.query(|(_, library), Args { filters }| async move {
+				.query(|(_, library), Args { mut filters, query }| async move {
 					let Library { db, .. } = library.as_ref();
 
-					Ok(db
-						.file_path()
-						.count({
-							let mut params = Vec::new();
+					if let Some(query) = query {
+						filters.extend(query::compile(&query, db).await?.filters);
+					}
 
-							for filter in filters {
-								params.extend(filter.into_file_path_params(db).await?);
-							}
+					let content_queries = filters
+						.iter()
+						.filter_map(SearchFilterArgs::content_query)
+						.map(str::to_string)
+						.collect::<Vec<_>>();
 
-							params
-						})
-						.exec()
-						.await? as u32)
+					if content_queries.is_empty() {
+						return Ok(db
+							.file_path()
+							.count(file_path_params(db, &filters).await?)
+							.exec()
+							.await? as u32);
+					}
+
+					// Counting the content matches in batches, as we do when searching them
+					filters.retain(|filter| filter.content_query().is_none());
+
+					let mut count = 0;
+					let mut ranked_skip = 0;
+
+					loop {
+						let ids = content_index::ranked_file_path_ids(
+							db,
+							&content_queries,
+							ranked_skip,
+							CONTENT_MATCHES_BATCH,
+						)
+						.await?;
+
+						if ids.is_empty() {
+							break;
+						}
+
+						let batch_len = ids.len() as i64;
+						ranked_skip += batch_len;
+
+						let mut params = file_path_params(db, &filters).await?;
+						params.push(prisma::file_path::id::in_vec(ids));
+
+						count += db.file_path().count(params).exec().await?;
+
+						if batch_len < CONTENT_MATCHES_BATCH {
+							break;
+						}
+					}
+
+					Ok(count as u32)
 				})
 		})
 		.procedure("objects", {
@@ -311,17 +527,35 @@ pub fn mount() -> AlphaRouter<Ctx> {
 				order_and_pagination: Option<object::OrderAndPagination>,
 				#[serde(default)]
 				filters: Vec<SearchFilterArgs>,
+				/// A textual query, compiled into more filters and an order when none was given
+				#[specta(optional)]
+				query: Option<String>,
 			}
 
 			R.with2(library()).query(
 				|(node, library),
 				 ObjectSearchArgs {
 				     take,
-				     order_and_pagination,
-				     filters,
+				     mut order_and_pagination,
+				     mut filters,
+				     query,
 				 }| async move {
 					let Library { db, .. } = library.as_ref();
 
+					if let Some(query) = query {
+						let compiled = query::compile(&query, db).await?;
+
+						filters.extend(compiled.filters);
+
+						// Only orders over the object itself make sense here
+						if order_and_pagination.is_none() {
+							if let Some(FilePathOrder::Object(order)) = compiled.order {
+								order_and_pagination =
+									Some(object::OrderAndPagination::OrderOnly(*order));
+							}
+						}
+					}
+
 					let take = take.max(MAX_TAKE);
 
 					let mut query = db
@@ -390,6 +624,7 @@ pub fn mount() -> AlphaRouter<Ctx> {
 						nodes,
 						items,
 						cursor,
+						content_snippets: HashMap::new(),
 					})
 				},
 			)
@@ -401,12 +636,18 @@ pub fn mount() -> AlphaRouter<Ctx> {
 			struct Args {
 				#[serde(default)]
 				filters: Vec<SearchFilterArgs>,
+				#[specta(optional)]
+				query: Option<String>,
 			}
 
 			R.with2(library())
-				.query(|(_, library), Args { filters }| async move {
+				.query(|(_, library), Args { mut filters, query }| async move {
 					let Library { db, .. } = library.as_ref();
 
+					if let Some(query) = query {
+						filters.extend(query::compile(&query, db).await?.filters);
+					}
+
 					Ok(db
 						.object()
 						.count({
@@ -422,5 +663,235 @@ pub fn mount() -> AlphaRouter<Ctx> {
 						.await? as u32)
 				})
 		})
+		.procedure("parseQuery", {
+			#[derive(Serialize, Type, Debug)]
+			#[serde(rename_all = "camelCase", tag = "type", content = "value")]
+			enum ParseQueryResult {
+				Ok(query::CompiledQuery),
+				Err(Vec<query::QueryError>),
+			}
+
+			R.with2(library())
+				.query(|(_, library), input: String| async move {
+					match query::compile(&input, &library.db).await {
+						Ok(compiled) => Ok(ParseQueryResult::Ok(compiled)),
+						Err(query::SearchQueryError::Invalid(errors)) => {
+							Ok(ParseQueryResult::Err(errors.0))
+						}
+						Err(e) => Err(e.into()),
+					}
+				})
+		})
+		.procedure("duplicates", {
+			R.with2(library()).query(
+				|(node, library), args: duplicates::DuplicatesArgs| async move {
+					duplicates::find(&node, &library, args).await
+				},
+			)
+		})
+		.procedure("verifyDuplicates", {
+			R.with2(library()).mutation(
+				|(node, library), filters: duplicates::DuplicatesFilters| async move {
+					duplicates::verify(&node, &library, filters)
+						.await
+						.map_err(Into::into)
+				},
+			)
+		})
+		.procedure("federatedPaths", {
+			R.query(|node, args: federated::FederatedSearchArgs| async move {
+				federated::search(&node, args).await
+			})
+		})
+		.procedure("similarImages", {
+			#[derive(Deserialize, Type, Debug)]
+			#[serde(rename_all = "camelCase")]
+			#[specta(inline)]
+			struct Args {
+				object_id: prisma::object::id::Type,
+				/// How many of the 64 bits of the perceptual hashes may differ
+				#[specta(optional)]
+				max_distance: Option<u32>,
+			}
+
+			#[derive(Serialize, Type, Debug)]
+			#[serde(rename_all = "camelCase")]
+			struct SimilarImages {
+				/// The most similar first
+				items: Vec<Reference<ExplorerItem>>,
+				nodes: Vec<CacheNode>,
+				/// Hamming distance to the perceptual hash of the searched image by object id
+				distances: HashMap<i32, u32>,
+			}
+
+			R.with2(library()).query(
+				|(node, library),
+				 Args {
+				     object_id,
+				     max_distance,
+				 }| async move {
+					let Library { db, .. } = library.as_ref();
+
+					let Some(similar) = perceptual_hash::find_similar(
+						db,
+						object_id,
+						max_distance.unwrap_or(perceptual_hash::DEFAULT_MAX_DISTANCE),
+					)
+					.await?
+					else {
+						return Err(rspc::Error::new(
+							ErrorCode::NotFound,
+							"Object doesn't have a perceptual hash, it may not be an image \
+							or its thumbnail wasn't generated yet"
+								.to_string(),
+						));
+					};
+
+					let mut objects = db
+						.object()
+						.find_many(vec![prisma::object::id::in_vec(
+							similar.iter().map(|(id, _)| *id).collect(),
+						)])
+						.include(object_with_file_paths::include())
+						.exec()
+						.await?
+						.into_iter()
+						.map(|object| (object.id, object))
+						.collect::<HashMap<_, _>>();
+
+					let mut items = Vec::with_capacity(similar.len());
+
+					for (object_id, _) in &similar {
+						let Some(object) = objects.remove(object_id) else {
+							continue;
+						};
+
+						let cas_id = object.file_paths.iter().find_map(|fp| fp.cas_id.as_ref());
+
+						let thumbnail_exists_locally = if let Some(cas_id) = cas_id {
+							library
+								.thumbnail_exists(&node, cas_id)
+								.await
+								.map_err(LocationError::from)?
+						} else {
+							false
+						};
+
+						items.push(ExplorerItem::Object {
+							thumbnail: cas_id
+								.filter(|_| thumbnail_exists_locally)
+								.map(|cas_id| get_indexed_thumb_key(cas_id, library.id)),
+							item: object,
+						});
+					}
+
+					let (nodes, items) = items.normalise(|item| item.id());
+
+					Ok(SimilarImages {
+						items,
+						nodes,
+						distances: similar.into_iter().collect(),
+					})
+				},
+			)
+		})
+		.procedure("mediaLocations", {
+			/// How many clusters fit in the width of a single map tile
+			const CLUSTERS_PER_TILE: f64 = 4.0;
+
+			#[derive(Deserialize, Type, Debug)]
+			#[serde(rename_all = "camelCase")]
+			#[specta(inline)]
+			struct Args {
+				/// Web map zoom level, from 0 (the whole world in a single tile) up to 22
+				zoom: u8,
+				#[specta(optional)]
+				bounds: Option<BoundingBox>,
+				#[serde(default)]
+				filters: Vec<SearchFilterArgs>,
+			}
+
+			#[derive(Serialize, Type, Debug)]
+			#[serde(rename_all = "camelCase")]
+			struct MediaLocationCluster {
+				latitude: f64,
+				longitude: f64,
+				count: u32,
+				/// Any object in this cluster, so the map has something to preview
+				object_id: prisma::object::id::Type,
+			}
+
+			R.with2(library()).query(
+				|(_, library),
+				 Args {
+				     zoom,
+				     bounds,
+				     filters,
+				 }| async move {
+					let Library { db, .. } = library.as_ref();
+
+					let mut params = vec![
+						not![prisma::media_data::latitude::equals(None)],
+						not![prisma::media_data::longitude::equals(None)],
+					];
+
+					if let Some(bounds) = bounds {
+						params.extend(bounds.into_params());
+					}
+
+					if !filters.is_empty() {
+						let mut object_params = Vec::new();
+
+						for filter in filters {
+							object_params.extend(filter.into_object_params(db).await?);
+						}
+
+						params.push(prisma::media_data::object::is(object_params));
+					}
+
+					let cell_size = 360.0 / 2f64.powi(zoom.min(22) as i32) / CLUSTERS_PER_TILE;
+
+					let mut clusters = HashMap::<(i64, i64), (f64, f64, u32, i32)>::new();
+
+					for data in db
+						.media_data()
+						.find_many(params)
+						.select(prisma::media_data::select!({ object_id latitude longitude }))
+						.exec()
+						.await?
+					{
+						let Some((latitude, longitude)) = data.latitude.zip(data.longitude) else {
+							continue;
+						};
+
+						// Offsetting the coordinates so they're always positive and `as` floors them
+						let cell = (
+							((latitude + 90.0) / cell_size) as i64,
+							((longitude + 180.0) / cell_size) as i64,
+						);
+
+						let (latitude_sum, longitude_sum, count, _) = clusters
+							.entry(cell)
+							.or_insert((0.0, 0.0, 0, data.object_id));
+
+						*latitude_sum += latitude;
+						*longitude_sum += longitude;
+						*count += 1;
+					}
+
+					Ok(clusters
+						.into_values()
+						.map(|(latitude_sum, longitude_sum, count, object_id)| {
+							MediaLocationCluster {
+								latitude: latitude_sum / count as f64,
+								longitude: longitude_sum / count as f64,
+								count,
+								object_id,
+							}
+						})
+						.collect::<Vec<_>>())
+				},
+			)
+		})
 		.merge("saved.", saved::mount())
 }
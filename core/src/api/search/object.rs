@@ -2,6 +2,8 @@
 
 use sd_prisma::prisma::{self, label_on_object, object, tag_on_object};
 
+use sd_utils::db::size_in_bytes_to_db;
+
 use chrono::{DateTime, FixedOffset};
 use prisma_client_rust::{not, or, OrderByQuery, PaginatedQuery, WhereQuery};
 use serde::{Deserialize, Serialize};
@@ -18,6 +20,7 @@ pub enum ObjectCursor {
 	None,
 	DateAccessed(CursorOrderItem<DateTime<FixedOffset>>),
 	Kind(CursorOrderItem<i32>),
+	SizeInBytes(CursorOrderItem<f64>),
 }
 
 impl ObjectCursor {
@@ -52,6 +55,7 @@ impl ObjectCursor {
 			}
 			Self::Kind(item) => arm!(kind, item),
 			Self::DateAccessed(item) => arm!(date_accessed, item),
+			Self::SizeInBytes(item) => arm!(size_in_bytes, item),
 		}
 	}
 }
@@ -61,6 +65,7 @@ impl ObjectCursor {
 pub enum ObjectOrder {
 	DateAccessed(SortOrder),
 	Kind(SortOrder),
+	SizeInBytes(SortOrder),
 	MediaData(Box<MediaDataOrder>),
 }
 
@@ -69,6 +74,7 @@ impl ObjectOrder {
 		(*match self {
 			Self::DateAccessed(v) => v,
 			Self::Kind(v) => v,
+			Self::SizeInBytes(v) => v,
 			Self::MediaData(v) => return v.get_sort_order(),
 		})
 		.into()
@@ -81,6 +87,7 @@ impl ObjectOrder {
 		match self {
 			Self::DateAccessed(_) => date_accessed::order(dir),
 			Self::Kind(_) => kind::order(dir),
+			Self::SizeInBytes(_) => size_in_bytes::order(dir),
 			Self::MediaData(v) => media_data::order(vec![v.into_param()]),
 		}
 	}
@@ -115,13 +122,18 @@ pub enum ObjectFilterArgs {
 	Tags(InOrNotIn<i32>),
 	Labels(InOrNotIn<i32>),
 	DateAccessed(Range<chrono::DateTime<FixedOffset>>),
+	SizeInBytes(#[specta(type = Range<f64>)] Range<u64>),
+	MediaData(MediaDataFilterArgs),
 }
 
 impl ObjectFilterArgs {
-	pub fn into_params(self) -> Vec<object::WhereParam> {
+	pub async fn into_params(
+		self,
+		db: &prisma::PrismaClient,
+	) -> Result<Vec<object::WhereParam>, rspc::Error> {
 		use object::*;
 
-		match self {
+		Ok(match self {
 			Self::Favorite(v) => vec![favorite::equals(Some(v))],
 			Self::Hidden(v) => v.to_param().map(|v| vec![v]).unwrap_or_default(),
 			Self::Tags(v) => v
@@ -151,7 +163,14 @@ impl ObjectFilterArgs {
 					},
 				]
 			}
-		}
+			Self::SizeInBytes(v) => {
+				vec![match v {
+					Range::From(v) => size_in_bytes::gte(size_in_bytes_to_db(v)),
+					Range::To(v) => size_in_bytes::lte(size_in_bytes_to_db(v)),
+				}]
+			}
+			Self::MediaData(v) => vec![media_data::is(v.into_params(db).await?)],
+		})
 	}
 }
 
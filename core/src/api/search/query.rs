@@ -0,0 +1,885 @@
+use sd_file_ext::kind::ObjectKind;
+use sd_file_path_helper::attributes::PERMISSION_BITS;
+use sd_prisma::prisma::{label, tag, PrismaClient};
+
+use std::fmt;
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use rspc::ErrorCode;
+use serde::Serialize;
+use specta::Type;
+use strum::IntoEnumIterator;
+
+use super::{
+	file_path::{FilePathFilterArgs, FilePathOrder},
+	media_data::{MediaDataFilterArgs, MediaDataOrder},
+	object::{ObjectFilterArgs, ObjectOrder},
+	utils::{InOrNotIn, Range, SortOrder, TextMatch},
+	SearchFilterArgs,
+};
+
+/// Positions are counted in chars instead of bytes, so the frontend can use them to highlight the query
+#[derive(Serialize, Type, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+	pub start: usize,
+	pub end: usize,
+}
+
+#[derive(thiserror::Error, Serialize, Type, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", tag = "type", content = "value")]
+pub enum QueryErrorKind {
+	#[error("missing closing quote")]
+	UnterminatedQuote,
+	#[error("missing value")]
+	EmptyValue,
+	#[error("unknown filter `{0}`")]
+	UnknownKey(String),
+	#[error("unknown kind `{0}`")]
+	UnknownKind(String),
+	#[error("invalid date `{0}`, expected YYYY-MM-DD or a RFC 3339 timestamp")]
+	InvalidDate(String),
+	#[error("invalid value `{0}`, expected true or false")]
+	InvalidBool(String),
+	#[error("invalid size `{0}`, expected a number with an optional unit like 500KB or 2GiB")]
+	InvalidSize(String),
+	#[error("invalid permissions `{0}`, expected octal permission bits like 755")]
+	InvalidPermissions(String),
+	#[error("invalid id `{0}`, expected a numeric user or group id")]
+	InvalidId(String),
+	#[error("unknown sort field `{0}`")]
+	UnknownSortField(String),
+	#[error("only one sort is allowed")]
+	DuplicateSort,
+	#[error("`{0}` can't be negated")]
+	NegationNotSupported(String),
+	#[error("unknown tag `{0}`")]
+	UnknownTag(String),
+	#[error("unknown label `{0}`")]
+	UnknownLabel(String),
+}
+
+#[derive(thiserror::Error, Serialize, Type, Debug, Clone, PartialEq, Eq)]
+#[error("{kind} at {}..{}", .span.start, .span.end)]
+pub struct QueryError {
+	pub kind: QueryErrorKind,
+	pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct QueryErrors(pub Vec<QueryError>);
+
+impl QueryErrors {
+	/// Whether the query is only invalid because of tags or labels missing from the library
+	pub fn only_unknown_names(&self) -> bool {
+		self.0.iter().all(|QueryError { kind, .. }| {
+			matches!(
+				kind,
+				QueryErrorKind::UnknownTag(_) | QueryErrorKind::UnknownLabel(_)
+			)
+		})
+	}
+}
+
+impl std::error::Error for QueryErrors {}
+
+impl fmt::Display for QueryErrors {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "invalid search query: ")?;
+
+		for (idx, error) in self.0.iter().enumerate() {
+			if idx > 0 {
+				write!(f, "; ")?;
+			}
+			write!(f, "{error}")?;
+		}
+
+		Ok(())
+	}
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SearchQueryError {
+	#[error(transparent)]
+	Invalid(#[from] QueryErrors),
+	#[error("database error: {0}")]
+	Database(#[from] prisma_client_rust::QueryError),
+}
+
+impl From<SearchQueryError> for rspc::Error {
+	fn from(err: SearchQueryError) -> Self {
+		match err {
+			SearchQueryError::Invalid(_) => {
+				rspc::Error::with_cause(ErrorCode::BadRequest, err.to_string(), err)
+			}
+			SearchQueryError::Database(_) => {
+				rspc::Error::with_cause(ErrorCode::InternalServerError, err.to_string(), err)
+			}
+		}
+	}
+}
+
+impl From<QueryErrors> for rspc::Error {
+	fn from(err: QueryErrors) -> Self {
+		SearchQueryError::from(err).into()
+	}
+}
+
+/// The filters and order described by a textual query, ready to be used in `search.paths` or `search.objects`
+#[derive(Serialize, Type, Debug)]
+pub struct CompiledQuery {
+	pub filters: Vec<SearchFilterArgs>,
+	pub order: Option<FilePathOrder>,
+}
+
+#[derive(Debug, Clone)]
+enum Term {
+	Filter(SearchFilterArgs),
+	// Tags and labels are written by name, so they need the database to become filters
+	Tags(InOrNotIn<String>),
+	Labels(InOrNotIn<String>),
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ParsedQuery {
+	terms: Vec<(Term, Span)>,
+	order: Option<FilePathOrder>,
+}
+
+/// Parses a query like `kind:image tag:Receipts ext:pdf,jpg modified:>2024-01-01 "invoice"`.
+///
+/// Every `key:value` pair becomes a filter and they all must match, bare words and quoted phrases
+/// are matched against file names. Lists are comma separated, and `kind`, `ext`, `tag` and `label`
+/// can be negated with a leading `-`. Dates are in UTC and accept `>`, `>=`, `<`, `<=` and `from..to`.
+/// Sizes take the same operators, a bare size means "at least", and units are either decimal (`KB`, `MB`...)
+/// or binary (`KiB`, `MiB`...). `perm` takes octal permission bits which must all be set, `owner`
+/// and `group` take numeric ids and can be negated too. `sort:field` or `sort:-field` picks the order.
+/// All errors are returned at once, each with its position.
+pub fn parse(input: &str) -> Result<ParsedQuery, QueryErrors> {
+	let (tokens, mut errors) = tokenize(input);
+
+	let mut query = ParsedQuery::default();
+
+	for token in tokens {
+		let span = token.span;
+		if let Err(kind) = query.push(token) {
+			errors.push(QueryError { kind, span });
+		}
+	}
+
+	if errors.is_empty() {
+		Ok(query)
+	} else {
+		errors.sort_by_key(|error| error.span.start);
+		Err(QueryErrors(errors))
+	}
+}
+
+/// Parses and compiles a query in one go
+pub async fn compile(input: &str, db: &PrismaClient) -> Result<CompiledQuery, SearchQueryError> {
+	parse(input)?.compile(db).await
+}
+
+impl ParsedQuery {
+	pub async fn compile(self, db: &PrismaClient) -> Result<CompiledQuery, SearchQueryError> {
+		let needs_tags = self
+			.terms
+			.iter()
+			.any(|(term, _)| matches!(term, Term::Tags(_)));
+		let needs_labels = self
+			.terms
+			.iter()
+			.any(|(term, _)| matches!(term, Term::Labels(_)));
+
+		let tags = if needs_tags {
+			db.tag()
+				.find_many(vec![])
+				.select(tag::select!({ id name }))
+				.exec()
+				.await?
+				.into_iter()
+				.filter_map(|tag| tag.name.map(|name| (name, tag.id)))
+				.collect()
+		} else {
+			vec![]
+		};
+
+		let labels = if needs_labels {
+			db.label()
+				.find_many(vec![])
+				.select(label::select!({ id name }))
+				.exec()
+				.await?
+				.into_iter()
+				.map(|label| (label.name, label.id))
+				.collect()
+		} else {
+			vec![]
+		};
+
+		let mut errors = vec![];
+		let mut filters = Vec::with_capacity(self.terms.len());
+
+		for (term, span) in self.terms {
+			match term {
+				Term::Filter(filter) => filters.push(filter),
+				Term::Tags(names) => {
+					match resolve_names(names, &tags, QueryErrorKind::UnknownTag) {
+						Ok(ids) => {
+							filters.push(SearchFilterArgs::Object(ObjectFilterArgs::Tags(ids)))
+						}
+						Err(kind) => errors.push(QueryError { kind, span }),
+					}
+				}
+				Term::Labels(names) => {
+					match resolve_names(names, &labels, QueryErrorKind::UnknownLabel) {
+						Ok(ids) => {
+							filters.push(SearchFilterArgs::Object(ObjectFilterArgs::Labels(ids)))
+						}
+						Err(kind) => errors.push(QueryError { kind, span }),
+					}
+				}
+			}
+		}
+
+		if errors.is_empty() {
+			Ok(CompiledQuery {
+				filters,
+				order: self.order,
+			})
+		} else {
+			Err(QueryErrors(errors).into())
+		}
+	}
+
+	fn push(
+		&mut self,
+		Token {
+			negated,
+			key,
+			value,
+			span,
+		}: Token,
+	) -> Result<(), QueryErrorKind> {
+		let Some(key) = key else {
+			if negated {
+				return Err(QueryErrorKind::NegationNotSupported("name".to_string()));
+			}
+
+			if value.is_empty() {
+				return Err(QueryErrorKind::EmptyValue);
+			}
+
+			self.push_filter(
+				span,
+				SearchFilterArgs::FilePath(FilePathFilterArgs::Name(TextMatch::Contains(value))),
+			);
+
+			return Ok(());
+		};
+
+		let key = key.to_lowercase();
+
+		if value.is_empty() {
+			return Err(QueryErrorKind::EmptyValue);
+		}
+
+		if negated
+			&& !matches!(
+				key.as_str(),
+				"kind" | "ext" | "extension" | "tag" | "label" | "owner" | "uid" | "group" | "gid"
+			) {
+			return Err(QueryErrorKind::NegationNotSupported(key));
+		}
+
+		match key.as_str() {
+			"kind" => {
+				let kinds = split_list(&value)?
+					.into_iter()
+					.map(parse_kind)
+					.collect::<Result<Vec<_>, _>>()?;
+
+				self.push_filter(
+					span,
+					SearchFilterArgs::Object(ObjectFilterArgs::Kind(in_or_not_in(negated, kinds))),
+				);
+			}
+			"ext" | "extension" => {
+				let mut extensions = vec![];
+
+				for extension in split_list(&value)? {
+					let extension = extension.trim_start_matches('.');
+
+					// We store extensions as they're written on disk, so we try the usual casings
+					for variant in [
+						extension.to_string(),
+						extension.to_lowercase(),
+						extension.to_uppercase(),
+					] {
+						if !extensions.contains(&variant) {
+							extensions.push(variant);
+						}
+					}
+				}
+
+				self.push_filter(
+					span,
+					SearchFilterArgs::FilePath(FilePathFilterArgs::Extension(in_or_not_in(
+						negated, extensions,
+					))),
+				);
+			}
+			"tag" => self
+				.terms
+				.push((Term::Tags(in_or_not_in(negated, owned_list(&value)?)), span)),
+			"label" => self.terms.push((
+				Term::Labels(in_or_not_in(negated, owned_list(&value)?)),
+				span,
+			)),
+			"name" => self.push_filter(
+				span,
+				SearchFilterArgs::FilePath(FilePathFilterArgs::Name(TextMatch::Contains(value))),
+			),
+			"content" => self.push_filter(
+				span,
+				SearchFilterArgs::FilePath(FilePathFilterArgs::Content(value)),
+			),
+			"created" => {
+				for range in parse_date_ranges(&value)? {
+					self.push_filter(
+						span,
+						SearchFilterArgs::FilePath(FilePathFilterArgs::CreatedAt(range)),
+					);
+				}
+			}
+			"modified" => {
+				for range in parse_date_ranges(&value)? {
+					self.push_filter(
+						span,
+						SearchFilterArgs::FilePath(FilePathFilterArgs::ModifiedAt(range)),
+					);
+				}
+			}
+			"indexed" => {
+				for range in parse_date_ranges(&value)? {
+					self.push_filter(
+						span,
+						SearchFilterArgs::FilePath(FilePathFilterArgs::IndexedAt(range)),
+					);
+				}
+			}
+			"accessed" => {
+				for range in parse_date_ranges(&value)? {
+					self.push_filter(
+						span,
+						SearchFilterArgs::Object(ObjectFilterArgs::DateAccessed(match range {
+							Range::From(date) => Range::From(date.into()),
+							Range::To(date) => Range::To(date.into()),
+						})),
+					);
+				}
+			}
+			"taken" => {
+				for range in parse_date_ranges(&value)? {
+					self.push_filter(
+						span,
+						SearchFilterArgs::Object(ObjectFilterArgs::MediaData(
+							MediaDataFilterArgs::CaptureDate(range),
+						)),
+					);
+				}
+			}
+			"hidden" => self.push_filter(
+				span,
+				SearchFilterArgs::FilePath(FilePathFilterArgs::Hidden(parse_bool(&value)?)),
+			),
+			"favorite" => self.push_filter(
+				span,
+				SearchFilterArgs::Object(ObjectFilterArgs::Favorite(parse_bool(&value)?)),
+			),
+			"size" => {
+				for range in parse_size_ranges(&value)? {
+					self.push_filter(
+						span,
+						SearchFilterArgs::FilePath(FilePathFilterArgs::SizeInBytes(range)),
+					);
+				}
+			}
+			"perm" | "permissions" => self.push_filter(
+				span,
+				SearchFilterArgs::FilePath(FilePathFilterArgs::Permissions(parse_permissions(
+					&value,
+				)?)),
+			),
+			"owner" | "uid" => self.push_filter(
+				span,
+				SearchFilterArgs::FilePath(FilePathFilterArgs::Owner(in_or_not_in(
+					negated,
+					parse_ids(&value)?,
+				))),
+			),
+			"group" | "gid" => self.push_filter(
+				span,
+				SearchFilterArgs::FilePath(FilePathFilterArgs::Group(in_or_not_in(
+					negated,
+					parse_ids(&value)?,
+				))),
+			),
+			"xattr" => self.push_filter(
+				span,
+				SearchFilterArgs::FilePath(FilePathFilterArgs::Xattr(value)),
+			),
+			"sort" => {
+				if self.order.is_some() {
+					return Err(QueryErrorKind::DuplicateSort);
+				}
+
+				self.order = Some(parse_sort(&value)?);
+			}
+			_ => return Err(QueryErrorKind::UnknownKey(key)),
+		}
+
+		Ok(())
+	}
+
+	fn push_filter(&mut self, span: Span, filter: SearchFilterArgs) {
+		self.terms.push((Term::Filter(filter), span));
+	}
+}
+
+#[derive(Debug)]
+struct Token {
+	negated: bool,
+	key: Option<String>,
+	value: String,
+	span: Span,
+}
+
+fn tokenize(input: &str) -> (Vec<Token>, Vec<QueryError>) {
+	let chars = input.chars().collect::<Vec<_>>();
+
+	let mut tokens = vec![];
+	let mut errors = vec![];
+
+	let mut i = 0;
+	while i < chars.len() {
+		if chars[i].is_whitespace() {
+			i += 1;
+			continue;
+		}
+
+		let start = i;
+
+		let negated = chars[i] == '-' && chars.get(i + 1).map_or(false, |c| !c.is_whitespace());
+		if negated {
+			i += 1;
+		}
+
+		// A key is a run of letters right before a colon, anything else is a bare word
+		let key_end = chars[i..]
+			.iter()
+			.position(|c| !c.is_alphabetic())
+			.map_or(chars.len(), |pos| i + pos);
+
+		let key = (key_end > i && chars.get(key_end) == Some(&':')).then(|| {
+			let key = chars[i..key_end].iter().collect::<String>();
+			i = key_end + 1;
+			key
+		});
+
+		let value = if chars.get(i) == Some(&'"') {
+			let Some(len) = chars[i + 1..].iter().position(|&c| c == '"') else {
+				errors.push(QueryError {
+					kind: QueryErrorKind::UnterminatedQuote,
+					span: Span {
+						start: i,
+						end: chars.len(),
+					},
+				});
+				break;
+			};
+
+			let value = chars[i + 1..i + 1 + len].iter().collect::<String>();
+			i += len + 2;
+			value
+		} else {
+			let end = chars[i..]
+				.iter()
+				.position(|c| c.is_whitespace())
+				.map_or(chars.len(), |pos| i + pos);
+
+			let value = chars[i..end].iter().collect::<String>();
+			i = end;
+			value
+		};
+
+		tokens.push(Token {
+			negated,
+			key,
+			value,
+			span: Span { start, end: i },
+		});
+	}
+
+	(tokens, errors)
+}
+
+fn split_list(value: &str) -> Result<Vec<&str>, QueryErrorKind> {
+	let items = value
+		.split(',')
+		.map(str::trim)
+		.filter(|item| !item.is_empty())
+		.collect::<Vec<_>>();
+
+	if items.is_empty() {
+		Err(QueryErrorKind::EmptyValue)
+	} else {
+		Ok(items)
+	}
+}
+
+fn owned_list(value: &str) -> Result<Vec<String>, QueryErrorKind> {
+	split_list(value).map(|items| items.into_iter().map(str::to_string).collect())
+}
+
+fn in_or_not_in<T>(negated: bool, values: Vec<T>) -> InOrNotIn<T> {
+	if negated {
+		InOrNotIn::NotIn(values)
+	} else {
+		InOrNotIn::In(values)
+	}
+}
+
+fn parse_kind(value: &str) -> Result<i32, QueryErrorKind> {
+	ObjectKind::iter()
+		.find(|kind| kind.to_string().eq_ignore_ascii_case(value))
+		.map(|kind| kind as i32)
+		.ok_or_else(|| QueryErrorKind::UnknownKind(value.to_string()))
+}
+
+fn parse_bool(value: &str) -> Result<bool, QueryErrorKind> {
+	match value.to_lowercase().as_str() {
+		"true" | "yes" => Ok(true),
+		"false" | "no" => Ok(false),
+		_ => Err(QueryErrorKind::InvalidBool(value.to_string())),
+	}
+}
+
+fn parse_permissions(value: &str) -> Result<u32, QueryErrorKind> {
+	u32::from_str_radix(value.trim_start_matches("0o"), 8)
+		.ok()
+		.filter(|bits| *bits <= PERMISSION_BITS)
+		.ok_or_else(|| QueryErrorKind::InvalidPermissions(value.to_string()))
+}
+
+fn parse_ids(value: &str) -> Result<Vec<u32>, QueryErrorKind> {
+	split_list(value)?
+		.into_iter()
+		.map(|id| {
+			id.parse()
+				.map_err(|_| QueryErrorKind::InvalidId(id.to_string()))
+		})
+		.collect()
+}
+
+fn parse_sort(value: &str) -> Result<FilePathOrder, QueryErrorKind> {
+	let (field, order) = match value.strip_prefix('-') {
+		Some(field) => (field, SortOrder::Desc),
+		None => (value, SortOrder::Asc),
+	};
+
+	Ok(match field.to_lowercase().as_str() {
+		"name" => FilePathOrder::Name(order),
+		"size" => FilePathOrder::SizeInBytes(order),
+		"created" => FilePathOrder::DateCreated(order),
+		"modified" => FilePathOrder::DateModified(order),
+		"indexed" => FilePathOrder::DateIndexed(order),
+		"accessed" => FilePathOrder::Object(Box::new(ObjectOrder::DateAccessed(order))),
+		"kind" => FilePathOrder::Object(Box::new(ObjectOrder::Kind(order))),
+		"taken" => FilePathOrder::Object(Box::new(ObjectOrder::MediaData(Box::new(
+			MediaDataOrder::EpochTime(order),
+		)))),
+		_ => return Err(QueryErrorKind::UnknownSortField(field.to_string())),
+	})
+}
+
+fn parse_date_ranges(value: &str) -> Result<Vec<Range<DateTime<Utc>>>, QueryErrorKind> {
+	let one_milli = Duration::milliseconds(1);
+
+	if let Some((from, to)) = value.split_once("..") {
+		let mut ranges = vec![];
+
+		if !from.is_empty() {
+			ranges.push(Range::From(parse_date(from)?.0));
+		}
+
+		if !to.is_empty() {
+			ranges.push(Range::To(parse_date(to)?.1));
+		}
+
+		return if ranges.is_empty() {
+			Err(QueryErrorKind::EmptyValue)
+		} else {
+			Ok(ranges)
+		};
+	}
+
+	Ok(if let Some(date) = value.strip_prefix(">=") {
+		vec![Range::From(parse_date(date)?.0)]
+	} else if let Some(date) = value.strip_prefix('>') {
+		vec![Range::From(parse_date(date)?.1 + one_milli)]
+	} else if let Some(date) = value.strip_prefix("<=") {
+		vec![Range::To(parse_date(date)?.1)]
+	} else if let Some(date) = value.strip_prefix('<') {
+		vec![Range::To(parse_date(date)?.0 - one_milli)]
+	} else {
+		let (start, end) = parse_date(value)?;
+		vec![Range::From(start), Range::To(end)]
+	})
+}
+
+/// Returns the first and last instants of a day, or the same instant twice for full timestamps
+fn parse_date(value: &str) -> Result<(DateTime<Utc>, DateTime<Utc>), QueryErrorKind> {
+	if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+		let start = Utc.from_utc_datetime(&date.and_time(NaiveTime::MIN));
+		return Ok((start, start + Duration::days(1) - Duration::milliseconds(1)));
+	}
+
+	DateTime::parse_from_rfc3339(value)
+		.map(|date| {
+			let date = date.with_timezone(&Utc);
+			(date, date)
+		})
+		.map_err(|_| QueryErrorKind::InvalidDate(value.to_string()))
+}
+
+fn parse_size_ranges(value: &str) -> Result<Vec<Range<u64>>, QueryErrorKind> {
+	if let Some((from, to)) = value.split_once("..") {
+		let mut ranges = vec![];
+
+		if !from.is_empty() {
+			ranges.push(Range::From(parse_size(from)?));
+		}
+
+		if !to.is_empty() {
+			ranges.push(Range::To(parse_size(to)?));
+		}
+
+		return if ranges.is_empty() {
+			Err(QueryErrorKind::EmptyValue)
+		} else {
+			Ok(ranges)
+		};
+	}
+
+	Ok(vec![if let Some(size) = value.strip_prefix(">=") {
+		Range::From(parse_size(size)?)
+	} else if let Some(size) = value.strip_prefix('>') {
+		Range::From(parse_size(size)?.saturating_add(1))
+	} else if let Some(size) = value.strip_prefix("<=") {
+		Range::To(parse_size(size)?)
+	} else if let Some(size) = value.strip_prefix('<') {
+		Range::To(parse_size(size)?.saturating_sub(1))
+	} else {
+		Range::From(parse_size(value)?)
+	}])
+}
+
+fn parse_size(value: &str) -> Result<u64, QueryErrorKind> {
+	let invalid = || QueryErrorKind::InvalidSize(value.to_string());
+
+	let unit_start = value
+		.find(|c: char| !c.is_ascii_digit() && c != '.')
+		.unwrap_or(value.len());
+	let (number, unit) = value.split_at(unit_start);
+
+	let multiplier: u64 = match unit.to_lowercase().as_str() {
+		"" | "b" => 1,
+		"kb" => 1000,
+		"mb" => 1000_u64.pow(2),
+		"gb" => 1000_u64.pow(3),
+		"tb" => 1000_u64.pow(4),
+		"kib" => 1024,
+		"mib" => 1024_u64.pow(2),
+		"gib" => 1024_u64.pow(3),
+		"tib" => 1024_u64.pow(4),
+		_ => return Err(invalid()),
+	};
+
+	if let Ok(number) = number.parse::<u64>() {
+		return number.checked_mul(multiplier).ok_or_else(invalid);
+	}
+
+	// Fractional sizes like 1.5GB
+	let size = number.parse::<f64>().map_err(|_| invalid())? * multiplier as f64;
+	if size.is_finite() && size < u64::MAX as f64 {
+		Ok(size.round() as u64)
+	} else {
+		Err(invalid())
+	}
+}
+
+fn resolve_names(
+	names: InOrNotIn<String>,
+	known: &[(String, i32)],
+	unknown_error: fn(String) -> QueryErrorKind,
+) -> Result<InOrNotIn<i32>, QueryErrorKind> {
+	let resolve = |names: Vec<String>| {
+		names
+			.into_iter()
+			.map(|name| {
+				known
+					.iter()
+					.find(|(known_name, _)| known_name.eq_ignore_ascii_case(&name))
+					.map(|(_, id)| *id)
+					.ok_or_else(|| unknown_error(name))
+			})
+			.collect::<Result<Vec<_>, _>>()
+	};
+
+	Ok(match names {
+		InOrNotIn::In(names) => InOrNotIn::In(resolve(names)?),
+		InOrNotIn::NotIn(names) => InOrNotIn::NotIn(resolve(names)?),
+	})
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::panic)]
+mod tests {
+	use super::*;
+
+	use serde_json::{json, Value};
+
+	fn filters(input: &str) -> Vec<Value> {
+		parse(input)
+			.unwrap()
+			.terms
+			.into_iter()
+			.map(|(term, _)| match term {
+				Term::Filter(filter) => serde_json::to_value(filter).unwrap(),
+				Term::Tags(names) => json!({ "tags": serde_json::to_value(names).unwrap() }),
+				Term::Labels(names) => json!({ "labels": serde_json::to_value(names).unwrap() }),
+			})
+			.collect()
+	}
+
+	fn error_kinds(input: &str) -> Vec<(QueryErrorKind, usize, usize)> {
+		parse(input)
+			.unwrap_err()
+			.0
+			.into_iter()
+			.map(|QueryError { kind, span }| (kind, span.start, span.end))
+			.collect()
+	}
+
+	#[test]
+	fn full_query() {
+		let query = parse(
+			r#"kind:image tag:Receipts ext:pdf,jpg modified:>2024-01-01 "invoice" sort:-modified"#,
+		)
+		.unwrap();
+
+		assert!(matches!(
+			query.order,
+			Some(FilePathOrder::DateModified(SortOrder::Desc))
+		));
+
+		assert_eq!(
+			filters(r#"kind:image tag:Receipts ext:pdf,jpg modified:>2024-01-01 "invoice""#),
+			vec![
+				json!({ "object": { "kind": { "in": [ObjectKind::Image as i32] } } }),
+				json!({ "tags": { "in": ["Receipts"] } }),
+				json!({ "filePath": { "extension": { "in": ["pdf", "PDF", "jpg", "JPG"] } } }),
+				json!({ "filePath": { "modifiedAt": { "from": "2024-01-02T00:00:00Z" } } }),
+				json!({ "filePath": { "name": { "contains": "invoice" } } }),
+			]
+		);
+	}
+
+	#[test]
+	fn negation_and_ranges() {
+		assert_eq!(
+			filters("-kind:video,audio created:2023-01-01..2023-12-31"),
+			vec![
+				json!({ "object": { "kind": { "notIn": [ObjectKind::Video as i32, ObjectKind::Audio as i32] } } }),
+				json!({ "filePath": { "createdAt": { "from": "2023-01-01T00:00:00Z" } } }),
+				json!({ "filePath": { "createdAt": { "to": "2023-12-31T23:59:59.999Z" } } }),
+			]
+		);
+	}
+
+	#[test]
+	fn sizes() {
+		assert_eq!(
+			filters("size:>2GiB size:1.5MB..10mb size:<1kb size:100"),
+			vec![
+				json!({ "filePath": { "sizeInBytes": { "from": 2 * 1024 * 1024 * 1024_u64 + 1 } } }),
+				json!({ "filePath": { "sizeInBytes": { "from": 1_500_000 } } }),
+				json!({ "filePath": { "sizeInBytes": { "to": 10_000_000 } } }),
+				json!({ "filePath": { "sizeInBytes": { "to": 999 } } }),
+				json!({ "filePath": { "sizeInBytes": { "from": 100 } } }),
+			]
+		);
+
+		assert_eq!(
+			error_kinds("size:big size:5XB"),
+			vec![
+				(QueryErrorKind::InvalidSize("big".to_string()), 0, 8),
+				(QueryErrorKind::InvalidSize("5XB".to_string()), 9, 17),
+			]
+		);
+	}
+
+	#[test]
+	fn permissions_and_ownership() {
+		assert_eq!(
+			filters("perm:111 -owner:0 gid:20,1000 xattr:com.apple.quarantine"),
+			vec![
+				json!({ "filePath": { "permissions": 0o111 } }),
+				json!({ "filePath": { "owner": { "notIn": [0] } } }),
+				json!({ "filePath": { "group": { "in": [20, 1000] } } }),
+				json!({ "filePath": { "xattr": "com.apple.quarantine" } }),
+			]
+		);
+
+		assert_eq!(
+			error_kinds("perm:999 uid:root"),
+			vec![
+				(QueryErrorKind::InvalidPermissions("999".to_string()), 0, 8),
+				(QueryErrorKind::InvalidId("root".to_string()), 9, 17),
+			]
+		);
+	}
+
+	#[test]
+	fn errors_have_positions() {
+		assert_eq!(
+			error_kinds("foo:bar kind:nope  -name:x \"open"),
+			vec![
+				(QueryErrorKind::UnknownKey("foo".to_string()), 0, 7),
+				(QueryErrorKind::UnknownKind("nope".to_string()), 8, 17),
+				(
+					QueryErrorKind::NegationNotSupported("name".to_string()),
+					19,
+					26
+				),
+				(QueryErrorKind::UnterminatedQuote, 27, 32),
+			]
+		);
+
+		assert_eq!(
+			error_kinds("sort:name sort:size modified:yesterday"),
+			vec![
+				(QueryErrorKind::DuplicateSort, 10, 19),
+				(QueryErrorKind::InvalidDate("yesterday".to_string()), 20, 38),
+			]
+		);
+	}
+
+	#[test]
+	fn spans_count_chars() {
+		assert_eq!(
+			error_kinds("naïve kind:nope"),
+			vec![(QueryErrorKind::UnknownKind("nope".to_string()), 6, 15)]
+		);
+	}
+}
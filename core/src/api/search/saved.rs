@@ -1,17 +1,63 @@
-use crate::{api::utils::library, invalidate_query, library::Library};
+use crate::{
+	api::{
+		locations::{file_path_with_object, ExplorerItem},
+		utils::{library, InvalidateOperationEvent},
+		CoreEvent,
+	},
+	invalidate_query,
+	library::Library,
+	location::LocationError,
+	object::media::thumbnail::get_indexed_thumb_key,
+	Node,
+};
 
-use sd_prisma::{prisma::saved_search, prisma_sync};
+use sd_cache::{CacheNode, Normalise, Reference};
+use sd_prisma::{
+	prisma::{file_path, saved_search, PrismaClient},
+	prisma_sync,
+};
 use sd_sync::{option_sync_db_entry, sync_db_entry, OperationFactory};
 use sd_utils::chain_optional_iter;
 
+use std::{collections::HashSet, time::Duration};
+
+use async_stream::stream;
 use chrono::{DateTime, FixedOffset, Utc};
-use rspc::alpha::AlphaRouter;
+use prisma_client_rust::QueryError;
+use rspc::{alpha::AlphaRouter, ErrorCode};
 use serde::{de::IgnoredAny, Deserialize, Serialize};
 use specta::Type;
+use tokio::{
+	sync::broadcast::{
+		self,
+		error::{RecvError, TryRecvError},
+	},
+	time::sleep,
+};
 use tracing::error;
 use uuid::Uuid;
 
-use super::{Ctx, R};
+use super::{query, Ctx, SearchFilterArgs, R};
+
+/// The indexer and the watcher invalidate searches many times in a row, so we wait this long
+/// for things to settle before refreshing a live saved search
+const LIVE_REFRESH_DELAY: Duration = Duration::from_millis(500);
+
+/// How many file paths that started matching a live saved search we fetch in a single query
+const ADDED_CHUNK_SIZE: usize = 1000;
+
+/// Changes to the file paths matching a saved search, so it can be shown as a live folder
+#[derive(Serialize, Type, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedSearchDiff {
+	/// File paths that started matching the search
+	pub added: Vec<Reference<ExplorerItem>>,
+	/// Ids of the file paths that stopped matching the search
+	pub removed: Vec<file_path::id::Type>,
+	pub nodes: Vec<CacheNode>,
+	/// How many file paths match the search after this diff
+	pub count: u32,
+}
 
 pub(crate) fn mount() -> AlphaRouter<Ctx> {
 	R.router()
@@ -25,6 +71,9 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 					pub search: Option<String>,
 					#[specta(optional)]
 					pub filters: Option<String>,
+					/// Compiled into more filters, so it must be a valid query
+					#[specta(optional)]
+					pub query: Option<String>,
 					#[specta(optional)]
 					pub description: Option<String>,
 					#[specta(optional)]
@@ -33,6 +82,11 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 
 				|(_, library), args: Args| async move {
 					let Library { db, sync, .. } = library.as_ref();
+
+					if let Some(query) = &args.query {
+						query::parse(query)?;
+					}
+
 					let pub_id = Uuid::new_v4().as_bytes().to_vec();
 					let date_created: DateTime<FixedOffset> = Utc::now().into();
 
@@ -57,6 +111,7 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 								saved_search::filters
 							),
 							option_sync_db_entry!(args.search, saved_search::search),
+							option_sync_db_entry!(args.query, saved_search::query),
 							option_sync_db_entry!(args.description, saved_search::description),
 							option_sync_db_entry!(args.icon, saved_search::icon),
 						],
@@ -95,6 +150,60 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 						.await?)
 				})
 		})
+		.procedure("count", {
+			R.with2(library()).query(
+				|(_, library), search_id: saved_search::id::Type| async move {
+					let Library { db, .. } = library.as_ref();
+
+					let params = file_path_params(db, search_id).await?.ok_or_else(|| {
+						rspc::Error::new(ErrorCode::NotFound, "search not found".into())
+					})?;
+
+					Ok(db.file_path().count(params).exec().await? as u32)
+				},
+			)
+		})
+		.procedure("live", {
+			R.with2(library()).subscription(
+				|(node, library), search_id: saved_search::id::Type| async move {
+					let params =
+						file_path_params(&library.db, search_id)
+							.await?
+							.ok_or_else(|| {
+								rspc::Error::new(ErrorCode::NotFound, "search not found".into())
+							})?;
+
+					// Subscribing before the first query, so we don't miss changes in between
+					let mut event_bus_rx = node.event_bus.0.subscribe();
+
+					let mut matching = matching_ids(&library.db, params).await?;
+
+					Ok(stream! {
+						yield SavedSearchDiff {
+							added: vec![],
+							removed: vec![],
+							nodes: vec![],
+							count: matching.len() as u32,
+						};
+
+						while wait_for_changes(&mut event_bus_rx).await {
+							match file_path_params(&library.db, search_id).await {
+								Ok(Some(params)) => {
+									match refresh(&node, &library, params, &mut matching).await {
+										Ok(Some(diff)) => yield diff,
+										Ok(None) => {}
+										Err(e) => error!("Failed to refresh live saved search: {e:#?}"),
+									}
+								}
+								// The saved search was deleted, so there is nothing left to watch
+								Ok(None) => break,
+								Err(e) => error!("Failed to refresh live saved search: {e:#?}"),
+							}
+						}
+					})
+				},
+			)
+		})
 		.procedure("list", {
 			R.with2(library()).query(|(_, library), _: ()| async move {
 				Ok(library
@@ -114,10 +223,16 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 					icon
 					search
 					filters
+					query
 				});
 
 				|(_, library), (id, args): (saved_search::id::Type, Args)| async move {
 					let Library { db, sync, .. } = library.as_ref();
+
+					if let Some(Some(query)) = &args.query {
+						query::parse(query)?;
+					}
+
 					let updated_at = Utc::now().into();
 
 					let search = db
@@ -138,6 +253,7 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 							option_sync_db_entry!(args.icon.flatten(), saved_search::icon),
 							option_sync_db_entry!(args.search.flatten(), saved_search::search),
 							option_sync_db_entry!(args.filters.flatten(), saved_search::filters),
+							option_sync_db_entry!(args.query.flatten(), saved_search::query),
 						],
 					)
 					.into_iter()
@@ -205,3 +321,162 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 				})
 		})
 }
+
+/// Builds the params to find the file paths matching a saved search, from both its filters
+/// and its query. Returns `None` if the saved search doesn't exist.
+async fn file_path_params(
+	db: &PrismaClient,
+	search_id: saved_search::id::Type,
+) -> Result<Option<Vec<file_path::WhereParam>>, rspc::Error> {
+	let Some(search) = db
+		.saved_search()
+		.find_unique(saved_search::id::equals(search_id))
+		.select(saved_search::select!({ filters query }))
+		.exec()
+		.await?
+	else {
+		return Ok(None);
+	};
+
+	let mut filters = if let Some(filters) = &search.filters {
+		serde_json::from_str::<Vec<SearchFilterArgs>>(filters).map_err(|e| {
+			rspc::Error::with_cause(
+				ErrorCode::InternalServerError,
+				"Failed to parse saved search filters".to_string(),
+				e,
+			)
+		})?
+	} else {
+		vec![]
+	};
+
+	if let Some(query) = &search.query {
+		filters.extend(query::compile(query, db).await?.filters);
+	}
+
+	let mut params = Vec::new();
+
+	for filter in filters {
+		params.extend(filter.into_file_path_params(db).await?);
+	}
+
+	Ok(Some(params))
+}
+
+async fn matching_ids(
+	db: &PrismaClient,
+	params: Vec<file_path::WhereParam>,
+) -> Result<HashSet<file_path::id::Type>, QueryError> {
+	db.file_path()
+		.find_many(params)
+		.select(file_path::select!({ id }))
+		.exec()
+		.await
+		.map(|file_paths| {
+			file_paths
+				.into_iter()
+				.map(|file_path| file_path.id)
+				.collect()
+		})
+}
+
+/// Waits for something that may change which file paths match a search, batching everything
+/// that arrives in the meantime. Returns `false` when there will be no more events.
+async fn wait_for_changes(event_bus_rx: &mut broadcast::Receiver<CoreEvent>) -> bool {
+	loop {
+		match event_bus_rx.recv().await {
+			Ok(CoreEvent::InvalidateOperation(InvalidateOperationEvent::Single(event)))
+				if matches!(
+					event.key,
+					"search.paths" | "search.objects" | "search.saved.get"
+				) =>
+			{
+				break
+			}
+			Ok(CoreEvent::InvalidateOperation(InvalidateOperationEvent::All)) => break,
+			Ok(_) => {}
+			// We don't know what we missed, so any of it may have changed the results
+			Err(RecvError::Lagged(_)) => break,
+			Err(RecvError::Closed) => return false,
+		}
+	}
+
+	sleep(LIVE_REFRESH_DELAY).await;
+
+	// The refresh we're about to do already covers anything that arrived while we slept
+	loop {
+		match event_bus_rx.try_recv() {
+			Ok(_) | Err(TryRecvError::Lagged(_)) => {}
+			Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+		}
+	}
+
+	true
+}
+
+/// Diffs the file paths currently matching `params` against the ones that matched before,
+/// returning `None` if nothing changed
+async fn refresh(
+	node: &Node,
+	library: &Library,
+	params: Vec<file_path::WhereParam>,
+	matching: &mut HashSet<file_path::id::Type>,
+) -> Result<Option<SavedSearchDiff>, rspc::Error> {
+	let Library { db, .. } = library;
+
+	let current = matching_ids(db, params).await?;
+
+	let removed = matching.difference(&current).copied().collect::<Vec<_>>();
+	let added_ids = current.difference(matching).copied().collect::<Vec<_>>();
+
+	if removed.is_empty() && added_ids.is_empty() {
+		return Ok(None);
+	}
+
+	let mut added = Vec::with_capacity(added_ids.len());
+
+	for file_path in db
+		._batch(
+			added_ids
+				.chunks(ADDED_CHUNK_SIZE)
+				.map(|ids| {
+					db.file_path()
+						.find_many(vec![file_path::id::in_vec(ids.to_vec())])
+						.include(file_path_with_object::include())
+				})
+				.collect::<Vec<_>>(),
+		)
+		.await?
+		.into_iter()
+		.flatten()
+	{
+		let thumbnail_exists_locally = if let Some(cas_id) = &file_path.cas_id {
+			library
+				.thumbnail_exists(node, cas_id)
+				.await
+				.map_err(LocationError::from)?
+		} else {
+			false
+		};
+
+		added.push(ExplorerItem::Path {
+			thumbnail: file_path
+				.cas_id
+				.as_ref()
+				.filter(|_| thumbnail_exists_locally)
+				.map(|i| get_indexed_thumb_key(i, library.id)),
+			item: file_path,
+		});
+	}
+
+	let (nodes, added) = added.normalise(|item| item.id());
+
+	*matching = current;
+
+	Ok(Some(SavedSearchDiff {
+		added,
+		removed,
+		nodes,
+		count: matching.len() as u32,
+	}))
+}
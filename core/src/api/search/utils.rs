@@ -10,6 +10,16 @@ pub enum Range<T> {
 	To(T),
 }
 
+impl<T: PartialOrd> Range<T> {
+	/// Checks a value in memory, with the same inclusive bounds we use on database filters
+	pub fn contains(&self, value: &T) -> bool {
+		match self {
+			Self::From(from) => value >= from,
+			Self::To(to) => value <= to,
+		}
+	}
+}
+
 #[derive(Serialize, Deserialize, Type, Debug, Clone, Copy)]
 #[serde(rename_all = "PascalCase")]
 pub enum SortOrder {
@@ -72,6 +82,18 @@ impl<T> InOrNotIn<T> {
 		}
 	}
 
+	/// Checks a value in memory, empty lists match everything as they aren't turned into
+	/// database filters either
+	pub fn matches(&self, value: &T) -> bool
+	where
+		T: PartialEq,
+	{
+		match self {
+			Self::In(v) => v.is_empty() || v.contains(value),
+			Self::NotIn(v) => !v.contains(value),
+		}
+	}
+
 	pub fn into_param<TParam>(
 		self,
 		in_fn: fn(Vec<T>) -> TParam,
@@ -105,6 +127,22 @@ impl TextMatch {
 		}
 	}
 
+	/// Checks a value in memory, ignoring ASCII case except for `Equals`, like SQLite does
+	pub fn matches(&self, value: &str) -> bool {
+		if self.is_empty() {
+			return true;
+		}
+
+		let value_lowercase = value.to_ascii_lowercase();
+
+		match self {
+			Self::Contains(v) => value_lowercase.contains(&v.to_ascii_lowercase()),
+			Self::StartsWith(v) => value_lowercase.starts_with(&v.to_ascii_lowercase()),
+			Self::EndsWith(v) => value_lowercase.ends_with(&v.to_ascii_lowercase()),
+			Self::Equals(v) => value == v,
+		}
+	}
+
 	// 3. Update the to_param method of TextMatch
 	pub fn into_param<TParam>(
 		self,
@@ -12,7 +12,11 @@ pub(crate) fn mount() -> AlphaRouter<Ctx> {
 			let volumes = get_volumes().await;
 
 			let (nodes, items) = volumes.normalise(|i| {
-				// TODO: This is a really bad key. Once we hook up volumes with the DB fix this!
+				if let Some(identifier) = i.identifier() {
+					return identifier;
+				}
+
+				// Volumes we can't identify are keyed by where they're mounted
 				blake3::hash(
 					&i.mount_points
 						.iter()
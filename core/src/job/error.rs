@@ -7,6 +7,7 @@ use crate::{
 };
 
 use sd_crypto::Error as CryptoError;
+use sd_task_system::TaskSystemError;
 use sd_utils::{db::MissingFieldError, error::FileIOError};
 
 use std::time::Duration;
@@ -54,6 +55,8 @@ pub enum JobError {
 	Timeout(Duration),
 	#[error("critical job error: {0}")]
 	Critical(&'static str),
+	#[error(transparent)]
+	TaskSystem(#[from] TaskSystemError),
 
 	// Specific job errors
 	#[error(transparent)]
@@ -1,7 +1,7 @@
 use crate::{
 	job::{worker::Worker, DynJob, Job, JobError},
 	library::Library,
-	location::indexer::indexer_job::IndexerJobInit,
+	location::{indexer::indexer_job::IndexerJobInit, relinker::LocationRelinkerJobInit},
 	object::{
 		file_identifier::file_identifier_job::FileIdentifierJobInit,
 		fs::{
@@ -404,6 +404,7 @@ fn initialize_resumable_job(
 			FileCopierJobInit,
 			FileDeleterJobInit,
 			FileEraserJobInit,
+			LocationRelinkerJobInit,
 		]
 	)
 }
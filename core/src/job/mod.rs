@@ -26,11 +26,13 @@ use uuid::Uuid;
 mod error;
 mod manager;
 mod report;
+mod tasks;
 mod worker;
 
 pub use error::*;
 pub use manager::*;
 pub use report::*;
+pub use tasks::*;
 pub use worker::*;
 
 pub type JobResult = Result<JobMetadata, JobError>;
@@ -890,6 +892,8 @@ async fn handle_init_phase<SJob: StatefulJob>(
 								"Total paused time {:?} Job <id='{id}', name='{name}'>",
 								paused_time.elapsed()
 							);
+							worker_ctx.resume();
+
 							status = JobStatus::Running;
 
 							continue 'messages;
@@ -1117,6 +1121,8 @@ async fn handle_single_step<SJob: StatefulJob>(
 								"Total paused time {:?} Job <id='{id}', name='{name}'>",
 								paused_time.elapsed(),
 							);
+							worker_ctx.resume();
+
 							status = JobStatus::Running;
 
 							continue 'messages;
@@ -0,0 +1,126 @@
+use sd_task_system::{AnyTaskOutput, IntoTask, TaskHandle, TaskOutput, TaskStatus};
+
+use std::{marker::PhantomData, mem};
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use tokio::{spawn, sync::watch};
+use tracing::{trace, warn};
+
+use super::{JobError, WorkerContext};
+
+/// The tasks a job step dispatched to the node's task system, all giving back the same `Output`.
+///
+/// The tasks are paused and resumed along with the job, and canceled when this is dropped before
+/// they finish, as happens to the running step when its job is canceled or shutdown.
+pub struct StepTasks<'ctx, Output> {
+	ctx: &'ctx WorkerContext,
+	paused_rx: watch::Receiver<bool>,
+	handles: FuturesUnordered<TaskHandle<JobError>>,
+	_output: PhantomData<fn() -> Output>,
+}
+
+impl<'ctx, Output: AnyTaskOutput> StepTasks<'ctx, Output> {
+	pub fn new(ctx: &'ctx WorkerContext) -> Self {
+		Self {
+			ctx,
+			paused_rx: ctx.paused_watcher(),
+			handles: FuturesUnordered::new(),
+			_output: PhantomData,
+		}
+	}
+
+	/// Runs a single task to completion, following the job like [`StepTasks`] do
+	pub async fn run(
+		ctx: &'ctx WorkerContext,
+		task: impl IntoTask<JobError>,
+	) -> Result<Output, JobError> {
+		let mut tasks = Self::new(ctx);
+		tasks.dispatch(task).await;
+
+		tasks
+			.next()
+			.await
+			.unwrap_or(Err(JobError::Critical("dispatched task vanished")))
+	}
+
+	pub async fn dispatch(&mut self, task: impl IntoTask<JobError>) {
+		self.handles
+			.push(self.ctx.node.task_system.dispatch(task).await);
+	}
+
+	pub async fn dispatch_many(&mut self, tasks: Vec<impl IntoTask<JobError>>) {
+		if !tasks.is_empty() {
+			self.handles
+				.extend(self.ctx.node.task_system.dispatch_many(tasks).await);
+		}
+	}
+
+	/// Waits for any of the tasks to finish, in no particular order, giving back `None` when there
+	/// are no tasks left
+	pub async fn next(&mut self) -> Option<Result<Output, JobError>> {
+		loop {
+			tokio::select! {
+				maybe_status = self.handles.next() => {
+					return maybe_status.map(|status| match status? {
+						TaskStatus::Done(TaskOutput::Out(out)) => out
+							.downcast::<Output>()
+							.map(|out| *out)
+							.map_err(|_| JobError::Critical("unexpected task output type")),
+						TaskStatus::Done(TaskOutput::Empty) => {
+							Err(JobError::Critical("task finished without output"))
+						}
+						TaskStatus::Error(e) => Err(e),
+						TaskStatus::Canceled
+						| TaskStatus::ForcedAbortion
+						| TaskStatus::Shutdown(_) => {
+							Err(JobError::Critical("task interrupted outside of its job"))
+						}
+					});
+				}
+
+				Ok(()) = self.paused_rx.changed() => {
+					let paused = *self.paused_rx.borrow_and_update();
+
+					trace!(
+						"Forwarding job {} to {} tasks",
+						if paused { "pause" } else { "resume" },
+						self.handles.len()
+					);
+
+					for handle in self.handles.iter() {
+						let res = if paused {
+							handle.pause().await
+						} else {
+							handle.resume().await
+						};
+
+						if let Err(e) = res {
+							return Some(Err(e.into()));
+						}
+					}
+				}
+			}
+		}
+	}
+}
+
+impl<Output> Drop for StepTasks<'_, Output> {
+	fn drop(&mut self) {
+		if self.handles.is_empty() {
+			return;
+		}
+
+		let handles = mem::take(&mut self.handles);
+
+		spawn(async move {
+			for handle in handles.iter() {
+				if let Err(e) = handle.cancel().await {
+					warn!(
+						"Failed to cancel task of a dropped job step <task_id='{}'>: {e:#?}",
+						handle.task_id()
+					);
+				}
+			}
+		});
+	}
+}
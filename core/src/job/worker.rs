@@ -69,6 +69,7 @@ pub struct WorkerContext {
 	pub library: Arc<Library>,
 	pub node: Arc<Node>,
 	pub(super) events_tx: chan::Sender<WorkerEvent>,
+	pub(super) paused_tx: watch::Sender<bool>,
 }
 
 impl fmt::Debug for WorkerContext {
@@ -87,11 +88,22 @@ impl Drop for WorkerContext {
 }
 impl WorkerContext {
 	pub fn pause(&self) {
+		self.paused_tx.send_replace(true);
 		if self.events_tx.send_blocking(WorkerEvent::Paused).is_err() {
 			error!("Error sending worker context pause event");
 		}
 	}
 
+	pub(super) fn resume(&self) {
+		self.paused_tx.send_replace(false);
+	}
+
+	/// Watches if the job is paused, so work done outside of the job's steps, like the tasks
+	/// dispatched to the task system, can be paused and resumed along with it
+	pub fn paused_watcher(&self) -> watch::Receiver<bool> {
+		self.paused_tx.subscribe()
+	}
+
 	pub fn progress_msg(&self, msg: String) {
 		self.progress(vec![JobReportUpdate::Message(msg)]);
 	}
@@ -375,6 +387,7 @@ impl Worker {
 							library,
 							node,
 							events_tx,
+							paused_tx: watch::channel(false).0,
 						},
 						commands_rx,
 					)
@@ -2,12 +2,14 @@
 
 use crate::{
 	api::{CoreEvent, Router},
+	job::JobError,
 	location::LocationManagerError,
 	object::media::thumbnail::actor::Thumbnailer,
 };
 
 #[cfg(feature = "ai")]
 use sd_ai::image_labeler::{DownloadModelError, ImageLabeler, YoloV8};
+use sd_task_system::TaskSystem;
 
 use api::notifications::{Notification, NotificationData, NotificationId};
 use chrono::{DateTime, Utc};
@@ -62,6 +64,7 @@ pub struct Node {
 	pub event_bus: (broadcast::Sender<CoreEvent>, broadcast::Receiver<CoreEvent>),
 	pub notifications: Notifications,
 	pub thumbnailer: Thumbnailer,
+	pub task_system: TaskSystem<JobError>,
 	pub files_over_p2p_flag: Arc<AtomicBool>,
 	pub cloud_sync_flag: Arc<AtomicBool>,
 	pub env: Arc<env::Env>,
@@ -131,6 +134,7 @@ impl Node {
 					config.preferences_watcher(),
 				)
 				.await,
+				task_system: TaskSystem::new(),
 				config,
 				event_bus,
 				libraries,
@@ -251,6 +255,7 @@ impl Node {
 		info!("Spacedrive shutting down...");
 		self.thumbnailer.shutdown().await;
 		self.jobs.shutdown().await;
+		self.task_system.shutdown().await;
 		self.p2p.shutdown().await;
 		#[cfg(feature = "ai")]
 		if let Some(image_labeller) = &self.image_labeller {
@@ -5,7 +5,10 @@ use crate::{
 
 use sd_p2p2::{Identity, IdentityOrRemoteIdentity};
 use sd_prisma::prisma::{file_path, indexer_rule, instance, location, node, PrismaClient};
-use sd_utils::{db::maybe_missing, error::FileIOError};
+use sd_utils::{
+	db::{maybe_missing, size_in_bytes_to_db},
+	error::FileIOError,
+};
 
 use std::{
 	path::Path,
@@ -226,7 +229,7 @@ impl LibraryConfig {
 										.map(|size_in_bytes| {
 											let size =
 												if let Ok(size) = size_in_bytes.parse::<u64>() {
-													Some(size.to_be_bytes().to_vec())
+													Some(size)
 												} else {
 													error!(
 														"File path <id='{}'> had invalid size: '{}'",
@@ -238,7 +241,12 @@ impl LibraryConfig {
 											db.file_path().update(
 												file_path::id::equals(path.id),
 												vec![
-													file_path::size_in_bytes_bytes::set(size),
+													file_path::size_in_bytes_bytes::set(
+														size.map(|size| size.to_be_bytes().to_vec()),
+													),
+													file_path::size_in_bytes_number::set(
+														size.map(size_in_bytes_to_db),
+													),
 													file_path::size_in_bytes::set(None),
 												],
 											)
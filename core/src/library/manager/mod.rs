@@ -9,6 +9,7 @@ use crate::{
 	object::tag,
 	p2p, sync,
 	util::{mpscrr, MaybeUndefined},
+	volume::watcher::spawn_volume_watcher,
 	Node,
 };
 
@@ -133,17 +134,11 @@ impl Libraries {
 					Err(e) => return Err(FileIOError::from((db_path, e)).into()),
 				}
 
-				let _library_arc = self
+				let library_arc = self
 					.load(library_id, &db_path, config_path, None, true, node)
 					.await?;
 
-				// FIX-ME: Linux releases crashes with *** stack smashing detected *** if spawn_volume_watcher is enabled
-				// No ideia why, but this will be irrelevant after the UDisk API is implemented, so let's leave it disabled for now
-				#[cfg(not(target_os = "linux"))]
-				{
-					use crate::volume::watcher::spawn_volume_watcher;
-					spawn_volume_watcher(_library_arc.clone());
-				}
+				spawn_volume_watcher(Arc::clone(node), library_arc);
 			}
 		}
 
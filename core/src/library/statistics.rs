@@ -1,4 +1,7 @@
-use crate::{api::utils::get_size, library::Library, volume::get_volumes, Node};
+use crate::{
+	api::utils::get_size, library::Library, location::hard_links::unique_size_in_bytes,
+	volume::get_volumes, Node,
+};
 
 use sd_prisma::prisma::statistics;
 
@@ -31,6 +34,9 @@ pub async fn update_library_statistics(
 	.await
 	.unwrap_or(0);
 
+	// Hard links to the same file only take its space once
+	let total_unique_bytes = unique_size_in_bytes(&library.db, None).await?;
+
 	let thumbnail_folder_size = get_size(node.config.data_directory().join("thumbnails"))
 		.await
 		.unwrap_or(0);
@@ -43,7 +49,7 @@ pub async fn update_library_statistics(
 		library_db_size::set(library_db_size.to_string()),
 		total_bytes_used::set(total_bytes_used.to_string()),
 		total_bytes_capacity::set(total_capacity.to_string()),
-		total_unique_bytes::set(0.to_string()),
+		total_unique_bytes::set(total_unique_bytes.to_string()),
 		total_bytes_free::set(available_capacity.to_string()),
 		preview_media_bytes::set(thumbnail_folder_size.to_string()),
 	];
@@ -0,0 +1,142 @@
+//! Hard links are different paths to the same file, which share its inode on the same device,
+//! be them in the same location or in different ones.
+//!
+//! Every `file_path` has the `device` and `inode` of the file it points to, and the ones which
+//! aren't the first indexed among the links to the same file point to that first path in their
+//! `hard_link_of_id` column. As these ids only make sense in the database of this instance, they
+//! aren't synced, and are worked out again whenever file paths are created, updated or removed.
+//!
+//! Devices and inodes are synced along with the file paths of every instance, and the same pair
+//! on two machines is just a coincidence, so we only look for links among the file paths in the
+//! locations of the same instance.
+
+use sd_prisma::prisma::{file_path, location, PrismaClient};
+use sd_utils::db::inode_to_db;
+
+use prisma_client_rust::{raw, PrismaValue, QueryError};
+use serde::Deserialize;
+
+/// Points every file path of the files in a location to the first indexed path with the same
+/// device and inode, clearing the link of that first path and of the files without any other
+/// links left. Links to paths that were removed are worked out again too, wherever they are.
+///
+/// Returns how many file paths changed.
+pub async fn update_hard_links(
+	db: &PrismaClient,
+	location_id: location::id::Type,
+) -> Result<i64, QueryError> {
+	db._execute_raw(raw!(
+		&update_hard_links_query(
+			"AND (f.device, f.inode) IN (
+				SELECT p.device, p.inode FROM file_path p
+				WHERE p.location_id = {}
+					OR (p.hard_link_of_id IS NOT NULL AND NOT EXISTS (
+						SELECT 1 FROM file_path first WHERE first.id = p.hard_link_of_id
+					))
+			)"
+		),
+		PrismaValue::Int(location_id as i64)
+	))
+	.exec()
+	.await
+}
+
+/// Same as [`update_hard_links`], but only for the links to a single file, which is much cheaper
+/// when we already know which file changed, as in the location watcher
+pub async fn update_hard_links_of(
+	db: &PrismaClient,
+	device: u64,
+	inode: u64,
+) -> Result<i64, QueryError> {
+	db._execute_raw(raw!(
+		&update_hard_links_query("AND f.device = {} AND f.inode = {}"),
+		PrismaValue::Bytes(inode_to_db(device)),
+		PrismaValue::Bytes(inode_to_db(inode))
+	))
+	.exec()
+	.await
+}
+
+/// `filter` must keep or leave out all the file paths with the same device and inode together
+fn update_hard_links_query(filter: &str) -> String {
+	format!(
+		"UPDATE file_path SET hard_link_of_id = NULLIF(first_link.id, file_path.id)
+		FROM location, (
+			SELECT l.instance_id AS instance_id, f.device AS device, f.inode AS inode,
+				MIN(f.id) AS id
+			FROM file_path f
+			INNER JOIN location l ON l.id = f.location_id
+			WHERE f.is_dir = FALSE AND f.device IS NOT NULL AND f.inode IS NOT NULL {filter}
+			GROUP BY l.instance_id, f.device, f.inode
+		) AS first_link
+		WHERE location.id = file_path.location_id
+			AND location.instance_id IS first_link.instance_id
+			AND file_path.device = first_link.device
+			AND file_path.inode = first_link.inode
+			AND file_path.is_dir = FALSE
+			AND file_path.hard_link_of_id IS NOT NULLIF(first_link.id, file_path.id)"
+	)
+}
+
+/// Sums the sizes of the files in a location, or in the whole library, counting the bytes of
+/// hard links to the same file only once. File paths without a device or inode count on their own.
+pub async fn unique_size_in_bytes(
+	db: &PrismaClient,
+	maybe_location_id: Option<location::id::Type>,
+) -> Result<u64, QueryError> {
+	#[derive(Deserialize)]
+	struct UniqueSize {
+		size_in_bytes: f64,
+	}
+
+	// A location id is just a number, so this is sql injection safe
+	db._query_raw::<UniqueSize>(raw!(&format!(
+		"SELECT TOTAL(size_in_bytes) AS size_in_bytes FROM (
+			SELECT MAX(f.size_in_bytes_number) AS size_in_bytes FROM file_path f
+			LEFT JOIN location l ON l.id = f.location_id
+			WHERE f.is_dir = FALSE {}
+			GROUP BY l.instance_id, f.device, f.inode,
+				CASE WHEN f.device IS NULL OR f.inode IS NULL THEN f.id END
+		)",
+		maybe_location_id.map_or_else(String::new, |location_id| format!(
+			"AND f.location_id = {location_id}"
+		))
+	)))
+	.exec()
+	.await
+	.map(|sizes| sizes.first().map_or(0, |size| size.size_in_bytes as u64))
+}
+
+/// Every other file path to the same file as this one, in any location of the same instance, or
+/// `None` if there's no file path with this id
+pub async fn find_hard_links(
+	db: &PrismaClient,
+	file_path_id: file_path::id::Type,
+) -> Result<Option<Vec<file_path::Data>>, QueryError> {
+	let Some(file_path) = db
+		.file_path()
+		.find_unique(file_path::id::equals(file_path_id))
+		.select(file_path::select!({ device inode location: select { instance_id } }))
+		.exec()
+		.await?
+	else {
+		return Ok(None);
+	};
+
+	let (Some(device), Some(inode), Some(location)) =
+		(file_path.device, file_path.inode, file_path.location)
+	else {
+		return Ok(Some(vec![]));
+	};
+
+	db.file_path()
+		.find_many(vec![
+			file_path::device::equals(Some(device)),
+			file_path::inode::equals(Some(inode)),
+			file_path::id::not(file_path_id),
+			file_path::location::is(vec![location::instance_id::equals(location.instance_id)]),
+		])
+		.exec()
+		.await
+		.map(Some)
+}
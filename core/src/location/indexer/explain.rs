@@ -0,0 +1,484 @@
+use crate::{
+	library::Library,
+	location::{
+		find_location, location_with_indexer_rules,
+		symlinks::{ResolvedPath, SymlinkPolicy, SymlinkResolver},
+		LocationError,
+	},
+};
+
+use sd_prisma::prisma::{indexer_rule, location};
+use sd_utils::{db::maybe_missing, error::FileIOError};
+
+use std::{
+	collections::{HashMap, VecDeque},
+	path::{Path, PathBuf},
+};
+
+use async_stream::stream;
+use chrono::Utc;
+use futures::Stream;
+use rspc::ErrorCode;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tokio::fs;
+
+use super::{
+	rules::{IndexerRule, RuleKind, RulePerKind},
+	walk::decide_by_rules,
+	IndexerError,
+};
+
+/// `IndexerRulesExplainArgs` is the argument received from the client using rspc to find out which
+/// indexer rules accept or reject each path, without indexing anything.
+///
+/// A location is walked with its own rules, unless `indexer_rules_ids` or `rules` are given, and
+/// `path` can be used to walk only a sub path of it. Without a location, `path` can be any directory.
+/// Symbolic links follow the location's [`SymlinkPolicy`], and are skipped without a location.
+/// The rules in `rules` are in the same format as `IndexerRuleCreateArgs`, to preview them before
+/// creating them.
+#[derive(Type, Deserialize, Debug)]
+pub struct IndexerRulesExplainArgs {
+	#[specta(optional)]
+	pub location_id: Option<location::id::Type>,
+	#[specta(optional)]
+	pub path: Option<PathBuf>,
+	#[serde(default)]
+	pub indexer_rules_ids: Vec<indexer_rule::id::Type>,
+	#[serde(default)]
+	pub rules: Vec<(RuleKind, Vec<String>)>,
+	/// How many levels of directories we walk into, where 1 only explains the entries of the
+	/// walked directory itself. Unlimited when missing.
+	#[specta(optional)]
+	pub max_depth: Option<u32>,
+	/// Skips accepted paths, to only find out why files are missing
+	#[serde(default)]
+	pub only_rejected: bool,
+}
+
+#[derive(Serialize, Type, Debug, Clone)]
+pub struct ResponsibleRule {
+	/// Missing for rules that weren't created yet
+	pub id: Option<indexer_rule::id::Type>,
+	pub name: String,
+	pub kind: RuleKind,
+}
+
+impl ResponsibleRule {
+	fn new(rule: &IndexerRule, kind: RuleKind) -> Self {
+		Self {
+			id: rule.id,
+			name: rule.name.clone(),
+			kind,
+		}
+	}
+}
+
+#[derive(Serialize, Type, Debug)]
+pub struct RuleDecision {
+	pub path: PathBuf,
+	pub is_dir: bool,
+	pub accepted: bool,
+	/// The rule that rejected this path, or that accepted it by its children directories.
+	/// Rejected paths without a rule are symbolic links skipped by the [`SymlinkPolicy`].
+	///
+	/// Directories rejected by accept rules are still walked, and are indexed anyway if any
+	/// of their contents are accepted.
+	pub rule: Option<ResponsibleRule>,
+}
+
+impl IndexerRulesExplainArgs {
+	pub async fn explain(
+		self,
+		library: &Library,
+	) -> Result<impl Stream<Item = Result<RuleDecision, IndexerError>> + Send + 'static, rspc::Error>
+	{
+		let (root, mut rules, symlinks) = match (self.location_id, self.path) {
+			(Some(location_id), maybe_sub_path) => {
+				let location = find_location(library, location_id)
+					.include(location_with_indexer_rules::include())
+					.exec()
+					.await?
+					.ok_or(LocationError::IdNotFound(location_id))?;
+
+				let location_path = PathBuf::from(maybe_missing(location.path, "location.path")?);
+
+				let symlinks = SymlinkResolver::new(
+					SymlinkPolicy::from_db(location.symlink_policy),
+					Some(&location_path),
+				)
+				.await;
+
+				let root = match maybe_sub_path {
+					Some(sub_path) if !sub_path.starts_with(&location_path) => {
+						return Err(rspc::Error::new(
+							ErrorCode::BadRequest,
+							format!(
+								"Path <path='{}'> isn't inside the location <id={location_id}>",
+								sub_path.display()
+							),
+						));
+					}
+					Some(sub_path) => sub_path,
+					None => location_path,
+				};
+
+				let rules = if self.indexer_rules_ids.is_empty() && self.rules.is_empty() {
+					location
+						.indexer_rules
+						.iter()
+						.map(|rule| IndexerRule::try_from(&rule.indexer_rule))
+						.collect::<Result<Vec<_>, _>>()?
+				} else {
+					vec![]
+				};
+
+				(root, rules, symlinks)
+			}
+			(None, Some(path)) => (
+				path,
+				vec![],
+				SymlinkResolver::new(SymlinkPolicy::default(), None::<&Path>).await,
+			),
+			(None, None) => {
+				return Err(rspc::Error::new(
+					ErrorCode::BadRequest,
+					"A location or a path is required".to_string(),
+				));
+			}
+		};
+
+		if !self.indexer_rules_ids.is_empty() {
+			let saved_rules = library
+				.db
+				.indexer_rule()
+				.find_many(vec![indexer_rule::id::in_vec(
+					self.indexer_rules_ids.clone(),
+				)])
+				.exec()
+				.await?;
+
+			if let Some(missing_id) = self
+				.indexer_rules_ids
+				.iter()
+				.find(|id| !saved_rules.iter().any(|rule| rule.id == **id))
+			{
+				return Err(IndexerError::IndexerRuleNotFound(*missing_id).into());
+			}
+
+			for rule in saved_rules {
+				rules.push(IndexerRule::try_from(rule)?);
+			}
+		}
+
+		if !self.rules.is_empty() {
+			rules.push(IndexerRule {
+				id: None,
+				name: "New rule".to_string(),
+				default: false,
+				rules: self
+					.rules
+					.into_iter()
+					.map(|(kind, parameters)| RulePerKind::from_parameters(kind, parameters))
+					.collect::<Result<_, _>>()?,
+				date_created: Utc::now(),
+				date_modified: Utc::now(),
+			});
+		}
+
+		Ok(explain(
+			root,
+			rules,
+			symlinks,
+			self.max_depth,
+			self.only_rejected,
+		))
+	}
+}
+
+/// Walks `root` in the same way the indexer walker does, telling which rule decided about each
+/// path. The decisions themselves are taken by `walk::decide_by_rules`, like in the walker.
+fn explain(
+	root: PathBuf,
+	rules: Vec<IndexerRule>,
+	symlinks: SymlinkResolver,
+	max_depth: Option<u32>,
+	only_rejected: bool,
+) -> impl Stream<Item = Result<RuleDecision, IndexerError>> + Send {
+	stream! {
+		let mut to_walk = VecDeque::from([(root, 1, None, Vec::<PathBuf>::new())]);
+
+		while let Some((path, depth, parent_accepted_by_children, followed_from)) =
+			to_walk.pop_front()
+		{
+			let mut read_dir = match fs::read_dir(&path).await {
+				Ok(read_dir) => read_dir,
+				Err(e) => {
+					yield Err(FileIOError::from((&path, e)).into());
+					continue;
+				}
+			};
+
+			loop {
+				let entry = match read_dir.next_entry().await {
+					Ok(Some(entry)) => entry,
+					Ok(None) => break,
+					Err(e) => {
+						yield Err(FileIOError::from((&path, e)).into());
+						break;
+					}
+				};
+
+				let current_path = entry.path();
+
+				let mut results = Vec::with_capacity(rules.len());
+				let mut failed = None;
+				for rule in &rules {
+					match rule.apply(&current_path).await {
+						Ok(rule_results) => results.extend(
+							rule_results
+								.into_iter()
+								.map(|(kind, passed)| (rule, kind, passed)),
+						),
+						Err(e) => {
+							failed = Some(e);
+							break;
+						}
+					}
+				}
+
+				if let Some(e) = failed {
+					yield Err(e.into());
+					continue;
+				}
+
+				let metadata = match entry.metadata().await {
+					Ok(metadata) => metadata,
+					Err(e) => {
+						yield Err(FileIOError::from((&current_path, e)).into());
+						continue;
+					}
+				};
+
+				let (is_dir, is_skipped_link, maybe_followed_from) = match symlinks
+					.resolve(&current_path, metadata, &followed_from)
+					.await
+				{
+					ResolvedPath::Regular(metadata) | ResolvedPath::Link(metadata) => {
+						(metadata.is_dir(), false, None)
+					}
+					ResolvedPath::Followed { metadata, from } => {
+						(metadata.is_dir(), false, Some(from))
+					}
+					ResolvedPath::Skipped => (false, true, None),
+				};
+
+				let decision = decide_by_rules(
+					&results.iter().fold(
+						HashMap::<_, Vec<_>>::new(),
+						|mut rules_per_kind, (_, kind, passed)| {
+							rules_per_kind.entry(*kind).or_default().push(*passed);
+							rules_per_kind
+						},
+					),
+					is_dir,
+					parent_accepted_by_children
+						.as_ref()
+						.map(|(accepted, _)| *accepted),
+				);
+
+				// The rule behind the accept by children decision: the directory's own rule that
+				// accepted it, else the one inherited from its parent, else its own rule that didn't
+				let own_accept_by_children = |passed: bool| {
+					results
+						.iter()
+						.filter(|_| is_dir)
+						.find(|(_, kind, result)| {
+							*kind == RuleKind::AcceptIfChildrenDirectoriesArePresent
+								&& *result == passed
+						})
+						.map(|(rule, kind, _)| ResponsibleRule::new(rule, *kind))
+				};
+				let accepted_by_children = decision.accepted_by_children.and_then(|accepted| {
+					accepted
+						.then(|| own_accept_by_children(true))
+						.flatten()
+						.or_else(|| {
+							parent_accepted_by_children
+								.clone()
+								.map(|(_, rule)| rule)
+						})
+						.or_else(|| own_accept_by_children(false))
+						.map(|rule| (accepted, rule))
+				});
+
+				if decision.walk_children
+					&& !is_skipped_link
+					&& max_depth.map_or(true, |max_depth| depth < max_depth)
+				{
+					to_walk.push_back((
+						current_path.clone(),
+						depth + 1,
+						accepted_by_children.clone(),
+						followed_from
+							.iter()
+							.cloned()
+							.chain(maybe_followed_from)
+							.collect(),
+					));
+				}
+
+				let rejection = match decision.rejected_by {
+					Some(
+						kind @ (RuleKind::RejectFilesByGlob
+						| RuleKind::RejectIgnoredByGit
+						| RuleKind::RejectFilesByMetadata),
+					) => Some(rejected_by(&results, kind)),
+					_ if is_skipped_link => Some(None),
+					Some(kind @ RuleKind::RejectIfChildrenDirectoriesArePresent) => {
+						Some(rejected_by(&results, kind))
+					}
+					Some(RuleKind::AcceptIfChildrenDirectoriesArePresent) => Some(
+						accepted_by_children
+							.clone()
+							.map(|(_, rule)| rule),
+					),
+					Some(kind) => Some(not_accepted_by(&results, kind)),
+					None => None,
+				};
+
+				if let Some(rule) = rejection {
+					yield Ok(RuleDecision {
+						path: current_path,
+						is_dir,
+						accepted: false,
+						rule,
+					});
+				} else if !only_rejected {
+					yield Ok(RuleDecision {
+						path: current_path,
+						is_dir,
+						accepted: true,
+						rule: accepted_by_children.map(|(_, rule)| rule),
+					});
+				}
+			}
+		}
+	}
+}
+
+/// The first rule of a reject kind that rejected the path
+fn rejected_by(
+	results: &[(&IndexerRule, RuleKind, bool)],
+	kind: RuleKind,
+) -> Option<ResponsibleRule> {
+	results
+		.iter()
+		.find(|(_, result_kind, passed)| *result_kind == kind && !passed)
+		.map(|(rule, kind, _)| ResponsibleRule::new(rule, *kind))
+}
+
+/// The first rule of an accept kind if none of the rules of this kind accepted the path
+fn not_accepted_by(
+	results: &[(&IndexerRule, RuleKind, bool)],
+	kind: RuleKind,
+) -> Option<ResponsibleRule> {
+	let mut of_kind = results
+		.iter()
+		.filter(|(_, result_kind, _)| *result_kind == kind);
+
+	let (first_rule, _, _) = of_kind.clone().next()?;
+
+	(!of_kind.any(|(_, _, passed)| *passed)).then(|| ResponsibleRule::new(first_rule, kind))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use futures::StreamExt;
+	use tempfile::tempdir;
+
+	fn rule(id: i32, name: &str, rules: Vec<RulePerKind>) -> IndexerRule {
+		IndexerRule {
+			id: Some(id),
+			name: name.to_string(),
+			default: false,
+			rules,
+			date_created: Utc::now(),
+			date_modified: Utc::now(),
+		}
+	}
+
+	#[tokio::test]
+	async fn test_explain_responsible_rules() {
+		let root = tempdir().unwrap();
+		let photos = root.path().join("photos");
+		fs::create_dir(&photos).await.unwrap();
+		fs::write(photos.join("photo.jpg"), []).await.unwrap();
+		fs::write(photos.join("notes.txt"), []).await.unwrap();
+		fs::write(root.path().join(".DS_Store"), []).await.unwrap();
+
+		let rules = vec![
+			rule(
+				1,
+				"No OS protected",
+				vec![RulePerKind::new_reject_files_by_globs_str(["**/.DS_Store"]).unwrap()],
+			),
+			rule(
+				2,
+				"Only photos",
+				vec![RulePerKind::new_accept_files_by_globs_str(["**/*.jpg"]).unwrap()],
+			),
+		];
+
+		let mut decisions = explain(
+			root.path().to_path_buf(),
+			rules,
+			SymlinkResolver::new(SymlinkPolicy::Ignore, None::<&Path>).await,
+			None,
+			false,
+		)
+		.map(|result| {
+			let decision = result.unwrap();
+			(
+				decision
+					.path
+					.strip_prefix(root.path())
+					.unwrap()
+					.to_path_buf(),
+				(decision.accepted, decision.rule.and_then(|rule| rule.id)),
+			)
+		})
+		.collect::<Vec<_>>()
+		.await;
+		decisions.sort();
+
+		assert_eq!(
+			decisions,
+			vec![
+				(PathBuf::from(".DS_Store"), (false, Some(1))),
+				(PathBuf::from("photos"), (false, Some(2))),
+				(PathBuf::from("photos/notes.txt"), (false, Some(2))),
+				(PathBuf::from("photos/photo.jpg"), (true, None)),
+			]
+		);
+
+		let rejected = explain(
+			root.path().to_path_buf(),
+			vec![rule(
+				1,
+				"No OS protected",
+				vec![RulePerKind::new_reject_files_by_globs_str(["**/.DS_Store"]).unwrap()],
+			)],
+			SymlinkResolver::new(SymlinkPolicy::Ignore, None::<&Path>).await,
+			Some(1),
+			true,
+		)
+		.collect::<Vec<_>>()
+		.await;
+
+		assert_eq!(rejected.len(), 1);
+	}
+}
@@ -0,0 +1,217 @@
+use crate::location::symlinks::SymlinkPolicy;
+
+use std::{
+	ffi::OsString,
+	fs::Metadata,
+	path::Path,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use blake3::Hasher;
+
+use super::rules::{IndexerRule, RulePerKind};
+
+/// A cheap summary of the direct children of a directory, stored in the `fingerprint` column of
+/// directory `file_path`s, so rescans can skip the entries of directories that didn't change since
+/// last walked. Their sub directories are still walked, each checked against its own fingerprint.
+///
+/// It's made of the number of children, the latest modification date among them and a hash of
+/// their names and inodes. The hash is seeded with the indexer rules, the symbolic links policy
+/// and the extended attributes allowlist in use, as changing them also changes what must be indexed.
+///
+/// On Unix, the latest status change date of the children also counts as a modification, so
+/// changing their permissions, owner or extended attributes is noticed too.
+///
+/// Editing a file in place only changes its own modification date, so it's noticed by the
+/// fingerprint of the directory where the file is, no matter how deep that is.
+///
+/// What git ignores inside a directory also depends on the ignore files of its ancestors, so with
+/// a rule rejecting paths ignored by git, their contents are hashed too. Rules depending on time,
+/// like the ones on the age of files, can change their minds without anything changing on disk,
+/// so directories are never skipped while they're in use.
+#[derive(Debug)]
+pub(super) struct DirectoryFingerprint {
+	hasher: Hasher,
+	children: Vec<(OsString, u64)>,
+	max_modified_at: Option<SystemTime>,
+}
+
+impl DirectoryFingerprint {
+	pub(super) fn new(
+		indexer_rules: &[IndexerRule],
+		symlink_policy: SymlinkPolicy,
+		xattr_allowlist: &[String],
+	) -> Self {
+		let mut hasher = Hasher::new();
+
+		hasher.update(&symlink_policy.to_db().to_le_bytes());
+		for name in xattr_allowlist {
+			hasher.update(name.as_bytes());
+			hasher.update(&[0]);
+		}
+		for rule in indexer_rules {
+			hasher.update(&rule.id.unwrap_or_default().to_le_bytes());
+			hasher.update(rule.name.as_bytes());
+			hasher.update(&rule.date_modified.timestamp_micros().to_le_bytes());
+		}
+
+		Self {
+			hasher,
+			children: vec![],
+			max_modified_at: None,
+		}
+	}
+
+	/// Adds the ignore files deciding what git ignores inside `dir`, if any of the rules needs them
+	pub(super) async fn add_ignore_files(&mut self, indexer_rules: &[IndexerRule], dir: &Path) {
+		for rule in indexer_rules.iter().flat_map(|rule| &rule.rules) {
+			if let RulePerKind::RejectIgnoredByGit(git_ignore_rules) = rule {
+				git_ignore_rules.hash_ignores(dir, &mut self.hasher).await;
+			}
+		}
+	}
+
+	pub(super) fn add(&mut self, name: OsString, metadata: &Metadata) {
+		self.max_modified_at = self.max_modified_at.max(metadata.modified().ok());
+
+		#[cfg(target_family = "unix")]
+		let inode = {
+			use std::{os::unix::fs::MetadataExt, time::Duration};
+
+			if let (Ok(secs), Ok(nanos)) = (
+				u64::try_from(metadata.ctime()),
+				u32::try_from(metadata.ctime_nsec()),
+			) {
+				self.max_modified_at = self
+					.max_modified_at
+					.max(Some(UNIX_EPOCH + Duration::new(secs, nanos)));
+			}
+
+			metadata.ino()
+		};
+
+		// We can't get inodes from the metadata of directory entries on Windows, so the size
+		// stands in for it
+		#[cfg(target_family = "windows")]
+		let inode = metadata.len();
+
+		self.children.push((name, inode));
+	}
+
+	pub(super) fn finish(mut self) -> Vec<u8> {
+		// Directory entries come in no particular order
+		self.children.sort_unstable();
+
+		for (name, inode) in &self.children {
+			self.hasher.update(name.as_encoded_bytes());
+			self.hasher.update(&[0]);
+			self.hasher.update(&inode.to_le_bytes());
+		}
+
+		let max_modified_at = self
+			.max_modified_at
+			.and_then(|modified_at| modified_at.duration_since(UNIX_EPOCH).ok())
+			.map_or(0, |since_epoch| since_epoch.as_nanos());
+
+		[
+			(self.children.len() as u64).to_be_bytes().as_slice(),
+			&max_modified_at.to_be_bytes(),
+			self.hasher.finalize().as_bytes(),
+		]
+		.concat()
+	}
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use std::path::Path;
+
+	use crate::location::indexer::rules::gitignore::GitIgnoreRules;
+
+	use tempfile::tempdir;
+	use tokio::fs;
+
+	async fn fingerprint(path: &Path, symlink_policy: SymlinkPolicy) -> Vec<u8> {
+		fingerprint_with_rules(path, symlink_policy, &[]).await
+	}
+
+	async fn fingerprint_with_rules(
+		path: &Path,
+		symlink_policy: SymlinkPolicy,
+		indexer_rules: &[IndexerRule],
+	) -> Vec<u8> {
+		let mut fingerprint = DirectoryFingerprint::new(indexer_rules, symlink_policy, &[]);
+		fingerprint.add_ignore_files(indexer_rules, path).await;
+
+		let mut read_dir = fs::read_dir(path).await.unwrap();
+		while let Some(entry) = read_dir.next_entry().await.unwrap() {
+			fingerprint.add(entry.file_name(), &entry.metadata().await.unwrap());
+		}
+
+		fingerprint.finish()
+	}
+
+	#[tokio::test]
+	async fn test_directory_fingerprint() {
+		let dir = tempdir().unwrap();
+		fs::write(dir.path().join("a.txt"), b"a").await.unwrap();
+		fs::write(dir.path().join("b.txt"), b"b").await.unwrap();
+
+		let original = fingerprint(dir.path(), SymlinkPolicy::Ignore).await;
+		assert_eq!(
+			original,
+			fingerprint(dir.path(), SymlinkPolicy::Ignore).await
+		);
+		assert_ne!(
+			original,
+			fingerprint(dir.path(), SymlinkPolicy::Follow).await
+		);
+
+		fs::rename(dir.path().join("b.txt"), dir.path().join("c.txt"))
+			.await
+			.unwrap();
+		let renamed = fingerprint(dir.path(), SymlinkPolicy::Ignore).await;
+		assert_ne!(original, renamed);
+
+		fs::create_dir(dir.path().join("d")).await.unwrap();
+		assert_ne!(
+			renamed,
+			fingerprint(dir.path(), SymlinkPolicy::Ignore).await
+		);
+	}
+
+	#[tokio::test]
+	async fn test_directory_fingerprint_ancestor_ignore_files() {
+		let repo = tempdir().unwrap();
+		let dir = repo.path().join("a").join("b");
+		fs::create_dir(repo.path().join(".git")).await.unwrap();
+		fs::create_dir_all(&dir).await.unwrap();
+		fs::write(dir.join("build.log"), b"log").await.unwrap();
+
+		let rules = [IndexerRule::new(
+			"git".to_string(),
+			false,
+			vec![RulePerKind::RejectIgnoredByGit(GitIgnoreRules::new(vec![]))],
+		)];
+
+		let original = fingerprint_with_rules(&dir, SymlinkPolicy::Ignore, &rules).await;
+
+		fs::write(repo.path().join(".gitignore"), b"*.log")
+			.await
+			.unwrap();
+		for rule in rules.iter().flat_map(|rule| &rule.rules) {
+			if let RulePerKind::RejectIgnoredByGit(git_ignore_rules) = rule {
+				git_ignore_rules.clear_cache();
+			}
+		}
+
+		// Nothing changed inside the directory, but what git ignores in it did
+		assert_ne!(
+			original,
+			fingerprint_with_rules(&dir, SymlinkPolicy::Ignore, &rules).await
+		);
+	}
+}
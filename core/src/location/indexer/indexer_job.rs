@@ -1,23 +1,23 @@
 use crate::{
-	file_paths_db_fetcher_fn, invalidate_query,
+	invalidate_query,
 	job::{
 		CurrentStep, JobError, JobInitOutput, JobReportUpdate, JobResult, JobRunMetadata,
-		JobStepOutput, StatefulJob, WorkerContext,
+		JobStepOutput, StatefulJob, StepTasks, WorkerContext,
 	},
 	library::Library,
-	location::{location_with_indexer_rules, update_location_size},
-	to_remove_db_fetcher_fn,
+	location::{
+		hard_links::update_hard_links,
+		location_with_indexer_rules,
+		symlinks::{SymlinkPolicy, SymlinkResolver},
+		update_location_size,
+	},
 };
 
 use sd_file_path_helper::{
-	ensure_file_path_exists, ensure_sub_path_is_directory, ensure_sub_path_is_in_location,
-	IsolatedFilePathData,
-};
-use sd_prisma::{
-	prisma::{file_path, location},
-	prisma_sync,
+	attributes::xattr_allowlist_from_db, ensure_file_path_exists, ensure_sub_path_is_directory,
+	ensure_sub_path_is_in_location, IsolatedFilePathData,
 };
-use sd_sync::*;
+use sd_prisma::prisma::{file_path, location};
 use sd_utils::{db::maybe_missing, from_bytes_to_uuid};
 
 use std::{
@@ -36,23 +36,32 @@ use tokio::time::Instant;
 use tracing::{debug, info, warn};
 
 use super::{
-	execute_indexer_save_step, execute_indexer_update_step, iso_file_path_factory,
 	remove_non_existing_file_paths, reverse_update_directories_sizes,
 	rules::IndexerRule,
-	walk::{keep_walking, walk, ToWalkEntry, WalkResult},
+	tasks::{SaveTask, UpdateTask, WalkDirTask, WalkDirTaskOutput},
+	update_directory_size,
+	walk::ToWalkEntry,
 	IndexerError, IndexerJobSaveStep, IndexerJobUpdateStep,
 };
 
 /// BATCH_SIZE is the number of files to index at each step, writing the chunk of files metadata in the database.
 const BATCH_SIZE: usize = 1000;
 
+/// How many new or updated entries each walk step looks for, the directories left to walk are
+/// walked by the next steps
+const WALK_STEP_LIMIT: usize = 50_000;
+
 /// `IndexerJobInit` receives a `location::Data` object to be indexed
 /// and possibly a `sub_path` to be indexed. The `sub_path` is used when
 /// we want do index just a part of a location.
+/// The entries of directories which didn't change since the last scan are skipped, unless
+/// `force_full` is set.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct IndexerJobInit {
 	pub location: location_with_indexer_rules::Data,
 	pub sub_path: Option<PathBuf>,
+	#[serde(default)]
+	pub force_full: bool,
 }
 
 impl Hash for IndexerJobInit {
@@ -70,7 +79,6 @@ impl Hash for IndexerJobInit {
 pub struct IndexerJobData {
 	location_path: PathBuf,
 	indexed_path: PathBuf,
-	indexer_rules: Vec<IndexerRule>,
 }
 
 #[derive(Serialize, Deserialize, Default, Debug)]
@@ -85,6 +93,7 @@ pub struct IndexerJobRunMetadata {
 	updated_count: u64,
 	removed_count: u64,
 	paths_and_sizes: HashMap<PathBuf, u64>,
+	paths_and_fingerprints: HashMap<PathBuf, Vec<u8>>,
 }
 
 impl JobRunMetadata for IndexerJobRunMetadata {
@@ -101,6 +110,9 @@ impl JobRunMetadata for IndexerJobRunMetadata {
 		for (path, size) in new_data.paths_and_sizes {
 			*self.paths_and_sizes.entry(path).or_default() += size;
 		}
+
+		self.paths_and_fingerprints
+			.extend(new_data.paths_and_fingerprints);
 	}
 }
 
@@ -140,6 +152,8 @@ pub enum IndexerJobStepInput {
 /// A `IndexerJob` is a stateful job that walks a directory and indexes all files.
 /// First it walks the directory and generates a list of files to index, chunked into
 /// batches of [`BATCH_SIZE`]. Then for each chunk it write the file metadata to the database.
+///
+/// The steps run as tasks on the node's task system, with directories being walked in parallel.
 #[async_trait::async_trait]
 impl StatefulJob for IndexerJobInit {
 	type Data = IndexerJobData;
@@ -166,14 +180,6 @@ impl StatefulJob for IndexerJobInit {
 		let db = Arc::clone(&ctx.library.db);
 		let sync = &ctx.library.sync;
 
-		let indexer_rules = init
-			.location
-			.indexer_rules
-			.iter()
-			.map(|rule| IndexerRule::try_from(&rule.indexer_rule))
-			.collect::<Result<Vec<_>, _>>()
-			.map_err(IndexerError::from)?;
-
 		let to_walk_path = match &init.sub_path {
 			Some(sub_path) if sub_path != Path::new("") => {
 				let full_path = ensure_sub_path_is_in_location(location_path, sub_path)
@@ -198,25 +204,23 @@ impl StatefulJob for IndexerJobInit {
 		};
 
 		let scan_start = Instant::now();
-		let WalkResult {
+		let WalkDirTaskOutput {
 			walked,
 			to_update,
 			to_walk,
 			to_remove,
 			errors,
 			paths_and_sizes,
-		} = walk(
-			&to_walk_path,
-			&indexer_rules,
-			update_notifier_fn(ctx),
-			file_paths_db_fetcher_fn!(&db),
-			to_remove_db_fetcher_fn!(location_id, &db),
-			iso_file_path_factory(location_id, location_path),
-			50_000,
+			paths_and_fingerprints,
+		} = walk_with_tasks(
+			ctx,
+			&init.location,
+			location_path,
+			to_walk_path.as_path().into(),
+			!init.force_full,
 		)
 		.await?;
 		let scan_read_time = scan_start.elapsed();
-		let to_remove = to_remove.collect::<Vec<_>>();
 
 		debug!(
 			"Walker at indexer job found {} file_paths to be removed",
@@ -246,6 +250,7 @@ impl StatefulJob for IndexerJobInit {
 		let to_update_chunks = &mut 0;
 
 		let steps = walked
+			.into_iter()
 			.chunks(BATCH_SIZE)
 			.into_iter()
 			.enumerate()
@@ -262,6 +267,7 @@ impl StatefulJob for IndexerJobInit {
 			})
 			.chain(
 				to_update
+					.into_iter()
 					.chunks(BATCH_SIZE)
 					.into_iter()
 					.enumerate()
@@ -297,7 +303,6 @@ impl StatefulJob for IndexerJobInit {
 		*data = Some(IndexerJobData {
 			location_path: location_path.to_path_buf(),
 			indexed_path: to_walk_path,
-			indexer_rules,
 		});
 
 		Ok((
@@ -312,6 +317,7 @@ impl StatefulJob for IndexerJobInit {
 				total_save_steps: *to_save_chunks as u64,
 				total_update_steps: *to_update_chunks as u64,
 				paths_and_sizes,
+				paths_and_fingerprints,
 			},
 			steps,
 			errors
@@ -328,7 +334,7 @@ impl StatefulJob for IndexerJobInit {
 		&self,
 		ctx: &WorkerContext,
 		CurrentStep { step, .. }: CurrentStep<'_, Self::Step>,
-		data: &Self::Data,
+		_: &Self::Data,
 		run_metadata: &Self::RunMetadata,
 	) -> Result<JobStepOutput<Self::Step, Self::RunMetadata>, JobError> {
 		let init = self;
@@ -348,7 +354,15 @@ impl StatefulJob for IndexerJobInit {
 					],
 				);
 
-				let count = execute_indexer_save_step(&init.location, step, &ctx.library).await?;
+				let count = StepTasks::<i64>::run(
+					ctx,
+					SaveTask::new(
+						init.location.clone(),
+						step.clone(),
+						Arc::clone(&ctx.library),
+					),
+				)
+				.await?;
 
 				new_metadata.indexed_count = count as u64;
 				new_metadata.db_write_time = start_time.elapsed();
@@ -368,7 +382,11 @@ impl StatefulJob for IndexerJobInit {
 					],
 				);
 
-				let count = execute_indexer_update_step(to_update, &ctx.library).await?;
+				let count = StepTasks::<i64>::run(
+					ctx,
+					UpdateTask::new(to_update.clone(), Arc::clone(&ctx.library)),
+				)
+				.await?;
 
 				new_metadata.updated_count = count as u64;
 				new_metadata.db_write_time = start_time.elapsed();
@@ -377,7 +395,6 @@ impl StatefulJob for IndexerJobInit {
 			}
 
 			IndexerJobStepInput::Walk(to_walk_entry) => {
-				let location_id = init.location.id;
 				let location_path =
 					maybe_missing(&init.location.path, "location.path").map(Path::new)?;
 
@@ -386,24 +403,25 @@ impl StatefulJob for IndexerJobInit {
 
 				let scan_start = Instant::now();
 
-				let WalkResult {
+				let WalkDirTaskOutput {
 					walked,
 					to_update,
 					to_walk,
 					to_remove,
 					errors,
 					paths_and_sizes,
-				} = keep_walking(
-					to_walk_entry,
-					&data.indexer_rules,
-					update_notifier_fn(ctx),
-					file_paths_db_fetcher_fn!(&db),
-					to_remove_db_fetcher_fn!(location_id, &db),
-					iso_file_path_factory(location_id, location_path),
+					paths_and_fingerprints,
+				} = walk_with_tasks(
+					ctx,
+					&init.location,
+					location_path,
+					to_walk_entry.clone(),
+					!init.force_full,
 				)
 				.await?;
 
 				new_metadata.paths_and_sizes = paths_and_sizes;
+				new_metadata.paths_and_fingerprints = paths_and_fingerprints;
 
 				new_metadata.scan_read_time = scan_start.elapsed();
 
@@ -416,6 +434,7 @@ impl StatefulJob for IndexerJobInit {
 				let to_walk_count = to_walk.len();
 
 				let more_steps = walked
+					.into_iter()
 					.chunks(BATCH_SIZE)
 					.into_iter()
 					.enumerate()
@@ -429,18 +448,23 @@ impl StatefulJob for IndexerJobInit {
 							walked: chunk_steps,
 						})
 					})
-					.chain(to_update.chunks(BATCH_SIZE).into_iter().enumerate().map(
-						|(i, chunk)| {
-							let chunk_updates = chunk.collect::<Vec<_>>();
-							new_metadata.total_updated_paths += chunk_updates.len() as u64;
-							new_metadata.total_update_steps += 1;
-
-							IndexerJobStepInput::Update(IndexerJobUpdateStep {
-								chunk_idx: i,
-								to_update: chunk_updates,
-							})
-						},
-					))
+					.chain(
+						to_update
+							.into_iter()
+							.chunks(BATCH_SIZE)
+							.into_iter()
+							.enumerate()
+							.map(|(i, chunk)| {
+								let chunk_updates = chunk.collect::<Vec<_>>();
+								new_metadata.total_updated_paths += chunk_updates.len() as u64;
+								new_metadata.total_update_steps += 1;
+
+								IndexerJobStepInput::Update(IndexerJobUpdateStep {
+									chunk_idx: i,
+									to_update: chunk_updates,
+								})
+							}),
+					)
 					.chain(to_walk.into_iter().map(IndexerJobStepInput::Walk))
 					.collect::<Vec<_>>();
 
@@ -527,26 +551,127 @@ impl StatefulJob for IndexerJobInit {
 					.map_err(IndexerError::from)?;
 				}
 
+				update_hard_links(&ctx.library.db, init.location.id)
+					.await
+					.map_err(IndexerError::from)?;
+
 				update_location_size(init.location.id, &ctx.library)
 					.await
 					.map_err(IndexerError::from)?;
 			}
 		}
 
+		// Only saving fingerprints now that every directory walked is in the database, so the next
+		// scans can skip the ones that don't change
+		if !run_metadata.paths_and_fingerprints.is_empty() {
+			if let Some(data) = data {
+				update_directories_fingerprints(
+					&run_metadata.paths_and_fingerprints,
+					init.location.id,
+					&data.location_path,
+					&ctx.library,
+				)
+				.await?;
+			}
+		}
+
 		Ok(Some(json!({"init: ": init, "run_metadata": run_metadata})))
 	}
 }
 
-fn update_notifier_fn(ctx: &WorkerContext) -> impl FnMut(&Path, usize) + '_ {
-	move |path, total_entries| {
+/// Walks from `to_walk_entry` with [`WalkDirTask`]s running in parallel on the task system, each
+/// task handing the directories it didn't reach over to new tasks, until we find
+/// [`WALK_STEP_LIMIT`] entries. The directories left to walk are given back in `to_walk`.
+async fn walk_with_tasks(
+	ctx: &WorkerContext,
+	location: &location_with_indexer_rules::Data,
+	location_path: &Path,
+	to_walk_entry: ToWalkEntry,
+	skip_unchanged: bool,
+) -> Result<WalkDirTaskOutput, JobError> {
+	let location_path = Arc::new(location_path.to_path_buf());
+
+	let indexer_rules = Arc::new(
+		location
+			.indexer_rules
+			.iter()
+			.map(|rule| IndexerRule::try_from(&rule.indexer_rule))
+			.collect::<Result<Vec<_>, _>>()
+			.map_err(IndexerError::from)?,
+	);
+
+	let symlinks = Arc::new(
+		SymlinkResolver::new(
+			SymlinkPolicy::from_db(location.symlink_policy),
+			Some(location_path.as_path()),
+		)
+		.await,
+	);
+
+	let xattr_allowlist = Arc::new(xattr_allowlist_from_db(location.xattr_allowlist.as_deref()));
+
+	let new_task = |entry| {
+		WalkDirTask::new(
+			entry,
+			location.id,
+			Arc::clone(&location_path),
+			Arc::clone(&indexer_rules),
+			Arc::clone(&symlinks),
+			Arc::clone(&xattr_allowlist),
+			Arc::clone(&ctx.library),
+			skip_unchanged,
+		)
+	};
+
+	let mut tasks = StepTasks::<WalkDirTaskOutput>::new(ctx);
+	tasks.dispatch(new_task(to_walk_entry)).await;
+	let mut running_tasks_count = 1;
+
+	let mut found = WalkDirTaskOutput::default();
+
+	while let Some(output) = tasks.next().await {
+		let WalkDirTaskOutput {
+			walked,
+			to_update,
+			to_walk,
+			to_remove,
+			errors,
+			paths_and_sizes,
+			paths_and_fingerprints,
+		} = output?;
+
+		running_tasks_count -= 1;
+
+		found.walked.extend(walked);
+		found.to_update.extend(to_update);
+		found.to_remove.extend(to_remove);
+		found.errors.extend(errors);
+		for (path, size) in paths_and_sizes {
+			*found.paths_and_sizes.entry(path).or_default() += size;
+		}
+		found.paths_and_fingerprints.extend(paths_and_fingerprints);
+
+		let found_count = found.walked.len() + found.to_update.len();
+
+		let new_tasks = if found_count < WALK_STEP_LIMIT {
+			to_walk.into_iter().map(&new_task).collect::<Vec<_>>()
+		} else {
+			found.to_walk.extend(to_walk);
+			vec![]
+		};
+
+		running_tasks_count += new_tasks.len();
+		tasks.dispatch_many(new_tasks).await;
+
 		IndexerJobData::on_scan_progress(
 			ctx,
 			vec![ScanProgress::Message(format!(
-				"Found: {total_entries} entries; Scanning: {:?}",
-				path.file_name().unwrap_or(path.as_os_str())
+				"Found: {found_count} entries; Scanning: {running_tasks_count} directories"
 			))],
 		);
 	}
+
+	Ok(found)
 }
 
 async fn update_directories_sizes(
@@ -591,21 +716,7 @@ async fn update_directories_sizes(
 					directory_full_path.push(name);
 
 					if let Some(size) = paths_and_sizes.get(&directory_full_path) {
-						let size_bytes = size.to_be_bytes().to_vec();
-
-						Some((
-							sync.shared_update(
-								prisma_sync::file_path::SyncId {
-									pub_id: file_path.pub_id.clone(),
-								},
-								file_path::size_in_bytes_bytes::NAME,
-								json!(size_bytes.clone()),
-							),
-							db.file_path().update(
-								file_path::pub_id::equals(file_path.pub_id),
-								vec![file_path::size_in_bytes_bytes::set(Some(size_bytes))],
-							),
-						))
+						Some(update_directory_size(file_path.pub_id, *size, sync, db))
 					} else {
 						warn!("Found a file_path without ancestor in the database, possible corruption");
 						None
@@ -622,7 +733,46 @@ async fn update_directories_sizes(
 		)
 		.unzip::<_, _, Vec<_>, Vec<_>>();
 
-	sync.write_ops(db, to_sync_and_update).await?;
+	sync.write_ops(
+		db,
+		(
+			to_sync_and_update.0.into_iter().flatten().collect(),
+			to_sync_and_update.1,
+		),
+	)
+	.await?;
+
+	Ok(())
+}
+
+/// Fingerprints are only meaningful to the filesystem of this instance, so they aren't synced
+async fn update_directories_fingerprints(
+	paths_and_fingerprints: &HashMap<PathBuf, Vec<u8>>,
+	location_id: location::id::Type,
+	location_path: impl AsRef<Path>,
+	library: &Library,
+) -> Result<(), IndexerError> {
+	let location_path = location_path.as_ref();
+
+	let db = &library.db;
+
+	for chunk in &paths_and_fingerprints.iter().chunks(200) {
+		db._batch(
+			chunk
+				.map(|(path, fingerprint)| {
+					IsolatedFilePathData::new(location_id, location_path, path, true).map(
+						|iso_file_path| {
+							db.file_path().update_many(
+								vec![iso_file_path.into()],
+								vec![file_path::fingerprint::set(Some(fingerprint.clone()))],
+							)
+						},
+					)
+				})
+				.collect::<Result<Vec<_>, _>>()?,
+		)
+		.await?;
+	}
 
 	Ok(())
 }
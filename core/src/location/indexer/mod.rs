@@ -8,7 +8,11 @@ use sd_prisma::{
 	prisma_sync,
 };
 use sd_sync::*;
-use sd_utils::{db::inode_to_db, error::FileIOError, from_bytes_to_uuid};
+use sd_utils::{
+	db::{inode_to_db, size_in_bytes_to_db},
+	error::FileIOError,
+	from_bytes_to_uuid,
+};
 
 use std::{collections::HashMap, path::Path};
 
@@ -24,10 +28,13 @@ use tracing::{trace, warn};
 
 use super::location_with_indexer_rules;
 
+pub mod explain;
+mod fingerprint;
 pub mod indexer_job;
 pub mod rules;
 mod shallow;
-mod walk;
+mod tasks;
+pub(crate) mod walk;
 
 use rules::IndexerRuleError;
 use walk::WalkedEntry;
@@ -35,13 +42,13 @@ use walk::WalkedEntry;
 pub use indexer_job::IndexerJobInit;
 pub use shallow::*;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct IndexerJobSaveStep {
 	chunk_idx: usize,
 	walked: Vec<WalkedEntry>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct IndexerJobUpdateStep {
 	chunk_idx: usize,
 	to_update: Vec<WalkedEntry>,
@@ -124,7 +131,12 @@ async fn execute_indexer_save_step(
 					entry.metadata.size_in_bytes.to_be_bytes().to_vec(),
 					size_in_bytes_bytes
 				),
+				sync_db_entry!(
+					size_in_bytes_to_db(entry.metadata.size_in_bytes),
+					size_in_bytes_number
+				),
 				sync_db_entry!(inode_to_db(entry.metadata.inode), inode),
+				sync_db_entry!(inode_to_db(entry.metadata.device), device),
 				{
 					let v = entry.metadata.created_at.into();
 					sync_db_entry!(v, date_created)
@@ -140,6 +152,7 @@ async fn execute_indexer_save_step(
 				sync_db_entry!(entry.metadata.hidden, hidden),
 			]
 			.into_iter()
+			.chain(entry.metadata.attributes_params())
 			.unzip();
 
 			(
@@ -205,7 +218,12 @@ async fn execute_indexer_update_step(
 					entry.metadata.size_in_bytes.to_be_bytes().to_vec(),
 					size_in_bytes_bytes
 				)),
+				Some(sync_db_entry!(
+					size_in_bytes_to_db(entry.metadata.size_in_bytes),
+					size_in_bytes_number
+				)),
 				Some(sync_db_entry!(inode_to_db(entry.metadata.inode), inode)),
+				Some(sync_db_entry!(inode_to_db(entry.metadata.device), device)),
 				Some({
 					let v = entry.metadata.created_at.into();
 					sync_db_entry!(v, date_created)
@@ -218,6 +236,7 @@ async fn execute_indexer_update_step(
 			]
 			.into_iter()
 			.flatten()
+			.chain(entry.metadata.attributes_params())
 			.unzip();
 
 			Ok::<_, IndexerError>((
@@ -265,7 +284,7 @@ fn iso_file_path_factory(
 	}
 }
 
-async fn remove_non_existing_file_paths(
+pub(crate) async fn remove_non_existing_file_paths(
 	to_remove: impl IntoIterator<Item = file_path_pub_and_cas_ids::Data>,
 	db: &PrismaClient,
 	sync: &sd_core_sync::Manager,
@@ -523,21 +542,7 @@ pub async fn reverse_update_directories_sizes(
 					.materialized_path_for_children()
 					.expect("each ancestor is a directory"),
 			) {
-				let size_bytes = size.to_be_bytes().to_vec();
-
-				Some((
-					sync.shared_update(
-						prisma_sync::file_path::SyncId {
-							pub_id: pub_id.clone(),
-						},
-						file_path::size_in_bytes_bytes::NAME,
-						json!(size_bytes.clone()),
-					),
-					db.file_path().update(
-						file_path::pub_id::equals(pub_id),
-						vec![file_path::size_in_bytes_bytes::set(Some(size_bytes))],
-					),
-				))
+				Some(update_directory_size(pub_id, size, sync, db))
 			} else {
 				warn!("Got a missing ancestor for a file_path in the database, maybe we have a corruption");
 				None
@@ -545,7 +550,50 @@ pub async fn reverse_update_directories_sizes(
 		})
 		.unzip::<_, _, Vec<_>, Vec<_>>();
 
-	sync.write_ops(db, to_sync_and_update).await?;
+	sync.write_ops(
+		db,
+		(
+			to_sync_and_update.0.into_iter().flatten().collect(),
+			to_sync_and_update.1,
+		),
+	)
+	.await?;
 
 	Ok(())
 }
+
+/// Directory sizes are stored both as big endian bytes and as a number, so both must be updated
+fn update_directory_size<'db>(
+	pub_id: Vec<u8>,
+	size: u64,
+	sync: &crate::sync::Manager,
+	db: &'db PrismaClient,
+) -> (Vec<CRDTOperation>, file_path::UpdateQuery<'db>) {
+	let (sync_params, db_params): (Vec<_>, Vec<_>) = {
+		use file_path::*;
+
+		[
+			sync_db_entry!(size.to_be_bytes().to_vec(), size_in_bytes_bytes),
+			sync_db_entry!(size_in_bytes_to_db(size), size_in_bytes_number),
+		]
+		.into_iter()
+		.unzip()
+	};
+
+	(
+		sync_params
+			.into_iter()
+			.map(|(field, value)| {
+				sync.shared_update(
+					prisma_sync::file_path::SyncId {
+						pub_id: pub_id.clone(),
+					},
+					field,
+					value,
+				)
+			})
+			.collect(),
+		db.file_path()
+			.update(file_path::pub_id::equals(pub_id), db_params),
+	)
+}
@@ -0,0 +1,245 @@
+use std::{
+	fmt, io,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+
+use blake3::Hasher;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use mini_moka::sync::Cache;
+use once_cell::sync::Lazy;
+use tokio::fs;
+use tracing::warn;
+
+/// Ignore files honoured when the rule doesn't name any, the later ones take precedence
+pub const DEFAULT_IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+/// How many directories we keep the ignore files of, as watchers keep their rules for as long as
+/// the location is watched
+const MAX_CACHED_DIRS: u64 = 10_000;
+
+/// The user's `core.excludesFile`, which git applies to every repository
+static GLOBAL_EXCLUDES: Lazy<Option<Gitignore>> = Lazy::new(|| {
+	let (global_excludes, maybe_error) = Gitignore::global();
+	if let Some(e) = maybe_error {
+		warn!("Failed to load git global excludes file: {e:#?}");
+	}
+
+	(!global_excludes.is_empty()).then_some(global_excludes)
+});
+
+#[derive(Debug)]
+struct DirIgnores {
+	is_repo_root: bool,
+	/// Patterns from the ignore files in this directory
+	ignore_files: Option<Gitignore>,
+	/// Patterns from `.git/info/exclude`, only loaded on repository roots
+	exclude: Option<Gitignore>,
+	/// Hash of the contents of all the files above
+	contents_hash: blake3::Hash,
+}
+
+/// Rejects paths in the same way git would ignore them, honouring the ignore files on each
+/// directory from the repository root down, along with `.git/info/exclude` and the user's global
+/// excludes file. Outside of git repositories nothing is rejected, as git wouldn't ignore anything.
+///
+/// The ignore files of the most recently checked directories are cached, so [`Self::clear_cache`]
+/// must be called if any of them change.
+pub struct GitIgnoreRules {
+	ignore_files: Vec<String>,
+	dirs: Cache<PathBuf, Arc<DirIgnores>>,
+}
+
+impl fmt::Debug for GitIgnoreRules {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("GitIgnoreRules")
+			.field("ignore_files", &self.ignore_files)
+			.finish_non_exhaustive()
+	}
+}
+
+impl GitIgnoreRules {
+	pub fn new(ignore_files: impl IntoIterator<Item = String>) -> Self {
+		let mut ignore_files = ignore_files.into_iter().collect::<Vec<_>>();
+		if ignore_files.is_empty() {
+			ignore_files = DEFAULT_IGNORE_FILES.map(ToString::to_string).to_vec();
+		}
+
+		Self {
+			ignore_files,
+			dirs: Cache::new(MAX_CACHED_DIRS),
+		}
+	}
+
+	pub fn ignore_files(&self) -> &[String] {
+		&self.ignore_files
+	}
+
+	/// If changes to this path can change what is ignored
+	pub fn is_ignore_file(&self, path: impl AsRef<Path>) -> bool {
+		let path = path.as_ref();
+
+		path.ends_with(".git/info/exclude")
+			|| path
+				.file_name()
+				.and_then(|name| name.to_str())
+				.map_or(false, |name| {
+					self.ignore_files.iter().any(|file| file == name)
+				})
+	}
+
+	pub fn clear_cache(&self) {
+		self.dirs.invalidate_all();
+	}
+
+	pub async fn is_ignored(&self, path: impl AsRef<Path>) -> bool {
+		let path = path.as_ref();
+
+		// Paths that don't exist anymore, like on removals, are checked as files
+		let is_dir = fs::symlink_metadata(path)
+			.await
+			.map_or(false, |metadata| metadata.is_dir());
+
+		// The deepest directories first, as their ignore files take precedence
+		let mut dirs = vec![];
+		let mut found_repo_root = false;
+		for dir in path.ancestors().skip(1) {
+			let ignores = self.dir_ignores(dir).await;
+			found_repo_root = ignores.is_repo_root;
+			dirs.push((dir, ignores));
+
+			if found_repo_root {
+				break;
+			}
+		}
+
+		if !found_repo_root {
+			return false;
+		}
+
+		// Git doesn't look inside ignored directories, so nothing inside them can be re-included,
+		// and we check them from the repository root down
+		for i in (1..dirs.len()).rev() {
+			if ignored_by(&dirs[i..], dirs[i - 1].0, true) {
+				return true;
+			}
+		}
+
+		ignored_by(&dirs, path, is_dir)
+	}
+
+	/// Feeds `hasher` with everything deciding what is ignored inside `dir`, which are the ignore
+	/// files of `dir` and of its ancestors up to the repository root
+	pub async fn hash_ignores(&self, dir: impl AsRef<Path>, hasher: &mut Hasher) {
+		for dir in dir.as_ref().ancestors() {
+			let ignores = self.dir_ignores(dir).await;
+			hasher.update(&[u8::from(ignores.is_repo_root)]);
+			hasher.update(ignores.contents_hash.as_bytes());
+
+			if ignores.is_repo_root {
+				break;
+			}
+		}
+	}
+
+	async fn dir_ignores(&self, dir: &Path) -> Arc<DirIgnores> {
+		if let Some(ignores) = self.dirs.get(&dir.to_path_buf()) {
+			return ignores;
+		}
+
+		let git = dir.join(".git");
+
+		// `.git` is a file on worktrees and submodules, which are still repositories of their own
+		let maybe_git_metadata = fs::symlink_metadata(&git).await.ok();
+
+		let mut hasher = Hasher::new();
+
+		let ignores = DirIgnores {
+			is_repo_root: maybe_git_metadata.is_some(),
+			ignore_files: build_matcher(
+				dir,
+				self.ignore_files.iter().map(|file| dir.join(file)),
+				&mut hasher,
+			)
+			.await,
+			exclude: if maybe_git_metadata.map_or(false, |metadata| metadata.is_dir()) {
+				build_matcher(dir, [git.join("info").join("exclude")], &mut hasher).await
+			} else {
+				None
+			},
+			contents_hash: hasher.finalize(),
+		};
+
+		let ignores = Arc::new(ignores);
+
+		self.dirs.insert(dir.to_path_buf(), Arc::clone(&ignores));
+
+		ignores
+	}
+}
+
+/// Checks `path` against the ignore files of `dirs`, which must end on the repository root.
+/// Just like git, the first directory with a matching pattern decides, and `.git/info/exclude`
+/// and the global excludes file have the lowest precedence.
+fn ignored_by(dirs: &[(&Path, Arc<DirIgnores>)], path: &Path, is_dir: bool) -> bool {
+	let Some((repo_root, repo_ignores)) = dirs.last() else {
+		return false;
+	};
+
+	dirs.iter()
+		.filter_map(|(_, ignores)| ignores.ignore_files.as_ref())
+		.chain(repo_ignores.exclude.as_ref())
+		.map(|matcher| matcher.matched(path, is_dir))
+		.chain(GLOBAL_EXCLUDES.as_ref().map(|matcher| {
+			// The global excludes file isn't rooted on any repository
+			matcher.matched(path.strip_prefix(repo_root).unwrap_or(path), is_dir)
+		}))
+		.find(|matched| !matched.is_none())
+		.map_or(false, |matched| matched.is_ignore())
+}
+
+/// Builds a single matcher from the patterns of `files`, where patterns of the later files take
+/// precedence. Missing and invalid files or patterns are skipped, as git does. The contents of
+/// the files found are fed to `hasher`.
+async fn build_matcher(
+	root: &Path,
+	files: impl IntoIterator<Item = PathBuf>,
+	hasher: &mut Hasher,
+) -> Option<Gitignore> {
+	let mut builder = GitignoreBuilder::new(root);
+	let mut found_any = false;
+
+	for file in files {
+		let contents = match fs::read_to_string(&file).await {
+			Ok(contents) => contents,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+			Err(e) => {
+				warn!(
+					"Failed to read ignore file <path='{}'>: {e:#?}",
+					file.display()
+				);
+				continue;
+			}
+		};
+
+		found_any = true;
+		hasher.update(file.as_os_str().as_encoded_bytes());
+		hasher.update(&[0]);
+		hasher.update(contents.as_bytes());
+
+		for line in contents.lines() {
+			if let Err(e) = builder.add_line(Some(file.clone()), line) {
+				warn!("Skipping invalid pattern on ignore file: {e:#?}");
+			}
+		}
+	}
+
+	if !found_any {
+		return None;
+	}
+
+	builder
+		.build()
+		.map_err(|e| warn!("Failed to build ignore file patterns: {e:#?}"))
+		.ok()
+}
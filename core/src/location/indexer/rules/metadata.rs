@@ -0,0 +1,246 @@
+use std::{fmt, fs::Metadata, str::FromStr, time::SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use super::IndexerRuleError;
+
+const SIZE_UNITS: &[(&str, u64)] = &[
+	("", 1),
+	("B", 1),
+	("KB", 1000),
+	("MB", 1000 * 1000),
+	("GB", 1000 * 1000 * 1000),
+	("TB", 1000 * 1000 * 1000 * 1000),
+	("KiB", 1 << 10),
+	("MiB", 1 << 20),
+	("GiB", 1 << 30),
+	("TiB", 1 << 40),
+];
+
+const AGE_UNITS: &[(&str, u64)] = &[
+	("", 1),
+	("s", 1),
+	("m", 60),
+	("h", 60 * 60),
+	("d", 24 * 60 * 60),
+	("w", 7 * 24 * 60 * 60),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+	SizeInBytes,
+	/// Seconds since the last modification
+	Age,
+	Uid,
+	Gid,
+	Mode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+	LessThan,
+	LessThanOrEqual,
+	GreaterThan,
+	GreaterThanOrEqual,
+	Equal,
+	NotEqual,
+	/// Any of the bits are set
+	AnyBits,
+}
+
+/// A condition on the metadata of a file, written as `<field> <operator> <value>`, like
+/// `size > 20GiB`, `age >= 30d`, `uid = 1000`, `gid != 0` or `mode & 002`.
+///
+/// Sizes accept decimal (`KB`, `MB`, ...) and binary (`KiB`, `MiB`, ...) units, ages are counted
+/// from the last modification and accept `s`, `m`, `h`, `d` and `w`, and modes are always octal.
+/// Ownership and permission conditions never match on systems without unix permissions.
+///
+/// We keep the condition as written, so it's stored and shown back to the user unchanged.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct MetadataCondition {
+	field: Field,
+	operator: Operator,
+	value: u64,
+	source: String,
+}
+
+impl MetadataCondition {
+	pub fn matches(&self, metadata: &Metadata) -> bool {
+		let Some(actual) = self.field_value(metadata) else {
+			return false;
+		};
+
+		match self.operator {
+			Operator::LessThan => actual < self.value,
+			Operator::LessThanOrEqual => actual <= self.value,
+			Operator::GreaterThan => actual > self.value,
+			Operator::GreaterThanOrEqual => actual >= self.value,
+			Operator::Equal => actual == self.value,
+			Operator::NotEqual => actual != self.value,
+			Operator::AnyBits => (actual & self.value) != 0,
+		}
+	}
+
+	/// If the condition may stop or start matching a file as time goes by, without it changing
+	pub fn depends_on_time(&self) -> bool {
+		self.field == Field::Age
+	}
+
+	fn field_value(&self, metadata: &Metadata) -> Option<u64> {
+		#[cfg(target_family = "unix")]
+		use std::os::unix::fs::MetadataExt;
+
+		match self.field {
+			Field::SizeInBytes => Some(metadata.len()),
+			Field::Age => metadata.modified().ok().map(|modified_at| {
+				// Files modified in the future are brand new for us
+				SystemTime::now()
+					.duration_since(modified_at)
+					.map_or(0, |age| age.as_secs())
+			}),
+			#[cfg(target_family = "unix")]
+			Field::Uid => Some(u64::from(metadata.uid())),
+			#[cfg(target_family = "unix")]
+			Field::Gid => Some(u64::from(metadata.gid())),
+			#[cfg(target_family = "unix")]
+			Field::Mode => Some(u64::from(metadata.mode() & 0o7777)),
+			#[cfg(not(target_family = "unix"))]
+			Field::Uid | Field::Gid | Field::Mode => None,
+		}
+	}
+}
+
+impl FromStr for MetadataCondition {
+	type Err = IndexerRuleError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let source = s.trim();
+		let invalid = || IndexerRuleError::InvalidMetadataCondition(source.to_string());
+
+		let (field, rest) = source.split_at(
+			source
+				.find(|c: char| !c.is_ascii_alphabetic())
+				.ok_or_else(invalid)?,
+		);
+		let rest = rest.trim_start();
+		let (operator, value) = rest.split_at(
+			rest.find(|c: char| !matches!(c, '<' | '>' | '=' | '!' | '&'))
+				.ok_or_else(invalid)?,
+		);
+		let value = value.trim();
+
+		let field = match field.to_ascii_lowercase().as_str() {
+			"size" => Field::SizeInBytes,
+			"age" => Field::Age,
+			"uid" => Field::Uid,
+			"gid" => Field::Gid,
+			"mode" => Field::Mode,
+			_ => return Err(invalid()),
+		};
+
+		let operator = match operator {
+			"<" => Operator::LessThan,
+			"<=" => Operator::LessThanOrEqual,
+			">" => Operator::GreaterThan,
+			">=" => Operator::GreaterThanOrEqual,
+			"=" | "==" => Operator::Equal,
+			"!=" => Operator::NotEqual,
+			"&" if field == Field::Mode => Operator::AnyBits,
+			_ => return Err(invalid()),
+		};
+
+		let value = match field {
+			Field::SizeInBytes => parse_with_unit(value, SIZE_UNITS),
+			Field::Age => parse_with_unit(value, AGE_UNITS),
+			Field::Uid | Field::Gid => value.parse().ok(),
+			Field::Mode => u64::from_str_radix(value.trim_start_matches("0o"), 8)
+				.ok()
+				.filter(|mode| *mode <= 0o7777),
+		}
+		.ok_or_else(invalid)?;
+
+		Ok(Self {
+			field,
+			operator,
+			value,
+			source: source.to_string(),
+		})
+	}
+}
+
+impl TryFrom<String> for MetadataCondition {
+	type Error = IndexerRuleError;
+
+	fn try_from(s: String) -> Result<Self, Self::Error> {
+		s.parse()
+	}
+}
+
+impl From<MetadataCondition> for String {
+	fn from(condition: MetadataCondition) -> Self {
+		condition.source
+	}
+}
+
+impl fmt::Display for MetadataCondition {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.source)
+	}
+}
+
+fn parse_with_unit(value: &str, units: &[(&str, u64)]) -> Option<u64> {
+	let (number, unit) = value.split_at(
+		value
+			.find(|c: char| !c.is_ascii_digit())
+			.unwrap_or(value.len()),
+	);
+	let unit = unit.trim();
+
+	units
+		.iter()
+		.find(|(name, _)| name.eq_ignore_ascii_case(unit))
+		.and_then(|(_, multiplier)| number.parse::<u64>().ok()?.checked_mul(*multiplier))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_conditions() {
+		let condition = "size > 20GiB".parse::<MetadataCondition>().unwrap();
+		assert_eq!(condition.field, Field::SizeInBytes);
+		assert_eq!(condition.operator, Operator::GreaterThan);
+		assert_eq!(condition.value, 20 * 1024 * 1024 * 1024);
+		assert_eq!(condition.to_string(), "size > 20GiB");
+
+		let condition = " age>=30 d ".parse::<MetadataCondition>().unwrap();
+		assert_eq!(condition.operator, Operator::GreaterThanOrEqual);
+		assert_eq!(condition.value, 30 * 24 * 60 * 60);
+		assert_eq!(condition.to_string(), "age>=30 d");
+
+		let condition = "mode & 0o022".parse::<MetadataCondition>().unwrap();
+		assert_eq!(condition.operator, Operator::AnyBits);
+		assert_eq!(condition.value, 0o022);
+
+		assert_eq!("uid != 0".parse::<MetadataCondition>().unwrap().value, 0);
+
+		for invalid in [
+			"",
+			"size",
+			"size >",
+			"size > 20XB",
+			"color = red",
+			"uid & 1",
+			"mode = 0o8",
+			"mode = 17777",
+		] {
+			assert!(
+				invalid.parse::<MetadataCondition>().is_err(),
+				"{invalid} should be invalid"
+			);
+		}
+	}
+}
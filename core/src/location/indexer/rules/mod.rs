@@ -1,7 +1,8 @@
 use crate::library::Library;
 
-use sd_prisma::prisma::indexer_rule;
+use sd_prisma::prisma::{indexer_rule, location};
 use sd_utils::{
+	chain_optional_iter,
 	db::{maybe_missing, MissingFieldError},
 	error::{FileIOError, NonUtf8PathError},
 };
@@ -24,8 +25,14 @@ use tokio::fs;
 use tracing::debug;
 use uuid::Uuid;
 
+pub mod gitignore;
+pub mod metadata;
+pub mod rule_set;
 pub mod seed;
 
+use gitignore::GitIgnoreRules;
+use metadata::MetadataCondition;
+
 #[derive(Error, Debug)]
 pub enum IndexerRuleError {
 	// User errors
@@ -33,18 +40,34 @@ pub enum IndexerRuleError {
 	InvalidRuleKindInt(i32),
 	#[error("glob builder error: {0}")]
 	Glob(#[from] globset::Error),
+	#[error("invalid metadata condition: <condition='{0}'>")]
+	InvalidMetadataCondition(String),
 	#[error(transparent)]
 	NonUtf8Path(#[from] NonUtf8PathError),
+	#[error("indexer rule not found: <id='{0}'>")]
+	NotFound(indexer_rule::id::Type),
+	#[error("system indexer rules can't be updated: <id='{0}'>")]
+	SystemRule(indexer_rule::id::Type),
+	#[error("invalid indexer rule set: {0}")]
+	InvalidRuleSet(String),
+	#[error("invalid indexer rule <name='{0}'> in rule set: {1}")]
+	InvalidRuleSetRule(String, Box<IndexerRuleError>),
+	#[error("location not found: <id='{0}'>")]
+	LocationNotFound(location::id::Type),
 
 	// Internal Errors
 	#[error("indexer rule parameters encode error: {0}")]
 	RuleParametersRMPEncode(#[from] encode::Error),
 	#[error("indexer rule parameters decode error: {0}")]
 	RuleParametersRMPDecode(#[from] decode::Error),
+	#[error("indexer rule set serialization error: {0}")]
+	RuleSetSerialization(serde_json::Error),
 	#[error("accept by its children file I/O error: {0}")]
 	AcceptByItsChildrenFileIO(FileIOError),
 	#[error("reject by its children file I/O error: {0}")]
 	RejectByItsChildrenFileIO(FileIOError),
+	#[error("files by metadata file I/O error: {0}")]
+	FilesByMetadataFileIO(FileIOError),
 	#[error("database error: {0}")]
 	Database(#[from] prisma_client_rust::QueryError),
 	#[error("missing-field: {0}")]
@@ -56,9 +79,18 @@ impl From<IndexerRuleError> for rspc::Error {
 		match err {
 			IndexerRuleError::InvalidRuleKindInt(_)
 			| IndexerRuleError::Glob(_)
+			| IndexerRuleError::InvalidMetadataCondition(_)
+			| IndexerRuleError::InvalidRuleSet(_)
+			| IndexerRuleError::InvalidRuleSetRule(_, _)
 			| IndexerRuleError::NonUtf8Path(_) => {
 				rspc::Error::with_cause(ErrorCode::BadRequest, err.to_string(), err)
 			}
+			IndexerRuleError::NotFound(_) | IndexerRuleError::LocationNotFound(_) => {
+				rspc::Error::with_cause(ErrorCode::NotFound, err.to_string(), err)
+			}
+			IndexerRuleError::SystemRule(_) => {
+				rspc::Error::with_cause(ErrorCode::Forbidden, err.to_string(), err)
+			}
 
 			_ => rspc::Error::with_cause(ErrorCode::InternalServerError, err.to_string(), err),
 		}
@@ -73,6 +105,12 @@ impl From<IndexerRuleError> for rspc::Error {
 ///
 /// In case of `RuleKind::AcceptIfChildrenDirectoriesArePresent` or `RuleKind::RejectIfChildrenDirectoriesArePresent` the
 /// `parameters` field must be a vector of strings containing the names of the directories.
+///
+/// In case of `RuleKind::RejectIgnoredByGit` the `parameters` field can contain the names of the ignore files
+/// to honour, or be empty to honour `.gitignore` and `.ignore` files.
+///
+/// In case of `RuleKind::AcceptFilesByMetadata` or `RuleKind::RejectFilesByMetadata` the `parameters` field
+/// must be a vector of conditions like `size > 20GiB`, check [`MetadataCondition`] for the syntax.
 #[derive(Type, Deserialize)]
 pub struct IndexerRuleCreateArgs {
 	pub name: String,
@@ -96,30 +134,7 @@ impl IndexerRuleCreateArgs {
 			self.rules
 		);
 
-		let rules_data = rmp_serde::to_vec_named(
-			&self
-				.rules
-				.into_iter()
-				.map(|(kind, parameters)| match kind {
-					RuleKind::AcceptFilesByGlob => {
-						RulePerKind::new_accept_files_by_globs_str(parameters)
-					}
-					RuleKind::RejectFilesByGlob => {
-						RulePerKind::new_reject_files_by_globs_str(parameters)
-					}
-					RuleKind::AcceptIfChildrenDirectoriesArePresent => {
-						Ok(RulePerKind::AcceptIfChildrenDirectoriesArePresent(
-							parameters.into_iter().collect(),
-						))
-					}
-					RuleKind::RejectIfChildrenDirectoriesArePresent => {
-						Ok(RulePerKind::RejectIfChildrenDirectoriesArePresent(
-							parameters.into_iter().collect(),
-						))
-					}
-				})
-				.collect::<Result<Vec<_>, _>>()?,
-		)?;
+		let rules_data = rules_per_kind_to_db(self.rules)?;
 
 		if self.dry_run {
 			return Ok(None);
@@ -148,6 +163,72 @@ impl IndexerRuleCreateArgs {
 	}
 }
 
+/// `IndexerRuleUpdateArgs` is the argument received from the client using rspc to update an indexer rule,
+/// where `rules` has the same format as in [`IndexerRuleCreateArgs`] and replaces all rules when present.
+///
+/// System indexer rules are seeded again on every library load, so they can't be updated.
+#[derive(Type, Deserialize)]
+pub struct IndexerRuleUpdateArgs {
+	pub id: indexer_rule::id::Type,
+	pub name: Option<String>,
+	pub rules: Option<Vec<(RuleKind, Vec<String>)>>,
+}
+
+impl IndexerRuleUpdateArgs {
+	pub async fn update(self, library: &Library) -> Result<(), IndexerRuleError> {
+		let indexer_rule = library
+			.db
+			.indexer_rule()
+			.find_unique(indexer_rule::id::equals(self.id))
+			.select(indexer_rule::select!({ pub_id }))
+			.exec()
+			.await?
+			.ok_or(IndexerRuleError::NotFound(self.id))?;
+
+		if is_system_rule(&indexer_rule.pub_id) {
+			return Err(IndexerRuleError::SystemRule(self.id));
+		}
+
+		debug!(
+			"Updating indexer rule (id = {}, name = {:?}, params = {:?})",
+			self.id, self.name, self.rules
+		);
+
+		use indexer_rule::*;
+
+		library
+			.db
+			.indexer_rule()
+			.update(
+				id::equals(self.id),
+				chain_optional_iter(
+					[date_modified::set(Some(Utc::now().into()))],
+					[
+						self.name.map(|new_name| name::set(Some(new_name))),
+						self.rules
+							.map(rules_per_kind_to_db)
+							.transpose()?
+							.map(|rules_data| rules_per_kind::set(Some(rules_data))),
+					],
+				),
+			)
+			.exec()
+			.await?;
+
+		Ok(())
+	}
+}
+
+fn rules_per_kind_to_db(rules: Vec<(RuleKind, Vec<String>)>) -> Result<Vec<u8>, IndexerRuleError> {
+	rmp_serde::to_vec_named(
+		&rules
+			.into_iter()
+			.map(|(kind, parameters)| RulePerKind::from_parameters(kind, parameters))
+			.collect::<Result<Vec<_>, _>>()?,
+	)
+	.map_err(Into::into)
+}
+
 #[repr(i32)]
 #[non_exhaustive]
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, Type, Eq, PartialEq, Hash)]
@@ -156,12 +237,15 @@ pub enum RuleKind {
 	RejectFilesByGlob = 1,
 	AcceptIfChildrenDirectoriesArePresent = 2,
 	RejectIfChildrenDirectoriesArePresent = 3,
+	RejectIgnoredByGit = 4,
+	AcceptFilesByMetadata = 5,
+	RejectFilesByMetadata = 6,
 }
 
 impl RuleKind {
 	pub const fn variant_count() -> usize {
 		// TODO: Use https://doc.rust-lang.org/std/mem/fn.variant_count.html if it ever gets stabilized
-		4
+		7
 	}
 }
 
@@ -172,6 +256,11 @@ impl RuleKind {
 ///
 /// In case of `ParametersPerKind::AcceptIfChildrenDirectoriesArePresent` or `ParametersPerKind::RejectIfChildrenDirectoriesArePresent`
 /// first we change the data structure to a vector, then we serialize it.
+///
+/// In case of `ParametersPerKind::RejectIgnoredByGit` we only store the names of the ignore files.
+///
+/// In case of `ParametersPerKind::AcceptFilesByMetadata` or `ParametersPerKind::RejectFilesByMetadata`
+/// we store the conditions as they were written.
 #[derive(Debug)]
 pub enum RulePerKind {
 	// TODO: Add an indexer rule that filter files based on their extended attributes
@@ -181,6 +270,9 @@ pub enum RulePerKind {
 	RejectFilesByGlob(Vec<Glob>, GlobSet),
 	AcceptIfChildrenDirectoriesArePresent(HashSet<String>),
 	RejectIfChildrenDirectoriesArePresent(HashSet<String>),
+	RejectIgnoredByGit(GitIgnoreRules),
+	AcceptFilesByMetadata(Vec<MetadataCondition>),
+	RejectFilesByMetadata(Vec<MetadataCondition>),
 }
 
 impl RulePerKind {
@@ -217,6 +309,75 @@ impl RulePerKind {
 	) -> Result<Self, IndexerRuleError> {
 		Self::new_files_by_globs_str_and_kind(globs_str, Self::RejectFilesByGlob)
 	}
+
+	pub fn from_parameters(
+		kind: RuleKind,
+		parameters: Vec<String>,
+	) -> Result<Self, IndexerRuleError> {
+		match kind {
+			RuleKind::AcceptFilesByGlob => Self::new_accept_files_by_globs_str(parameters),
+			RuleKind::RejectFilesByGlob => Self::new_reject_files_by_globs_str(parameters),
+			RuleKind::AcceptIfChildrenDirectoriesArePresent => Ok(
+				Self::AcceptIfChildrenDirectoriesArePresent(parameters.into_iter().collect()),
+			),
+			RuleKind::RejectIfChildrenDirectoriesArePresent => Ok(
+				Self::RejectIfChildrenDirectoriesArePresent(parameters.into_iter().collect()),
+			),
+			RuleKind::RejectIgnoredByGit => {
+				Ok(Self::RejectIgnoredByGit(GitIgnoreRules::new(parameters)))
+			}
+			RuleKind::AcceptFilesByMetadata => parameters
+				.iter()
+				.map(|condition| condition.parse())
+				.collect::<Result<_, _>>()
+				.map(Self::AcceptFilesByMetadata),
+			RuleKind::RejectFilesByMetadata => parameters
+				.iter()
+				.map(|condition| condition.parse())
+				.collect::<Result<_, _>>()
+				.map(Self::RejectFilesByMetadata),
+		}
+	}
+
+	/// The inverse of [`RulePerKind::from_parameters`], so rules can be edited by users
+	pub fn to_parameters(&self) -> (RuleKind, Vec<String>) {
+		fn globs_to_parameters(globs: &[Glob]) -> Vec<String> {
+			globs.iter().map(|glob| glob.glob().to_string()).collect()
+		}
+
+		fn conditions_to_parameters(conditions: &[MetadataCondition]) -> Vec<String> {
+			conditions.iter().map(ToString::to_string).collect()
+		}
+
+		match self {
+			Self::AcceptFilesByGlob(globs, _) => {
+				(RuleKind::AcceptFilesByGlob, globs_to_parameters(globs))
+			}
+			Self::RejectFilesByGlob(globs, _) => {
+				(RuleKind::RejectFilesByGlob, globs_to_parameters(globs))
+			}
+			Self::AcceptIfChildrenDirectoriesArePresent(children) => (
+				RuleKind::AcceptIfChildrenDirectoriesArePresent,
+				children.iter().cloned().collect(),
+			),
+			Self::RejectIfChildrenDirectoriesArePresent(children) => (
+				RuleKind::RejectIfChildrenDirectoriesArePresent,
+				children.iter().cloned().collect(),
+			),
+			Self::RejectIgnoredByGit(git_ignore_rules) => (
+				RuleKind::RejectIgnoredByGit,
+				git_ignore_rules.ignore_files().to_vec(),
+			),
+			Self::AcceptFilesByMetadata(conditions) => (
+				RuleKind::AcceptFilesByMetadata,
+				conditions_to_parameters(conditions),
+			),
+			Self::RejectFilesByMetadata(conditions) => (
+				RuleKind::RejectFilesByMetadata,
+				conditions_to_parameters(conditions),
+			),
+		}
+	}
 }
 
 /// We're implementing `Serialize` by hand as `GlobSet`s aren't serializable, so we ignore them on
@@ -245,6 +406,27 @@ impl Serialize for RulePerKind {
 					"RejectIfChildrenDirectoriesArePresent",
 					children,
 				),
+			RulePerKind::RejectIgnoredByGit(ref git_ignore_rules) => serializer
+				.serialize_newtype_variant(
+					"ParametersPerKind",
+					4,
+					"RejectIgnoredByGit",
+					git_ignore_rules.ignore_files(),
+				),
+			RulePerKind::AcceptFilesByMetadata(ref conditions) => serializer
+				.serialize_newtype_variant(
+					"ParametersPerKind",
+					5,
+					"AcceptFilesByMetadata",
+					conditions,
+				),
+			RulePerKind::RejectFilesByMetadata(ref conditions) => serializer
+				.serialize_newtype_variant(
+					"ParametersPerKind",
+					6,
+					"RejectFilesByMetadata",
+					conditions,
+				),
 		}
 	}
 }
@@ -259,6 +441,9 @@ impl<'de> Deserialize<'de> for RulePerKind {
 			"RejectFilesByGlob",
 			"AcceptIfChildrenDirectoriesArePresent",
 			"RejectIfChildrenDirectoriesArePresent",
+			"RejectIgnoredByGit",
+			"AcceptFilesByMetadata",
+			"RejectFilesByMetadata",
 		];
 
 		enum Fields {
@@ -266,6 +451,9 @@ impl<'de> Deserialize<'de> for RulePerKind {
 			RejectFilesByGlob,
 			AcceptIfChildrenDirectoriesArePresent,
 			RejectIfChildrenDirectoriesArePresent,
+			RejectIgnoredByGit,
+			AcceptFilesByMetadata,
+			RejectFilesByMetadata,
 		}
 
 		struct FieldsVisitor;
@@ -278,7 +466,10 @@ impl<'de> Deserialize<'de> for RulePerKind {
 					"`AcceptFilesByGlob` \
 				or `RejectFilesByGlob` \
 				or `AcceptIfChildrenDirectoriesArePresent` \
-				or `RejectIfChildrenDirectoriesArePresent`",
+				or `RejectIfChildrenDirectoriesArePresent` \
+				or `RejectIgnoredByGit` \
+				or `AcceptFilesByMetadata` \
+				or `RejectFilesByMetadata`",
 				)
 			}
 
@@ -291,9 +482,12 @@ impl<'de> Deserialize<'de> for RulePerKind {
 					1 => Ok(Fields::RejectFilesByGlob),
 					2 => Ok(Fields::AcceptIfChildrenDirectoriesArePresent),
 					3 => Ok(Fields::RejectIfChildrenDirectoriesArePresent),
+					4 => Ok(Fields::RejectIgnoredByGit),
+					5 => Ok(Fields::AcceptFilesByMetadata),
+					6 => Ok(Fields::RejectFilesByMetadata),
 					_ => Err(de::Error::invalid_value(
 						de::Unexpected::Unsigned(value),
-						&"variant index 0 <= i < 3",
+						&"variant index 0 <= i < 7",
 					)),
 				}
 			}
@@ -310,6 +504,9 @@ impl<'de> Deserialize<'de> for RulePerKind {
 					"RejectIfChildrenDirectoriesArePresent" => {
 						Ok(Fields::RejectIfChildrenDirectoriesArePresent)
 					}
+					"RejectIgnoredByGit" => Ok(Fields::RejectIgnoredByGit),
+					"AcceptFilesByMetadata" => Ok(Fields::AcceptFilesByMetadata),
+					"RejectFilesByMetadata" => Ok(Fields::RejectFilesByMetadata),
 					_ => Err(de::Error::unknown_variant(value, VARIANTS)),
 				}
 			}
@@ -326,6 +523,9 @@ impl<'de> Deserialize<'de> for RulePerKind {
 					b"RejectIfChildrenDirectoriesArePresent" => {
 						Ok(Fields::RejectIfChildrenDirectoriesArePresent)
 					}
+					b"RejectIgnoredByGit" => Ok(Fields::RejectIgnoredByGit),
+					b"AcceptFilesByMetadata" => Ok(Fields::AcceptFilesByMetadata),
+					b"RejectFilesByMetadata" => Ok(Fields::RejectFilesByMetadata),
 					_ => Err(de::Error::unknown_variant(
 						&String::from_utf8_lossy(bytes),
 						VARIANTS,
@@ -411,6 +611,24 @@ impl<'de> Deserialize<'de> for RulePerKind {
 						reject_if_children_directories_are_present,
 					)
 					.map(Self::Value::RejectIfChildrenDirectoriesArePresent),
+					(Fields::RejectIgnoredByGit, reject_ignored_by_git) => {
+						de::VariantAccess::newtype_variant::<Vec<String>>(reject_ignored_by_git)
+							.map(|ignore_files| {
+								Self::Value::RejectIgnoredByGit(GitIgnoreRules::new(ignore_files))
+							})
+					}
+					(Fields::AcceptFilesByMetadata, accept_files_by_metadata) => {
+						de::VariantAccess::newtype_variant::<Vec<MetadataCondition>>(
+							accept_files_by_metadata,
+						)
+						.map(Self::Value::AcceptFilesByMetadata)
+					}
+					(Fields::RejectFilesByMetadata, reject_files_by_metadata) => {
+						de::VariantAccess::newtype_variant::<Vec<MetadataCondition>>(
+							reject_files_by_metadata,
+						)
+						.map(Self::Value::RejectFilesByMetadata)
+					}
 				})
 			}
 		}
@@ -448,6 +666,20 @@ impl RulePerKind {
 				RuleKind::RejectFilesByGlob,
 				reject_by_glob(source, reject_glob_set),
 			)),
+			RulePerKind::RejectIgnoredByGit(git_ignore_rules) => Ok((
+				RuleKind::RejectIgnoredByGit,
+				!git_ignore_rules.is_ignored(source).await,
+			)),
+			RulePerKind::AcceptFilesByMetadata(conditions) => {
+				accept_by_metadata(source, conditions)
+					.await
+					.map(|accepted| (RuleKind::AcceptFilesByMetadata, accepted))
+			}
+			RulePerKind::RejectFilesByMetadata(conditions) => {
+				reject_by_metadata(source, conditions)
+					.await
+					.map(|rejected| (RuleKind::RejectFilesByMetadata, rejected))
+			}
 		}
 	}
 }
@@ -463,6 +695,17 @@ pub struct IndexerRule {
 }
 
 impl IndexerRule {
+	/// If the rule may decide differently about a path as time goes by, without the path changing
+	pub fn depends_on_time(&self) -> bool {
+		self.rules.iter().any(|rule| match rule {
+			RulePerKind::AcceptFilesByMetadata(conditions)
+			| RulePerKind::RejectFilesByMetadata(conditions) => {
+				conditions.iter().any(MetadataCondition::depends_on_time)
+			}
+			_ => false,
+		})
+	}
+
 	pub async fn apply(
 		&self,
 		source: impl AsRef<Path>,
@@ -522,6 +765,39 @@ fn reject_by_glob(source: impl AsRef<Path>, reject_glob_set: &GlobSet) -> bool {
 	!accept_by_glob(source.as_ref(), reject_glob_set)
 }
 
+async fn accept_by_metadata(
+	source: impl AsRef<Path>,
+	conditions: &[MetadataCondition],
+) -> Result<bool, IndexerRuleError> {
+	let source = source.as_ref();
+
+	let metadata = fs::symlink_metadata(source)
+		.await
+		.map_err(|e| IndexerRuleError::FilesByMetadataFileIO(FileIOError::from((source, e))))?;
+
+	// Directories are always accepted, otherwise we wouldn't walk into them to find any file
+	Ok(metadata.is_dir()
+		|| conditions
+			.iter()
+			.any(|condition| condition.matches(&metadata)))
+}
+
+async fn reject_by_metadata(
+	source: impl AsRef<Path>,
+	conditions: &[MetadataCondition],
+) -> Result<bool, IndexerRuleError> {
+	let source = source.as_ref();
+
+	let metadata = fs::symlink_metadata(source)
+		.await
+		.map_err(|e| IndexerRuleError::FilesByMetadataFileIO(FileIOError::from((source, e))))?;
+
+	Ok(metadata.is_dir()
+		|| !conditions
+			.iter()
+			.any(|condition| condition.matches(&metadata)))
+}
+
 async fn accept_dir_for_its_children(
 	source: impl AsRef<Path>,
 	children: &HashSet<String>,
@@ -608,6 +884,11 @@ async fn reject_dir_for_its_children(
 	Ok(true)
 }
 
+/// System indexer rules are seeded with the smallest pub ids, which [`generate_pub_id`] never generates
+fn is_system_rule(pub_id: &[u8]) -> bool {
+	sd_utils::from_bytes_to_uuid(pub_id).as_u128() < 0xFFF
+}
+
 pub fn generate_pub_id() -> Uuid {
 	loop {
 		let pub_id = Uuid::new_v4();
@@ -795,6 +1076,106 @@ mod tests {
 		assert!(check_rule(&rule, not_project).await);
 	}
 
+	#[tokio::test]
+	async fn test_reject_ignored_by_git() {
+		let root = tempdir().unwrap();
+
+		let repo = root.path().join("repo");
+		let not_repo = root.path().join("not_repo");
+
+		fs::create_dir_all(repo.join(".git").join("info"))
+			.await
+			.unwrap();
+		fs::create_dir_all(repo.join("target").join("debug"))
+			.await
+			.unwrap();
+		fs::create_dir_all(repo.join("src").join("generated"))
+			.await
+			.unwrap();
+		fs::create_dir(&not_repo).await.unwrap();
+
+		fs::write(repo.join(".gitignore"), "target/\n*.log\n!keep.log\n")
+			.await
+			.unwrap();
+		fs::write(repo.join(".ignore"), "*.tmp\n").await.unwrap();
+		fs::write(
+			repo.join("src").join(".gitignore"),
+			"generated/\n!debug.log\n",
+		)
+		.await
+		.unwrap();
+		fs::write(
+			repo.join(".git").join("info").join("exclude"),
+			"secrets.txt\n",
+		)
+		.await
+		.unwrap();
+		fs::write(not_repo.join(".gitignore"), "*.log\n")
+			.await
+			.unwrap();
+
+		let rule = IndexerRule::new(
+			"git ignored".to_string(),
+			false,
+			vec![RulePerKind::RejectIgnoredByGit(GitIgnoreRules::new(vec![]))],
+		);
+
+		assert!(check_rule(&rule, repo.join("src").join("main.rs")).await);
+		assert!(check_rule(&rule, repo.join("keep.log")).await);
+		assert!(check_rule(&rule, repo.join("src").join("debug.log")).await);
+		assert!(check_rule(&rule, not_repo.join("debug.log")).await);
+		assert!(!check_rule(&rule, repo.join("target")).await);
+		assert!(!check_rule(&rule, repo.join("target").join("debug").join("app")).await);
+		assert!(!check_rule(&rule, repo.join("error.log")).await);
+		assert!(!check_rule(&rule, repo.join("src").join("generated")).await);
+		assert!(!check_rule(&rule, repo.join("notes.tmp")).await);
+		assert!(!check_rule(&rule, repo.join("secrets.txt")).await);
+	}
+
+	#[tokio::test]
+	async fn test_files_by_metadata() {
+		let root = tempdir().unwrap();
+
+		let small = root.path().join("small.txt");
+		let big = root.path().join("big.bin");
+		let dir = root.path().join("dir");
+
+		fs::write(&small, [0_u8; 16]).await.unwrap();
+		fs::write(&big, [0_u8; 4096]).await.unwrap();
+		fs::create_dir(&dir).await.unwrap();
+
+		let reject_rule = IndexerRule::new(
+			"no big files".to_string(),
+			false,
+			vec![RulePerKind::from_parameters(
+				RuleKind::RejectFilesByMetadata,
+				vec!["size > 1KiB".to_string()],
+			)
+			.unwrap()],
+		);
+
+		assert!(check_rule(&reject_rule, &small).await);
+		assert!(!check_rule(&reject_rule, &big).await);
+		assert!(check_rule(&reject_rule, &dir).await);
+
+		let accept_rule = IndexerRule::new(
+			"only fresh files".to_string(),
+			false,
+			vec![RulePerKind::from_parameters(
+				RuleKind::AcceptFilesByMetadata,
+				vec!["age < 1h".to_string(), "size = 0".to_string()],
+			)
+			.unwrap()],
+		);
+
+		assert!(check_rule(&accept_rule, &small).await);
+		assert!(check_rule(&accept_rule, &dir).await);
+
+		let (kind, parameters) = reject_rule.rules[0].to_parameters();
+		assert_eq!(kind, RuleKind::RejectFilesByMetadata);
+		assert_eq!(parameters, vec!["size > 1KiB".to_string()]);
+	}
+
 	impl PartialEq for RulePerKind {
 		fn eq(&self, other: &Self) -> bool {
 			match (self, other) {
@@ -814,6 +1195,18 @@ mod tests {
 					RulePerKind::RejectIfChildrenDirectoriesArePresent(self_childrens),
 					RulePerKind::RejectIfChildrenDirectoriesArePresent(other_childrens),
 				) => self_childrens == other_childrens,
+				(
+					RulePerKind::RejectIgnoredByGit(self_rules),
+					RulePerKind::RejectIgnoredByGit(other_rules),
+				) => self_rules.ignore_files() == other_rules.ignore_files(),
+				(
+					RulePerKind::AcceptFilesByMetadata(self_conditions),
+					RulePerKind::AcceptFilesByMetadata(other_conditions),
+				) => self_conditions == other_conditions,
+				(
+					RulePerKind::RejectFilesByMetadata(self_conditions),
+					RulePerKind::RejectFilesByMetadata(other_conditions),
+				) => self_conditions == other_conditions,
 				_ => false,
 			}
 		}
@@ -0,0 +1,383 @@
+use crate::library::Library;
+
+use sd_prisma::prisma::{
+	indexer_rule, indexer_rules_in_location, location, PrismaClient, SortOrder,
+};
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tracing::debug;
+
+use super::{
+	generate_pub_id, is_system_rule, rules_per_kind_to_db, IndexerRule, IndexerRuleError, RuleKind,
+};
+
+/// The latest version of the rule set format, bumped on breaking changes
+pub const RULE_SET_VERSION: u32 = 1;
+
+/// A portable set of indexer rules, to share them between libraries and nodes as a human-editable
+/// JSON file like:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "rules": [
+///     {
+///       "name": "No node_modules",
+///       "rules": [{ "kind": "RejectFilesByGlob", "parameters": ["**/node_modules"] }]
+///     }
+///   ]
+/// }
+/// ```
+///
+/// The `parameters` of each kind are the same as in [`super::IndexerRuleCreateArgs`].
+#[derive(Serialize, Deserialize, Type, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct IndexerRuleSet {
+	pub version: u32,
+	pub rules: Vec<IndexerRuleSetEntry>,
+}
+
+#[derive(Serialize, Deserialize, Type, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct IndexerRuleSetEntry {
+	pub name: String,
+	pub rules: Vec<IndexerRuleSetRule>,
+}
+
+#[derive(Serialize, Deserialize, Type, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct IndexerRuleSetRule {
+	pub kind: RuleKind,
+	#[serde(default)]
+	pub parameters: Vec<String>,
+}
+
+impl IndexerRuleSet {
+	/// Exports the indexer rules with these ids, or every indexer rule of the library if empty
+	pub async fn export(
+		library: &Library,
+		indexer_rules_ids: Vec<indexer_rule::id::Type>,
+	) -> Result<Self, IndexerRuleError> {
+		let indexer_rules = library
+			.db
+			.indexer_rule()
+			.find_many(if indexer_rules_ids.is_empty() {
+				vec![]
+			} else {
+				vec![indexer_rule::id::in_vec(indexer_rules_ids.clone())]
+			})
+			.order_by(indexer_rule::id::order(SortOrder::Asc))
+			.exec()
+			.await?;
+
+		if let Some(missing_id) = indexer_rules_ids
+			.into_iter()
+			.find(|id| !indexer_rules.iter().any(|rule| rule.id == *id))
+		{
+			return Err(IndexerRuleError::NotFound(missing_id));
+		}
+
+		indexer_rules
+			.into_iter()
+			.map(|data| {
+				IndexerRule::try_from(data).map(|rule| IndexerRuleSetEntry {
+					name: rule.name,
+					rules: rule
+						.rules
+						.iter()
+						.map(|rule| {
+							let (kind, parameters) = rule.to_parameters();
+							IndexerRuleSetRule { kind, parameters }
+						})
+						.collect(),
+				})
+			})
+			.collect::<Result<_, _>>()
+			.map(|rules| Self {
+				version: RULE_SET_VERSION,
+				rules,
+			})
+	}
+
+	pub fn to_json(&self) -> Result<String, IndexerRuleError> {
+		serde_json::to_string_pretty(self).map_err(IndexerRuleError::RuleSetSerialization)
+	}
+
+	pub fn from_json(json: &str) -> Result<Self, IndexerRuleError> {
+		let rule_set = serde_json::from_str::<Self>(json)
+			.map_err(|e| IndexerRuleError::InvalidRuleSet(e.to_string()))?;
+
+		if rule_set.version > RULE_SET_VERSION {
+			return Err(IndexerRuleError::InvalidRuleSet(format!(
+				"unsupported version {}, the latest supported is {RULE_SET_VERSION}",
+				rule_set.version
+			)));
+		}
+
+		Ok(rule_set)
+	}
+
+	/// Checks every rule, like the globs and metadata conditions, and gives back the serialized
+	/// rules of each name, ready to be written to the database
+	fn validate(self) -> Result<Vec<(String, Vec<u8>)>, IndexerRuleError> {
+		let mut names = HashSet::with_capacity(self.rules.len());
+
+		self.rules
+			.into_iter()
+			.map(|IndexerRuleSetEntry { name, rules }| {
+				let name = name.trim().to_string();
+
+				if name.is_empty() {
+					return Err(IndexerRuleError::InvalidRuleSet(
+						"indexer rules must have a name".to_string(),
+					));
+				}
+
+				if !names.insert(name.clone()) {
+					return Err(IndexerRuleError::InvalidRuleSet(format!(
+						"duplicated indexer rule name: <name='{name}'>"
+					)));
+				}
+
+				if rules.is_empty() {
+					return Err(IndexerRuleError::InvalidRuleSet(format!(
+						"indexer rule without rules: <name='{name}'>"
+					)));
+				}
+
+				rules_per_kind_to_db(
+					rules
+						.into_iter()
+						.map(|IndexerRuleSetRule { kind, parameters }| (kind, parameters))
+						.collect(),
+				)
+				.map(|rules_data| (name.clone(), rules_data))
+				.map_err(|e| IndexerRuleError::InvalidRuleSetRule(name, Box::new(e)))
+			})
+			.collect()
+	}
+}
+
+/// `IndexerRuleSetImportArgs` is the argument received from the client using rspc to import an
+/// [`IndexerRuleSet`] from the contents of its file.
+///
+/// Rules are upserted by name, so importing a rule set again replaces the rules of the ones
+/// imported before, but system indexer rules can't be replaced. Imported rules are also attached
+/// to the locations in `location_ids`.
+#[derive(Type, Deserialize, Debug)]
+pub struct IndexerRuleSetImportArgs {
+	pub contents: String,
+	#[serde(default)]
+	pub location_ids: Vec<location::id::Type>,
+}
+
+impl IndexerRuleSetImportArgs {
+	/// Nothing is written unless every rule is valid, returning the ids of the imported rules
+	pub async fn import(
+		self,
+		library: &Library,
+	) -> Result<Vec<indexer_rule::id::Type>, IndexerRuleError> {
+		let rules = IndexerRuleSet::from_json(&self.contents)?.validate()?;
+
+		debug!(
+			"Importing indexer rules (names = {:?}, location_ids = {:?})",
+			rules.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+			self.location_ids
+		);
+
+		let db = &library.db;
+
+		let locations = db
+			.location()
+			.find_many(vec![location::id::in_vec(self.location_ids.clone())])
+			.select(location::select!({ id }))
+			.exec()
+			.await?;
+
+		if let Some(missing_id) = self
+			.location_ids
+			.iter()
+			.find(|id| !locations.iter().any(|location| location.id == **id))
+		{
+			return Err(IndexerRuleError::LocationNotFound(*missing_id));
+		}
+
+		// In case of many rules with the same name, we keep updating the oldest one
+		let mut existing_by_name = HashMap::with_capacity(rules.len());
+		for existing in db
+			.indexer_rule()
+			.find_many(vec![indexer_rule::name::in_vec(
+				rules.iter().map(|(name, _)| name.clone()).collect(),
+			)])
+			.order_by(indexer_rule::id::order(SortOrder::Desc))
+			.select(indexer_rule::select!({ id pub_id name }))
+			.exec()
+			.await?
+		{
+			if let Some(name) = existing.name {
+				existing_by_name.insert(name, (existing.id, existing.pub_id));
+			}
+		}
+
+		if let Some((id, _)) = existing_by_name
+			.values()
+			.find(|(_, pub_id)| is_system_rule(pub_id))
+		{
+			return Err(IndexerRuleError::SystemRule(*id));
+		}
+
+		let location_ids = self.location_ids;
+
+		db._transaction()
+			.run(|db| async move {
+				let mut imported_ids = Vec::with_capacity(rules.len());
+
+				for (rule_name, rules_data) in rules {
+					imported_ids.push(upsert(&db, rule_name, rules_data, &existing_by_name).await?);
+				}
+
+				for location_id in location_ids {
+					let linked_ids = db
+						.indexer_rules_in_location()
+						.find_many(vec![indexer_rules_in_location::location_id::equals(
+							location_id,
+						)])
+						.select(indexer_rules_in_location::select!({ indexer_rule_id }))
+						.exec()
+						.await?
+						.into_iter()
+						.map(|link| link.indexer_rule_id)
+						.collect::<HashSet<_>>();
+
+					db.indexer_rules_in_location()
+						.create_many(
+							imported_ids
+								.iter()
+								.filter(|id| !linked_ids.contains(id))
+								.map(|id| {
+									indexer_rules_in_location::create_unchecked(
+										location_id,
+										*id,
+										vec![],
+									)
+								})
+								.collect(),
+						)
+						.exec()
+						.await?;
+				}
+
+				Ok::<_, IndexerRuleError>(imported_ids)
+			})
+			.await
+	}
+}
+
+async fn upsert(
+	db: &PrismaClient,
+	rule_name: String,
+	rules_data: Vec<u8>,
+	existing_by_name: &HashMap<String, (indexer_rule::id::Type, Vec<u8>)>,
+) -> Result<indexer_rule::id::Type, IndexerRuleError> {
+	use indexer_rule::*;
+
+	let now = Utc::now();
+
+	if let Some((existing_id, _)) = existing_by_name.get(&rule_name) {
+		db.indexer_rule()
+			.update(
+				id::equals(*existing_id),
+				vec![
+					rules_per_kind::set(Some(rules_data)),
+					date_modified::set(Some(now.into())),
+				],
+			)
+			.select(select!({ id }))
+			.exec()
+			.await
+			.map(|updated| updated.id)
+	} else {
+		db.indexer_rule()
+			.create(
+				sd_utils::uuid_to_bytes(generate_pub_id()),
+				vec![
+					name::set(Some(rule_name)),
+					rules_per_kind::set(Some(rules_data)),
+					date_created::set(Some(now.into())),
+					date_modified::set(Some(now.into())),
+				],
+			)
+			.select(select!({ id }))
+			.exec()
+			.await
+			.map(|created| created.id)
+	}
+	.map_err(Into::into)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_rule_set_validation() {
+		let rule_set = IndexerRuleSet::from_json(
+			r#"{
+				"version": 1,
+				"rules": [
+					{
+						"name": " No node_modules ",
+						"rules": [{ "kind": "RejectFilesByGlob", "parameters": ["**/node_modules"] }]
+					},
+					{
+						"name": "No git ignored",
+						"rules": [{ "kind": "RejectIgnoredByGit" }]
+					}
+				]
+			}"#,
+		)
+		.unwrap();
+
+		let validated = IndexerRuleSet::from_json(&rule_set.to_json().unwrap())
+			.unwrap()
+			.validate()
+			.unwrap();
+		assert_eq!(
+			validated
+				.iter()
+				.map(|(name, _)| name.as_str())
+				.collect::<Vec<_>>(),
+			["No node_modules", "No git ignored"]
+		);
+
+		for invalid in [
+			r#"{ "version": 2, "rules": [] }"#,
+			r#"{ "version": 1, "rules": [], "extra": true }"#,
+			r#"{ "version": 1, "rules": [{ "name": "a", "rules": [{ "kind": "Unknown" }] }] }"#,
+			r#"{ "version": 1, "rules": [{ "name": " ", "rules": [{ "kind": "RejectIgnoredByGit" }] }] }"#,
+			r#"{ "version": 1, "rules": [{ "name": "a", "rules": [] }] }"#,
+			r#"{ "version": 1, "rules": [
+				{ "name": "a", "rules": [{ "kind": "RejectIgnoredByGit" }] },
+				{ "name": "a", "rules": [{ "kind": "RejectIgnoredByGit" }] }
+			] }"#,
+			r#"{ "version": 1, "rules": [
+				{ "name": "a", "rules": [{ "kind": "AcceptFilesByGlob", "parameters": ["a[b"] }] }
+			] }"#,
+			r#"{ "version": 1, "rules": [
+				{ "name": "a", "rules": [{ "kind": "RejectFilesByMetadata", "parameters": ["size ~ 1"] }] }
+			] }"#,
+		] {
+			assert!(
+				IndexerRuleSet::from_json(invalid)
+					.and_then(IndexerRuleSet::validate)
+					.is_err(),
+				"{invalid} should be invalid"
+			);
+		}
+	}
+}
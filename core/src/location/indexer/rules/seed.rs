@@ -1,6 +1,9 @@
 use crate::{
 	library::Library,
-	location::indexer::rules::{IndexerRule, IndexerRuleError, RulePerKind},
+	location::indexer::rules::{
+		gitignore::{GitIgnoreRules, DEFAULT_IGNORE_FILES},
+		IndexerRule, IndexerRuleError, RulePerKind,
+	},
 };
 
 use sd_prisma::prisma::indexer_rule;
@@ -39,9 +42,15 @@ impl From<SystemIndexerRule> for IndexerRule {
 /// Seeds system indexer rules into a new or existing library,
 pub async fn new_or_existing_library(library: &Library) -> Result<(), SeederError> {
 	// DO NOT REORDER THIS ARRAY!
-	for (i, rule) in [no_os_protected(), no_hidden(), no_git(), only_images()]
-		.into_iter()
-		.enumerate()
+	for (i, rule) in [
+		no_os_protected(),
+		no_hidden(),
+		no_git(),
+		only_images(),
+		no_git_ignored(),
+	]
+	.into_iter()
+	.enumerate()
 	{
 		let pub_id = sd_utils::uuid_to_bytes(Uuid::from_u128(i as u128));
 		let rules = rmp_serde::to_vec_named(&rule.rules).map_err(IndexerRuleError::from)?;
@@ -207,3 +216,13 @@ fn only_images() -> SystemIndexerRule {
 		.expect("this is hardcoded and should always work")],
 	}
 }
+
+fn no_git_ignored() -> SystemIndexerRule {
+	SystemIndexerRule {
+		name: "No Git Ignored",
+		default: false,
+		rules: vec![RulePerKind::RejectIgnoredByGit(GitIgnoreRules::new(
+			DEFAULT_IGNORE_FILES.map(ToString::to_string),
+		))],
+	}
+}
@@ -3,17 +3,20 @@ use crate::{
 	job::JobError,
 	library::Library,
 	location::{
+		hard_links::update_hard_links,
 		indexer::{
 			execute_indexer_update_step, reverse_update_directories_sizes, IndexerJobUpdateStep,
 		},
-		scan_location_sub_path, update_location_size,
+		scan_location_sub_path,
+		symlinks::{SymlinkPolicy, SymlinkResolver},
+		update_location_size,
 	},
 	to_remove_db_fetcher_fn, Node,
 };
 
 use sd_file_path_helper::{
-	check_file_path_exists, ensure_sub_path_is_directory, ensure_sub_path_is_in_location,
-	IsolatedFilePathData,
+	attributes::xattr_allowlist_from_db, check_file_path_exists, ensure_sub_path_is_directory,
+	ensure_sub_path_is_in_location, IsolatedFilePathData,
 };
 use sd_utils::db::maybe_missing;
 
@@ -76,10 +79,18 @@ pub async fn shallow(
 		(false, location_path.to_path_buf())
 	};
 
+	let symlinks = SymlinkResolver::new(
+		SymlinkPolicy::from_db(location.symlink_policy),
+		Some(location_path),
+	)
+	.await;
+
 	let (walked, to_update, to_remove, errors, _s) = {
 		walk_single_dir(
 			&to_walk_path,
 			&indexer_rules,
+			&symlinks,
+			&xattr_allowlist_from_db(location.xattr_allowlist.as_deref()),
 			|_, _| {},
 			file_paths_db_fetcher_fn!(&db),
 			to_remove_db_fetcher_fn!(location_id, &db),
@@ -183,6 +194,10 @@ pub async fn shallow(
 				.map_err(IndexerError::from)?;
 		}
 
+		update_hard_links(&library.db, location_id)
+			.await
+			.map_err(IndexerError::from)?;
+
 		update_location_size(location.id, library)
 			.await
 			.map_err(IndexerError::from)?;
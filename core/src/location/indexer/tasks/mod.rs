@@ -0,0 +1,16 @@
+use sd_task_system::{ExecStatus, InterruptionKind};
+
+mod saver;
+mod updater;
+mod walker;
+
+pub(super) use saver::SaveTask;
+pub(super) use updater::UpdateTask;
+pub(super) use walker::{WalkDirTask, WalkDirTaskOutput};
+
+fn interrupted(kind: InterruptionKind) -> ExecStatus {
+	match kind {
+		InterruptionKind::Pause => ExecStatus::Paused,
+		InterruptionKind::Cancel => ExecStatus::Canceled,
+	}
+}
@@ -0,0 +1,53 @@
+use crate::{job::JobError, library::Library, location::location_with_indexer_rules};
+
+use sd_task_system::{ExecStatus, Interrupter, IntoAnyTaskOutput, Task, TaskId};
+
+use std::sync::Arc;
+
+use super::{
+	super::{execute_indexer_save_step, IndexerJobSaveStep},
+	interrupted,
+};
+
+/// Writes a chunk of newly found entries to the database, giving back how many were saved
+#[derive(Debug)]
+pub struct SaveTask {
+	id: TaskId,
+	location: location_with_indexer_rules::Data,
+	step: IndexerJobSaveStep,
+	library: Arc<Library>,
+}
+
+impl SaveTask {
+	pub fn new(
+		location: location_with_indexer_rules::Data,
+		step: IndexerJobSaveStep,
+		library: Arc<Library>,
+	) -> Self {
+		Self {
+			id: TaskId::new_v4(),
+			location,
+			step,
+			library,
+		}
+	}
+}
+
+#[async_trait::async_trait]
+impl Task<JobError> for SaveTask {
+	fn id(&self) -> TaskId {
+		self.id
+	}
+
+	async fn run(&mut self, interrupter: &Interrupter) -> Result<ExecStatus, JobError> {
+		// The whole chunk is written at once, so we can only stop before writing it
+		if let Some(kind) = interrupter.try_check_interrupt() {
+			return Ok(interrupted(kind));
+		}
+
+		let saved_count =
+			execute_indexer_save_step(&self.location, &self.step, &self.library).await?;
+
+		Ok(ExecStatus::Done(saved_count.into_output()))
+	}
+}
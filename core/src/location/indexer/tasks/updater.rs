@@ -0,0 +1,46 @@
+use crate::{job::JobError, library::Library};
+
+use sd_task_system::{ExecStatus, Interrupter, IntoAnyTaskOutput, Task, TaskId};
+
+use std::sync::Arc;
+
+use super::{
+	super::{execute_indexer_update_step, IndexerJobUpdateStep},
+	interrupted,
+};
+
+/// Writes a chunk of changed entries to the database, giving back how many were updated
+#[derive(Debug)]
+pub struct UpdateTask {
+	id: TaskId,
+	step: IndexerJobUpdateStep,
+	library: Arc<Library>,
+}
+
+impl UpdateTask {
+	pub fn new(step: IndexerJobUpdateStep, library: Arc<Library>) -> Self {
+		Self {
+			id: TaskId::new_v4(),
+			step,
+			library,
+		}
+	}
+}
+
+#[async_trait::async_trait]
+impl Task<JobError> for UpdateTask {
+	fn id(&self) -> TaskId {
+		self.id
+	}
+
+	async fn run(&mut self, interrupter: &Interrupter) -> Result<ExecStatus, JobError> {
+		// The whole chunk is written at once, so we can only stop before writing it
+		if let Some(kind) = interrupter.try_check_interrupt() {
+			return Ok(interrupted(kind));
+		}
+
+		let updated_count = execute_indexer_update_step(&self.step, &self.library).await?;
+
+		Ok(ExecStatus::Done(updated_count.into_output()))
+	}
+}
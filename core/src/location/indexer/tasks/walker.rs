@@ -0,0 +1,150 @@
+use crate::{
+	file_paths_db_fetcher_fn, job::JobError, library::Library, location::symlinks::SymlinkResolver,
+	to_remove_db_fetcher_fn,
+};
+
+use sd_file_path_helper::file_path_pub_and_cas_ids;
+use sd_prisma::prisma::location;
+use sd_task_system::{ExecStatus, Interrupter, IntoAnyTaskOutput, Task, TaskId};
+
+use std::{
+	collections::{HashMap, VecDeque},
+	future::IntoFuture,
+	path::PathBuf,
+	sync::Arc,
+};
+
+use futures::FutureExt;
+use futures_concurrency::future::Race;
+
+use super::{
+	super::{
+		iso_file_path_factory,
+		rules::IndexerRule,
+		walk::{walk, ToWalkEntry, WalkResult, WalkedEntry},
+		IndexerError,
+	},
+	interrupted,
+};
+
+/// How many entries a single task looks for before handing the directories it didn't reach over
+/// to new tasks, so a big tree gets spread among all workers of the task system
+const WALK_DIR_TASK_LIMIT: u64 = 1_000;
+
+/// Walks a directory and its sub directories, until finding [`WALK_DIR_TASK_LIMIT`] entries
+#[derive(Debug)]
+pub struct WalkDirTask {
+	id: TaskId,
+	entry: ToWalkEntry,
+	location_id: location::id::Type,
+	location_path: Arc<PathBuf>,
+	indexer_rules: Arc<Vec<IndexerRule>>,
+	symlinks: Arc<SymlinkResolver>,
+	xattr_allowlist: Arc<Vec<String>>,
+	library: Arc<Library>,
+	skip_unchanged: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct WalkDirTaskOutput {
+	pub walked: Vec<WalkedEntry>,
+	pub to_update: Vec<WalkedEntry>,
+	pub to_walk: VecDeque<ToWalkEntry>,
+	pub to_remove: Vec<file_path_pub_and_cas_ids::Data>,
+	pub errors: Vec<IndexerError>,
+	pub paths_and_sizes: HashMap<PathBuf, u64>,
+	pub paths_and_fingerprints: HashMap<PathBuf, Vec<u8>>,
+}
+
+impl WalkDirTask {
+	#[allow(clippy::too_many_arguments)]
+	pub fn new(
+		entry: ToWalkEntry,
+		location_id: location::id::Type,
+		location_path: Arc<PathBuf>,
+		indexer_rules: Arc<Vec<IndexerRule>>,
+		symlinks: Arc<SymlinkResolver>,
+		xattr_allowlist: Arc<Vec<String>>,
+		library: Arc<Library>,
+		skip_unchanged: bool,
+	) -> Self {
+		Self {
+			id: TaskId::new_v4(),
+			entry,
+			location_id,
+			location_path,
+			indexer_rules,
+			symlinks,
+			xattr_allowlist,
+			library,
+			skip_unchanged,
+		}
+	}
+}
+
+#[async_trait::async_trait]
+impl Task<JobError> for WalkDirTask {
+	fn id(&self) -> TaskId {
+		self.id
+	}
+
+	async fn run(&mut self, interrupter: &Interrupter) -> Result<ExecStatus, JobError> {
+		let Self {
+			entry,
+			location_id,
+			location_path,
+			indexer_rules,
+			symlinks,
+			xattr_allowlist,
+			library,
+			skip_unchanged,
+			..
+		} = &*self;
+
+		let db = &library.db;
+
+		let walking = walk(
+			entry.clone(),
+			indexer_rules,
+			symlinks,
+			xattr_allowlist,
+			|_, _| {},
+			file_paths_db_fetcher_fn!(db),
+			to_remove_db_fetcher_fn!(*location_id, db),
+			iso_file_path_factory(*location_id, location_path),
+			WALK_DIR_TASK_LIMIT,
+			*skip_unchanged,
+		)
+		.map(Ok);
+
+		// Walking doesn't write anything, so when interrupted we just stop right away, and walk
+		// the same directories again if resumed
+		match (walking, interrupter.into_future().map(Err)).race().await {
+			Ok(walk_result) => {
+				let WalkResult {
+					walked,
+					to_update,
+					to_walk,
+					to_remove,
+					errors,
+					paths_and_sizes,
+					paths_and_fingerprints,
+				} = walk_result?;
+
+				Ok(ExecStatus::Done(
+					WalkDirTaskOutput {
+						walked: walked.collect(),
+						to_update: to_update.collect(),
+						to_walk,
+						to_remove: to_remove.collect(),
+						errors,
+						paths_and_sizes,
+						paths_and_fingerprints,
+					}
+					.into_output(),
+				))
+			}
+			Err(kind) => Ok(interrupted(kind)),
+		}
+	}
+}
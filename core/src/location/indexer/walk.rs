@@ -1,3 +1,5 @@
+use crate::location::symlinks::{ResolvedPath, SymlinkResolver};
+
 use sd_file_path_helper::{
 	file_path_pub_and_cas_ids, file_path_walker, FilePathMetadata, IsolatedFilePathData,
 };
@@ -18,6 +20,7 @@ use tracing::trace;
 use uuid::Uuid;
 
 use super::{
+	fingerprint::DirectoryFingerprint,
 	rules::{IndexerRule, RuleKind},
 	IndexerError,
 };
@@ -28,7 +31,7 @@ const WALK_SINGLE_DIR_PATHS_BUFFER_INITIAL_CAPACITY: usize = 32;
 
 /// `WalkEntry` represents a single path in the filesystem, for any comparison purposes, we only
 /// consider the path itself, not the metadata.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WalkedEntry {
 	pub pub_id: Uuid,
 	pub maybe_object_id: file_path::object_id::Type,
@@ -36,11 +39,25 @@ pub struct WalkedEntry {
 	pub metadata: FilePathMetadata,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToWalkEntry {
 	path: PathBuf,
 	parent_dir_accepted_by_its_children: Option<bool>,
 	maybe_parent: Option<PathBuf>,
+	/// Canonical paths of the directories where we followed symbolic links to get here
+	#[serde(default)]
+	followed_from: Vec<PathBuf>,
+}
+
+impl From<&Path> for ToWalkEntry {
+	fn from(path: &Path) -> Self {
+		Self {
+			path: path.to_path_buf(),
+			parent_dir_accepted_by_its_children: None,
+			maybe_parent: None,
+			followed_from: vec![],
+		}
+	}
 }
 
 #[derive(Debug)]
@@ -99,6 +116,82 @@ impl Hash for WalkingEntry {
 	}
 }
 
+/// What the indexer rules decided about a path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RulesDecision {
+	/// The kind of the rules that kept the path out of the index, if any
+	pub rejected_by: Option<RuleKind>,
+	/// Whether we walk into it, for directories that weren't rejected along with their children
+	pub walk_children: bool,
+	/// What the accept by children rules decided so far, to be passed on to its children
+	pub accepted_by_children: Option<bool>,
+}
+
+/// Decides about a path from the results of [`IndexerRule::apply_all`] on it, the same way for
+/// every walk over a location, so the indexer, the watchers and rule explanations all agree.
+///
+/// The accept by children rules have three states: `None` if we don't know yet or they don't
+/// apply, `Some(true)` if they accepted a directory above the path and `Some(false)` if they
+/// rejected it, so `parent_accepted_by_children` is what they decided for the parent directory.
+pub(crate) fn decide_by_rules(
+	rules_per_kind: &HashMap<RuleKind, Vec<bool>>,
+	is_dir: bool,
+	parent_accepted_by_children: Option<bool>,
+) -> RulesDecision {
+	let rejected_by = |kind: &RuleKind| {
+		rules_per_kind
+			.get(kind)
+			.map_or(false, |results| results.iter().any(|accepted| !accepted))
+	};
+	let not_accepted_by = |kind: &RuleKind| {
+		rules_per_kind
+			.get(kind)
+			.map_or(false, |results| results.iter().all(|accepted| !accepted))
+	};
+
+	// Rejected along with its children, if it's a directory
+	if let Some(kind) = [
+		RuleKind::RejectFilesByGlob,
+		RuleKind::RejectIgnoredByGit,
+		RuleKind::RejectFilesByMetadata,
+	]
+	.into_iter()
+	.chain(is_dir.then_some(RuleKind::RejectIfChildrenDirectoriesArePresent))
+	.find(rejected_by)
+	{
+		return RulesDecision {
+			rejected_by: Some(kind),
+			walk_children: false,
+			accepted_by_children: parent_accepted_by_children,
+		};
+	}
+
+	let mut accepted_by_children = parent_accepted_by_children;
+
+	if is_dir {
+		if let Some(results) = rules_per_kind.get(&RuleKind::AcceptIfChildrenDirectoriesArePresent)
+		{
+			if results.iter().any(|accepted| *accepted) {
+				accepted_by_children = Some(true);
+			} else if accepted_by_children.is_none() {
+				accepted_by_children = Some(false);
+			}
+		}
+	}
+
+	RulesDecision {
+		rejected_by: [RuleKind::AcceptFilesByGlob, RuleKind::AcceptFilesByMetadata]
+			.into_iter()
+			.find(not_accepted_by)
+			.or_else(|| {
+				(!accepted_by_children.unwrap_or(true))
+					.then_some(RuleKind::AcceptIfChildrenDirectoriesArePresent)
+			}),
+		walk_children: is_dir,
+		accepted_by_children,
+	}
+}
+
 pub struct WalkResult<Walked, ToUpdate, ToRemove>
 where
 	Walked: Iterator<Item = WalkedEntry>,
@@ -111,14 +204,25 @@ where
 	pub to_remove: ToRemove,
 	pub errors: Vec<IndexerError>,
 	pub paths_and_sizes: HashMap<PathBuf, u64>,
+	/// The new fingerprints of the directories walked, to be saved once they're indexed
+	pub paths_and_fingerprints: HashMap<PathBuf, Vec<u8>>,
 }
 
 /// This function walks through the filesystem, applying the rules to each entry and then returning
 /// a list of accepted entries. There are some useful comments in the implementation of this function
 /// in case of doubts.
+///
+/// The walk starts at `to_walk_entry` and stops after finding `limit` entries, returning the
+/// directories left to walk, so they can be walked later or by someone else.
+///
+/// With `skip_unchanged`, the entries of directories whose [`DirectoryFingerprint`] didn't change
+/// since they were last walked are skipped, while their sub directories are still walked.
+#[allow(clippy::too_many_arguments)]
 pub(super) async fn walk<FilePathDBFetcherFut, ToRemoveDbFetcherFut>(
-	root: impl AsRef<Path>,
+	to_walk_entry: ToWalkEntry,
 	indexer_rules: &[IndexerRule],
+	symlinks: &SymlinkResolver,
+	xattr_allowlist: &[String],
 	mut update_notifier: impl FnMut(&Path, usize),
 	file_paths_db_fetcher: impl Fn(Vec<file_path::WhereParam>) -> FilePathDBFetcherFut,
 	to_remove_db_fetcher: impl Fn(
@@ -127,6 +231,7 @@ pub(super) async fn walk<FilePathDBFetcherFut, ToRemoveDbFetcherFut>(
 	) -> ToRemoveDbFetcherFut,
 	iso_file_path_factory: impl Fn(&Path, bool) -> Result<IsolatedFilePathData<'static>, IndexerError>,
 	limit: u64,
+	skip_unchanged: bool,
 ) -> Result<
 	WalkResult<
 		impl Iterator<Item = WalkedEntry>,
@@ -140,32 +245,34 @@ where
 	ToRemoveDbFetcherFut:
 		Future<Output = Result<Vec<file_path_pub_and_cas_ids::Data>, IndexerError>>,
 {
-	let root = root.as_ref();
+	let root = to_walk_entry.path.clone();
 
 	let mut to_walk = VecDeque::with_capacity(TO_WALK_QUEUE_INITIAL_CAPACITY);
-	to_walk.push_back(ToWalkEntry {
-		path: root.to_path_buf(),
-		parent_dir_accepted_by_its_children: None,
-		maybe_parent: None,
-	});
+	to_walk.push_back(to_walk_entry);
 	let mut indexed_paths = HashSet::with_capacity(WALKER_PATHS_BUFFER_INITIAL_CAPACITY);
 	let mut errors = vec![];
 	let mut paths_buffer = HashSet::with_capacity(WALKER_PATHS_BUFFER_INITIAL_CAPACITY);
 	let mut paths_and_sizes = HashMap::with_capacity(TO_WALK_QUEUE_INITIAL_CAPACITY);
+	let mut paths_and_fingerprints = HashMap::with_capacity(TO_WALK_QUEUE_INITIAL_CAPACITY);
 	let mut to_remove = vec![];
 
 	while let Some(entry) = to_walk.pop_front() {
 		let (entry_size, current_to_remove) = inner_walk_single_dir(
-			root,
+			&root,
 			&entry,
 			indexer_rules,
+			symlinks,
+			xattr_allowlist,
 			&mut update_notifier,
+			&file_paths_db_fetcher,
 			&to_remove_db_fetcher,
 			&iso_file_path_factory,
+			skip_unchanged,
 			WorkingTable {
 				indexed_paths: &mut indexed_paths,
 				paths_buffer: &mut paths_buffer,
 				maybe_to_walk: Some(&mut to_walk),
+				maybe_fingerprints: Some(&mut paths_and_fingerprints),
 				errors: &mut errors,
 			},
 		)
@@ -194,77 +301,16 @@ where
 		to_remove: to_remove.into_iter().flatten(),
 		errors,
 		paths_and_sizes,
+		paths_and_fingerprints,
 	})
 }
 
-pub(super) async fn keep_walking<FilePathDBFetcherFut, ToRemoveDbFetcherFut>(
-	to_walk_entry: &ToWalkEntry,
-	indexer_rules: &[IndexerRule],
-	mut update_notifier: impl FnMut(&Path, usize),
-	file_paths_db_fetcher: impl Fn(Vec<file_path::WhereParam>) -> FilePathDBFetcherFut,
-	to_remove_db_fetcher: impl Fn(
-		IsolatedFilePathData<'static>,
-		Vec<file_path::WhereParam>,
-	) -> ToRemoveDbFetcherFut,
-	iso_file_path_factory: impl Fn(&Path, bool) -> Result<IsolatedFilePathData<'static>, IndexerError>,
-) -> Result<
-	WalkResult<
-		impl Iterator<Item = WalkedEntry>,
-		impl Iterator<Item = WalkedEntry>,
-		impl Iterator<Item = file_path_pub_and_cas_ids::Data>,
-	>,
-	IndexerError,
->
-where
-	FilePathDBFetcherFut: Future<Output = Result<Vec<file_path_walker::Data>, IndexerError>>,
-	ToRemoveDbFetcherFut:
-		Future<Output = Result<Vec<file_path_pub_and_cas_ids::Data>, IndexerError>>,
-{
-	let mut to_keep_walking = VecDeque::with_capacity(TO_WALK_QUEUE_INITIAL_CAPACITY);
-	let mut indexed_paths = HashSet::with_capacity(WALK_SINGLE_DIR_PATHS_BUFFER_INITIAL_CAPACITY);
-	let mut paths_buffer = HashSet::with_capacity(WALK_SINGLE_DIR_PATHS_BUFFER_INITIAL_CAPACITY);
-	let mut errors = vec![];
-
-	let (to_walk_entry_size, to_remove) = inner_walk_single_dir(
-		to_walk_entry.path.clone(),
-		to_walk_entry,
-		indexer_rules,
-		&mut update_notifier,
-		&to_remove_db_fetcher,
-		&iso_file_path_factory,
-		WorkingTable {
-			indexed_paths: &mut indexed_paths,
-			paths_buffer: &mut paths_buffer,
-			maybe_to_walk: Some(&mut to_keep_walking),
-			errors: &mut errors,
-		},
-	)
-	.await;
-
-	let (walked, to_update) = filter_existing_paths(indexed_paths, file_paths_db_fetcher).await?;
-
-	Ok(WalkResult {
-		walked,
-		to_update,
-		to_walk: to_keep_walking,
-		to_remove: to_remove.into_iter(),
-		errors,
-		paths_and_sizes: [
-			Some((to_walk_entry.path.clone(), to_walk_entry_size)),
-			to_walk_entry
-				.maybe_parent
-				.as_ref()
-				.map(|parent_path| (parent_path.clone(), to_walk_entry_size)),
-		]
-		.into_iter()
-		.flatten()
-		.collect(),
-	})
-}
-
+#[allow(clippy::too_many_arguments)]
 pub(super) async fn walk_single_dir<FilePathDBFetcherFut, ToRemoveDbFetcherFut>(
 	root: impl AsRef<Path>,
 	indexer_rules: &[IndexerRule],
+	symlinks: &SymlinkResolver,
+	xattr_allowlist: &[String],
 	mut update_notifier: impl FnMut(&Path, usize) + '_,
 	file_paths_db_fetcher: impl Fn(Vec<file_path::WhereParam>) -> FilePathDBFetcherFut,
 	to_remove_db_fetcher: impl Fn(
@@ -299,7 +345,9 @@ where
 
 		indexed_paths.insert(WalkingEntry {
 			iso_file_path: iso_file_path_factory(root, true)?,
-			maybe_metadata: Some(FilePathMetadata::from_path(&root, &metadata).await?),
+			maybe_metadata: Some(
+				FilePathMetadata::from_path(&root, &metadata, xattr_allowlist).await?,
+			),
 		});
 	}
 
@@ -312,15 +360,21 @@ where
 			path: root.to_path_buf(),
 			parent_dir_accepted_by_its_children: None,
 			maybe_parent: None,
+			followed_from: vec![],
 		},
 		indexer_rules,
+		symlinks,
+		xattr_allowlist,
 		&mut update_notifier,
+		&file_paths_db_fetcher,
 		&to_remove_db_fetcher,
 		&iso_file_path_factory,
+		false,
 		WorkingTable {
 			indexed_paths: &mut indexed_paths,
 			paths_buffer: &mut paths_buffer,
 			maybe_to_walk: None,
+			maybe_fingerprints: None,
 			errors: &mut errors,
 		},
 	)
@@ -378,10 +432,15 @@ where
 					) {
 						if (
 								inode_from_db(&inode[0..8]) != metadata.inode
+								// Paths indexed before we stored devices only get theirs when they change, to
+								// avoid identifying every file again
+								|| file_path.device.as_ref().is_some_and(|device| inode_from_db(&device[0..8]) != metadata.device)
 								// Datetimes stored in DB loses a bit of precision, so we need to check against a delta
 								// instead of using != operator
 								|| DateTime::<FixedOffset>::from(metadata.modified_at) - *date_modified
 									> Duration::milliseconds(1) || file_path.hidden.is_none() || metadata.hidden != file_path.hidden.unwrap_or_default()
+								// Permissions, ownership and extended attributes change without touching the modification date
+								|| metadata.attributes_changed(file_path.mode, file_path.uid, file_path.gid, file_path.xattrs.as_deref())
 							)
 							// We ignore the size of directories because it is not reliable, we need to
 							// calculate it ourselves later
@@ -426,31 +485,40 @@ struct WorkingTable<'a> {
 	indexed_paths: &'a mut HashSet<WalkingEntry>,
 	paths_buffer: &'a mut HashSet<WalkingEntry>,
 	maybe_to_walk: Option<&'a mut VecDeque<ToWalkEntry>>,
+	maybe_fingerprints: Option<&'a mut HashMap<PathBuf, Vec<u8>>>,
 	errors: &'a mut Vec<IndexerError>,
 }
 
-async fn inner_walk_single_dir<ToRemoveDbFetcherFut>(
+#[allow(clippy::too_many_arguments)]
+async fn inner_walk_single_dir<FilePathDBFetcherFut, ToRemoveDbFetcherFut>(
 	root: impl AsRef<Path>,
 	ToWalkEntry {
 		path,
 		parent_dir_accepted_by_its_children,
-		..
+		maybe_parent,
+		followed_from,
 	}: &ToWalkEntry,
 	indexer_rules: &[IndexerRule],
+	symlinks: &SymlinkResolver,
+	xattr_allowlist: &[String],
 	update_notifier: &mut impl FnMut(&Path, usize),
+	file_paths_db_fetcher: &impl Fn(Vec<file_path::WhereParam>) -> FilePathDBFetcherFut,
 	to_remove_db_fetcher: impl Fn(
 		IsolatedFilePathData<'static>,
 		Vec<file_path::WhereParam>,
 	) -> ToRemoveDbFetcherFut,
 	iso_file_path_factory: &impl Fn(&Path, bool) -> Result<IsolatedFilePathData<'static>, IndexerError>,
+	skip_unchanged: bool,
 	WorkingTable {
 		indexed_paths,
 		paths_buffer,
 		mut maybe_to_walk,
+		maybe_fingerprints,
 		errors,
 	}: WorkingTable<'_>,
 ) -> (u64, Vec<file_path_pub_and_cas_ids::Data>)
 where
+	FilePathDBFetcherFut: Future<Output = Result<Vec<file_path_walker::Data>, IndexerError>>,
 	ToRemoveDbFetcherFut:
 		Future<Output = Result<Vec<file_path_pub_and_cas_ids::Data>, IndexerError>>,
 {
@@ -468,13 +536,15 @@ where
 
 	let root = root.as_ref();
 
-	// Just to make sure...
-	paths_buffer.clear();
-
-	let mut found_paths_counts = 0;
+	// Any error from now on means this directory wasn't fully indexed, so we can't save its fingerprint
+	let errors_count = errors.len();
 
-	// Marking with a loop label here in case of rejection or errors, to continue with next entry
-	'entries: loop {
+	// Reading every entry upfront, so we can fingerprint the directory before indexing its contents
+	let mut fingerprint =
+		DirectoryFingerprint::new(indexer_rules, symlinks.policy(), xattr_allowlist);
+	fingerprint.add_ignore_files(indexer_rules, path).await;
+	let mut entries = vec![];
+	loop {
 		let entry = match read_dir.next_entry().await {
 			Ok(Some(entry)) => entry,
 			Ok(None) => break,
@@ -484,15 +554,51 @@ where
 			}
 		};
 
-		// Accept by children has three states,
-		// None if we don't now yet or if this check doesn't apply
-		// Some(true) if this check applies and it passes
-		// Some(false) if this check applies and it was rejected
-		// and we pass the current parent state to its children
-		let mut accept_by_children_dir = *parent_dir_accepted_by_its_children;
-
 		let current_path = entry.path();
 
+		let Ok(metadata) = entry
+			.metadata()
+			.await
+			.map_err(|e| errors.push(FileIOError::from((&current_path, e)).into()))
+		else {
+			continue;
+		};
+
+		fingerprint.add(entry.file_name(), &metadata);
+		entries.push((current_path, metadata));
+	}
+	let fingerprint = fingerprint.finish();
+
+	// The root of a walk was explicitly asked for, so we always walk it. An unchanged directory
+	// still has its sub directories walked, as changes deeper in the tree don't reach its fingerprint.
+	// Rules depending on time may now decide otherwise about entries that didn't change at all.
+	let unchanged = skip_unchanged
+		&& maybe_parent.is_some()
+		&& !indexer_rules.iter().any(IndexerRule::depends_on_time)
+		&& match file_paths_db_fetcher(vec![(&iso_file_path_to_walk).into()]).await {
+			Ok(file_paths) => file_paths
+				.iter()
+				.any(|file_path| file_path.fingerprint.as_ref() == Some(&fingerprint)),
+			Err(e) => {
+				errors.push(e);
+				false
+			}
+		};
+
+	if unchanged {
+		trace!("Skipping entries of unchanged directory {}", path.display());
+	}
+
+	// The size of the entries of an unchanged directory, which we don't index again
+	let mut unchanged_size = 0;
+
+	// Just to make sure...
+	paths_buffer.clear();
+
+	let mut found_paths_counts = 0;
+
+	// Marking with a loop label here in case of rejection or errors, to continue with next entry
+	'entries: for (current_path, metadata) in entries {
 		// Just sending updates if we found more paths since the last loop
 		let current_found_paths_count = paths_buffer.len();
 		if found_paths_counts != current_found_paths_count {
@@ -504,9 +610,9 @@ where
 		}
 
 		trace!(
-			"Current filesystem path: {}, accept_by_children_dir: {:#?}",
+			"Current filesystem path: {}, parent_dir_accepted_by_its_children: {:#?}",
 			current_path.display(),
-			accept_by_children_dir
+			parent_dir_accepted_by_its_children
 		);
 
 		let Ok(rules_per_kind) = IndexerRule::apply_all(indexer_rules, &current_path)
@@ -516,152 +622,129 @@ where
 			continue 'entries;
 		};
 
-		if rules_per_kind
-			.get(&RuleKind::RejectFilesByGlob)
-			.map_or(false, |reject_results| {
-				reject_results.iter().any(|reject| !reject)
-			}) {
-			trace!(
-				"Path {} rejected by `RuleKind::RejectFilesByGlob`",
-				current_path.display()
-			);
-			continue 'entries;
-		}
-
-		let Ok(metadata) = entry
-			.metadata()
+		let (metadata, maybe_followed_from) = match symlinks
+			.resolve(&current_path, metadata, followed_from)
 			.await
-			.map_err(|e| errors.push(FileIOError::from((&current_path, e)).into()))
-		else {
-			continue 'entries;
-		};
-
-		// TODO: Hard ignoring symlinks for now, but this should be configurable
-		if metadata.is_symlink() {
-			continue 'entries;
-		}
-
-		let is_dir = metadata.is_dir();
-
-		if is_dir {
-			// If it is a directory, first we check if we must reject it and its children entirely
-			if rules_per_kind
-				.get(&RuleKind::RejectIfChildrenDirectoriesArePresent)
-				.map_or(false, |reject_results| {
-					reject_results.iter().any(|reject| !reject)
-				}) {
+		{
+			ResolvedPath::Regular(metadata) | ResolvedPath::Link(metadata) => (metadata, None),
+			ResolvedPath::Followed { metadata, from } => (metadata, Some(from)),
+			ResolvedPath::Skipped => {
 				trace!(
-					"Path {} rejected by rule `RuleKind::RejectIfChildrenDirectoriesArePresent`",
-					current_path.display(),
+					"Path {} skipped by the location's symlink policy",
+					current_path.display()
 				);
 				continue 'entries;
 			}
+		};
 
-			// Then we check if we must accept it and its children
-			if let Some(accept_by_children_rules) =
-				rules_per_kind.get(&RuleKind::AcceptIfChildrenDirectoriesArePresent)
-			{
-				if accept_by_children_rules.iter().any(|accept| *accept) {
-					accept_by_children_dir = Some(true);
-				}
+		let is_dir = metadata.is_dir();
 
-				// If it wasn't accepted then we mark as rejected
-				if accept_by_children_dir.is_none() {
-					trace!(
-						"Path {} rejected because it didn't passed in any AcceptIfChildrenDirectoriesArePresent rule",
-						current_path.display()
-					);
-					accept_by_children_dir = Some(false);
-				}
-			}
+		let RulesDecision {
+			rejected_by,
+			walk_children,
+			accepted_by_children,
+		} = decide_by_rules(
+			&rules_per_kind,
+			is_dir,
+			*parent_dir_accepted_by_its_children,
+		);
 
-			// Then we mark this directory the be walked in too
+		if walk_children {
 			if let Some(ref mut to_walk) = maybe_to_walk {
 				to_walk.push_back(ToWalkEntry {
 					path: current_path.clone(),
-					parent_dir_accepted_by_its_children: accept_by_children_dir,
+					parent_dir_accepted_by_its_children: accepted_by_children,
 					maybe_parent: Some(path.clone()),
+					followed_from: followed_from
+						.iter()
+						.cloned()
+						.chain(maybe_followed_from)
+						.collect(),
 				});
 			}
 		}
 
-		if rules_per_kind
-			.get(&RuleKind::AcceptFilesByGlob)
-			.map_or(false, |accept_rules| {
-				accept_rules.iter().all(|accept| !accept)
-			}) {
+		if let Some(kind) = rejected_by {
 			trace!(
-				"Path {} reject because it didn't passed in any AcceptFilesByGlob rules",
+				"Path {} rejected by `RuleKind::{kind:?}`",
 				current_path.display()
 			);
 			continue 'entries;
 		}
 
-		if accept_by_children_dir.unwrap_or(true) {
-			let Ok(iso_file_path) =
-				iso_file_path_factory(&current_path, is_dir).map_err(|e| errors.push(e))
-			else {
-				continue 'entries;
-			};
+		if unchanged {
+			unchanged_size += metadata.len();
+			continue 'entries;
+		}
+
+		let Ok(iso_file_path) =
+			iso_file_path_factory(&current_path, is_dir).map_err(|e| errors.push(e))
+		else {
+			continue 'entries;
+		};
+
+		let Ok(metadata) = FilePathMetadata::from_path(&current_path, &metadata, xattr_allowlist)
+			.await
+			.map_err(|e| errors.push(e.into()))
+		else {
+			continue;
+		};
 
-			let Ok(metadata) = FilePathMetadata::from_path(&current_path, &metadata)
-				.await
-				.map_err(|e| errors.push(e.into()))
+		paths_buffer.insert(WalkingEntry {
+			iso_file_path,
+			maybe_metadata: Some(metadata),
+		});
+
+		// If the ancestors directories wasn't indexed before, now we do
+		for ancestor in current_path
+			.ancestors()
+			.skip(1) // Skip the current directory as it was already indexed
+			.take_while(|&ancestor| ancestor != root)
+		{
+			let Ok(iso_file_path) =
+				iso_file_path_factory(ancestor, true).map_err(|e| errors.push(e))
 			else {
+				// Checking the next ancestor, as this one we got an error
 				continue;
 			};
 
-			paths_buffer.insert(WalkingEntry {
+			let mut ancestor_iso_walking_entry = WalkingEntry {
 				iso_file_path,
-				maybe_metadata: Some(metadata),
-			});
-
-			// If the ancestors directories wasn't indexed before, now we do
-			for ancestor in current_path
-				.ancestors()
-				.skip(1) // Skip the current directory as it was already indexed
-				.take_while(|&ancestor| ancestor != root)
-			{
-				let Ok(iso_file_path) =
-					iso_file_path_factory(ancestor, true).map_err(|e| errors.push(e))
+				maybe_metadata: None,
+			};
+			trace!("Indexing ancestor {}", ancestor.display());
+			if !indexed_paths.contains(&ancestor_iso_walking_entry) {
+				let Ok(metadata) = fs::metadata(ancestor)
+					.await
+					.map_err(|e| errors.push(FileIOError::from((&ancestor, e)).into()))
 				else {
 					// Checking the next ancestor, as this one we got an error
 					continue;
 				};
 
-				let mut ancestor_iso_walking_entry = WalkingEntry {
-					iso_file_path,
-					maybe_metadata: None,
-				};
-				trace!("Indexing ancestor {}", ancestor.display());
-				if !indexed_paths.contains(&ancestor_iso_walking_entry) {
-					let Ok(metadata) = fs::metadata(ancestor)
-						.await
-						.map_err(|e| errors.push(FileIOError::from((&ancestor, e)).into()))
-					else {
-						// Checking the next ancestor, as this one we got an error
-						continue;
-					};
-
-					let Ok(metadata) = FilePathMetadata::from_path(&ancestor, &metadata)
+				let Ok(metadata) =
+					FilePathMetadata::from_path(&ancestor, &metadata, xattr_allowlist)
 						.await
 						.map_err(|e| errors.push(e.into()))
-					else {
-						continue;
-					};
+				else {
+					continue;
+				};
 
-					ancestor_iso_walking_entry.maybe_metadata = Some(metadata);
+				ancestor_iso_walking_entry.maybe_metadata = Some(metadata);
 
-					paths_buffer.insert(ancestor_iso_walking_entry);
-				} else {
-					// If indexed_paths contains the current ancestors, then it will contain
-					// also all if its ancestors too, so we can stop here
-					break;
-				}
+				paths_buffer.insert(ancestor_iso_walking_entry);
+			} else {
+				// If indexed_paths contains the current ancestors, then it will contain
+				// also all if its ancestors too, so we can stop here
+				break;
 			}
 		}
 	}
 
+	if unchanged {
+		return (unchanged_size, vec![]);
+	}
+
 	// We continue the function even if we fail to fetch `file_path`s to remove,
 	// the DB will have old `file_path`s but at least this is better than
 	// don't adding the newly indexed paths
@@ -679,6 +762,12 @@ where
 		vec![]
 	});
 
+	if let Some(fingerprints) = maybe_fingerprints {
+		if errors.len() == errors_count {
+			fingerprints.insert(path.clone(), fingerprint);
+		}
+	}
+
 	let mut to_walk_entry_size = 0;
 
 	// Just merging the `found_paths` with `indexed_paths` here in the end to avoid possibly
@@ -698,6 +787,7 @@ where
 mod tests {
 	use super::super::rules::RulePerKind;
 	use super::*;
+	use crate::location::symlinks::SymlinkPolicy;
 	use chrono::Utc;
 	use globset::{Glob, GlobSetBuilder};
 	use tempfile::{tempdir, TempDir};
@@ -782,46 +872,119 @@ mod tests {
 
 		let metadata = FilePathMetadata {
 			inode: 0,
+			device: 0,
+			size_in_bytes: 0,
+			created_at: Utc::now(),
+			modified_at: Utc::now(),
+			hidden: false,
+			permissions: None,
+			xattrs: Default::default(),
+		};
+
+		let f = |path, is_dir| IsolatedFilePathData::new(0, root_path, path, is_dir).unwrap();
+		let pub_id = Uuid::new_v4();
+		let maybe_object_id = None;
+
+		#[rustfmt::skip]
+		let expected = [
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/.git"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/Cargo.toml"), false), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src/main.rs"), false), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target/debug"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target/debug/main"), false), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/.git"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/package.json"), false), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src/App.tsx"), false), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react/package.json"), false), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo1.png"), false), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo2.jpg"), false), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo3.jpeg"), false), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/text.txt"), false), metadata: metadata.clone() },
+		]
+		.into_iter()
+		.collect::<HashSet<_>>();
+
+		let walk_result = walk(
+			root_path.into(),
+			&[],
+			&SymlinkResolver::new(SymlinkPolicy::Ignore, None::<&Path>).await,
+			&[],
+			|_, _| {},
+			|_| async { Ok(vec![]) },
+			|_, _| async { Ok(vec![]) },
+			|path, is_dir| {
+				IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
+			},
+			420,
+			true,
+		)
+		.await
+		.unwrap();
+
+		if !walk_result.errors.is_empty() {
+			panic!("errors: {:#?}", walk_result.errors);
+		}
+
+		let actual = walk_result.walked.collect::<HashSet<_>>();
+
+		if actual != expected {
+			panic!("difference: {:#?}", expected.difference(&actual));
+		}
+	}
+
+	#[cfg(target_family = "unix")]
+	#[tokio::test]
+	async fn test_follow_symlinks_inside_location() {
+		use std::os::unix::fs::symlink;
+
+		let root = tempdir().unwrap();
+		let root_path = root.path();
+		let dir = root_path.join("dir");
+		fs::create_dir(&dir).await.unwrap();
+		fs::write(dir.join("file.txt"), b"text").await.unwrap();
+		symlink(dir.join("file.txt"), root_path.join("to_file")).unwrap();
+		symlink(&dir, root_path.join("to_dir")).unwrap();
+
+		let metadata = FilePathMetadata {
+			inode: 0,
+			device: 0,
 			size_in_bytes: 0,
 			created_at: Utc::now(),
 			modified_at: Utc::now(),
 			hidden: false,
+			permissions: None,
+			xattrs: Default::default(),
 		};
 
 		let f = |path, is_dir| IsolatedFilePathData::new(0, root_path, path, is_dir).unwrap();
 		let pub_id = Uuid::new_v4();
 		let maybe_object_id = None;
 
+		// The links are indexed as their targets, and walked into when they're directories
 		#[rustfmt::skip]
 		let expected = [
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/.git"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/Cargo.toml"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src/main.rs"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target/debug"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target/debug/main"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/.git"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/package.json"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src/App.tsx"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react/package.json"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo1.png"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo2.jpg"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo3.jpeg"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/text.txt"), false), metadata },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("dir"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("dir/file.txt"), false), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("to_file"), false), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("to_dir"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("to_dir/file.txt"), false), metadata: metadata.clone() },
 		]
 		.into_iter()
 		.collect::<HashSet<_>>();
 
 		let walk_result = walk(
-			root_path.to_path_buf(),
+			root_path.into(),
+			&[],
+			&SymlinkResolver::new(SymlinkPolicy::Follow, Some(root_path)).await,
 			&[],
 			|_, _| {},
 			|_| async { Ok(vec![]) },
@@ -830,6 +993,7 @@ mod tests {
 				IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
 			},
 			420,
+			true,
 		)
 		.await
 		.unwrap();
@@ -843,6 +1007,14 @@ mod tests {
 		if actual != expected {
 			panic!("difference: {:#?}", expected.difference(&actual));
 		}
+
+		// Files reached both ways share their device and inode, so they're linked as hard links
+		let inodes = actual
+			.iter()
+			.filter(|entry| !entry.iso_file_path.to_parts().is_dir)
+			.map(|entry| (entry.metadata.device, entry.metadata.inode))
+			.collect::<HashSet<_>>();
+		assert_eq!(inodes.len(), 1);
 	}
 
 	#[tokio::test]
@@ -853,10 +1025,13 @@ mod tests {
 
 		let metadata = FilePathMetadata {
 			inode: 0,
+			device: 0,
 			size_in_bytes: 0,
 			created_at: Utc::now(),
 			modified_at: Utc::now(),
 			hidden: false,
+			permissions: None,
+			xattrs: Default::default(),
 		};
 
 		let f = |path, is_dir| IsolatedFilePathData::new(0, root_path, path, is_dir).unwrap();
@@ -865,10 +1040,10 @@ mod tests {
 
 		#[rustfmt::skip]
 		let expected = [
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo1.png"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo2.jpg"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo3.jpeg"), false), metadata },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo1.png"), false), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo2.jpg"), false), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("photos/photo3.jpeg"), false), metadata: metadata.clone() },
 		]
 		.into_iter()
 		.collect::<HashSet<_>>();
@@ -886,8 +1061,10 @@ mod tests {
 		)];
 
 		let walk_result = walk(
-			root_path.to_path_buf(),
+			root_path.into(),
 			only_photos_rule,
+			&SymlinkResolver::new(SymlinkPolicy::Ignore, None::<&Path>).await,
+			&[],
 			|_, _| {},
 			|_| async { Ok(vec![]) },
 			|_, _| async { Ok(vec![]) },
@@ -895,6 +1072,7 @@ mod tests {
 				IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
 			},
 			420,
+			true,
 		)
 		.await
 		.unwrap();
@@ -918,10 +1096,13 @@ mod tests {
 
 		let metadata = FilePathMetadata {
 			inode: 0,
+			device: 0,
 			size_in_bytes: 0,
 			created_at: Utc::now(),
 			modified_at: Utc::now(),
 			hidden: false,
+			permissions: None,
+			xattrs: Default::default(),
 		};
 
 		let f = |path, is_dir| IsolatedFilePathData::new(0, root_path, path, is_dir).unwrap();
@@ -930,23 +1111,23 @@ mod tests {
 
 		#[rustfmt::skip]
 		let expected = [
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/.git"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/Cargo.toml"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src/main.rs"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target/debug"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target/debug/main"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/.git"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/package.json"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src/App.tsx"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react/package.json"), false), metadata },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/.git"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/Cargo.toml"), false), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src/main.rs"), false), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target/debug"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/target/debug/main"), false), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/.git"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/package.json"), false), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src/App.tsx"), false), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/node_modules/react/package.json"), false), metadata: metadata.clone() },
 		]
 		.into_iter()
 		.collect::<HashSet<_>>();
@@ -960,8 +1141,10 @@ mod tests {
 		)];
 
 		let walk_result = walk(
-			root_path.to_path_buf(),
+			root_path.into(),
 			git_repos,
+			&SymlinkResolver::new(SymlinkPolicy::Ignore, None::<&Path>).await,
+			&[],
 			|_, _| {},
 			|_| async { Ok(vec![]) },
 			|_, _| async { Ok(vec![]) },
@@ -969,6 +1152,7 @@ mod tests {
 				IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
 			},
 			420,
+			true,
 		)
 		.await
 		.unwrap();
@@ -984,6 +1168,109 @@ mod tests {
 		}
 	}
 
+	#[tokio::test]
+	async fn test_skip_unchanged_walks_sub_directories() {
+		let root = tempdir().unwrap();
+		let root_path = root.path();
+		let deep_dir = root_path.join("a/b/c");
+
+		fs::create_dir_all(&deep_dir).await.unwrap();
+		fs::File::create(root_path.join("a/sibling.txt"))
+			.await
+			.unwrap();
+		fs::File::create(deep_dir.join("old.txt")).await.unwrap();
+
+		let symlinks = SymlinkResolver::new(SymlinkPolicy::Ignore, None::<&Path>).await;
+		let iso_file_path_factory =
+			|path: &Path, is_dir| -> Result<IsolatedFilePathData<'static>, IndexerError> {
+				IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
+			};
+
+		let first_walk = walk(
+			root_path.into(),
+			&[],
+			&symlinks,
+			&[],
+			|_, _| {},
+			|_| async { Ok(vec![]) },
+			|_, _| async { Ok(vec![]) },
+			iso_file_path_factory,
+			420,
+			true,
+		)
+		.await
+		.unwrap();
+
+		// What the database would have for the directories after the first walk
+		let stored = first_walk
+			.paths_and_fingerprints
+			.into_iter()
+			.filter(|(path, _)| path != root_path)
+			.map(|(path, fingerprint)| {
+				let iso_file_path = IsolatedFilePathData::new(0, root_path, &path, true).unwrap();
+				let parts = iso_file_path.to_parts();
+
+				file_path_walker::Data {
+					pub_id: Uuid::new_v4().as_bytes().to_vec(),
+					location_id: Some(0),
+					object_id: None,
+					materialized_path: Some(parts.materialized_path.to_string()),
+					is_dir: Some(true),
+					name: Some(parts.name.to_string()),
+					extension: Some(parts.extension.to_string()),
+					date_modified: None,
+					inode: None,
+					device: None,
+					size_in_bytes_bytes: None,
+					hidden: None,
+					fingerprint: Some(fingerprint),
+					mode: None,
+					uid: None,
+					gid: None,
+					xattrs: None,
+				}
+			})
+			.collect::<Vec<_>>();
+
+		// Three levels deep, so `a` doesn't change
+		fs::File::create(deep_dir.join("new.txt")).await.unwrap();
+
+		let second_walk = walk(
+			root_path.into(),
+			&[],
+			&symlinks,
+			&[],
+			|_, _| {},
+			|_| {
+				let stored = stored.clone();
+				async move { Ok(stored) }
+			},
+			|_, _| async { Ok(vec![]) },
+			iso_file_path_factory,
+			420,
+			true,
+		)
+		.await
+		.unwrap();
+
+		if !second_walk.errors.is_empty() {
+			panic!("errors: {:#?}", second_walk.errors);
+		}
+
+		let walked = second_walk
+			.walked
+			.map(|entry| entry.iso_file_path)
+			.collect::<HashSet<_>>();
+
+		assert!(walked.contains(
+			&IsolatedFilePathData::new(0, root_path, deep_dir.join("new.txt"), false).unwrap()
+		));
+		assert!(!walked.contains(
+			&IsolatedFilePathData::new(0, root_path, root_path.join("a/sibling.txt"), false)
+				.unwrap()
+		));
+	}
+
 	#[tokio::test]
 	// #[traced_test]
 	async fn git_repos_without_deps_or_build_dirs() {
@@ -992,10 +1279,13 @@ mod tests {
 
 		let metadata = FilePathMetadata {
 			inode: 0,
+			device: 0,
 			size_in_bytes: 0,
 			created_at: Utc::now(),
 			modified_at: Utc::now(),
 			hidden: false,
+			permissions: None,
+			xattrs: Default::default(),
 		};
 
 		let f = |path, is_dir| IsolatedFilePathData::new(0, root_path, path, is_dir).unwrap();
@@ -1004,17 +1294,17 @@ mod tests {
 
 		#[rustfmt::skip]
 		let expected = [
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/.git"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/Cargo.toml"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src/main.rs"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/.git"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/package.json"), false), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src"), true), metadata },
-			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src/App.tsx"), false), metadata },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/.git"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/Cargo.toml"), false), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("rust_project/src/main.rs"), false), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/.git"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/package.json"), false), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src"), true), metadata: metadata.clone() },
+			WalkedEntry { pub_id, maybe_object_id, iso_file_path: f(root_path.join("inner/node_project/src/App.tsx"), false), metadata: metadata.clone() },
 		]
 		.into_iter()
 		.collect::<HashSet<_>>();
@@ -1052,8 +1342,10 @@ mod tests {
 		];
 
 		let walk_result = walk(
-			root_path.to_path_buf(),
+			root_path.into(),
 			git_repos_no_deps_no_build_dirs,
+			&SymlinkResolver::new(SymlinkPolicy::Ignore, None::<&Path>).await,
+			&[],
 			|_, _| {},
 			|_| async { Ok(vec![]) },
 			|_, _| async { Ok(vec![]) },
@@ -1061,6 +1353,7 @@ mod tests {
 				IsolatedFilePathData::new(0, root_path, path, is_dir).map_err(Into::into)
 			},
 			420,
+			true,
 		)
 		.await
 		.unwrap();
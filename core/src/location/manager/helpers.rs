@@ -3,7 +3,7 @@ use crate::{
 	Node,
 };
 
-use sd_prisma::prisma::location;
+use sd_prisma::prisma::{location, volume};
 use sd_utils::db::maybe_missing;
 
 use std::{
@@ -35,6 +35,12 @@ pub(super) async fn check_online(
 	// TODO(N): This isn't gonna work with removable media and this will likely permanently break if the DB is restored from a backup.
 	if location.instance_id == Some(library.config().await.instance_id) {
 		match fs::metadata(&location_path).await {
+			// The path may still be there when the volume of the location is gone, as the empty
+			// directory where the volume was mounted
+			Ok(_) if !is_volume_mounted(location, library).await? => {
+				node.locations.remove_online(&pub_id).await;
+				Ok(false)
+			}
 			Ok(_) => {
 				node.locations.add_online(pub_id).await;
 				Ok(true)
@@ -55,6 +61,25 @@ pub(super) async fn check_online(
 	}
 }
 
+/// Locations not bound to a volume yet are only checked by their path
+async fn is_volume_mounted(
+	location: &location::Data,
+	library: &Library,
+) -> Result<bool, LocationManagerError> {
+	let Some(volume_id) = location.volume_id else {
+		return Ok(true);
+	};
+
+	Ok(library
+		.db
+		.volume()
+		.find_unique(volume::id::equals(volume_id))
+		.select(volume::select!({ is_mounted }))
+		.exec()
+		.await?
+		.map_or(true, |volume| volume.is_mounted))
+}
+
 pub(super) async fn location_check_sleep(
 	location_id: location::id::Type,
 	library: Arc<Library>,
@@ -1,6 +1,7 @@
 use crate::{
 	job::JobManagerError,
 	library::{Library, LibraryManagerEvent},
+	location::indexer::IndexerError,
 	Node,
 };
 
@@ -27,6 +28,8 @@ use uuid::Uuid;
 
 mod watcher;
 
+pub use watcher::WatcherMode;
+
 mod helpers;
 
 #[derive(Clone, Copy, Debug)]
@@ -103,6 +106,8 @@ pub enum LocationManagerError {
 	JobManager(#[from] JobManagerError),
 	#[error("missing-field")]
 	MissingField(#[from] MissingFieldError),
+	#[error(transparent)]
+	Indexer(#[from] IndexerError),
 
 	#[error(transparent)]
 	FileIO(#[from] FileIOError),
@@ -356,6 +361,7 @@ impl Locations {
 		use std::collections::{HashMap, HashSet};
 
 		use futures::stream::{FuturesUnordered, StreamExt};
+		use sd_utils::from_bytes_to_uuid;
 		use tokio::select;
 		use tracing::warn;
 
@@ -364,13 +370,16 @@ impl Locations {
 			handle_reinit_watcher_request, handle_remove_location_request,
 			handle_stop_watcher_request, location_check_sleep, unwatch_location, watch_location,
 		};
-		use watcher::LocationWatcher;
+		use watcher::{spawn_reconcile, LocationWatcher};
 
 		let mut to_check_futures = FuturesUnordered::new();
 		let mut to_remove = HashSet::new();
 		let mut locations_watched = HashMap::new();
 		let mut locations_unwatched = HashMap::new();
 		let mut forced_unwatch = HashSet::new();
+		// Locations added since their library was loaded, as watchers are also reloaded by
+		// removing and adding their locations again, which must not reconcile them every time
+		let mut added_once = HashSet::new();
 
 		loop {
 			select! {
@@ -389,12 +398,17 @@ impl Locations {
 							if let Some(location) = get_location(location_id, &library).await {
 								match check_online(&location, &node, &library).await {
 									Ok(is_online) => {
+										let first_add = added_once.insert((location_id, library.id));
 
 										LocationWatcher::new(location, library.clone(), node.clone())
 										.await
 										.map(|mut watcher| {
 											if is_online {
 												watcher.watch();
+												if first_add {
+													// Catching up with what changed while we weren't running
+													spawn_reconcile(location_id, node.clone(), library.clone());
+												}
 												locations_watched.insert(
 													(location_id, library.id),
 													watcher
@@ -499,6 +513,11 @@ impl Locations {
 					} else if let Some(location) = get_location(location_id, &library).await {
 						// TODO(N): This isn't gonna work with removable media and this will likely permanently break if the DB is restored from a backup.
 						if location.instance_id == Some(library.config().await.instance_id) {
+							let was_online = node
+								.locations
+								.is_online(&from_bytes_to_uuid(&location.pub_id))
+								.await;
+
 							let is_online = match check_online(&location, &node, &library).await {
 								Ok(is_online) => is_online,
 								Err(e) => {
@@ -510,6 +529,11 @@ impl Locations {
 							if is_online
 								&& !forced_unwatch.contains(&key)
 							{
+								if !was_online {
+									// Catching up with what changed while it was unmounted
+									spawn_reconcile(location_id, node.clone(), library.clone());
+								}
+
 								watch_location(
 									location,
 									library.id,
@@ -181,6 +181,13 @@ impl<'lib> EventHandler<'lib> for AndroidEventHandler<'lib> {
 			self.last_events_eviction_check = Instant::now();
 		}
 	}
+
+	fn has_pending_events(&self) -> bool {
+		!(self.files_to_update.is_empty()
+			&& self.reincident_to_update_files.is_empty()
+			&& self.rename_from.is_empty()
+			&& self.to_recalculate_size.is_empty())
+	}
 }
 
 impl AndroidEventHandler<'_> {
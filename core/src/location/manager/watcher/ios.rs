@@ -25,7 +25,7 @@ use super::{
 		create_dir, create_file, extract_inode_from_path, extract_location_path,
 		recalculate_directories_size, remove, rename, update_file,
 	},
-	EventHandler, INode, InstantAndPath, HUNDRED_MILLIS, ONE_SECOND,
+	EventHandler, PathsByInode, HUNDRED_MILLIS, ONE_SECOND,
 };
 
 #[derive(Debug)]
@@ -37,9 +37,8 @@ pub(super) struct IosEventHandler<'lib> {
 	reincident_to_update_files: HashMap<PathBuf, Instant>,
 	last_events_eviction_check: Instant,
 	latest_created_dir: Option<PathBuf>,
-	old_paths_map: HashMap<INode, InstantAndPath>,
-	new_paths_map: HashMap<INode, InstantAndPath>,
-	paths_map_buffer: Vec<(INode, InstantAndPath)>,
+	old_paths_map: PathsByInode,
+	new_paths_map: PathsByInode,
 	to_recalculate_size: HashMap<PathBuf, Instant>,
 	path_and_instant_buffer: Vec<(PathBuf, Instant)>,
 }
@@ -62,9 +61,8 @@ impl<'lib> EventHandler<'lib> for IosEventHandler<'lib> {
 			reincident_to_update_files: HashMap::new(),
 			last_events_eviction_check: Instant::now(),
 			latest_created_dir: None,
-			old_paths_map: HashMap::new(),
-			new_paths_map: HashMap::new(),
-			paths_map_buffer: Vec::new(),
+			old_paths_map: PathsByInode::default(),
+			new_paths_map: PathsByInode::default(),
 			to_recalculate_size: HashMap::new(),
 			path_and_instant_buffer: Vec::new(),
 		}
@@ -171,6 +169,14 @@ impl<'lib> EventHandler<'lib> for IosEventHandler<'lib> {
 			self.last_events_eviction_check = Instant::now();
 		}
 	}
+
+	fn has_pending_events(&self) -> bool {
+		!(self.files_to_update.is_empty()
+			&& self.reincident_to_update_files.is_empty()
+			&& self.old_paths_map.is_empty()
+			&& self.new_paths_map.is_empty()
+			&& self.to_recalculate_size.is_empty())
+	}
 }
 
 impl IosEventHandler<'_> {
@@ -229,38 +235,31 @@ impl IosEventHandler<'_> {
 	}
 
 	async fn handle_rename_create_eviction(&mut self) -> Result<(), LocationManagerError> {
-		// Just to make sure that our buffer is clean
-		self.paths_map_buffer.clear();
 		let mut should_invalidate = false;
 
-		for (inode, (instant, path)) in self.new_paths_map.drain() {
-			if instant.elapsed() > HUNDRED_MILLIS {
-				if !self.files_to_update.contains_key(&path) {
-					let metadata = fs::metadata(&path)
-						.await
-						.map_err(|e| FileIOError::from((&path, e)))?;
+		for path in self.new_paths_map.take_expired(HUNDRED_MILLIS) {
+			if !self.files_to_update.contains_key(&path) {
+				let metadata = fs::metadata(&path)
+					.await
+					.map_err(|e| FileIOError::from((&path, e)))?;
 
-					if metadata.is_dir() {
-						// Don't need to dispatch a recalculate directory event as `create_dir` dispatches
-						// a `scan_location_sub_path` function, which recalculates the size already
-						create_dir(self.location_id, &path, &metadata, self.node, self.library)
-							.await?;
-					} else {
-						if let Some(parent) = path.parent() {
-							if parent != Path::new("") {
-								self.to_recalculate_size
-									.insert(parent.to_path_buf(), Instant::now());
-							}
+				if metadata.is_dir() {
+					// Don't need to dispatch a recalculate directory event as `create_dir` dispatches
+					// a `scan_location_sub_path` function, which recalculates the size already
+					create_dir(self.location_id, &path, &metadata, self.node, self.library).await?;
+				} else {
+					if let Some(parent) = path.parent() {
+						if parent != Path::new("") {
+							self.to_recalculate_size
+								.insert(parent.to_path_buf(), Instant::now());
 						}
-						create_file(self.location_id, &path, &metadata, self.node, self.library)
-							.await?;
 					}
-
-					trace!("Created file_path due timeout: {}", path.display());
-					should_invalidate = true;
+					create_file(self.location_id, &path, &metadata, self.node, self.library)
+						.await?;
 				}
-			} else {
-				self.paths_map_buffer.push((inode, (instant, path)));
+
+				trace!("Created file_path due timeout: {}", path.display());
+				should_invalidate = true;
 			}
 		}
 
@@ -268,38 +267,28 @@ impl IosEventHandler<'_> {
 			invalidate_query!(self.library, "search.paths");
 		}
 
-		self.new_paths_map.extend(self.paths_map_buffer.drain(..));
-
 		Ok(())
 	}
 
 	async fn handle_rename_remove_eviction(&mut self) -> Result<(), LocationManagerError> {
-		// Just to make sure that our buffer is clean
-		self.paths_map_buffer.clear();
 		let mut should_invalidate = false;
 
-		for (inode, (instant, path)) in self.old_paths_map.drain() {
-			if instant.elapsed() > HUNDRED_MILLIS {
-				if let Some(parent) = path.parent() {
-					if parent != Path::new("") {
-						self.to_recalculate_size
-							.insert(parent.to_path_buf(), Instant::now());
-					}
+		for path in self.old_paths_map.take_expired(HUNDRED_MILLIS) {
+			if let Some(parent) = path.parent() {
+				if parent != Path::new("") {
+					self.to_recalculate_size
+						.insert(parent.to_path_buf(), Instant::now());
 				}
-				remove(self.location_id, &path, self.library).await?;
-				trace!("Removed file_path due timeout: {}", path.display());
-				should_invalidate = true;
-			} else {
-				self.paths_map_buffer.push((inode, (instant, path)));
 			}
+			remove(self.location_id, &path, self.library).await?;
+			trace!("Removed file_path due timeout: {}", path.display());
+			should_invalidate = true;
 		}
 
 		if should_invalidate {
 			invalidate_query!(self.library, "search.paths");
 		}
 
-		self.old_paths_map.extend(self.paths_map_buffer.drain(..));
-
 		Ok(())
 	}
 
@@ -326,7 +315,7 @@ impl IosEventHandler<'_> {
 				)
 				.await?
 				{
-					if let Some((_, old_path)) = self.old_paths_map.remove(&inode) {
+					if let Some(old_path) = self.old_paths_map.take(inode) {
 						trace!(
 							"Got a match new -> old: {} -> {}",
 							path.display(),
@@ -337,7 +326,7 @@ impl IosEventHandler<'_> {
 						rename(self.location_id, &path, &old_path, meta, self.library).await?;
 					} else {
 						trace!("No match for new path yet: {}", path.display());
-						self.new_paths_map.insert(inode, (Instant::now(), path));
+						self.new_paths_map.insert(inode, path);
 					}
 				} else {
 					warn!(
@@ -362,7 +351,7 @@ impl IosEventHandler<'_> {
 						Err(e) => return Err(e),
 					};
 
-				if let Some((_, new_path)) = self.new_paths_map.remove(&inode) {
+				if let Some(new_path) = self.new_paths_map.take(inode) {
 					trace!(
 						"Got a match old -> new: {} -> {}",
 						path.display(),
@@ -383,7 +372,7 @@ impl IosEventHandler<'_> {
 				} else {
 					trace!("No match for old path yet: {}", path.display());
 					// We didn't find a new path for this old path, so we store ir for later
-					self.old_paths_map.insert(inode, (Instant::now(), path));
+					self.old_paths_map.insert(inode, path);
 				}
 			}
 			Err(e) => return Err(FileIOError::from((path, e)).into()),
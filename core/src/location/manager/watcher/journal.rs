@@ -0,0 +1,205 @@
+//! Event handlers hold on to some events for a while before acting on them, like renames waiting
+//! for their other half, files still being written and directories waiting for their sizes to be
+//! recalculated. So that a crash or a shutdown in the middle of a burst of events doesn't lose
+//! them until the next rescan, the events are written to the library's `watcher_event` table while
+//! the handler has something pending, and cleared once it's done.
+//!
+//! When a watcher starts, whatever was left for its location is replayed before any new event is
+//! handled. As we can't know how far the handler got with each event, we don't replay the events
+//! themselves, but check how each of their paths is now on disk, creating, updating or removing
+//! its file path accordingly.
+
+use crate::{
+	invalidate_query,
+	library::Library,
+	location::{
+		indexer::{reverse_update_directories_sizes, rules::gitignore::GitIgnoreRules},
+		manager::LocationManagerError,
+		symlinks::SymlinkResolver,
+		update_location_size,
+	},
+	Node,
+};
+
+use sd_prisma::prisma::{location, watcher_event};
+
+use std::{collections::HashSet, path::PathBuf, sync::Arc};
+
+use chrono::Utc;
+use notify::{Event, EventKind};
+use tracing::{debug, error};
+
+use super::utils::{check_git_ignore, check_symlinks, extract_location_path, reconcile_path};
+
+#[derive(Debug)]
+pub(super) struct Journal {
+	location_id: location::id::Type,
+	/// Events received since the last sync, only written if the handler still has them pending
+	received: Vec<watcher_event::CreateUnchecked>,
+	/// How many events of this location are in the journal
+	journaled_count: usize,
+}
+
+impl Journal {
+	pub(super) fn new(location_id: location::id::Type) -> Self {
+		Self {
+			location_id,
+			received: vec![],
+			journaled_count: 0,
+		}
+	}
+
+	pub(super) fn record(&mut self, event: &Event) {
+		match rmp_serde::to_vec_named(&event.paths) {
+			Ok(paths) => self.received.push(watcher_event::create_unchecked(
+				self.location_id,
+				paths,
+				Utc::now().into(),
+				vec![],
+			)),
+			Err(e) => error!(
+				"Failed to serialize watcher event paths for the journal: \
+				<id='{}', error='{e:#?}'>",
+				self.location_id
+			),
+		}
+	}
+
+	/// Called after every tick of the event handler. Events received since the last sync are only
+	/// written when the handler still has some events pending, otherwise they're all handled and
+	/// the journal of this location is cleared instead.
+	pub(super) async fn sync(&mut self, has_pending_events: bool, library: &Library) {
+		if has_pending_events {
+			if self.received.is_empty() {
+				return;
+			}
+
+			let received = std::mem::take(&mut self.received);
+			let received_count = received.len();
+
+			match library
+				.db
+				.watcher_event()
+				.create_many(received)
+				.exec()
+				.await
+			{
+				Ok(_) => {
+					self.journaled_count += received_count;
+					invalidate_query!(library, "locations.state");
+				}
+				Err(e) => error!(
+					"Failed to write watcher events to the journal: <id='{}', error='{e:#?}'>",
+					self.location_id
+				),
+			}
+		} else {
+			self.received.clear();
+
+			if self.journaled_count > 0 {
+				if let Err(e) = clear(self.location_id, library).await {
+					error!(
+						"Failed to clear the watcher journal: <id='{}', error='{e:#?}'>",
+						self.location_id
+					);
+				} else {
+					self.journaled_count = 0;
+				}
+			}
+		}
+	}
+}
+
+/// Brings every path in the journal of the location up to date with how it is now on disk, then
+/// clears the journal. Paths skipped by the location's gitignore rules or symlink policy are left
+/// alone, just like their events would have been.
+pub(super) async fn replay(
+	location_id: location::id::Type,
+	git_ignore_rules: &[GitIgnoreRules],
+	symlinks: &SymlinkResolver,
+	node: &Arc<Node>,
+	library: &Arc<Library>,
+) -> Result<(), LocationManagerError> {
+	let events = library
+		.db
+		.watcher_event()
+		.find_many(vec![watcher_event::location_id::equals(location_id)])
+		.exec()
+		.await?;
+
+	if events.is_empty() {
+		return Ok(());
+	}
+
+	let mut seen = HashSet::new();
+	let mut paths = events
+		.iter()
+		.filter_map(|event| {
+			rmp_serde::from_slice::<Vec<PathBuf>>(&event.paths)
+				.map_err(|e| error!("Failed to deserialize watcher journal paths: {e:#?}"))
+				.ok()
+		})
+		.flatten()
+		.filter(|path| seen.insert(path.clone()))
+		.collect::<Vec<_>>();
+
+	// Parents first, as creating a directory indexes its contents and removing it removes them
+	paths.sort_by_key(|path| path.components().count());
+
+	debug!(
+		"Replaying {} paths from the watcher journal: <id='{location_id}'>",
+		paths.len()
+	);
+
+	let location_path = extract_location_path(location_id, library).await?;
+	let mut parents = HashSet::new();
+
+	for path in paths {
+		let event = Event::new(EventKind::Any).add_path(path.clone());
+		if path == location_path
+			|| !check_git_ignore(&event, git_ignore_rules).await
+			|| !check_symlinks(&event, symlinks).await
+		{
+			continue;
+		}
+
+		if let Err(e) = reconcile_path(location_id, &path, node, library).await {
+			error!(
+				"Failed to replay watcher journal path: <path='{}', error='{e:#?}'>",
+				path.display()
+			);
+		}
+
+		if let Some(parent) = path.parent() {
+			parents.insert(parent.to_path_buf());
+		}
+	}
+
+	for parent in parents {
+		if parent != location_path && parent.starts_with(&location_path) {
+			reverse_update_directories_sizes(&parent, location_id, &location_path, library).await?;
+		}
+	}
+	update_location_size(location_id, library).await?;
+
+	invalidate_query!(library, "search.paths");
+	invalidate_query!(library, "search.objects");
+
+	clear(location_id, library).await
+}
+
+async fn clear(
+	location_id: location::id::Type,
+	library: &Library,
+) -> Result<(), LocationManagerError> {
+	library
+		.db
+		.watcher_event()
+		.delete_many(vec![watcher_event::location_id::equals(location_id)])
+		.exec()
+		.await?;
+
+	invalidate_query!(library, "locations.state");
+
+	Ok(())
+}
@@ -19,14 +19,16 @@ use std::{
 
 use async_trait::async_trait;
 use notify::{
-	event::{CreateKind, DataChange, ModifyKind, RenameMode},
+	event::{CreateKind, DataChange, MetadataKind, ModifyKind, RenameMode},
 	Event, EventKind,
 };
 use tokio::{fs, time::Instant};
 use tracing::{error, trace};
 
 use super::{
-	utils::{create_dir, recalculate_directories_size, remove, rename, update_file},
+	utils::{
+		create_dir, recalculate_directories_size, remove, rename, update_attributes, update_file,
+	},
 	EventHandler, HUNDRED_MILLIS, ONE_SECOND,
 };
 
@@ -111,6 +113,11 @@ impl<'lib> EventHandler<'lib> for LinuxEventHandler<'lib> {
 				)
 				.await?;
 			}
+			EventKind::Modify(ModifyKind::Metadata(MetadataKind::Any)) => {
+				// Changes to permissions, owner or extended attributes, which don't touch the
+				// contents, so there is no need to wait for more events like for data changes
+				update_attributes(self.location_id, &paths[0], self.library).await?;
+			}
 			EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
 				// Just in case we can't garantee that we receive the Rename From event before the
 				// Rename Both event. Just a safeguard
@@ -185,6 +192,13 @@ impl<'lib> EventHandler<'lib> for LinuxEventHandler<'lib> {
 			self.last_events_eviction_check = Instant::now();
 		}
 	}
+
+	fn has_pending_events(&self) -> bool {
+		!(self.files_to_update.is_empty()
+			&& self.reincident_to_update_files.is_empty()
+			&& self.rename_from.is_empty()
+			&& self.to_recalculate_size.is_empty())
+	}
 }
 
 impl LinuxEventHandler<'_> {
@@ -1,10 +1,17 @@
-use crate::{library::Library, Node};
+use crate::{
+	library::Library,
+	location::{
+		indexer::rules::gitignore::GitIgnoreRules,
+		symlinks::{SymlinkPolicy, SymlinkResolver},
+	},
+	Node,
+};
 
 use sd_prisma::prisma::location;
 use sd_utils::db::maybe_missing;
 
 use std::{
-	collections::HashSet,
+	collections::{HashMap, HashSet},
 	path::{Path, PathBuf},
 	sync::Arc,
 	time::Duration,
@@ -30,9 +37,19 @@ mod linux;
 mod macos;
 mod windows;
 
+mod journal;
+mod polling;
+mod reconcile;
 mod utils;
 
-use utils::check_event;
+pub use polling::WatcherMode;
+pub(super) use reconcile::spawn_reconcile;
+
+use journal::Journal;
+use polling::{poll_interval_from_db, PollingEventHandler, PollingWatcher};
+use utils::{
+	check_event, check_git_ignore, check_symlinks, load_git_ignore_rules, load_symlink_resolver,
+};
 
 #[cfg(target_os = "linux")]
 type Handler<'lib> = linux::LinuxEventHandler<'lib>;
@@ -57,6 +74,55 @@ type InstantAndPath = (Instant, PathBuf);
 const ONE_SECOND: Duration = Duration::from_secs(1);
 const HUNDRED_MILLIS: Duration = Duration::from_millis(100);
 
+/// Paths waiting to be paired by inode with the other half of their rename. Hard links to the same
+/// file share its inode, so many paths can be waiting under the same one at once
+#[derive(Debug, Default)]
+struct PathsByInode(HashMap<INode, Vec<InstantAndPath>>);
+
+impl PathsByInode {
+	fn insert(&mut self, inode: INode, path: PathBuf) {
+		self.0
+			.entry(inode)
+			.or_default()
+			.push((Instant::now(), path));
+	}
+
+	/// Takes the path waiting for the longest under this inode
+	fn take(&mut self, inode: INode) -> Option<PathBuf> {
+		let paths = self.0.get_mut(&inode)?;
+		let (_, path) = paths.remove(0);
+
+		if paths.is_empty() {
+			self.0.remove(&inode);
+		}
+
+		Some(path)
+	}
+
+	/// Takes every path waiting for longer than `timeout`
+	fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+
+	fn take_expired(&mut self, timeout: Duration) -> Vec<PathBuf> {
+		let mut expired = vec![];
+
+		self.0.retain(|_, paths| {
+			paths.retain(|(instant, path)| {
+				let to_retain = instant.elapsed() <= timeout;
+				if !to_retain {
+					expired.push(path.clone());
+				}
+				to_retain
+			});
+
+			!paths.is_empty()
+		});
+
+		expired
+	}
+}
+
 #[async_trait]
 trait EventHandler<'lib> {
 	fn new(
@@ -73,13 +139,24 @@ trait EventHandler<'lib> {
 	/// As Event Handlers have some inner state, from time to time we need to call this tick method
 	/// so the event handler can update its state.
 	async fn tick(&mut self);
+
+	/// Whether some of the events received are still waiting in the inner state to be handled, so
+	/// they must be kept in the [`Journal`]
+	fn has_pending_events(&self) -> bool;
+}
+
+/// Where the events of a location come from, see [`WatcherMode`]
+#[derive(Debug)]
+enum EventSource {
+	Native(RecommendedWatcher),
+	Polling(PollingWatcher),
 }
 
 #[derive(Debug)]
 pub(super) struct LocationWatcher {
 	id: i32,
 	path: String,
-	watcher: RecommendedWatcher,
+	source: EventSource,
 	ignore_path_tx: mpsc::UnboundedSender<IgnorePath>,
 	handle: Option<JoinHandle<()>>,
 	stop_tx: Option<oneshot::Sender<()>>,
@@ -95,28 +172,43 @@ impl LocationWatcher {
 		let (ignore_path_tx, ignore_path_rx) = mpsc::unbounded_channel();
 		let (stop_tx, stop_rx) = oneshot::channel();
 
-		let watcher = RecommendedWatcher::new(
-			move |result| {
-				if !events_tx.is_closed() {
-					if events_tx.send(result).is_err() {
+		let path = maybe_missing(location.path, "location.path")?;
+
+		let source = if WatcherMode::from_db(location.watcher_mode)
+			.should_poll(&path)
+			.await
+		{
+			EventSource::Polling(PollingWatcher::new(
+				location.id,
+				Arc::clone(&library),
+				poll_interval_from_db(location.watcher_poll_interval),
+				events_tx,
+			))
+		} else {
+			EventSource::Native(RecommendedWatcher::new(
+				move |result| {
+					if !events_tx.is_closed() {
+						if events_tx.send(result).is_err() {
+							error!(
+							"Unable to send watcher event to location manager for location: <id='{}'>",
+							location.id
+						);
+						}
+					} else {
 						error!(
-						"Unable to send watcher event to location manager for location: <id='{}'>",
-						location.id
-					);
+							"Tried to send location file system events to a closed channel: <id='{}'",
+							location.id
+						);
 					}
-				} else {
-					error!(
-						"Tried to send location file system events to a closed channel: <id='{}'",
-						location.id
-					);
-				}
-			},
-			Config::default(),
-		)?;
+				},
+				Config::default(),
+			)?)
+		};
 
 		let handle = tokio::spawn(Self::handle_watch_events(
 			location.id,
 			Uuid::from_slice(&location.pub_id)?,
+			matches!(source, EventSource::Polling(_)),
 			node,
 			library,
 			events_rx,
@@ -126,25 +218,64 @@ impl LocationWatcher {
 
 		Ok(Self {
 			id: location.id,
-			path: maybe_missing(location.path, "location.path")?,
-			watcher,
+			path,
+			source,
 			ignore_path_tx,
 			handle: Some(handle),
 			stop_tx: Some(stop_tx),
 		})
 	}
 
+	#[allow(clippy::too_many_arguments)]
 	async fn handle_watch_events(
 		location_id: location::id::Type,
 		location_pub_id: Uuid,
+		is_polling: bool,
 		node: Arc<Node>,
 		library: Arc<Library>,
 		mut events_rx: mpsc::UnboundedReceiver<notify::Result<Event>>,
 		mut ignore_path_rx: mpsc::UnboundedReceiver<IgnorePath>,
 		mut stop_rx: oneshot::Receiver<()>,
 	) {
-		let mut event_handler = Handler::new(location_id, &library, &node);
+		let mut event_handler: Box<dyn EventHandler<'_> + Send + '_> = if is_polling {
+			Box::new(PollingEventHandler::new(location_id, &library, &node))
+		} else {
+			Box::new(Handler::new(location_id, &library, &node))
+		};
 
+		let git_ignore_rules = load_git_ignore_rules(location_id, &library)
+			.await
+			.unwrap_or_else(|e| {
+				error!(
+					"Failed to load git ignore rules for watcher: \
+					<id='{location_id}', error='{e:#?}'>"
+				);
+				vec![]
+			});
+
+		let symlinks = match load_symlink_resolver(location_id, &library).await {
+			Ok(symlinks) => symlinks,
+			Err(e) => {
+				error!(
+					"Failed to load symbolic links policy for watcher: \
+					<id='{location_id}', error='{e:#?}'>"
+				);
+				SymlinkResolver::new(SymlinkPolicy::default(), None::<&Path>).await
+			}
+		};
+
+		// Changes left from the last time this location was watched go before any new ones
+		if node.locations.is_online(&location_pub_id).await {
+			if let Err(e) =
+				journal::replay(location_id, &git_ignore_rules, &symlinks, &node, &library).await
+			{
+				error!(
+					"Failed to replay the watcher journal: <id='{location_id}', error='{e:#?}'>"
+				);
+			}
+		}
+
+		let mut journal = Journal::new(location_id);
 		let mut paths_to_ignore = HashSet::new();
 
 		let mut handler_interval = interval_at(Instant::now() + HUNDRED_MILLIS, HUNDRED_MILLIS);
@@ -156,14 +287,17 @@ impl LocationWatcher {
 					match event {
 						Ok(event) => {
 							debug!("[Debug - handle_watch_events] Received event: {:#?}", event);
+							journal.record(&event);
 							if let Err(e) = Self::handle_single_event(
 								location_id,
 								location_pub_id,
 								event,
-								&mut event_handler,
+								event_handler.as_mut(),
 								&node,
 								&library,
 								&paths_to_ignore,
+								&git_ignore_rules,
+								&symlinks,
 							).await {
 								error!("Failed to handle location file system event: \
 									<id='{location_id}', error='{e:#?}'>",
@@ -186,9 +320,11 @@ impl LocationWatcher {
 
 				_ = handler_interval.tick() => {
 					event_handler.tick().await;
+					journal.sync(event_handler.has_pending_events(), &library).await;
 				}
 
 				_ = &mut stop_rx => {
+					journal.sync(event_handler.has_pending_events(), &library).await;
 					debug!("Stop Location Manager event handler for location: <id='{}'>", location_id);
 					break
 				}
@@ -196,17 +332,23 @@ impl LocationWatcher {
 		}
 	}
 
+	#[allow(clippy::too_many_arguments)]
 	async fn handle_single_event<'lib>(
 		location_id: location::id::Type,
 		location_pub_id: Uuid,
 		event: Event,
-		event_handler: &mut impl EventHandler<'lib>,
+		event_handler: &mut (dyn EventHandler<'lib> + Send + 'lib),
 		node: &'lib Node,
 		_library: &'lib Library,
 		ignore_paths: &HashSet<PathBuf>,
+		git_ignore_rules: &[GitIgnoreRules],
+		symlinks: &SymlinkResolver,
 	) -> Result<(), LocationManagerError> {
 		debug!("Event: {:#?}", event);
-		if !check_event(&event, ignore_paths) {
+		if !check_event(&event, ignore_paths)
+			|| !check_git_ignore(&event, git_ignore_rules).await
+			|| !check_symlinks(&event, symlinks).await
+		{
 			return Ok(());
 		}
 
@@ -245,10 +387,16 @@ impl LocationWatcher {
 		let path = &self.path;
 		debug!("Start watching location: (path: {path})");
 
-		if let Err(e) = self
-			.watcher
-			.watch(Path::new(path), RecursiveMode::Recursive)
-		{
+		let watcher = match &mut self.source {
+			EventSource::Native(watcher) => watcher,
+			EventSource::Polling(poller) => {
+				poller.watch();
+				debug!("Now polling location: (path: {path})");
+				return;
+			}
+		};
+
+		if let Err(e) = watcher.watch(Path::new(path), RecursiveMode::Recursive) {
 			error!("Unable to watch location: (path: {path}, error: {e:#?})");
 		} else {
 			debug!("Now watching location: (path: {path})");
@@ -257,7 +405,17 @@ impl LocationWatcher {
 
 	pub(super) fn unwatch(&mut self) {
 		let path = &self.path;
-		if let Err(e) = self.watcher.unwatch(Path::new(path)) {
+
+		let watcher = match &mut self.source {
+			EventSource::Native(watcher) => watcher,
+			EventSource::Polling(poller) => {
+				poller.unwatch();
+				debug!("Stop polling location: (path: {path})");
+				return;
+			}
+		};
+
+		if let Err(e) = watcher.unwatch(Path::new(path)) {
 			/**************************************** TODO: ****************************************
 			 * According to an unit test, this error may occur when a subdirectory is removed	   *
 			 * and we try to unwatch the parent directory then we have to check the implications   *
@@ -0,0 +1,592 @@
+//! Network file systems like NFS and SMB, and most FUSE mounts like sshfs, don't deliver native
+//! events for changes made through other machines, or by the FUSE daemon itself, so the native
+//! watchers silently see nothing at all. For these locations we poll instead: every so often we
+//! walk the location and diff what we find against the `file_path`s we have stored, comparing
+//! their modification dates and sizes, and dispatch the events a native watcher would have sent.
+//!
+//! As the diff is done against the database, changes made while we weren't watching are picked up
+//! by the first scan too. Renames show up as a removal followed by a creation, and we don't walk
+//! through symbolic links to directories, leaving whatever is behind them to rescans.
+
+use crate::{
+	library::Library,
+	location::{
+		find_location,
+		indexer::{
+			rules::IndexerRule,
+			walk::{decide_by_rules, RulesDecision},
+		},
+		location_with_indexer_rules,
+		manager::LocationManagerError,
+		symlinks::SymlinkPolicy,
+	},
+	volume::file_system_of,
+	Node,
+};
+
+use sd_file_path_helper::{file_path_walker, IsolatedFilePathData};
+use sd_prisma::prisma::{file_path, location};
+use sd_utils::{db::maybe_missing, error::FileIOError};
+
+use std::{
+	collections::HashMap,
+	fs::Metadata,
+	path::{Path, PathBuf},
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+	time::Duration,
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset, Utc};
+use notify::{
+	event::{CreateKind, DataChange, ModifyKind, RemoveKind},
+	Event, EventKind,
+};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tokio::{
+	fs,
+	sync::mpsc,
+	task::JoinHandle,
+	time::{interval_at, Instant, MissedTickBehavior},
+};
+use tracing::{debug, error, trace, warn};
+
+use super::{
+	utils::{create_dir, recalculate_directories_size, remove, update_file},
+	EventHandler, HUNDRED_MILLIS,
+};
+
+/// Scans happen this often unless the location sets its own `watcher_poll_interval`
+pub(super) const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// Shorter intervals are raised to this one, as each scan reads every directory of the location
+pub(super) const MIN_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How a location is watched for changes, stored in the `watcher_mode` column of the `location`
+/// table, along with the interval between scans in `watcher_poll_interval` when polling.
+#[repr(i32)]
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, Type, Eq, PartialEq, Hash)]
+pub enum WatcherMode {
+	/// Polls locations on network and FUSE file systems, and uses native events everywhere else
+	#[default]
+	Auto = 0,
+	/// Always relies on the native events of the platform
+	Native = 1,
+	/// Always polls, for file systems we don't recognize as needing it
+	Polling = 2,
+}
+
+impl WatcherMode {
+	pub fn from_db(maybe_mode: Option<i32>) -> Self {
+		match maybe_mode {
+			None | Some(0) => Self::Auto,
+			Some(1) => Self::Native,
+			Some(2) => Self::Polling,
+			Some(mode) => {
+				warn!("Unknown watcher mode <mode={mode}>, choosing it automatically");
+				Self::Auto
+			}
+		}
+	}
+
+	pub const fn to_db(self) -> i32 {
+		self as i32
+	}
+
+	/// Whether a location at `location_path` must be polled under this mode
+	pub(super) async fn should_poll(self, location_path: impl AsRef<Path>) -> bool {
+		match self {
+			Self::Native => false,
+			Self::Polling => true,
+			Self::Auto => {
+				let location_path = location_path.as_ref();
+				let Some(file_system) = file_system_of(location_path).await else {
+					warn!(
+						"Couldn't find the file system of {}, using native events",
+						location_path.display()
+					);
+					return false;
+				};
+
+				let should_poll = is_without_native_events(&file_system);
+				debug!(
+					"Location at {} is on a {file_system} file system, {}",
+					location_path.display(),
+					if should_poll {
+						"polling it"
+					} else {
+						"using native events"
+					}
+				);
+
+				should_poll
+			}
+		}
+	}
+}
+
+pub(super) fn poll_interval_from_db(maybe_interval: Option<i32>) -> Duration {
+	maybe_interval
+		.and_then(|secs| u64::try_from(secs).ok())
+		.map_or(DEFAULT_POLL_INTERVAL, |secs| {
+			Duration::from_secs(secs).max(MIN_POLL_INTERVAL)
+		})
+}
+
+/// Network file systems and FUSE mounts, as named by Linux, macOS and sysinfo. `fuseblk` is left
+/// out, as it's used for local disks, like NTFS ones, which only change through our own kernel.
+const FILE_SYSTEMS_WITHOUT_NATIVE_EVENTS: &[&str] = &[
+	"nfs",
+	"nfs4",
+	"cifs",
+	"smb",
+	"smb2",
+	"smb3",
+	"smbfs",
+	"afpfs",
+	"webdav",
+	"davfs",
+	"9p",
+	"afs",
+	"ceph",
+	"glusterfs",
+	"lustre",
+	"sshfs",
+	"fuse",
+	"macfuse",
+	"osxfuse",
+];
+
+fn is_without_native_events(file_system: &str) -> bool {
+	FILE_SYSTEMS_WITHOUT_NATIVE_EVENTS.contains(&file_system) || file_system.starts_with("fuse.")
+}
+
+/// Produces the events for a polled location, standing in for the native watcher
+#[derive(Debug)]
+pub(super) struct PollingWatcher {
+	watching: Arc<AtomicBool>,
+	handle: JoinHandle<()>,
+}
+
+impl PollingWatcher {
+	pub(super) fn new(
+		location_id: location::id::Type,
+		library: Arc<Library>,
+		poll_interval: Duration,
+		events_tx: mpsc::UnboundedSender<notify::Result<Event>>,
+	) -> Self {
+		let watching = Arc::new(AtomicBool::new(false));
+
+		let handle = tokio::spawn({
+			let watching = Arc::clone(&watching);
+			async move {
+				let mut interval = interval_at(Instant::now() + poll_interval, poll_interval);
+				// Scans can take longer than the interval on big locations, so we just wait for them
+				interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+				loop {
+					interval.tick().await;
+
+					if !watching.load(Ordering::Acquire) {
+						continue;
+					}
+
+					let start = Instant::now();
+					let events = match scan(location_id, &library).await {
+						Ok(events) => events,
+						Err(e) => {
+							error!(
+								"Failed to scan polled location: <id='{location_id}', error='{e:#?}'>"
+							);
+							continue;
+						}
+					};
+
+					trace!(
+						"Scanned polled location <id='{location_id}'> in {:?}, found {} changes",
+						start.elapsed(),
+						events.len()
+					);
+
+					for event in events {
+						if events_tx.send(Ok(event)).is_err() {
+							debug!("Stopped polling location: <id='{location_id}'>");
+							return;
+						}
+					}
+				}
+			}
+		});
+
+		Self { watching, handle }
+	}
+
+	pub(super) fn watch(&self) {
+		self.watching.store(true, Ordering::Release);
+	}
+
+	pub(super) fn unwatch(&self) {
+		self.watching.store(false, Ordering::Release);
+	}
+}
+
+impl Drop for PollingWatcher {
+	fn drop(&mut self) {
+		self.handle.abort();
+	}
+}
+
+/// Walks the location, diffing every directory against its stored file paths, and returns the
+/// events for the changes found. New directories are left for `create_dir` to index, so we only
+/// walk into the directories we already have.
+async fn scan(
+	location_id: location::id::Type,
+	library: &Library,
+) -> Result<Vec<Event>, LocationManagerError> {
+	let location = find_location(library, location_id)
+		.include(location_with_indexer_rules::include())
+		.exec()
+		.await?
+		.ok_or(LocationManagerError::MissingLocation(location_id))?;
+
+	let location_path = PathBuf::from(maybe_missing(&location.path, "location.path")?);
+	let symlink_policy = SymlinkPolicy::from_db(location.symlink_policy);
+	let indexer_rules = location
+		.indexer_rules
+		.iter()
+		.filter_map(|rule| {
+			IndexerRule::try_from(&rule.indexer_rule)
+				.map_err(|e| error!("Failed to load indexer rule for polling: {e:#?}"))
+				.ok()
+		})
+		.collect::<Vec<_>>();
+
+	let mut events = vec![];
+	let mut to_walk = vec![location_path.clone()];
+
+	while let Some(dir) = to_walk.pop() {
+		let materialized_path = IsolatedFilePathData::new(location_id, &location_path, &dir, true)?
+			.materialized_path_for_children()
+			.expect("it's a directory");
+
+		let mut stored = library
+			.db
+			.file_path()
+			.find_many(vec![
+				file_path::location_id::equals(Some(location_id)),
+				file_path::materialized_path::equals(Some(materialized_path)),
+			])
+			.select(file_path_walker::select())
+			.exec()
+			.await?
+			.into_iter()
+			.filter_map(|file_path| {
+				IsolatedFilePathData::try_from(&file_path)
+					.map(|iso_file_path| (iso_file_path.full_name(), file_path))
+					.ok()
+			})
+			// The root of the location has an empty name, and isn't a child of anything
+			.filter(|(name, _)| !name.is_empty())
+			.collect::<HashMap<_, _>>();
+
+		let mut read_dir = match fs::read_dir(&dir).await {
+			Ok(read_dir) => read_dir,
+			Err(e) => {
+				// Most likely removed since we listed its parent, the next scan will catch it
+				error!("{:#?}", FileIOError::from((&dir, e)));
+				continue;
+			}
+		};
+
+		loop {
+			let entry = match read_dir.next_entry().await {
+				Ok(Some(entry)) => entry,
+				Ok(None) => break,
+				Err(e) => {
+					error!("{:#?}", FileIOError::from((&dir, e)));
+					break;
+				}
+			};
+
+			let path = entry.path();
+			let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+				continue;
+			};
+
+			let metadata = match symlink_policy.metadata(&path).await {
+				Ok(metadata) => metadata,
+				Err(e) => {
+					trace!("{:#?}", FileIOError::from((&path, e)));
+					continue;
+				}
+			};
+
+			if metadata.is_symlink() && symlink_policy == SymlinkPolicy::Ignore {
+				continue;
+			}
+
+			let is_dir = metadata.is_dir();
+
+			match stored.remove(name) {
+				Some(file_path) if file_path.is_dir.unwrap_or_default() == is_dir => {
+					if is_dir {
+						if !entry
+							.file_type()
+							.await
+							.map_or(true, |file_type| file_type.is_symlink())
+						{
+							to_walk.push(path);
+						}
+					} else if changed(
+						file_path.size_in_bytes_bytes.as_deref(),
+						file_path.date_modified,
+						&metadata,
+					) {
+						events.push(
+							Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Any)))
+								.add_path(path),
+						);
+					}
+				}
+
+				Some(_) => {
+					// A file turned into a directory or the other way around, as the path still
+					// exists we can't remove it like the rest, so we leave it for a rescan
+					trace!(
+						"Polled path changed between file and directory: {}",
+						path.display()
+					);
+				}
+
+				None => {
+					if accepted_by_rules(&indexer_rules, &location_path, &path, is_dir).await {
+						events.push(create_event(path, is_dir));
+					}
+				}
+			}
+		}
+
+		events.extend(stored.into_values().filter_map(|file_path| {
+			IsolatedFilePathData::try_from(&file_path)
+				.map(|iso_file_path| {
+					Event::new(EventKind::Remove(if file_path.is_dir.unwrap_or_default() {
+						RemoveKind::Folder
+					} else {
+						RemoveKind::File
+					}))
+					.add_path(location_path.join(iso_file_path))
+				})
+				.ok()
+		}));
+	}
+
+	Ok(events)
+}
+
+fn create_event(path: PathBuf, is_dir: bool) -> Event {
+	Event::new(EventKind::Create(if is_dir {
+		CreateKind::Folder
+	} else {
+		CreateKind::File
+	}))
+	.add_path(path)
+}
+
+/// Same checks the indexer does on a file's stored size and modification date, comparing against a
+/// delta as datetimes stored in DB lose a bit of precision
+pub(super) fn changed(
+	size_in_bytes_bytes: Option<&[u8]>,
+	date_modified: Option<DateTime<FixedOffset>>,
+	metadata: &Metadata,
+) -> bool {
+	let size_changed = size_in_bytes_bytes
+		.and_then(|size_in_bytes_bytes| <[u8; 8]>::try_from(size_in_bytes_bytes).ok())
+		.map(u64::from_be_bytes)
+		!= Some(metadata.len());
+
+	let modified_changed = match (metadata.modified(), date_modified) {
+		(Ok(modified_at), Some(date_modified)) => {
+			(DateTime::<FixedOffset>::from(DateTime::<Utc>::from(modified_at)) - date_modified)
+				.num_milliseconds()
+				.abs() > 1
+		}
+		(Ok(_), None) => true,
+		// Platforms without modification dates only have the size to go by
+		(Err(_), _) => false,
+	};
+
+	size_changed || modified_changed
+}
+
+/// New paths must pass the location's indexer rules, otherwise we would index whatever the rules
+/// keep out on every scan. The rules are decided the same way the walker does, so the ancestors of
+/// the path are checked too, as they carry the accept by children rules down to it. Directories
+/// only need to be walked into, as their contents are checked when `create_dir` indexes them.
+pub(super) async fn accepted_by_rules(
+	indexer_rules: &[IndexerRule],
+	location_path: &Path,
+	path: &Path,
+	is_dir: bool,
+) -> bool {
+	let mut accepted_by_children = None;
+
+	if let Ok(relative_path) = path.strip_prefix(location_path) {
+		let mut ancestor = location_path.to_path_buf();
+		for component in relative_path
+			.parent()
+			.into_iter()
+			.flat_map(Path::components)
+		{
+			ancestor.push(component);
+
+			match decide(indexer_rules, &ancestor, true, accepted_by_children).await {
+				Some(decision) if decision.walk_children => {
+					accepted_by_children = decision.accepted_by_children;
+				}
+				_ => return false,
+			}
+		}
+	}
+
+	decide(indexer_rules, path, is_dir, accepted_by_children)
+		.await
+		.map_or(false, |decision| {
+			if is_dir {
+				decision.walk_children
+			} else {
+				decision.rejected_by.is_none()
+			}
+		})
+}
+
+async fn decide(
+	indexer_rules: &[IndexerRule],
+	path: &Path,
+	is_dir: bool,
+	parent_accepted_by_children: Option<bool>,
+) -> Option<RulesDecision> {
+	match IndexerRule::apply_all(indexer_rules, path).await {
+		Ok(rules_per_kind) => Some(decide_by_rules(
+			&rules_per_kind,
+			is_dir,
+			parent_accepted_by_children,
+		)),
+		Err(e) => {
+			error!(
+				"Failed to apply indexer rules to polled path: <path='{}', error='{e:#?}'>",
+				path.display()
+			);
+			None
+		}
+	}
+}
+
+/// Handles the events produced by [`PollingWatcher`], which are already deduplicated by the scan,
+/// so unlike the native handlers there are no bursts of events to wait out
+#[derive(Debug)]
+pub(super) struct PollingEventHandler<'lib> {
+	location_id: location::id::Type,
+	library: &'lib Arc<Library>,
+	node: &'lib Arc<Node>,
+	last_events_eviction_check: Instant,
+	to_recalculate_size: HashMap<PathBuf, Instant>,
+	path_and_instant_buffer: Vec<(PathBuf, Instant)>,
+}
+
+#[async_trait]
+impl<'lib> EventHandler<'lib> for PollingEventHandler<'lib> {
+	fn new(
+		location_id: location::id::Type,
+		library: &'lib Arc<Library>,
+		node: &'lib Arc<Node>,
+	) -> Self {
+		Self {
+			location_id,
+			library,
+			node,
+			last_events_eviction_check: Instant::now(),
+			to_recalculate_size: HashMap::new(),
+			path_and_instant_buffer: Vec::new(),
+		}
+	}
+
+	async fn handle_event(&mut self, event: Event) -> Result<(), LocationManagerError> {
+		trace!("Received polling event: {:#?}", event);
+
+		let Event { kind, paths, .. } = event;
+		let Some(path) = paths.into_iter().next() else {
+			return Ok(());
+		};
+
+		match kind {
+			EventKind::Create(CreateKind::Folder) => {
+				// `create_dir` dispatches a `scan_location_sub_path`, which recalculates the sizes
+				create_dir(
+					self.location_id,
+					&path,
+					&fs::metadata(&path)
+						.await
+						.map_err(|e| FileIOError::from((&path, e)))?,
+					self.node,
+					self.library,
+				)
+				.await?;
+			}
+
+			EventKind::Create(CreateKind::File)
+			| EventKind::Modify(ModifyKind::Data(DataChange::Any)) => {
+				// `update_file` creates the file path when we don't have it yet
+				update_file(self.location_id, &path, self.node, self.library).await?;
+				self.recalculate_size_of_parent(&path);
+			}
+
+			EventKind::Remove(_) => {
+				remove(self.location_id, &path, self.library).await?;
+				self.recalculate_size_of_parent(&path);
+			}
+
+			other_event_kind => {
+				trace!("Other polling event that we don't handle: {other_event_kind:#?}");
+			}
+		}
+
+		Ok(())
+	}
+
+	async fn tick(&mut self) {
+		if self.last_events_eviction_check.elapsed() > HUNDRED_MILLIS {
+			if !self.to_recalculate_size.is_empty() {
+				if let Err(e) = recalculate_directories_size(
+					&mut self.to_recalculate_size,
+					&mut self.path_and_instant_buffer,
+					self.location_id,
+					self.library,
+				)
+				.await
+				{
+					error!("Failed to recalculate directories size: {e:#?}");
+				}
+			}
+
+			self.last_events_eviction_check = Instant::now();
+		}
+	}
+
+	fn has_pending_events(&self) -> bool {
+		!self.to_recalculate_size.is_empty()
+	}
+}
+
+impl PollingEventHandler<'_> {
+	fn recalculate_size_of_parent(&mut self, path: &Path) {
+		if let Some(parent) = path.parent() {
+			if parent != Path::new("") {
+				self.to_recalculate_size
+					.insert(parent.to_path_buf(), Instant::now());
+			}
+		}
+	}
+}
@@ -0,0 +1,426 @@
+//! Nothing watches a location while Spacedrive isn't running or while its drive is unplugged, so
+//! whatever changed in the meantime would only be noticed by the next full rescan. When a location
+//! is added to the manager on startup, and whenever it comes back online, we walk its stored
+//! directories and compare them against the disk:
+//!
+//! - A directory modified after its `date_indexed` had entries created, removed or renamed since we
+//!   indexed it, so we list it to find the new ones;
+//! - A file whose size or modification date differs from the stored ones was changed;
+//! - A file path missing from disk was removed.
+//!
+//! Directories with any difference are light scanned, same as when the user opens them, which also
+//! queues scans for their new sub-directories. When too many of them diverged, we queue sub-path
+//! rescans for the top-most ones instead of scanning them all inline. Missing directories are
+//! removed along with everything under them, as the shallow indexer only removes their own row.
+
+use crate::{
+	api::notifications::{NotificationData, NotificationKind},
+	job::StatefulJob,
+	library::Library,
+	location::{
+		find_location,
+		hard_links::update_hard_links,
+		indexer::{remove_non_existing_file_paths, rules::IndexerRule, IndexerJobInit},
+		light_scan_location, location_with_indexer_rules,
+		manager::LocationManagerError,
+		metadata::SpacedriveLocationMetadataFile,
+		scan_location_sub_path,
+		symlinks::SymlinkPolicy,
+	},
+	object::file_identifier::file_identifier_job::FileIdentifierJobInit,
+	Node,
+};
+
+use sd_file_path_helper::{
+	file_path_pub_and_cas_ids, file_path_to_reconcile, filter_existing_file_path_params,
+	IsolatedFilePathData,
+};
+use sd_prisma::prisma::{file_path, location};
+use sd_utils::{db::maybe_missing, error::FileIOError};
+
+use std::{
+	collections::HashSet,
+	fmt,
+	fs::Metadata,
+	io::ErrorKind,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+
+use chrono::{DateTime, FixedOffset, Utc};
+use prisma_client_rust::{operator::and, or};
+use tokio::fs;
+use tracing::{debug, error, info, trace};
+
+use super::polling::{accepted_by_rules, changed};
+
+/// Above this many diverged directories we queue rescans instead of light scanning each of them
+const LIGHT_SCAN_LIMIT: usize = 32;
+
+/// What changed in a location while it wasn't being watched
+#[derive(Debug, Default, Clone, Copy)]
+struct ReconcileSummary {
+	created: usize,
+	modified: usize,
+	removed: usize,
+	diverged_directories: usize,
+	queued_rescans: usize,
+}
+
+impl ReconcileSummary {
+	fn has_changes(&self) -> bool {
+		self.created > 0 || self.modified > 0 || self.removed > 0
+	}
+}
+
+impl fmt::Display for ReconcileSummary {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"{} created, {} modified and {} removed, in {} directories",
+			self.created, self.modified, self.removed, self.diverged_directories
+		)
+	}
+}
+
+/// Runs [`reconcile_location`] in the background, so the location manager doesn't wait for it
+pub(crate) fn spawn_reconcile(
+	location_id: location::id::Type,
+	node: Arc<Node>,
+	library: Arc<Library>,
+) {
+	tokio::spawn(async move {
+		if let Err(e) = reconcile_location(location_id, &node, &library).await {
+			error!("Failed to reconcile location: <id='{location_id}', error='{e:#?}'>");
+		}
+	});
+}
+
+/// Compares the stored file paths of the location against the disk, scanning the directories
+/// that diverged and notifying the user of what changed while the location wasn't watched.
+async fn reconcile_location(
+	location_id: location::id::Type,
+	node: &Arc<Node>,
+	library: &Arc<Library>,
+) -> Result<ReconcileSummary, LocationManagerError> {
+	// A location being indexed is brought up to date by the indexer itself
+	if node
+		.jobs
+		.has_job_running(|job_identity| {
+			job_identity.target_location == location_id
+				&& (job_identity.name == <IndexerJobInit as StatefulJob>::NAME
+					|| job_identity.name == <FileIdentifierJobInit as StatefulJob>::NAME)
+		})
+		.await
+	{
+		debug!("Skipping reconciliation of location being indexed: <id='{location_id}'>");
+		return Ok(ReconcileSummary::default());
+	}
+
+	let location = find_location(library, location_id)
+		.include(location_with_indexer_rules::include())
+		.exec()
+		.await?
+		.ok_or(LocationManagerError::MissingLocation(location_id))?;
+
+	let location_path = PathBuf::from(maybe_missing(&location.path, "location.path")?);
+
+	// An empty directory left where the volume of the location was mounted would look like all of
+	// its files were removed, so we only go on if the metadata file of the location is there
+	match SpacedriveLocationMetadataFile::try_load(&location_path).await {
+		Ok(Some(_)) => {}
+		Ok(None) => {
+			debug!(
+				"Skipping reconciliation of location without its metadata file: \
+				<id='{location_id}'>"
+			);
+			return Ok(ReconcileSummary::default());
+		}
+		Err(e) => {
+			error!("Failed to load location metadata file for reconciliation: {e:#?}");
+			return Ok(ReconcileSummary::default());
+		}
+	}
+
+	let symlink_policy = SymlinkPolicy::from_db(location.symlink_policy);
+	let indexer_rules = location
+		.indexer_rules
+		.iter()
+		.filter_map(|rule| {
+			IndexerRule::try_from(&rule.indexer_rule)
+				.map_err(|e| error!("Failed to load indexer rule for reconciliation: {e:#?}"))
+				.ok()
+		})
+		.collect::<Vec<_>>();
+
+	let mut summary = ReconcileSummary::default();
+	let mut diverged = vec![];
+	// The location root has no file path of its own, so there's no `date_indexed` to tell us if
+	// its entries changed, and we always list it
+	let mut to_walk = vec![(location_path.clone(), true)];
+
+	while let Some((dir, listing_changed)) = to_walk.pop() {
+		let materialized_path = IsolatedFilePathData::new(location_id, &location_path, &dir, true)?
+			.materialized_path_for_children()
+			.expect("it's a directory");
+
+		let stored = library
+			.db
+			.file_path()
+			.find_many(vec![
+				file_path::location_id::equals(Some(location_id)),
+				file_path::materialized_path::equals(Some(materialized_path)),
+			])
+			.select(file_path_to_reconcile::select())
+			.exec()
+			.await?;
+
+		// Never indexed, the scan queued when it was created takes care of it
+		if dir == location_path && stored.is_empty() {
+			return Ok(summary);
+		}
+
+		let mut stored_names = HashSet::with_capacity(stored.len());
+		let mut dir_diverged = false;
+
+		for file_path in stored {
+			let Ok(iso_file_path) = IsolatedFilePathData::try_from(&file_path) else {
+				continue;
+			};
+			let name = iso_file_path.full_name();
+			let path = dir.join(&name);
+			stored_names.insert(name);
+
+			let is_dir = file_path.is_dir.unwrap_or_default();
+
+			let metadata = match symlink_policy.metadata(&path).await {
+				Ok(metadata) => metadata,
+				Err(e) if e.kind() == ErrorKind::NotFound => {
+					if is_dir {
+						remove_directory(location_id, &iso_file_path, library).await?;
+					}
+					summary.removed += 1;
+					dir_diverged = true;
+					continue;
+				}
+				Err(e) => {
+					trace!("{:#?}", FileIOError::from((&path, e)));
+					continue;
+				}
+			};
+
+			if metadata.is_dir() != is_dir {
+				// Turned from a file into a directory or the other way around
+				summary.modified += 1;
+				dir_diverged = true;
+			} else if is_dir {
+				// Like the polling watcher, we don't walk through links to directories
+				if !fs::symlink_metadata(&path)
+					.await
+					.map_or(true, |metadata| metadata.is_symlink())
+				{
+					to_walk.push((path, modified_after(&metadata, file_path.date_indexed)));
+				}
+			} else if changed(
+				file_path.size_in_bytes_bytes.as_deref(),
+				file_path.date_modified,
+				&metadata,
+			) {
+				summary.modified += 1;
+				dir_diverged = true;
+			}
+		}
+
+		if listing_changed {
+			let created = count_created(
+				&location_path,
+				&dir,
+				&stored_names,
+				symlink_policy,
+				&indexer_rules,
+			)
+			.await;
+			if created > 0 {
+				summary.created += created;
+				dir_diverged = true;
+			}
+		}
+
+		if dir_diverged {
+			diverged.push(dir);
+		}
+	}
+
+	summary.diverged_directories = diverged.len();
+
+	if diverged.len() <= LIGHT_SCAN_LIMIT {
+		for dir in diverged {
+			let sub_path = relative_path(&location_path, &dir);
+			if let Err(e) = light_scan_location(
+				Arc::clone(node),
+				Arc::clone(library),
+				location.clone(),
+				sub_path,
+			)
+			.await
+			{
+				error!(
+					"Failed to light scan diverged directory: <path='{}', error='{e:#?}'>",
+					dir.display()
+				);
+			}
+		}
+	} else {
+		// Rescanning a directory also rescans everything under it
+		diverged.sort_by_key(|dir| dir.components().count());
+		let mut top_most = Vec::<PathBuf>::new();
+		for dir in diverged {
+			if !top_most.iter().any(|parent| dir.starts_with(parent)) {
+				top_most.push(dir);
+			}
+		}
+
+		for dir in top_most {
+			let sub_path = relative_path(&location_path, &dir);
+			match scan_location_sub_path(node, library, location.clone(), sub_path).await {
+				Ok(()) => summary.queued_rescans += 1,
+				Err(e) => error!(
+					"Failed to queue rescan of diverged directory: <path='{}', error='{e:#?}'>",
+					dir.display()
+				),
+			}
+		}
+	}
+
+	if summary.has_changes() {
+		info!(
+			"Reconciled location <id='{location_id}'>: {summary}, {} rescans queued",
+			summary.queued_rescans
+		);
+
+		node.emit_notification(
+			NotificationData {
+				title: format!(
+					"Changes found in {}",
+					location.name.as_deref().unwrap_or("a location")
+				),
+				content: format!("While it wasn't being watched, {summary}."),
+				kind: NotificationKind::Info,
+			},
+			None,
+		)
+		.await;
+	} else {
+		debug!("Nothing changed in location <id='{location_id}'> while it wasn't being watched");
+	}
+
+	Ok(summary)
+}
+
+/// Removes a directory missing from disk along with everything under it, as the shallow indexer
+/// only removes its own row. The removals are synced, so other instances drop them too.
+async fn remove_directory(
+	location_id: location::id::Type,
+	iso_file_path: &IsolatedFilePathData<'_>,
+	library: &Library,
+) -> Result<(), LocationManagerError> {
+	let Library { db, sync, .. } = library;
+
+	let to_remove = db
+		.file_path()
+		.find_many(vec![
+			file_path::location_id::equals(Some(location_id)),
+			or![
+				and(filter_existing_file_path_params(iso_file_path)),
+				file_path::materialized_path::starts_with(
+					iso_file_path
+						.materialized_path_for_children()
+						.expect("it's a directory"),
+				),
+			],
+		])
+		.select(file_path_pub_and_cas_ids::select())
+		.exec()
+		.await?;
+
+	remove_non_existing_file_paths(to_remove, db, sync).await?;
+
+	// Other links to the files under it may now be the first ones
+	update_hard_links(db, location_id).await?;
+
+	Ok(())
+}
+
+/// Counts the entries of `dir` we don't have yet and that the location's indexer rules accept
+async fn count_created(
+	location_path: &Path,
+	dir: &Path,
+	stored_names: &HashSet<String>,
+	symlink_policy: SymlinkPolicy,
+	indexer_rules: &[IndexerRule],
+) -> usize {
+	let mut read_dir = match fs::read_dir(dir).await {
+		Ok(read_dir) => read_dir,
+		Err(e) => {
+			error!("{:#?}", FileIOError::from((dir, e)));
+			return 0;
+		}
+	};
+
+	let mut created = 0;
+
+	loop {
+		let entry = match read_dir.next_entry().await {
+			Ok(Some(entry)) => entry,
+			Ok(None) => break,
+			Err(e) => {
+				error!("{:#?}", FileIOError::from((dir, e)));
+				break;
+			}
+		};
+
+		let path = entry.path();
+		let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+			continue;
+		};
+
+		if stored_names.contains(name) {
+			continue;
+		}
+
+		let Ok(metadata) = symlink_policy.metadata(&path).await else {
+			continue;
+		};
+
+		if metadata.is_symlink() && symlink_policy == SymlinkPolicy::Ignore {
+			continue;
+		}
+
+		if accepted_by_rules(indexer_rules, location_path, &path, metadata.is_dir()).await {
+			created += 1;
+		}
+	}
+
+	created
+}
+
+/// Whether a directory was modified after we indexed it, comparing against a delta as datetimes
+/// stored in DB lose a bit of precision
+fn modified_after(metadata: &Metadata, date_indexed: Option<DateTime<FixedOffset>>) -> bool {
+	match (metadata.modified(), date_indexed) {
+		(Ok(modified_at), Some(date_indexed)) => {
+			(DateTime::<FixedOffset>::from(DateTime::<Utc>::from(modified_at)) - date_indexed)
+				.num_milliseconds()
+				> 1
+		}
+		// Without both dates we can't tell, so we list it to be sure
+		_ => true,
+	}
+}
+
+/// The scans take sub paths relative to the location, with an empty one for its root
+fn relative_path(location_path: &Path, dir: &Path) -> PathBuf {
+	dir.strip_prefix(location_path)
+		.map(Path::to_path_buf)
+		.unwrap_or_default()
+}
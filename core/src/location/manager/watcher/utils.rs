@@ -3,10 +3,19 @@ use crate::{
 	library::Library,
 	location::{
 		create_file_path, delete_directory, find_location,
-		indexer::reverse_update_directories_sizes, location_with_indexer_rules,
-		manager::LocationManagerError, scan_location_sub_path, update_location_size,
+		hard_links::update_hard_links_of,
+		indexer::{
+			reverse_update_directories_sizes,
+			rules::{gitignore::GitIgnoreRules, IndexerRule, RulePerKind},
+		},
+		location_with_indexer_rules,
+		manager::LocationManagerError,
+		scan_location_sub_path,
+		symlinks::{ResolvedPath, SymlinkPolicy, SymlinkResolver},
+		update_location_size,
 	},
 	object::{
+		content_index,
 		file_identifier::FileMetadata,
 		media::{
 			media_data_extractor::{can_extract_media_data_for_image, extract_media_data},
@@ -20,28 +29,23 @@ use crate::{
 
 use sd_file_ext::{extensions::ImageExtension, kind::ObjectKind};
 use sd_file_path_helper::{
-	check_file_path_exists, file_path_with_object, filter_existing_file_path_params,
+	attributes::xattr_allowlist_from_db, check_file_path_exists, file_path_with_object,
+	filter_existing_file_path_params, get_device_and_inode_from_path,
 	isolated_file_path_data::extract_normalized_materialized_path_str,
 	loose_find_existing_file_path_params, path_is_hidden, FilePathError, FilePathMetadata,
 	IsolatedFilePathData, MetadataExt,
 };
 use sd_prisma::{
-	prisma::{file_path, location, media_data, object},
+	prisma::{file_path, location, media_data, object, PrismaClient},
 	prisma_sync,
 };
 use sd_sync::OperationFactory;
 use sd_utils::{
-	db::{inode_from_db, inode_to_db, maybe_missing},
+	db::{inode_from_db, inode_to_db, maybe_missing, size_in_bytes_to_db},
 	error::FileIOError,
 	uuid_to_bytes,
 };
 
-#[cfg(target_family = "unix")]
-use sd_file_path_helper::get_inode;
-
-#[cfg(target_family = "windows")]
-use sd_file_path_helper::get_inode_from_path;
-
 use std::{
 	collections::{HashMap, HashSet},
 	ffi::OsStr,
@@ -53,6 +57,7 @@ use std::{
 
 use chrono::{DateTime, FixedOffset, Local, Utc};
 use notify::Event;
+use prisma_client_rust::or;
 use serde_json::json;
 use tokio::{
 	fs,
@@ -75,6 +80,101 @@ pub(super) fn check_event(event: &Event, ignore_paths: &HashSet<PathBuf>) -> boo
 	})
 }
 
+/// Loads the git ignore rules among the location's indexer rules, so we skip the same paths
+/// the indexer would reject
+pub(super) async fn load_git_ignore_rules(
+	location_id: location::id::Type,
+	library: &Library,
+) -> Result<Vec<GitIgnoreRules>, LocationManagerError> {
+	let location = find_location(library, location_id)
+		.include(location_with_indexer_rules::include())
+		.exec()
+		.await?
+		.ok_or(LocationManagerError::MissingLocation(location_id))?;
+
+	Ok(location
+		.indexer_rules
+		.iter()
+		.filter_map(|rule| {
+			IndexerRule::try_from(&rule.indexer_rule)
+				.map_err(|e| error!("Failed to load indexer rule for watcher: {e:#?}"))
+				.ok()
+		})
+		.flat_map(|rule| rule.rules)
+		.filter_map(|rule| match rule {
+			RulePerKind::RejectIgnoredByGit(git_ignore_rules) => Some(git_ignore_rules),
+			_ => None,
+		})
+		.collect())
+}
+
+/// Skips events where every path is ignored by git, forgetting the cached ignore files
+/// whenever one of them changes
+pub(super) async fn check_git_ignore(event: &Event, git_ignore_rules: &[GitIgnoreRules]) -> bool {
+	if git_ignore_rules.is_empty() || event.paths.is_empty() {
+		return true;
+	}
+
+	for rules in git_ignore_rules {
+		if event.paths.iter().any(|path| rules.is_ignore_file(path)) {
+			rules.clear_cache();
+		}
+	}
+
+	'paths: for path in &event.paths {
+		for rules in git_ignore_rules {
+			if rules.is_ignored(path).await {
+				continue 'paths;
+			}
+		}
+
+		return true;
+	}
+
+	false
+}
+
+/// Loads how the location handles symbolic links, so we skip the same links the indexer would
+pub(super) async fn load_symlink_resolver(
+	location_id: location::id::Type,
+	library: &Library,
+) -> Result<SymlinkResolver, LocationManagerError> {
+	let location = find_location(library, location_id)
+		.select(location::select!({ path symlink_policy }))
+		.exec()
+		.await?
+		.ok_or(LocationManagerError::MissingLocation(location_id))?;
+
+	Ok(SymlinkResolver::new(
+		SymlinkPolicy::from_db(location.symlink_policy),
+		Some(maybe_missing(&location.path, "location.path")?),
+	)
+	.await)
+}
+
+/// Skips events where every path is a symbolic link skipped by the location's policy, but never
+/// events about paths that don't exist anymore, as they must be removed anyway
+pub(super) async fn check_symlinks(event: &Event, symlinks: &SymlinkResolver) -> bool {
+	if event.paths.is_empty() {
+		return true;
+	}
+
+	for path in &event.paths {
+		let Ok(metadata) = fs::symlink_metadata(path).await else {
+			return true;
+		};
+
+		if !matches!(
+			symlinks.resolve(path, metadata, &[]).await,
+			ResolvedPath::Skipped
+		) {
+			return true;
+		}
+	}
+
+	false
+}
+
 pub(super) async fn create_dir(
 	location_id: location::id::Type,
 	path: impl AsRef<Path>,
@@ -92,6 +192,28 @@ pub(super) async fn create_dir(
 
 	let location_path = maybe_missing(&location.path, "location.path")?;
 
+	let symlink_policy = SymlinkPolicy::from_db(location.symlink_policy);
+	if symlink_policy != SymlinkPolicy::Follow {
+		let link_metadata = fs::symlink_metadata(path)
+			.await
+			.map_err(|e| FileIOError::from((path, e)))?;
+
+		// Links to directories are indexed as files unless we follow them
+		if link_metadata.is_symlink() {
+			return inner_create_file(
+				location_id,
+				location_path,
+				path,
+				&link_metadata,
+				node,
+				library,
+				symlink_policy,
+				&xattr_allowlist_from_db(location.xattr_allowlist.as_deref()),
+			)
+			.await;
+		}
+	}
+
 	trace!(
 		"Location: <root_path ='{}'> creating directory: {}",
 		location_path,
@@ -121,7 +243,12 @@ pub(super) async fn create_dir(
 		library,
 		iso_file_path.to_parts(),
 		None,
-		FilePathMetadata::from_path(&path, metadata).await?,
+		FilePathMetadata::from_path(
+			&path,
+			metadata,
+			&xattr_allowlist_from_db(location.xattr_allowlist.as_deref()),
+		)
+		.await?,
 	)
 	.await?;
 
@@ -141,17 +268,36 @@ pub(super) async fn create_file(
 	node: &Arc<Node>,
 	library: &Arc<Library>,
 ) -> Result<(), LocationManagerError> {
+	let path = path.as_ref();
+	let (location_path, symlink_policy, xattr_allowlist) =
+		extract_location_path_and_settings(location_id, library).await?;
+
+	// Platform handlers give us the metadata of link targets, but we index links by themselves
+	// unless we follow them
+	let link_metadata;
+	let metadata = if symlink_policy == SymlinkPolicy::Follow {
+		metadata
+	} else {
+		link_metadata = fs::symlink_metadata(path)
+			.await
+			.map_err(|e| FileIOError::from((path, e)))?;
+		&link_metadata
+	};
+
 	inner_create_file(
 		location_id,
-		extract_location_path(location_id, library).await?,
+		location_path,
 		path,
 		metadata,
 		node,
 		library,
+		symlink_policy,
+		&xattr_allowlist,
 	)
 	.await
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn inner_create_file(
 	location_id: location::id::Type,
 	location_path: impl AsRef<Path>,
@@ -164,6 +310,8 @@ async fn inner_create_file(
 		sync,
 		..
 	}: &Library,
+	symlink_policy: SymlinkPolicy,
+	xattr_allowlist: &[String],
 ) -> Result<(), LocationManagerError> {
 	let path = path.as_ref();
 	let location_path = location_path.as_ref();
@@ -178,22 +326,25 @@ async fn inner_create_file(
 	let iso_file_path_parts = iso_file_path.to_parts();
 	let extension = iso_file_path_parts.extension.to_string();
 
-	let metadata = FilePathMetadata::from_path(&path, metadata).await?;
+	let metadata = FilePathMetadata::from_path(&path, metadata, xattr_allowlist).await?;
 
 	// First we check if already exist a file with this same inode number
 	// if it does, we just update it
-	if let Some(file_path) = db
-		.file_path()
-		.find_unique(file_path::location_id_inode(
-			location_id,
-			inode_to_db(metadata.inode),
-		))
-		.include(file_path_with_object::include())
-		.exec()
-		.await?
+	if let Some(file_path) =
+		find_same_file_by_inode(location_id, location_path, &iso_file_path, &metadata, db).await?
 	{
 		trace!("File already exists with that inode: {}", iso_file_path);
-		return inner_update_file(location_path, &file_path, path, node, library, None).await;
+		return inner_update_file(
+			location_path,
+			&file_path,
+			path,
+			node,
+			library,
+			None,
+			symlink_policy,
+			xattr_allowlist,
+		)
+		.await;
 
 	// If we can't find an existing file with the same inode, we check if there is a file with the same path
 	} else if let Some(file_path) = db
@@ -219,6 +370,8 @@ async fn inner_create_file(
 			node,
 			library,
 			Some(metadata.inode),
+			symlink_policy,
+			xattr_allowlist,
 		)
 		.await;
 	}
@@ -236,7 +389,7 @@ async fn inner_create_file(
 		cas_id,
 		kind,
 		fs_metadata,
-	} = FileMetadata::new(&location_path, &iso_file_path).await?;
+	} = FileMetadata::new(&location_path, &iso_file_path, symlink_policy).await?;
 
 	debug!("Creating path: {}", iso_file_path);
 
@@ -265,6 +418,7 @@ async fn inner_create_file(
 		let date_created: DateTime<FixedOffset> =
 			DateTime::<Local>::from(fs_metadata.created_or_now()).into();
 		let int_kind = kind as i32;
+		let size = size_in_bytes_to_db(fs_metadata.len());
 		sync.write_ops(
 			db,
 			(
@@ -275,6 +429,7 @@ async fn inner_create_file(
 					[
 						(object::date_created::NAME, json!(date_created)),
 						(object::kind::NAME, json!(int_kind)),
+						(object::size_in_bytes::NAME, json!(size)),
 					],
 				),
 				db.object()
@@ -283,6 +438,7 @@ async fn inner_create_file(
 						vec![
 							object::date_created::set(Some(date_created)),
 							object::kind::set(Some(int_kind)),
+							object::size_in_bytes::set(Some(size)),
 						],
 					)
 					.select(object_ids::select()),
@@ -311,6 +467,10 @@ async fn inner_create_file(
 	)
 	.await?;
 
+	if content_index::can_index_kind(kind) {
+		content_index::index_objects(db, [(object_id, path.to_path_buf())]).await;
+	}
+
 	if !extension.is_empty() && matches!(kind, ObjectKind::Image | ObjectKind::Video) {
 		// Running in a detached task as thumbnail generation can take a while and we don't want to block the watcher
 
@@ -385,7 +545,10 @@ pub(super) async fn update_file(
 ) -> Result<(), LocationManagerError> {
 	let full_path = full_path.as_ref();
 
-	let metadata = match fs::metadata(full_path).await {
+	let (location_path, symlink_policy, xattr_allowlist) =
+		extract_location_path_and_settings(location_id, library).await?;
+
+	let metadata = match symlink_policy.metadata(full_path).await {
 		Ok(metadata) => metadata,
 		Err(e) if e.kind() == io::ErrorKind::NotFound => {
 			// If the file doesn't exist anymore, it was just a temporary file
@@ -394,8 +557,6 @@ pub(super) async fn update_file(
 		Err(e) => return Err(FileIOError::from((full_path, e)).into()),
 	};
 
-	let location_path = extract_location_path(location_id, library).await?;
-
 	if let Some(ref file_path) = library
 		.db
 		.file_path()
@@ -407,7 +568,17 @@ pub(super) async fn update_file(
 		.exec()
 		.await?
 	{
-		inner_update_file(location_path, file_path, full_path, node, library, None).await
+		inner_update_file(
+			location_path,
+			file_path,
+			full_path,
+			node,
+			library,
+			None,
+			symlink_policy,
+			&xattr_allowlist,
+		)
+		.await
 	} else {
 		inner_create_file(
 			location_id,
@@ -416,6 +587,8 @@ pub(super) async fn update_file(
 			&metadata,
 			node,
 			library,
+			symlink_policy,
+			&xattr_allowlist,
 		)
 		.await
 	}
@@ -425,6 +598,59 @@ pub(super) async fn update_file(
 	})
 }
 
+/// Looks for a file path in the location with the same device and inode of a file we just found,
+/// for it to be this same file. Hard links to the same file share its device and inode, so a file
+/// path which isn't for this same path and still exists on disk is for another link instead.
+async fn find_same_file_by_inode(
+	location_id: location::id::Type,
+	location_path: &Path,
+	iso_file_path: &IsolatedFilePathData<'_>,
+	metadata: &FilePathMetadata,
+	db: &PrismaClient,
+) -> Result<Option<file_path_with_object::Data>, LocationManagerError> {
+	for file_path in db
+		.file_path()
+		.find_many(vec![
+			file_path::location_id::equals(Some(location_id)),
+			file_path::inode::equals(Some(inode_to_db(metadata.inode))),
+			// Paths indexed before we stored devices don't have one
+			or![
+				file_path::device::equals(Some(inode_to_db(metadata.device))),
+				file_path::device::equals(None),
+			],
+		])
+		.include(file_path_with_object::include())
+		.exec()
+		.await?
+	{
+		let other_iso_file_path = IsolatedFilePathData::try_from(&file_path)?;
+
+		if other_iso_file_path == *iso_file_path {
+			return Ok(Some(file_path));
+		}
+
+		match get_device_and_inode_from_path(location_path.join(&other_iso_file_path)).await {
+			Ok((device, inode)) if device == metadata.device && inode == metadata.inode => {
+				trace!(
+					"Found a hard link to the same file: {} -> {}",
+					iso_file_path,
+					other_iso_file_path
+				);
+			}
+			Ok(_) => return Ok(Some(file_path)),
+			Err(FilePathError::FileIO(FileIOError { source, .. }))
+				if source.kind() == ErrorKind::NotFound =>
+			{
+				return Ok(Some(file_path))
+			}
+			Err(e) => return Err(e.into()),
+		}
+	}
+
+	Ok(None)
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn inner_update_file(
 	location_path: impl AsRef<Path>,
 	file_path: &file_path_with_object::Data,
@@ -432,6 +658,8 @@ async fn inner_update_file(
 	node: &Arc<Node>,
 	library @ Library { db, sync, .. }: &Library,
 	maybe_new_inode: Option<INode>,
+	symlink_policy: SymlinkPolicy,
+	xattr_allowlist: &[String],
 ) -> Result<(), LocationManagerError> {
 	let full_path = full_path.as_ref();
 	let location_path = location_path.as_ref();
@@ -451,24 +679,23 @@ async fn inner_update_file(
 		cas_id,
 		fs_metadata,
 		kind,
-	} = FileMetadata::new(&location_path, &iso_file_path).await?;
-
-	let inode = if let Some(inode) = maybe_new_inode {
-		inode
-	} else {
-		#[cfg(target_family = "unix")]
-		{
-			get_inode(&fs_metadata)
-		}
-
-		#[cfg(target_family = "windows")]
-		{
-			// FIXME: This is a workaround for Windows, because we can't get the inode from the metadata
-			get_inode_from_path(full_path).await?
-		}
-	};
+	} = FileMetadata::new(&location_path, &iso_file_path, symlink_policy).await?;
+
+	let metadata = FilePathMetadata::from_path(full_path, &fs_metadata, xattr_allowlist).await?;
+
+	let inode = maybe_new_inode.unwrap_or(metadata.inode);
+	let current_device = file_path
+		.device
+		.as_ref()
+		.map(|device| inode_from_db(&device[0..8]));
+	let is_hidden = metadata.hidden;
+	let attributes_changed = metadata.attributes_changed(
+		file_path.mode,
+		file_path.uid,
+		file_path.gid,
+		file_path.xattrs.as_deref(),
+	);
 
-	let is_hidden = path_is_hidden(full_path, &fs_metadata);
 	if file_path.cas_id != cas_id {
 		let (sync_params, db_params): (Vec<_>, Vec<_>) = {
 			use file_path::*;
@@ -487,6 +714,15 @@ async fn inner_update_file(
 						fs_metadata.len().to_be_bytes().to_vec(),
 					))),
 				),
+				(
+					(
+						size_in_bytes_number::NAME,
+						json!(size_in_bytes_to_db(fs_metadata.len())),
+					),
+					Some(size_in_bytes_number::set(Some(size_in_bytes_to_db(
+						fs_metadata.len(),
+					)))),
+				),
 				{
 					let date = DateTime::<Utc>::from(fs_metadata.modified_or_now()).into();
 
@@ -523,6 +759,16 @@ async fn inner_update_file(
 						((inode::NAME, serde_json::Value::Null), None)
 					}
 				},
+				{
+					if current_device != Some(metadata.device) {
+						(
+							(device::NAME, json!(inode_to_db(metadata.device))),
+							Some(device::set(Some(inode_to_db(metadata.device)))),
+						)
+					} else {
+						((device::NAME, serde_json::Value::Null), None)
+					}
+				},
 				{
 					if is_hidden != file_path.hidden.unwrap_or_default() {
 						(
@@ -538,6 +784,12 @@ async fn inner_update_file(
 			.filter_map(|(sync_param, maybe_db_param)| {
 				maybe_db_param.map(|db_param| (sync_param, db_param))
 			})
+			.chain(
+				attributes_changed
+					.then(|| metadata.attributes_params())
+					.into_iter()
+					.flatten(),
+			)
 			.unzip()
 		};
 
@@ -565,59 +817,101 @@ async fn inner_update_file(
 		)
 		.await?;
 
+		// Replacing a file gives it a new inode, so it stops being a hard link of the old one
+		if current_inode != inode || current_device != Some(metadata.device) {
+			if let Some(current_device) = current_device {
+				update_hard_links_of(db, current_device, current_inode).await?;
+			}
+			update_hard_links_of(db, metadata.device, inode).await?;
+		}
+
 		if let Some(ref object) = file_path.object {
 			let int_kind = kind as i32;
+			let size = size_in_bytes_to_db(fs_metadata.len());
 
-			if db
+			let object_id = if db
 				.file_path()
 				.count(vec![file_path::object_id::equals(Some(object.id))])
 				.exec()
 				.await? == 1
 			{
-				if object.kind.map(|k| k != int_kind).unwrap_or_default() {
-					sync.write_op(
+				let (sync_params, db_params): (Vec<_>, Vec<_>) = [
+					object
+						.kind
+						.map(|k| k != int_kind)
+						.unwrap_or_default()
+						.then(|| {
+							(
+								(object::kind::NAME, json!(int_kind)),
+								object::kind::set(Some(int_kind)),
+							)
+						}),
+					(object.size_in_bytes != Some(size)).then(|| {
+						(
+							(object::size_in_bytes::NAME, json!(size)),
+							object::size_in_bytes::set(Some(size)),
+						)
+					}),
+				]
+				.into_iter()
+				.flatten()
+				.unzip();
+
+				if !db_params.is_empty() {
+					sync.write_ops(
 						db,
-						sync.shared_update(
-							prisma_sync::object::SyncId {
-								pub_id: object.pub_id.clone(),
-							},
-							object::kind::NAME,
-							json!(int_kind),
-						),
-						db.object().update(
-							object::id::equals(object.id),
-							vec![object::kind::set(Some(int_kind))],
+						(
+							sync_params
+								.into_iter()
+								.map(|(field, value)| {
+									sync.shared_update(
+										prisma_sync::object::SyncId {
+											pub_id: object.pub_id.clone(),
+										},
+										field,
+										value,
+									)
+								})
+								.collect(),
+							db.object().update(object::id::equals(object.id), db_params),
 						),
 					)
 					.await?;
 				}
+
+				object.id
 			} else {
 				let pub_id = uuid_to_bytes(Uuid::new_v4());
 				let date_created: DateTime<FixedOffset> =
 					DateTime::<Local>::from(fs_metadata.created_or_now()).into();
 
-				sync.write_ops(
-					db,
-					(
-						sync.shared_create(
-							prisma_sync::object::SyncId {
-								pub_id: pub_id.clone(),
-							},
-							[
-								(object::date_created::NAME, json!(date_created)),
-								(object::kind::NAME, json!(int_kind)),
-							],
-						),
-						db.object().create(
-							pub_id.to_vec(),
-							vec![
-								object::date_created::set(Some(date_created)),
-								object::kind::set(Some(int_kind)),
-							],
+				let new_object = sync
+					.write_ops(
+						db,
+						(
+							sync.shared_create(
+								prisma_sync::object::SyncId {
+									pub_id: pub_id.clone(),
+								},
+								[
+									(object::date_created::NAME, json!(date_created)),
+									(object::kind::NAME, json!(int_kind)),
+									(object::size_in_bytes::NAME, json!(size)),
+								],
+							),
+							db.object()
+								.create(
+									pub_id.to_vec(),
+									vec![
+										object::date_created::set(Some(date_created)),
+										object::kind::set(Some(int_kind)),
+										object::size_in_bytes::set(Some(size)),
+									],
+								)
+								.select(object::select!({ id })),
 						),
-					),
-				)
-				.await?;
+					)
+					.await?;
 
 				sync.write_op(
 					db,
@@ -636,6 +930,15 @@ async fn inner_update_file(
 					),
 				)
 				.await?;
+
+				new_object.id
+			};
+
+			if content_index::can_index_kind(kind) {
+				content_index::index_objects(db, [(object_id, full_path.to_path_buf())]).await;
+			} else {
+				// The file might have been a text file before this change
+				content_index::remove_object(db, object_id).await?;
 			}
 
 			if let Some(old_cas_id) = &file_path.cas_id {
@@ -722,31 +1025,128 @@ async fn inner_update_file(
 
 		invalidate_query!(library, "search.paths");
 		invalidate_query!(library, "search.objects");
-	} else if is_hidden != file_path.hidden.unwrap_or_default() {
-		sync.write_ops(
-			db,
-			(
-				vec![sync.shared_update(
-					prisma_sync::file_path::SyncId {
-						pub_id: file_path.pub_id.clone(),
-					},
-					file_path::hidden::NAME,
-					json!(is_hidden),
-				)],
-				db.file_path().update(
-					file_path::pub_id::equals(file_path.pub_id.clone()),
-					vec![file_path::hidden::set(Some(is_hidden))],
-				),
-			),
+	} else if is_hidden != file_path.hidden.unwrap_or_default() || attributes_changed {
+		update_attributes_in_db(
+			&file_path.pub_id,
+			&metadata,
+			file_path.hidden != Some(is_hidden),
+			attributes_changed,
+			library,
 		)
 		.await?;
+	}
 
-		invalidate_query!(library, "search.paths");
+	Ok(())
+}
+
+/// Updates the hidden state, permissions, owner and extended attributes of an indexed path, for
+/// the events which tell us about metadata changes only, so the contents are left untouched
+pub(super) async fn update_attributes(
+	location_id: location::id::Type,
+	path: impl AsRef<Path>,
+	library: &Library,
+) -> Result<(), LocationManagerError> {
+	let path = path.as_ref();
+
+	let (location_path, symlink_policy, xattr_allowlist) =
+		extract_location_path_and_settings(location_id, library).await?;
+
+	let fs_metadata = match symlink_policy.metadata(path).await {
+		Ok(metadata) => metadata,
+		// Removals have their own events
+		Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+		Err(e) => return Err(FileIOError::from((path, e)).into()),
+	};
+
+	let Some(file_path) = library
+		.db
+		.file_path()
+		.find_first(filter_existing_file_path_params(
+			&IsolatedFilePathData::new(location_id, &location_path, path, fs_metadata.is_dir())?,
+		))
+		.select(file_path::select!({ pub_id hidden mode uid gid xattrs }))
+		.exec()
+		.await?
+	else {
+		// Paths not indexed yet get their attributes when they're created
+		return Ok(());
+	};
+
+	let metadata = FilePathMetadata::from_path(path, &fs_metadata, &xattr_allowlist).await?;
+
+	let hidden_changed = file_path.hidden != Some(metadata.hidden);
+	let attributes_changed = metadata.attributes_changed(
+		file_path.mode,
+		file_path.uid,
+		file_path.gid,
+		file_path.xattrs.as_deref(),
+	);
+
+	if hidden_changed || attributes_changed {
+		trace!("Updating attributes of {}", path.display());
+
+		update_attributes_in_db(
+			&file_path.pub_id,
+			&metadata,
+			hidden_changed,
+			attributes_changed,
+			library,
+		)
+		.await?;
 	}
 
 	Ok(())
 }
 
+async fn update_attributes_in_db(
+	pub_id: &[u8],
+	metadata: &FilePathMetadata,
+	hidden_changed: bool,
+	attributes_changed: bool,
+	library @ Library { db, sync, .. }: &Library,
+) -> Result<(), LocationManagerError> {
+	let (sync_params, db_params): (Vec<_>, Vec<_>) = hidden_changed
+		.then(|| {
+			(
+				(file_path::hidden::NAME, json!(metadata.hidden)),
+				file_path::hidden::set(Some(metadata.hidden)),
+			)
+		})
+		.into_iter()
+		.chain(
+			attributes_changed
+				.then(|| metadata.attributes_params())
+				.into_iter()
+				.flatten(),
+		)
+		.unzip();
+
+	sync.write_ops(
+		db,
+		(
+			sync_params
+				.into_iter()
+				.map(|(field, value)| {
+					sync.shared_update(
+						prisma_sync::file_path::SyncId {
+							pub_id: pub_id.to_vec(),
+						},
+						field,
+						value,
+					)
+				})
+				.collect(),
+			db.file_path()
+				.update(file_path::pub_id::equals(pub_id.to_vec()), db_params),
+		),
+	)
+	.await?;
+
+	invalidate_query!(library, "search.paths");
+
+	Ok(())
+}
+
 pub(super) async fn rename(
 	location_id: location::id::Type,
 	new_path: impl AsRef<Path>,
@@ -903,6 +1303,38 @@ pub(super) async fn rename(
 	Ok(())
 }
 
+/// Brings the file path of `path` up to date with how it is now on disk, whatever the events that
+/// led here were, by creating, updating or removing it
+pub(super) async fn reconcile_path(
+	location_id: location::id::Type,
+	path: impl AsRef<Path>,
+	node: &Arc<Node>,
+	library: &Arc<Library>,
+) -> Result<(), LocationManagerError> {
+	let path = path.as_ref();
+
+	match fs::metadata(path).await {
+		Ok(metadata) if metadata.is_dir() => {
+			let location_path = extract_location_path(location_id, library).await?;
+
+			if check_file_path_exists::<FilePathError>(
+				&IsolatedFilePathData::new(location_id, &location_path, path, true)?,
+				&library.db,
+			)
+			.await?
+			{
+				// Its contents are reconciled by their own paths
+				Ok(())
+			} else {
+				create_dir(location_id, path, &metadata, node, library).await
+			}
+		}
+		Ok(_) => update_file(location_id, path, node, library).await,
+		Err(e) if e.kind() == ErrorKind::NotFound => remove(location_id, path, library).await,
+		Err(e) => Err(FileIOError::from((path, e)).into()),
+	}
+}
+
 pub(super) async fn remove(
 	location_id: location::id::Type,
 	full_path: impl AsRef<Path>,
@@ -963,6 +1395,16 @@ pub(super) async fn remove_by_file_path(
 				)
 				.await?;
 
+				// Another link to the same file may now be the first one
+				if let (Some(device), Some(inode)) = (&file_path.device, &file_path.inode) {
+					update_hard_links_of(
+						db,
+						inode_from_db(&device[0..8]),
+						inode_from_db(&inode[0..8]),
+					)
+					.await?;
+				}
+
 				if let Some(object_id) = file_path.object_id {
 					db.object()
 						.delete_many(vec![
@@ -1034,6 +1476,24 @@ pub(super) async fn extract_location_path(
 		)
 }
 
+/// The location's path, along with its symlink policy and extended attributes allowlist
+async fn extract_location_path_and_settings(
+	location_id: location::id::Type,
+	library: &Library,
+) -> Result<(PathBuf, SymlinkPolicy, Vec<String>), LocationManagerError> {
+	let location = find_location(library, location_id)
+		.select(location::select!({ path symlink_policy xattr_allowlist }))
+		.exec()
+		.await?
+		.ok_or(LocationManagerError::MissingLocation(location_id))?;
+
+	Ok((
+		maybe_missing(location.path, "location.path")?.into(),
+		SymlinkPolicy::from_db(location.symlink_policy),
+		xattr_allowlist_from_db(location.xattr_allowlist.as_deref()),
+	))
+}
+
 pub(super) async fn recalculate_directories_size(
 	candidates: &mut HashMap<PathBuf, Instant>,
 	buffer: &mut Vec<(PathBuf, Instant)>,
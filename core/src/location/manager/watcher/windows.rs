@@ -32,7 +32,7 @@ use super::{
 		create_dir, extract_inode_from_path, recalculate_directories_size, remove, rename,
 		update_file,
 	},
-	EventHandler, INode, InstantAndPath, HUNDRED_MILLIS, ONE_SECOND,
+	EventHandler, INode, InstantAndPath, PathsByInode, HUNDRED_MILLIS, ONE_SECOND,
 };
 
 /// Windows file system event handler
@@ -44,8 +44,7 @@ pub(super) struct WindowsEventHandler<'lib> {
 	last_events_eviction_check: Instant,
 	rename_from_map: BTreeMap<INode, InstantAndPath>,
 	rename_to_map: BTreeMap<INode, InstantAndPath>,
-	files_to_remove: HashMap<INode, InstantAndPath>,
-	files_to_remove_buffer: Vec<(INode, InstantAndPath)>,
+	files_to_remove: PathsByInode,
 	files_to_update: HashMap<PathBuf, Instant>,
 	reincident_to_update_files: HashMap<PathBuf, Instant>,
 	to_recalculate_size: HashMap<PathBuf, Instant>,
@@ -69,8 +68,7 @@ impl<'lib> EventHandler<'lib> for WindowsEventHandler<'lib> {
 			last_events_eviction_check: Instant::now(),
 			rename_from_map: BTreeMap::new(),
 			rename_to_map: BTreeMap::new(),
-			files_to_remove: HashMap::new(),
-			files_to_remove_buffer: Vec::new(),
+			files_to_remove: PathsByInode::default(),
 			files_to_update: HashMap::new(),
 			reincident_to_update_files: HashMap::new(),
 			to_recalculate_size: HashMap::new(),
@@ -102,7 +100,7 @@ impl<'lib> EventHandler<'lib> for WindowsEventHandler<'lib> {
 					}
 				};
 
-				if let Some((_, old_path)) = self.files_to_remove.remove(&inode) {
+				if let Some(old_path) = self.files_to_remove.take(inode) {
 					// if previously we added a file to be removed with the same inode
 					// of this "newly created" created file, it means that the file was just moved to another location
 					// so we can treat if just as a file rename, like in other OSes
@@ -208,7 +206,7 @@ impl<'lib> EventHandler<'lib> for WindowsEventHandler<'lib> {
 				let path = paths.remove(0);
 				self.files_to_remove.insert(
 					extract_inode_from_path(self.location_id, &path, self.library).await?,
-					(Instant::now(), path),
+					path,
 				);
 			}
 
@@ -261,6 +259,15 @@ impl<'lib> EventHandler<'lib> for WindowsEventHandler<'lib> {
 			self.last_events_eviction_check = Instant::now();
 		}
 	}
+
+	fn has_pending_events(&self) -> bool {
+		!(self.files_to_update.is_empty()
+			&& self.reincident_to_update_files.is_empty()
+			&& self.rename_from_map.is_empty()
+			&& self.rename_to_map.is_empty()
+			&& self.files_to_remove.is_empty()
+			&& self.to_recalculate_size.is_empty())
+	}
 }
 
 impl WindowsEventHandler<'_> {
@@ -321,32 +328,23 @@ impl WindowsEventHandler<'_> {
 	}
 
 	async fn handle_removes_eviction(&mut self) -> Result<(), LocationManagerError> {
-		self.files_to_remove_buffer.clear();
 		let mut should_invalidate = false;
 
-		for (inode, (instant, path)) in self.files_to_remove.drain() {
-			if instant.elapsed() > HUNDRED_MILLIS {
-				if let Some(parent) = path.parent() {
-					if parent != Path::new("") {
-						self.to_recalculate_size
-							.insert(parent.to_path_buf(), Instant::now());
-					}
+		for path in self.files_to_remove.take_expired(HUNDRED_MILLIS) {
+			if let Some(parent) = path.parent() {
+				if parent != Path::new("") {
+					self.to_recalculate_size
+						.insert(parent.to_path_buf(), Instant::now());
 				}
-				remove(self.location_id, &path, self.library).await?;
-				should_invalidate = true;
-				trace!("Removed file_path due timeout: {}", path.display());
-			} else {
-				self.files_to_remove_buffer.push((inode, (instant, path)));
 			}
+			remove(self.location_id, &path, self.library).await?;
+			should_invalidate = true;
+			trace!("Removed file_path due timeout: {}", path.display());
 		}
 		if should_invalidate {
 			invalidate_query!(self.library, "search.paths");
 		}
 
-		for (key, value) in self.files_to_remove_buffer.drain(..) {
-			self.files_to_remove.insert(key, value);
-		}
-
 		Ok(())
 	}
 }
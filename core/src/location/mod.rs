@@ -9,7 +9,9 @@ use crate::{
 	Node,
 };
 
-use sd_file_path_helper::{filter_existing_file_path_params, IsolatedFilePathData};
+use sd_file_path_helper::{
+	attributes::xattr_allowlist_to_db, filter_existing_file_path_params, IsolatedFilePathData,
+};
 use sd_prisma::{
 	prisma::{file_path, indexer_rules_in_location, location, PrismaClient},
 	prisma_sync,
@@ -41,15 +43,19 @@ use tracing::{debug, info, warn};
 use uuid::Uuid;
 
 mod error;
+pub mod hard_links;
 pub mod indexer;
 mod manager;
 pub mod metadata;
 pub mod non_indexed;
+pub mod relinker;
+pub mod symlinks;
 
 pub use error::LocationError;
 use indexer::IndexerJobInit;
-pub use manager::{LocationManagerError, Locations};
+pub use manager::{LocationManagerError, Locations, WatcherMode};
 use metadata::SpacedriveLocationMetadataFile;
+use symlinks::SymlinkPolicy;
 
 pub type LocationPubId = Uuid;
 
@@ -277,6 +283,19 @@ pub struct LocationUpdateArgs {
 	hidden: Option<bool>,
 	indexer_rules_ids: Vec<i32>,
 	path: Option<String>,
+	/// Only applied to new paths found from now on, a rescan applies it to the whole location
+	#[specta(optional)]
+	symlink_policy: Option<SymlinkPolicy>,
+	/// Names of the extended attributes to keep for each path, where a trailing `*` matches any
+	/// name starting with what comes before it. Like the symlink policy, a rescan is needed to
+	/// apply it to the paths already indexed
+	#[specta(optional)]
+	xattr_allowlist: Option<Vec<String>>,
+	#[specta(optional)]
+	watcher_mode: Option<WatcherMode>,
+	/// Seconds between the scans of the location when it's polled, at least 5
+	#[specta(optional)]
+	watcher_poll_interval: Option<u32>,
 }
 
 impl LocationUpdateArgs {
@@ -290,6 +309,16 @@ impl LocationUpdateArgs {
 			.ok_or(LocationError::IdNotFound(self.id))?;
 
 		let name = self.name.clone();
+		let new_symlink_policy = self
+			.symlink_policy
+			.filter(|policy| SymlinkPolicy::from_db(location.symlink_policy) != *policy);
+		let new_watcher_mode = self
+			.watcher_mode
+			.filter(|mode| WatcherMode::from_db(location.watcher_mode) != *mode);
+		let new_watcher_poll_interval = self
+			.watcher_poll_interval
+			.map(|secs| i32::try_from(secs).unwrap_or(i32::MAX))
+			.filter(|secs| location.watcher_poll_interval != Some(*secs));
 
 		let (sync_params, db_params): (Vec<_>, Vec<_>) = [
 			self.name
@@ -324,11 +353,45 @@ impl LocationUpdateArgs {
 					location::path::set(Some(v)),
 				)
 			}),
+			new_symlink_policy.map(|v| {
+				(
+					(location::symlink_policy::NAME, json!(v.to_db())),
+					location::symlink_policy::set(Some(v.to_db())),
+				)
+			}),
+			self.xattr_allowlist
+				.map(|allowlist| xattr_allowlist_to_db(&allowlist))
+				.filter(|allowlist| *allowlist != location.xattr_allowlist)
+				.map(|v| {
+					(
+						(location::xattr_allowlist::NAME, json!(v)),
+						location::xattr_allowlist::set(v),
+					)
+				}),
+			new_watcher_mode.map(|v| {
+				(
+					(location::watcher_mode::NAME, json!(v.to_db())),
+					location::watcher_mode::set(Some(v.to_db())),
+				)
+			}),
+			new_watcher_poll_interval.map(|v| {
+				(
+					(location::watcher_poll_interval::NAME, json!(v)),
+					location::watcher_poll_interval::set(Some(v)),
+				)
+			}),
 		]
 		.into_iter()
 		.flatten()
 		.unzip();
 
+		// The watcher must pick up the new path, symlink policy, way of watching or indexer rules
+		let mut reload_watcher = !sync_params.is_empty()
+			&& (self.path.is_some()
+				|| new_symlink_policy.is_some()
+				|| new_watcher_mode.is_some()
+				|| new_watcher_poll_interval.is_some());
+
 		if !sync_params.is_empty() {
 			sync.write_ops(
 				db,
@@ -363,11 +426,6 @@ impl LocationUpdateArgs {
 					}
 				}
 			}
-
-			if self.path.is_some() {
-				node.locations.remove(self.id, library.clone()).await?;
-				node.locations.add(self.id, library.clone()).await?;
-			}
 		}
 
 		let current_rules_ids = location
@@ -403,6 +461,13 @@ impl LocationUpdateArgs {
 			if !rule_ids_to_add.is_empty() {
 				link_location_and_indexer_rules(library, self.id, &rule_ids_to_add).await?;
 			}
+
+			reload_watcher = true;
+		}
+
+		if reload_watcher {
+			node.locations.remove(self.id, library.clone()).await?;
+			node.locations.add(self.id, library.clone()).await?;
 		}
 
 		Ok(())
@@ -443,6 +508,7 @@ pub async fn scan_location(
 	node: &Arc<Node>,
 	library: &Arc<Library>,
 	location: location_with_indexer_rules::Data,
+	force_full: bool,
 ) -> Result<(), JobManagerError> {
 	// TODO(N): This isn't gonna work with removable media and this will likely permanently break if the DB is restored from a backup.
 	if location.instance_id != Some(library.config().await.instance_id) {
@@ -454,6 +520,7 @@ pub async fn scan_location(
 	JobBuilder::new(IndexerJobInit {
 		location,
 		sub_path: None,
+		force_full,
 	})
 	.with_action("scan_location")
 	.with_metadata(json!({"location": location_base_data.clone()}))
@@ -491,6 +558,7 @@ pub async fn scan_location_sub_path(
 	JobBuilder::new(IndexerJobInit {
 		location,
 		sub_path: Some(sub_path.clone()),
+		force_full: false,
 	})
 	.with_action("scan_location_sub_path")
 	.with_metadata(json!({
@@ -872,6 +940,7 @@ impl From<location_with_indexer_rules::Data> for location::Data {
 			pub_id: data.pub_id,
 			path: data.path,
 			instance_id: data.instance_id,
+			volume_id: data.volume_id,
 			name: data.name,
 			total_capacity: data.total_capacity,
 			available_capacity: data.available_capacity,
@@ -880,10 +949,16 @@ impl From<location_with_indexer_rules::Data> for location::Data {
 			generate_preview_media: data.generate_preview_media,
 			sync_preview_media: data.sync_preview_media,
 			hidden: data.hidden,
+			symlink_policy: data.symlink_policy,
+			xattr_allowlist: data.xattr_allowlist,
+			watcher_mode: data.watcher_mode,
+			watcher_poll_interval: data.watcher_poll_interval,
 			date_created: data.date_created,
 			file_paths: None,
 			indexer_rules: None,
+			watcher_events: None,
 			instance: None,
+			volume: None,
 		}
 	}
 }
@@ -895,6 +970,7 @@ impl From<&location_with_indexer_rules::Data> for location::Data {
 			pub_id: data.pub_id.clone(),
 			path: data.path.clone(),
 			instance_id: data.instance_id,
+			volume_id: data.volume_id,
 			name: data.name.clone(),
 			total_capacity: data.total_capacity,
 			available_capacity: data.available_capacity,
@@ -903,10 +979,16 @@ impl From<&location_with_indexer_rules::Data> for location::Data {
 			generate_preview_media: data.generate_preview_media,
 			sync_preview_media: data.sync_preview_media,
 			hidden: data.hidden,
+			symlink_policy: data.symlink_policy,
+			xattr_allowlist: data.xattr_allowlist.clone(),
+			watcher_mode: data.watcher_mode,
+			watcher_poll_interval: data.watcher_poll_interval,
 			date_created: data.date_created,
 			file_paths: None,
 			indexer_rules: None,
+			watcher_events: None,
 			instance: None,
+			volume: None,
 		}
 	}
 }
@@ -973,31 +1055,9 @@ pub async fn update_location_size(
 ) -> Result<(), QueryError> {
 	let Library { db, .. } = library;
 
-	let total_size = db
-		.file_path()
-		.find_many(vec![
-			file_path::location_id::equals(Some(location_id)),
-			file_path::materialized_path::equals(Some("/".to_string())),
-		])
-		.select(file_path::select!({ size_in_bytes_bytes }))
-		.exec()
-		.await?
-		.into_iter()
-		.filter_map(|file_path| {
-			file_path.size_in_bytes_bytes.map(|size_in_bytes_bytes| {
-				u64::from_be_bytes([
-					size_in_bytes_bytes[0],
-					size_in_bytes_bytes[1],
-					size_in_bytes_bytes[2],
-					size_in_bytes_bytes[3],
-					size_in_bytes_bytes[4],
-					size_in_bytes_bytes[5],
-					size_in_bytes_bytes[6],
-					size_in_bytes_bytes[7],
-				])
-			})
-		})
-		.sum::<u64>();
+	// Summing the files instead of the directories in the root of the location, as the sizes of
+	// directories count every hard link to the same file
+	let total_size = hard_links::unique_size_in_bytes(db, Some(location_id)).await?;
 
 	db.location()
 		.update(
@@ -1049,7 +1109,7 @@ pub async fn create_file_path(
 	cas_id: Option<String>,
 	metadata: sd_file_path_helper::FilePathMetadata,
 ) -> Result<file_path::Data, sd_file_path_helper::FilePathError> {
-	use sd_utils::db::inode_to_db;
+	use sd_utils::db::{inode_to_db, size_in_bytes_to_db};
 
 	use sd_prisma::prisma;
 
@@ -1095,10 +1155,21 @@ pub async fn create_file_path(
 				),
 				size_in_bytes_bytes::set(Some(metadata.size_in_bytes.to_be_bytes().to_vec())),
 			),
+			(
+				(
+					size_in_bytes_number::NAME,
+					json!(size_in_bytes_to_db(metadata.size_in_bytes)),
+				),
+				size_in_bytes_number::set(Some(size_in_bytes_to_db(metadata.size_in_bytes))),
+			),
 			(
 				(inode::NAME, json!(metadata.inode.to_le_bytes())),
 				inode::set(Some(inode_to_db(metadata.inode))),
 			),
+			(
+				(device::NAME, json!(metadata.device.to_le_bytes())),
+				device::set(Some(inode_to_db(metadata.device))),
+			),
 			((is_dir::NAME, json!(is_dir)), is_dir::set(Some(is_dir))),
 			(
 				(date_created::NAME, json!(metadata.created_at)),
@@ -1118,6 +1189,7 @@ pub async fn create_file_path(
 			),
 		]
 		.into_iter()
+		.chain(metadata.attributes_params())
 		.unzip()
 	};
 
@@ -1138,5 +1210,10 @@ pub async fn create_file_path(
 		)
 		.await?;
 
+	// The new path can be another hard link to a file we already have
+	if !is_dir {
+		hard_links::update_hard_links_of(db, metadata.device, metadata.inode).await?;
+	}
+
 	Ok(created_path)
 }
@@ -16,7 +16,7 @@ use sd_prisma::prisma::location;
 use sd_utils::{chain_optional_iter, error::FileIOError};
 
 use std::{
-	collections::HashMap,
+	collections::{HashMap, VecDeque},
 	io::ErrorKind,
 	path::{Path, PathBuf},
 	sync::Arc,
@@ -32,11 +32,15 @@ use tokio_stream::wrappers::ReceiverStream;
 use tracing::{error, span, warn, Level};
 
 use super::{
-	indexer::rules::{
-		seed::{no_hidden, no_os_protected},
-		IndexerRule, RuleKind,
+	indexer::{
+		rules::{
+			seed::{no_hidden, no_os_protected},
+			IndexerRule, RuleKind,
+		},
+		walk::{decide_by_rules, RulesDecision},
 	},
 	normalize_path,
+	symlinks::{ResolvedPath, SymlinkPolicy, SymlinkResolver},
 };
 
 #[derive(Debug, Error)]
@@ -101,6 +105,7 @@ pub struct NonIndexedPathItem {
 pub async fn walk(
 	path: PathBuf,
 	with_hidden_files: bool,
+	symlink_policy: SymlinkPolicy,
 	node: Arc<Node>,
 	library: Arc<Library>,
 	sort_fn: impl FnOnce(&mut Vec<Entry>) + Send,
@@ -123,6 +128,8 @@ pub async fn walk(
 	// We wanna process and let the caller use the stream.
 	let task = tokio::spawn(async move {
 		let path = &path;
+		// Non indexed paths don't belong to a location, so links can be followed anywhere
+		let symlinks = SymlinkResolver::new(symlink_policy, None::<&Path>).await;
 		let rules = chain_optional_iter(
 			[IndexerRule::from(no_os_protected())],
 			[(!with_hidden_files).then(|| IndexerRule::from(no_hidden()))],
@@ -134,6 +141,15 @@ pub async fn walk(
 		let mut directories = vec![];
 
 		for entry in entries.into_iter() {
+			let (metadata, is_link) = match symlinks.resolve(&entry.path, entry.metadata, &[]).await
+			{
+				ResolvedPath::Regular(metadata) | ResolvedPath::Followed { metadata, .. } => {
+					(metadata, false)
+				}
+				ResolvedPath::Link(metadata) => (metadata, true),
+				ResolvedPath::Skipped => continue,
+			};
+
 			let (entry_path, name) = match normalize_path(entry.path) {
 				Ok(v) => v,
 				Err(e) => {
@@ -161,8 +177,8 @@ pub async fn walk(
 				}
 			};
 
-			if entry.metadata.is_dir() {
-				directories.push((entry_path, name, entry.metadata));
+			if metadata.is_dir() {
+				directories.push((entry_path, name, metadata));
 			} else {
 				let path = Path::new(&entry_path);
 
@@ -179,35 +195,21 @@ pub async fn walk(
 					.and_then(|s| s.to_str().map(str::to_string))
 					.unwrap_or_default();
 
-				let kind = Extension::resolve_conflicting(&path, false)
-					.await
-					.map(Into::into)
-					.unwrap_or(ObjectKind::Unknown);
-
-				let should_generate_thumbnail = {
-					#[cfg(feature = "ffmpeg")]
-					{
-						matches!(
-							kind,
-							ObjectKind::Image | ObjectKind::Video | ObjectKind::Document
-						)
-					}
-
-					#[cfg(not(feature = "ffmpeg"))]
-					{
-						matches!(kind, ObjectKind::Image | ObjectKind::Document)
-					}
+				let kind = if is_link {
+					ObjectKind::Alias
+				} else {
+					Extension::resolve_conflicting(&path, false)
+						.await
+						.map(Into::into)
+						.unwrap_or(ObjectKind::Unknown)
 				};
 
-				let thumbnail_key = if should_generate_thumbnail {
-					if let Ok(cas_id) =
-						generate_cas_id(&path, entry.metadata.len())
-							.await
-							.map_err(|e| {
-								tx.send(Err(Either::Left(
-									NonIndexedLocationError::from((path, e)).into(),
-								)))
-							}) {
+				let thumbnail_key = if should_generate_thumbnail(kind) {
+					if let Ok(cas_id) = generate_cas_id(&path, metadata.len()).await.map_err(|e| {
+						tx.send(Err(Either::Left(
+							NonIndexedLocationError::from((path, e)).into(),
+						)))
+					}) {
 						if kind == ObjectKind::Document {
 							document_thumbnails_to_generate.push(GenerateThumbnailArgs::new(
 								extension.clone(),
@@ -233,15 +235,15 @@ pub async fn walk(
 				tx.send(Ok(ExplorerItem::NonIndexedPath {
 					thumbnail: thumbnail_key,
 					item: NonIndexedPathItem {
-						hidden: path_is_hidden(Path::new(&entry_path), &entry.metadata),
+						hidden: path_is_hidden(Path::new(&entry_path), &metadata),
 						path: entry_path,
 						name,
 						extension,
 						kind: kind as i32,
 						is_dir: false,
-						date_created: entry.metadata.created_or_now().into(),
-						date_modified: entry.metadata.modified_or_now().into(),
-						size_in_bytes_bytes: entry.metadata.len().to_be_bytes().to_vec(),
+						date_created: metadata.created_or_now().into(),
+						date_modified: metadata.modified_or_now().into(),
+						size_in_bytes_bytes: metadata.len().to_be_bytes().to_vec(),
 					},
 				}))
 				.await?;
@@ -317,6 +319,239 @@ pub async fn walk(
 	Ok(ReceiverStream::new(rx))
 }
 
+/// Walks `root` recursively, up to `max_depth` levels of directories, streaming the entries
+/// accepted by `rules` that also pass `filter`.
+///
+/// Each directory is streamed as soon as it's read, so results show up while we're still walking,
+/// and unreadable directories deep in the tree are reported as errors without stopping the search.
+pub async fn search(
+	root: PathBuf,
+	max_depth: Option<u32>,
+	rules: Vec<IndexerRule>,
+	symlink_policy: SymlinkPolicy,
+	filter: impl Fn(&NonIndexedPathItem) -> bool + Send + Sync + 'static,
+	node: Arc<Node>,
+) -> Result<
+	impl Stream<Item = Result<ExplorerItem, Either<rspc::Error, NonIndexedLocationError>>> + Send,
+	NonIndexedLocationError,
+> {
+	// Only the root is checked upfront, so a bad path is reported as an error of the search itself
+	let mut maybe_root_entries = Some(get_all_entries(root.clone()).await?);
+
+	let (tx, rx) = mpsc::channel(128);
+	let tx2 = tx.clone();
+
+	let task = tokio::spawn(async move {
+		let symlinks = SymlinkResolver::new(symlink_policy, None::<&Path>).await;
+
+		// Directories to walk with their depth, if they were accepted by their children and where
+		// we followed symbolic links to reach them, following the same rules as the indexer walker
+		let mut to_walk = VecDeque::from([(root, 1, None::<bool>, Vec::<PathBuf>::new())]);
+
+		while let Some((path, depth, parent_accepted_by_children, followed_from)) =
+			to_walk.pop_front()
+		{
+			let entries = if let Some(root_entries) = maybe_root_entries.take() {
+				root_entries
+			} else {
+				match get_all_entries(path).await {
+					Ok(entries) => entries,
+					Err(e) => {
+						tx.send(Err(Either::Left(e.into()))).await?;
+						continue;
+					}
+				}
+			};
+
+			let mut thumbnails_to_generate = vec![];
+
+			for entry in entries {
+				let (metadata, is_link, maybe_followed_from) = match symlinks
+					.resolve(&entry.path, entry.metadata, &followed_from)
+					.await
+				{
+					ResolvedPath::Regular(metadata) => (metadata, false, None),
+					ResolvedPath::Link(metadata) => (metadata, true, None),
+					ResolvedPath::Followed { metadata, from } => (metadata, false, Some(from)),
+					ResolvedPath::Skipped => continue,
+				};
+				let entry = Entry { metadata, ..entry };
+
+				let (entry_path, name) = match normalize_path(&entry.path) {
+					Ok(v) => v,
+					Err(e) => {
+						tx.send(Err(Either::Left(
+							NonIndexedLocationError::from((&entry.path, e)).into(),
+						)))
+						.await?;
+						continue;
+					}
+				};
+
+				let rules_per_kind = match IndexerRule::apply_all(&rules, &entry_path).await {
+					Ok(rules_per_kind) => rules_per_kind,
+					Err(e) => {
+						tx.send(Err(Either::Left(e.into()))).await?;
+						continue;
+					}
+				};
+
+				let is_dir = entry.metadata.is_dir();
+				let RulesDecision {
+					rejected_by,
+					walk_children,
+					accepted_by_children,
+				} = decide_by_rules(&rules_per_kind, is_dir, parent_accepted_by_children);
+
+				if walk_children && max_depth.map_or(true, |max_depth| depth < max_depth) {
+					to_walk.push_back((
+						entry.path.clone(),
+						depth + 1,
+						accepted_by_children,
+						followed_from
+							.iter()
+							.cloned()
+							.chain(maybe_followed_from)
+							.collect(),
+					));
+				}
+
+				if rejected_by.is_some() {
+					continue;
+				}
+
+				let (item, kind) = if is_dir {
+					let item = NonIndexedPathItem {
+						hidden: path_is_hidden(&entry.path, &entry.metadata),
+						path: entry_path,
+						name,
+						extension: String::new(),
+						kind: ObjectKind::Folder as i32,
+						is_dir: true,
+						date_created: entry.metadata.created_or_now().into(),
+						date_modified: entry.metadata.modified_or_now().into(),
+						size_in_bytes_bytes: entry.metadata.len().to_be_bytes().to_vec(),
+					};
+
+					(item, ObjectKind::Folder)
+				} else {
+					let Some(name) = entry
+						.path
+						.file_stem()
+						.and_then(|s| s.to_str().map(str::to_string))
+					else {
+						warn!("Failed to extract name from path: {}", &entry_path);
+						continue;
+					};
+
+					let extension = entry
+						.path
+						.extension()
+						.and_then(|s| s.to_str().map(str::to_string))
+						.unwrap_or_default();
+
+					let kind = if is_link {
+						ObjectKind::Alias
+					} else {
+						Extension::resolve_conflicting(&entry.path, false)
+							.await
+							.map(Into::into)
+							.unwrap_or(ObjectKind::Unknown)
+					};
+
+					let item = NonIndexedPathItem {
+						hidden: path_is_hidden(&entry.path, &entry.metadata),
+						path: entry_path,
+						name,
+						extension,
+						kind: kind as i32,
+						is_dir: false,
+						date_created: entry.metadata.created_or_now().into(),
+						date_modified: entry.metadata.modified_or_now().into(),
+						size_in_bytes_bytes: entry.metadata.len().to_be_bytes().to_vec(),
+					};
+
+					(item, kind)
+				};
+
+				if !filter(&item) {
+					continue;
+				}
+
+				// Only the matches get a thumbnail, as we could be walking a whole drive
+				let thumbnail_key = if should_generate_thumbnail(kind) {
+					match generate_cas_id(&entry.path, entry.metadata.len()).await {
+						Ok(cas_id) => {
+							thumbnails_to_generate.push(GenerateThumbnailArgs::new(
+								item.extension.clone(),
+								cas_id.clone(),
+								entry.path.clone(),
+							));
+
+							Some(get_ephemeral_thumb_key(&cas_id))
+						}
+						Err(e) => {
+							tx.send(Err(Either::Left(
+								NonIndexedLocationError::from((&entry.path, e)).into(),
+							)))
+							.await?;
+
+							None
+						}
+					}
+				} else {
+					None
+				};
+
+				tx.send(Ok(ExplorerItem::NonIndexedPath {
+					thumbnail: thumbnail_key,
+					item,
+				}))
+				.await?;
+			}
+
+			if !thumbnails_to_generate.is_empty() {
+				node.thumbnailer
+					.new_ephemeral_thumbnails_batch(BatchToProcess::new(
+						thumbnails_to_generate,
+						false,
+						true,
+					))
+					.await;
+			}
+		}
+
+		Ok::<_, NonIndexedLocationError>(())
+	});
+
+	tokio::spawn(async move {
+		match task.await {
+			Ok(Ok(())) => {}
+			Ok(Err(e)) => {
+				let _ = tx2.send(Err(Either::Left(e.into()))).await;
+			}
+			Err(e) => error!("error joining tokio task: {}", e),
+		}
+	});
+
+	Ok(ReceiverStream::new(rx))
+}
+
+fn should_generate_thumbnail(kind: ObjectKind) -> bool {
+	#[cfg(feature = "ffmpeg")]
+	{
+		matches!(
+			kind,
+			ObjectKind::Image | ObjectKind::Video | ObjectKind::Document
+		)
+	}
+
+	#[cfg(not(feature = "ffmpeg"))]
+	{
+		matches!(kind, ObjectKind::Image | ObjectKind::Document)
+	}
+}
+
 #[derive(Debug)]
 pub struct Entry {
 	path: PathBuf,
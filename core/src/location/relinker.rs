@@ -0,0 +1,392 @@
+//! Every location has a `.spacedrive` metadata file at its root, with the pub id the location has
+//! in each library. So when a location goes offline because its files were moved, or because its
+//! drive is now mounted somewhere else, we can find it again by searching for these files.
+//!
+//! The relinker job walks some roots, like the mount points of newly mounted volumes, a few
+//! directories deep, and never below a location, as locations can't be nested. Then, for each
+//! location of this library found somewhere other than its path:
+//!
+//! - If it's offline and was found in a single place, that's where it moved to, so it's relinked,
+//!   or only proposed to the user when the job was asked not to relink;
+//! - If it's still at its path, or was found in more than one place, the location was cloned, like
+//!   when a drive is copied onto another one. As we can't know which copy the user wants to keep, we
+//!   don't relink it, and let the user know about the copies instead.
+
+use crate::{
+	api::notifications::{NotificationData, NotificationKind},
+	invalidate_query,
+	job::{
+		CurrentStep, JobError, JobInitOutput, JobResult, JobRunErrors, JobRunMetadata,
+		JobStepOutput, StatefulJob, WorkerContext,
+	},
+	library::{Library, LibraryId},
+	Node,
+};
+
+use sd_prisma::prisma::location;
+use sd_utils::{error::FileIOError, from_bytes_to_uuid};
+
+use std::{
+	collections::HashMap,
+	io::ErrorKind,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use specta::Type;
+use tokio::fs;
+use tracing::{error, info, trace, warn};
+
+use super::{metadata::SpacedriveLocationMetadataFile, relink_location, LocationPubId};
+
+/// How many directories deep we search under each root, when not told otherwise
+const DEFAULT_MAX_DEPTH: u32 = 4;
+
+#[derive(Serialize, Deserialize, Hash, Type, Debug)]
+pub struct LocationRelinkerJobInit {
+	/// Directories to search for the locations of the library
+	pub roots: Vec<PathBuf>,
+	/// How many directories deep to search under each root
+	#[serde(default)]
+	pub max_depth: Option<u32>,
+	/// Relinks the offline locations found in a single place, instead of only proposing it
+	pub relink: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LocationRelinkerJobStep {
+	path: PathBuf,
+	depth: u32,
+}
+
+/// Where a metadata file with a location of this library was found
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FoundLocation {
+	pub_id: LocationPubId,
+	path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct LocationRelinkerJobRunMetadata {
+	searched_directories: u64,
+	found: Vec<FoundLocation>,
+}
+
+impl JobRunMetadata for LocationRelinkerJobRunMetadata {
+	fn update(&mut self, new_data: Self) {
+		self.searched_directories += new_data.searched_directories;
+		self.found.extend(new_data.found);
+	}
+}
+
+/// What the relinker did with the locations it found
+#[derive(Serialize, Default, Debug)]
+struct RelinkerOutput {
+	relinked: Vec<(location::id::Type, PathBuf)>,
+	proposed: Vec<(location::id::Type, PathBuf)>,
+	cloned: Vec<(location::id::Type, Vec<PathBuf>)>,
+}
+
+#[async_trait::async_trait]
+impl StatefulJob for LocationRelinkerJobInit {
+	type Data = ();
+	type Step = LocationRelinkerJobStep;
+	type RunMetadata = LocationRelinkerJobRunMetadata;
+
+	const NAME: &'static str = "location_relinker";
+
+	/// The relinker isn't bound to a single location, and location ids start at 1
+	fn target_location(&self) -> location::id::Type {
+		0
+	}
+
+	async fn init(
+		&self,
+		_: &WorkerContext,
+		data: &mut Option<Self::Data>,
+	) -> Result<JobInitOutput<Self::RunMetadata, Self::Step>, JobError> {
+		let mut steps = Vec::with_capacity(self.roots.len());
+		for root in &self.roots {
+			match fs::metadata(root).await {
+				Ok(metadata) if metadata.is_dir() => steps.push(LocationRelinkerJobStep {
+					path: root.clone(),
+					depth: 0,
+				}),
+				Ok(_) => warn!("Skipping root that isn't a directory: {}", root.display()),
+				Err(e) => error!("{:#?}", FileIOError::from((root, e))),
+			}
+		}
+
+		// Must fill in the data, otherwise the job will not run
+		*data = Some(());
+
+		if steps.is_empty() {
+			return Err(JobError::EarlyFinish {
+				name: <Self as StatefulJob>::NAME.to_string(),
+				reason: "Found no directories to search for locations".to_string(),
+			});
+		}
+
+		Ok(steps.into())
+	}
+
+	async fn execute_step(
+		&self,
+		ctx: &WorkerContext,
+		CurrentStep { step, .. }: CurrentStep<'_, Self::Step>,
+		_: &Self::Data,
+		_: &Self::RunMetadata,
+	) -> Result<JobStepOutput<Self::Step, Self::RunMetadata>, JobError> {
+		let mut run_metadata = LocationRelinkerJobRunMetadata {
+			searched_directories: 1,
+			..Default::default()
+		};
+
+		match SpacedriveLocationMetadataFile::try_load(&step.path).await {
+			// Locations can't be nested, so there's nothing else to find under this one
+			Ok(Some(metadata_file)) => {
+				if let Ok(pub_id) = metadata_file.location_pub_id(ctx.library.id) {
+					run_metadata.found.push(FoundLocation {
+						pub_id,
+						path: step.path.clone(),
+					});
+				}
+
+				return Ok(run_metadata.into());
+			}
+			Ok(None) => {}
+			Err(e) => return Ok((run_metadata, JobRunErrors(vec![e.to_string()])).into()),
+		}
+
+		if step.depth >= self.max_depth.unwrap_or(DEFAULT_MAX_DEPTH) {
+			return Ok(run_metadata.into());
+		}
+
+		ctx.progress_msg(format!("Searching {}", step.path.display()));
+
+		let mut read_dir = match fs::read_dir(&step.path).await {
+			Ok(read_dir) => read_dir,
+			// We search wherever we're told to, so there's bound to be some directories we can't read
+			Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+				trace!("{:#?}", FileIOError::from((&step.path, e)));
+				return Ok(run_metadata.into());
+			}
+			Err(e) => {
+				return Ok((
+					run_metadata,
+					JobRunErrors(vec![FileIOError::from((&step.path, e)).to_string()]),
+				)
+					.into())
+			}
+		};
+
+		let mut more_steps = vec![];
+		let mut errors = vec![];
+
+		loop {
+			match read_dir.next_entry().await {
+				Ok(Some(entry)) => {
+					// Following links could lead us out of the root or around in circles
+					if entry
+						.file_type()
+						.await
+						.map_or(false, |file_type| file_type.is_dir())
+					{
+						more_steps.push(LocationRelinkerJobStep {
+							path: entry.path(),
+							depth: step.depth + 1,
+						});
+					}
+				}
+				Ok(None) => break,
+				Err(e) => {
+					errors.push(FileIOError::from((&step.path, e)).to_string());
+					break;
+				}
+			}
+		}
+
+		Ok((more_steps, run_metadata, JobRunErrors(errors)).into())
+	}
+
+	async fn finalize(
+		&self,
+		ctx: &WorkerContext,
+		_data: &Option<Self::Data>,
+		run_metadata: &Self::RunMetadata,
+	) -> JobResult {
+		let init = self;
+		let (node, library) = (&ctx.node, &ctx.library);
+
+		let mut found = HashMap::<_, Vec<_>>::new();
+		for FoundLocation { pub_id, path } in &run_metadata.found {
+			found.entry(*pub_id).or_default().push(path.clone());
+		}
+
+		let instance_id = library.config().await.instance_id;
+		let locations = library
+			.db
+			.location()
+			.find_many(vec![location::instance_id::equals(Some(instance_id))])
+			.select(location::select!({ id pub_id name path }))
+			.exec()
+			.await?;
+
+		let mut output = RelinkerOutput::default();
+
+		for location in locations {
+			let pub_id = from_bytes_to_uuid(&location.pub_id);
+			let Some(paths) = found.remove(&pub_id) else {
+				continue;
+			};
+
+			let current_path = location.path.as_deref().map(Path::new);
+			let elsewhere = paths
+				.into_iter()
+				.filter(|path| Some(path.as_path()) != current_path)
+				.collect::<Vec<_>>();
+
+			if elsewhere.is_empty() {
+				continue;
+			}
+
+			let name = location.name.as_deref().unwrap_or("A location");
+
+			// It may not be online yet, like when its volume was just mounted along with the copy
+			let is_at_path = node.locations.is_online(&pub_id).await
+				|| match current_path {
+					Some(current_path) => is_location_at(current_path, library.id, pub_id).await,
+					None => false,
+				};
+
+			if is_at_path || elsewhere.len() > 1 {
+				let copies = current_path
+					.filter(|_| is_at_path)
+					.map(Path::to_path_buf)
+					.into_iter()
+					.chain(elsewhere)
+					.collect::<Vec<_>>();
+
+				warn!(
+					"Found copies of location <id='{}'>, not relinking it: {copies:?}",
+					location.id
+				);
+
+				notify(
+					node,
+					format!("{name} was copied"),
+					format!(
+						"It was found at {}, relink it to the one you want to keep.",
+						list_paths(&copies)
+					),
+				)
+				.await;
+
+				output.cloned.push((location.id, copies));
+			} else {
+				let new_path = elsewhere
+					.into_iter()
+					.next()
+					.expect("we checked it's not empty");
+
+				if !init.relink {
+					notify(
+						node,
+						format!("{name} may have moved"),
+						format!("It was found at {}.", new_path.display()),
+					)
+					.await;
+
+					output.proposed.push((location.id, new_path));
+					continue;
+				}
+
+				match relink_and_watch(node, library, &new_path).await {
+					Ok(location_id) => {
+						info!(
+							"Relinked location <id='{location_id}'> to {}",
+							new_path.display()
+						);
+
+						notify(
+							node,
+							format!("{name} was relinked"),
+							format!("It's now at {}.", new_path.display()),
+						)
+						.await;
+
+						output.relinked.push((location_id, new_path));
+					}
+					Err(e) => error!(
+						"Failed to relink location <id='{}'> to {}: {e:#?}",
+						location.id,
+						new_path.display()
+					),
+				}
+			}
+		}
+
+		if !output.relinked.is_empty() {
+			invalidate_query!(library, "locations.list");
+			invalidate_query!(library, "locations.get");
+		}
+
+		Ok(Some(json!({
+			"init": init,
+			"run_metadata": run_metadata,
+			"output": output,
+		})))
+	}
+}
+
+/// Relinks the location through the metadata file at `path` and restarts its watcher there
+async fn relink_and_watch(
+	node: &Arc<Node>,
+	library: &Arc<Library>,
+	path: &Path,
+) -> Result<location::id::Type, JobError> {
+	let location_id = relink_location(library, path).await?;
+
+	if let Err(e) = node
+		.locations
+		.remove(location_id, Arc::clone(library))
+		.await
+	{
+		error!("Failed to stop watching relinked location: {e:#?}");
+	}
+	if let Err(e) = node.locations.add(location_id, Arc::clone(library)).await {
+		error!("Failed to watch relinked location: {e:#?}");
+	}
+
+	Ok(location_id)
+}
+
+/// Whether the metadata file at `path` is the one of this location
+async fn is_location_at(path: &Path, library_id: LibraryId, pub_id: LocationPubId) -> bool {
+	matches!(
+		SpacedriveLocationMetadataFile::try_load(path).await,
+		Ok(Some(metadata_file)) if metadata_file.location_pub_id(library_id).ok() == Some(pub_id)
+	)
+}
+
+async fn notify(node: &Node, title: String, content: String) {
+	node.emit_notification(
+		NotificationData {
+			title,
+			content,
+			kind: NotificationKind::Info,
+		},
+		None,
+	)
+	.await;
+}
+
+fn list_paths(paths: &[PathBuf]) -> String {
+	paths
+		.iter()
+		.map(|path| path.display().to_string())
+		.collect::<Vec<_>>()
+		.join(", ")
+}
@@ -0,0 +1,280 @@
+use std::{
+	fs::Metadata,
+	io,
+	path::{Path, PathBuf},
+};
+
+use futures::future::OptionFuture;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tokio::fs;
+use tracing::{trace, warn};
+
+/// How symbolic links found inside a location are handled, stored in the `symlink_policy` column
+/// of the `location` table, and honoured by the indexer, the watcher and non indexed walks alike.
+#[repr(i32)]
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, Type, Eq, PartialEq, Hash)]
+pub enum SymlinkPolicy {
+	/// Symbolic links are skipped altogether
+	#[default]
+	Ignore = 0,
+	/// The link itself is indexed as a file of `ObjectKind::Alias`, without looking at its target
+	IndexLink = 1,
+	/// The link is indexed as if it was its target, as long as the target is inside the location
+	/// and following it doesn't take us back into a directory we're already walking.
+	/// Files reached both ways are indexed at both paths, but as they share their device and inode,
+	/// they're linked like hard links, so their bytes are only counted once.
+	Follow = 2,
+}
+
+impl SymlinkPolicy {
+	pub fn from_db(maybe_policy: Option<i32>) -> Self {
+		match maybe_policy {
+			None | Some(0) => Self::Ignore,
+			Some(1) => Self::IndexLink,
+			Some(2) => Self::Follow,
+			Some(policy) => {
+				warn!("Unknown symlink policy <policy={policy}>, ignoring symbolic links");
+				Self::Ignore
+			}
+		}
+	}
+
+	pub const fn to_db(self) -> i32 {
+		self as i32
+	}
+	/// The metadata of `path` as this policy sees it, which is the metadata of the link itself
+	/// for symbolic links, unless we follow them
+	pub async fn metadata(self, path: impl AsRef<Path>) -> io::Result<Metadata> {
+		let path = path.as_ref();
+		let metadata = fs::symlink_metadata(path).await?;
+
+		if metadata.is_symlink() && self == Self::Follow {
+			fs::metadata(path).await
+		} else {
+			Ok(metadata)
+		}
+	}
+}
+
+/// What to do with a path found while walking, given its [`SymlinkPolicy`]
+#[derive(Debug)]
+pub enum ResolvedPath {
+	/// Not a symbolic link, to be handled as usual with its own metadata
+	Regular(Metadata),
+	/// A symbolic link to be indexed by itself, with the metadata of the link
+	Link(Metadata),
+	/// A symbolic link to be indexed with the metadata of its target. If it's a directory, `from`
+	/// must be appended to the followed links of the directories walked through this link.
+	Followed { metadata: Metadata, from: PathBuf },
+	/// A symbolic link that we must skip
+	Skipped,
+}
+
+/// Applies a [`SymlinkPolicy`] to the paths found while walking.
+///
+/// Links are only followed to targets inside `maybe_root`, when there is one, and never to
+/// directories which contain the directory being walked, nor the directories where we followed
+/// other links to get here, as we would be walking in circles.
+#[derive(Debug, Clone)]
+pub struct SymlinkResolver {
+	policy: SymlinkPolicy,
+	/// Canonical path of the root we can't follow links out of
+	maybe_root: Option<PathBuf>,
+}
+
+impl SymlinkResolver {
+	pub async fn new(policy: SymlinkPolicy, maybe_root: Option<impl AsRef<Path>>) -> Self {
+		let maybe_root = if policy == SymlinkPolicy::Follow {
+			if let Some(root) = maybe_root {
+				let root = root.as_ref();
+				// If we can't canonicalize the root, nothing can be proven to be inside it
+				Some(fs::canonicalize(root).await.unwrap_or_else(|e| {
+					warn!(
+						"Failed to canonicalize root to follow symbolic links <path='{}'>: {e:#?}",
+						root.display()
+					);
+					root.to_path_buf()
+				}))
+			} else {
+				None
+			}
+		} else {
+			None
+		};
+
+		Self { policy, maybe_root }
+	}
+
+	pub fn policy(&self) -> SymlinkPolicy {
+		self.policy
+	}
+
+	/// Decides what to do with `path`, where `metadata` must come from `symlink_metadata`, so it
+	/// tells us if `path` is a symbolic link. `followed_from` are the canonical paths of the
+	/// directories where links were followed to reach `path`.
+	pub async fn resolve(
+		&self,
+		path: impl AsRef<Path>,
+		metadata: Metadata,
+		followed_from: &[PathBuf],
+	) -> ResolvedPath {
+		if !metadata.is_symlink() {
+			return ResolvedPath::Regular(metadata);
+		}
+
+		let path = path.as_ref();
+
+		match self.policy {
+			SymlinkPolicy::Ignore => ResolvedPath::Skipped,
+			SymlinkPolicy::IndexLink => ResolvedPath::Link(metadata),
+			SymlinkPolicy::Follow => self.follow(path, followed_from).await,
+		}
+	}
+
+	async fn follow(&self, path: &Path, followed_from: &[PathBuf]) -> ResolvedPath {
+		let (Ok(target), Some(Ok(from))) = (
+			fs::canonicalize(path).await,
+			// The canonical path of the directory being walked
+			OptionFuture::from(path.parent().map(fs::canonicalize)).await,
+		) else {
+			trace!("Skipping broken symbolic link <path='{}'>", path.display());
+			return ResolvedPath::Skipped;
+		};
+
+		if let Some(root) = &self.maybe_root {
+			if !target.starts_with(root) {
+				trace!(
+					"Skipping symbolic link out of the location <path='{}', target='{}'>",
+					path.display(),
+					target.display()
+				);
+				return ResolvedPath::Skipped;
+			}
+		}
+
+		let Ok(metadata) = fs::metadata(&target).await else {
+			return ResolvedPath::Skipped;
+		};
+
+		if metadata.is_dir()
+			&& followed_from
+				.iter()
+				.chain([&from])
+				.any(|walking| walking.starts_with(&target))
+		{
+			trace!(
+				"Skipping symbolic link to a directory we're already walking \
+				<path='{}', target='{}'>",
+				path.display(),
+				target.display()
+			);
+			return ResolvedPath::Skipped;
+		}
+
+		ResolvedPath::Followed { metadata, from }
+	}
+}
+
+#[cfg(all(test, target_family = "unix"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	use std::os::unix::fs::symlink;
+
+	use tempfile::tempdir;
+
+	async fn resolve(resolver: &SymlinkResolver, path: &Path) -> ResolvedPath {
+		resolver
+			.resolve(path, fs::symlink_metadata(path).await.unwrap(), &[])
+			.await
+	}
+
+	#[tokio::test]
+	async fn test_symlink_policies() {
+		let outside = tempdir().unwrap();
+		let root = tempdir().unwrap();
+		let dir = root.path().join("dir");
+		fs::create_dir(&dir).await.unwrap();
+		fs::write(dir.join("file.txt"), b"text").await.unwrap();
+
+		let to_file = root.path().join("to_file");
+		let to_parent = dir.join("to_parent");
+		let to_outside = root.path().join("to_outside");
+		let broken = root.path().join("broken");
+		symlink(dir.join("file.txt"), &to_file).unwrap();
+		symlink(root.path(), &to_parent).unwrap();
+		symlink(outside.path(), &to_outside).unwrap();
+		symlink(root.path().join("missing"), &broken).unwrap();
+
+		let ignore = SymlinkResolver::new(SymlinkPolicy::Ignore, Some(root.path())).await;
+		assert!(matches!(
+			resolve(&ignore, &dir).await,
+			ResolvedPath::Regular(_)
+		));
+		assert!(matches!(
+			resolve(&ignore, &to_file).await,
+			ResolvedPath::Skipped
+		));
+
+		let index_link = SymlinkResolver::new(SymlinkPolicy::IndexLink, Some(root.path())).await;
+		assert!(matches!(
+			resolve(&index_link, &broken).await,
+			ResolvedPath::Link(metadata) if metadata.is_symlink()
+		));
+
+		let follow = SymlinkResolver::new(SymlinkPolicy::Follow, Some(root.path())).await;
+		assert!(matches!(
+			resolve(&follow, &to_file).await,
+			ResolvedPath::Followed { metadata, .. } if metadata.len() == 4
+		));
+		assert!(matches!(
+			resolve(&follow, &to_parent).await,
+			ResolvedPath::Skipped
+		));
+		assert!(matches!(
+			resolve(&follow, &to_outside).await,
+			ResolvedPath::Skipped
+		));
+		assert!(matches!(
+			resolve(&follow, &broken).await,
+			ResolvedPath::Skipped
+		));
+
+		// Without a root, links can go anywhere but still can't take us in circles
+		let follow_anywhere = SymlinkResolver::new(SymlinkPolicy::Follow, None::<&Path>).await;
+		assert!(matches!(
+			resolve(&follow_anywhere, &to_outside).await,
+			ResolvedPath::Followed { .. }
+		));
+		assert!(matches!(
+			resolve(&follow_anywhere, &to_parent).await,
+			ResolvedPath::Skipped
+		));
+
+		// Links between sibling directories only make a cycle through the links followed before
+		let other = root.path().join("other");
+		fs::create_dir(&other).await.unwrap();
+		let to_other = dir.join("to_other");
+		let back_to_dir = other.join("back_to_dir");
+		symlink(&other, &to_other).unwrap();
+		symlink(&dir, &back_to_dir).unwrap();
+
+		let through_link = to_other.join("back_to_dir");
+		let metadata = fs::symlink_metadata(&through_link).await.unwrap();
+		assert!(matches!(
+			follow.resolve(&through_link, metadata, &[]).await,
+			ResolvedPath::Followed { .. }
+		));
+
+		let metadata = fs::symlink_metadata(&through_link).await.unwrap();
+		let followed_from = [fs::canonicalize(&dir).await.unwrap()];
+		assert!(matches!(
+			follow
+				.resolve(&through_link, metadata, &followed_from)
+				.await,
+			ResolvedPath::Skipped
+		));
+	}
+}
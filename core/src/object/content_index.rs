@@ -0,0 +1,280 @@
+use sd_file_ext::kind::ObjectKind;
+use sd_prisma::prisma::{file_path, object, PrismaClient};
+use sd_utils::error::FileIOError;
+
+use std::path::{Path, PathBuf};
+
+use prisma_client_rust::{raw, PrismaValue, QueryError};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tokio::{fs::File, io::AsyncReadExt};
+use tracing::{error, trace};
+
+/// We only index the beginning of each file, huge logs or dumps would just bloat the index
+pub const MAX_INDEXED_BYTES: u64 = 1024 * 1024;
+
+/// How many tokens around the match we return as a snippet
+const SNIPPET_TOKENS: i64 = 16;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ContentIndexError {
+	#[error(transparent)]
+	FileIO(#[from] FileIOError),
+	#[error("database error: {0}")]
+	Database(#[from] QueryError),
+}
+
+/// A single hit on the content index, `rank` is the FTS5 bm25 score, so lower is better
+#[derive(Serialize, Deserialize, Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentMatch {
+	pub object_id: object::id::Type,
+	pub rank: f64,
+}
+
+#[derive(Serialize, Deserialize, Type, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentSnippet {
+	pub object_id: object::id::Type,
+	pub snippet: String,
+}
+
+pub fn can_index_kind(kind: ObjectKind) -> bool {
+	matches!(
+		kind,
+		ObjectKind::Text | ObjectKind::Code | ObjectKind::Config | ObjectKind::Document
+	)
+}
+
+/// Reads the text contents of a file, returning `None` for binary files.
+///
+/// Documents like PDFs or office files are binary containers, so they're skipped here until
+/// we have proper extractors for them, but plain text documents (like `.ics`) are indexed.
+pub async fn extract_text(path: impl AsRef<Path>) -> Result<Option<String>, FileIOError> {
+	let path = path.as_ref();
+
+	let mut buf = Vec::new();
+	File::open(path)
+		.await
+		.map_err(|e| FileIOError::from((path, e)))?
+		.take(MAX_INDEXED_BYTES)
+		.read_to_end(&mut buf)
+		.await
+		.map_err(|e| FileIOError::from((path, e)))?;
+
+	if buf.is_empty() || buf.contains(&0) {
+		return Ok(None);
+	}
+
+	// Lossy as we may have cut a multi-byte character in half when truncating
+	Ok(Some(String::from_utf8_lossy(&buf).into_owned()))
+}
+
+/// Replaces the indexed contents of an object with the current contents of the file at `path`.
+/// Returns `false` if the file wasn't textual and so nothing got indexed.
+pub async fn index_object(
+	db: &PrismaClient,
+	object_id: object::id::Type,
+	path: impl AsRef<Path>,
+) -> Result<bool, ContentIndexError> {
+	let maybe_text = extract_text(path).await?;
+
+	remove_object(db, object_id).await?;
+
+	let Some(text) = maybe_text else {
+		return Ok(false);
+	};
+
+	db._execute_raw(raw!(
+		"INSERT INTO content_index (object_id, content) VALUES ({}, {})",
+		PrismaValue::Int(object_id as i64),
+		PrismaValue::String(text)
+	))
+	.exec()
+	.await?;
+
+	Ok(true)
+}
+
+/// Indexes a batch of objects, logging errors instead of failing as the content index
+/// is just a best effort on top of the identifier
+pub async fn index_objects(
+	db: &PrismaClient,
+	objects: impl IntoIterator<Item = (object::id::Type, PathBuf)>,
+) -> usize {
+	let mut indexed_count = 0;
+
+	for (object_id, path) in objects {
+		match index_object(db, object_id, &path).await {
+			Ok(true) => indexed_count += 1,
+			Ok(false) => trace!(
+				"Skipped non textual file for content index: {}",
+				path.display()
+			),
+			Err(e) => error!("Failed to index file contents: {e:#?}"),
+		}
+	}
+
+	indexed_count
+}
+
+pub async fn remove_object(
+	db: &PrismaClient,
+	object_id: object::id::Type,
+) -> Result<(), QueryError> {
+	db._execute_raw(raw!(
+		"DELETE FROM content_index WHERE object_id = {}",
+		PrismaValue::Int(object_id as i64)
+	))
+	.exec()
+	.await
+	.map(|_| ())
+}
+
+/// Searches the content index, with the best matches first.
+///
+/// Every match is returned, as the content filter turns them into an `object_id IN (...)` filter
+/// and dropping any would silently drop results. File path searches rank and page through the
+/// matches in the database with [`ranked_file_path_ids`] instead.
+pub async fn search(db: &PrismaClient, query: &str) -> Result<Vec<ContentMatch>, QueryError> {
+	let Some(fts_query) = to_fts_query(query) else {
+		return Ok(vec![]);
+	};
+
+	db._query_raw(raw!(
+		"SELECT object_id, rank
+		FROM content_index
+		WHERE content_index MATCH {}
+		ORDER BY rank",
+		PrismaValue::String(fts_query)
+	))
+	.exec()
+	.await
+}
+
+/// A page of the ids of the file paths whose objects match all of `queries`, the best matches
+/// first, and the ones of the same object in the order they were indexed
+pub async fn ranked_file_path_ids(
+	db: &PrismaClient,
+	queries: &[impl AsRef<str>],
+	skip: i64,
+	take: i64,
+) -> Result<Vec<file_path::id::Type>, QueryError> {
+	#[derive(Deserialize)]
+	struct RankedFilePath {
+		id: file_path::id::Type,
+	}
+
+	let Some(fts_query) = to_fts_queries(queries) else {
+		return Ok(vec![]);
+	};
+
+	db._query_raw::<RankedFilePath>(raw!(
+		"SELECT file_path.id AS id
+		FROM content_index
+		INNER JOIN file_path ON file_path.object_id = content_index.object_id
+		WHERE content_index MATCH {}
+		ORDER BY content_index.rank, file_path.id
+		LIMIT {} OFFSET {}",
+		PrismaValue::String(fts_query),
+		PrismaValue::Int(take),
+		PrismaValue::Int(skip)
+	))
+	.exec()
+	.await
+	.map(|file_paths| {
+		file_paths
+			.into_iter()
+			.map(|file_path| file_path.id)
+			.collect()
+	})
+}
+
+/// Snippets of the matched contents of `object_ids`, for the results of a search with `queries`
+pub async fn snippets(
+	db: &PrismaClient,
+	queries: &[impl AsRef<str>],
+	object_ids: impl IntoIterator<Item = object::id::Type>,
+) -> Result<Vec<ContentSnippet>, QueryError> {
+	let Some(fts_query) = to_fts_queries(queries) else {
+		return Ok(vec![]);
+	};
+
+	// Object ids are integers from our own database, so they're safe to inline
+	let object_ids = object_ids
+		.into_iter()
+		.map(|object_id| object_id.to_string())
+		.collect::<Vec<_>>();
+
+	if object_ids.is_empty() {
+		return Ok(vec![]);
+	}
+
+	db._query_raw(raw!(
+		&format!(
+			"SELECT object_id, snippet(content_index, 1, '<b>', '</b>', '…', {SNIPPET_TOKENS}) AS snippet
+			FROM content_index
+			WHERE content_index MATCH {{}} AND object_id IN ({})",
+			object_ids.join(", ")
+		),
+		PrismaValue::String(fts_query)
+	))
+	.exec()
+	.await
+}
+
+/// A single FTS5 query matching every one of `queries`
+fn to_fts_queries(queries: &[impl AsRef<str>]) -> Option<String> {
+	let fts_queries = queries
+		.iter()
+		.filter_map(|query| to_fts_query(query.as_ref()))
+		.collect::<Vec<_>>();
+
+	(!fts_queries.is_empty()).then(|| fts_queries.join(" AND "))
+}
+
+/// Turns user input into a FTS5 query where every term must be present.
+///
+/// Terms are quoted, so FTS5 operators typed by the user are matched literally instead of
+/// producing syntax errors, and double quoted phrases are kept together.
+fn to_fts_query(query: &str) -> Option<String> {
+	let mut terms = Vec::new();
+
+	for (idx, part) in query.split('"').enumerate() {
+		// Odd parts are the ones between double quotes
+		if idx % 2 == 1 {
+			let phrase = part.split_whitespace().collect::<Vec<_>>().join(" ");
+			if !phrase.is_empty() {
+				terms.push(phrase);
+			}
+		} else {
+			terms.extend(part.split_whitespace().map(str::to_string));
+		}
+	}
+
+	(!terms.is_empty()).then(|| {
+		terms
+			.into_iter()
+			.map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+			.collect::<Vec<_>>()
+			.join(" ")
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn fts_query_quotes_terms() {
+		assert_eq!(to_fts_query("   "), None);
+		assert_eq!(
+			to_fts_query("database_url AND"),
+			Some(r#""database_url" "AND""#.to_string())
+		);
+		assert_eq!(
+			to_fts_query(r#"port "listen  address" *"#),
+			Some(r#""port" "listen address" "*""#.to_string())
+		);
+	}
+}
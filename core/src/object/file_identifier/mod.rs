@@ -1,7 +1,8 @@
 use crate::{
 	job::JobError,
 	library::Library,
-	object::{cas::generate_cas_id, object_for_file_identifier},
+	location::symlinks::SymlinkPolicy,
+	object::{cas::generate_cas_id, content_index, object_for_file_identifier},
 };
 
 use sd_file_ext::{extensions::Extension, kind::ObjectKind};
@@ -11,7 +12,11 @@ use sd_prisma::{
 	prisma_sync,
 };
 use sd_sync::{CRDTOperation, OperationFactory};
-use sd_utils::{db::maybe_missing, error::FileIOError, uuid_to_bytes};
+use sd_utils::{
+	db::{maybe_missing, size_in_bytes_to_db},
+	error::FileIOError,
+	uuid_to_bytes,
+};
 
 use std::{
 	collections::{HashMap, HashSet},
@@ -21,7 +26,6 @@ use std::{
 
 use futures::future::join_all;
 use serde_json::json;
-use tokio::fs;
 use tracing::{error, trace};
 use uuid::Uuid;
 
@@ -53,17 +57,32 @@ pub struct FileMetadata {
 }
 
 impl FileMetadata {
-	/// Assembles `create_unchecked` params for a given file path
+	/// Assembles `create_unchecked` params for a given file path.
+	///
+	/// Symbolic links are only read through when their location follows them, otherwise they're
+	/// identified as [`ObjectKind::Alias`] without a `cas_id`, with the metadata of the link.
 	pub async fn new(
 		location_path: impl AsRef<Path>,
 		iso_file_path: &IsolatedFilePathData<'_>, // TODO: use dedicated CreateUnchecked type
+		symlink_policy: SymlinkPolicy,
 	) -> Result<FileMetadata, FileIOError> {
 		let path = location_path.as_ref().join(iso_file_path);
 
-		let fs_metadata = fs::metadata(&path)
+		let fs_metadata = symlink_policy
+			.metadata(&path)
 			.await
 			.map_err(|e| FileIOError::from((&path, e)))?;
 
+		if fs_metadata.is_symlink() {
+			trace!("Analyzed symbolic link: {path:?}");
+
+			return Ok(FileMetadata {
+				cas_id: None,
+				kind: ObjectKind::Alias,
+				fs_metadata,
+			});
+		}
+
 		assert!(
 			!fs_metadata.is_dir(),
 			"We can't generate cas_id for directories"
@@ -101,6 +120,7 @@ async fn identifier_job_step(
 	file_paths: &[file_path_for_file_identifier::Data],
 ) -> Result<(usize, usize), JobError> {
 	let location_path = maybe_missing(&location.path, "location.path").map(Path::new)?;
+	let symlink_policy = SymlinkPolicy::from_db(location.symlink_policy);
 
 	let file_paths_metadatas = join_all(
 		file_paths
@@ -112,7 +132,7 @@ async fn identifier_job_step(
 					.ok()
 			})
 			.map(|(iso_file_path, file_path)| async move {
-				FileMetadata::new(&location_path, &iso_file_path)
+				FileMetadata::new(&location_path, &iso_file_path, symlink_policy)
 					.await
 					.map(|metadata| {
 						(
@@ -266,7 +286,9 @@ async fn identifier_job_step(
 					|(
 						file_path_pub_id,
 						(
-							FileMetadata { kind, .. },
+							FileMetadata {
+								kind, fs_metadata, ..
+							},
 							file_path_for_file_identifier::Data { date_created, .. },
 						),
 					)| {
@@ -276,6 +298,7 @@ async fn identifier_job_step(
 						};
 
 						let kind = *kind as i32;
+						let size = size_in_bytes_to_db(fs_metadata.len());
 
 						let (sync_params, db_params): (Vec<_>, Vec<_>) = [
 							(
@@ -286,6 +309,10 @@ async fn identifier_job_step(
 								(object::kind::NAME, json!(kind)),
 								object::kind::set(Some(kind)),
 							),
+							(
+								(object::size_in_bytes::NAME, json!(size)),
+								object::size_in_bytes::set(Some(size)),
+							),
 						]
 						.into_iter()
 						.unzip();
@@ -339,6 +366,14 @@ async fn identifier_job_step(
 			.await?;
 
 			trace!("Updated file paths with created objects");
+
+			index_new_objects_contents(
+				db,
+				location.id,
+				location_path,
+				&file_paths_requiring_new_object,
+			)
+			.await?;
 		}
 
 		total_created_files as usize
@@ -349,6 +384,52 @@ async fn identifier_job_step(
 	Ok((total_created, updated_file_paths.len()))
 }
 
+/// Feeds the content index with the text of newly created objects, objects that were just linked
+/// to more file paths already had their contents indexed when they were created
+async fn index_new_objects_contents(
+	db: &PrismaClient,
+	location_id: location::id::Type,
+	location_path: &Path,
+	file_paths: &[(Uuid, (FileMetadata, &file_path_for_file_identifier::Data))],
+) -> Result<(), JobError> {
+	let mut paths_by_pub_id = file_paths
+		.iter()
+		.filter(|(_, (FileMetadata { kind, .. }, _))| content_index::can_index_kind(*kind))
+		.filter_map(|(pub_id, (_, file_path))| {
+			IsolatedFilePathData::try_from((location_id, *file_path))
+				.map(|iso_file_path| (uuid_to_bytes(*pub_id), location_path.join(iso_file_path)))
+				.map_err(|e| error!("Failed to extract isolated file path data: {e:#?}"))
+				.ok()
+		})
+		.collect::<HashMap<_, _>>();
+
+	if paths_by_pub_id.is_empty() {
+		return Ok(());
+	}
+
+	let objects_to_index = db
+		.file_path()
+		.find_many(vec![file_path::pub_id::in_vec(
+			paths_by_pub_id.keys().cloned().collect(),
+		)])
+		.select(file_path::select!({ pub_id object_id }))
+		.exec()
+		.await?
+		.into_iter()
+		.filter_map(|file_path| {
+			file_path
+				.object_id
+				.zip(paths_by_pub_id.remove(&file_path.pub_id))
+		})
+		.collect::<Vec<_>>();
+
+	let indexed_count = content_index::index_objects(db, objects_to_index).await;
+
+	trace!("Indexed contents of {indexed_count} new Objects");
+
+	Ok(())
+}
+
 fn connect_file_path_to_object<'db>(
 	file_path_id: Uuid,
 	object_id: Uuid,
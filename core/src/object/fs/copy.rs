@@ -1,23 +1,29 @@
 use crate::{
 	invalidate_query,
 	job::{
-		CurrentStep, JobError, JobInitOutput, JobResult, JobRunErrors, JobStepOutput, StatefulJob,
-		WorkerContext,
+		CurrentStep, JobError, JobInitOutput, JobResult, JobRunErrors, JobRunMetadata,
+		JobStepOutput, StatefulJob, WorkerContext,
 	},
 	library::Library,
 };
 
-use sd_file_path_helper::{join_location_relative_path, IsolatedFilePathData};
+use sd_file_path_helper::{
+	attributes::{copy_attributes, copy_permissions},
+	join_location_relative_path, IsolatedFilePathData,
+};
 use sd_prisma::prisma::{file_path, location};
 use sd_utils::{db::maybe_missing, error::FileIOError};
 
-use std::{hash::Hash, path::PathBuf};
+use std::{
+	hash::Hash,
+	path::{Path, PathBuf},
+};
 
 use futures_concurrency::future::TryJoin;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use specta::Type;
-use tokio::{fs, io};
+use tokio::{fs, io, task::spawn_blocking};
 use tracing::{trace, warn};
 
 use super::{
@@ -39,6 +45,19 @@ pub struct FileCopierJobInit {
 	pub target_location_relative_directory_path: PathBuf,
 }
 
+/// Directories are kept writable while we copy their children, so we must give them back their
+/// permissions in the end
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct FileCopierJobRunMetadata {
+	copied_directories: Vec<(PathBuf, PathBuf)>,
+}
+
+impl JobRunMetadata for FileCopierJobRunMetadata {
+	fn update(&mut self, new_data: Self) {
+		self.copied_directories.extend(new_data.copied_directories);
+	}
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct FileCopierJobStep {
 	pub source_file_data: FileData,
@@ -49,7 +68,7 @@ pub struct FileCopierJobStep {
 impl StatefulJob for FileCopierJobInit {
 	type Data = FileCopierJobData;
 	type Step = FileCopierJobStep;
-	type RunMetadata = ();
+	type RunMetadata = FileCopierJobRunMetadata;
 
 	const NAME: &'static str = "file_copier";
 
@@ -128,6 +147,8 @@ impl StatefulJob for FileCopierJobInit {
 				.await
 				.map_err(|e| FileIOError::from((target_full_path, e)))?;
 
+			preserve_attributes(&source_file_data.full_path, target_full_path, true).await;
+
 			let mut read_dir = fs::read_dir(&source_file_data.full_path)
 				.await
 				.map_err(|e| FileIOError::from((&source_file_data.full_path, e)))?;
@@ -179,7 +200,16 @@ impl StatefulJob for FileCopierJobInit {
 				}
 			}
 
-			Ok(more_steps.into())
+			Ok((
+				more_steps,
+				FileCopierJobRunMetadata {
+					copied_directories: vec![(
+						source_file_data.full_path.clone(),
+						target_full_path.clone(),
+					)],
+				},
+			)
+				.into())
 		} else {
 			match fs::metadata(target_full_path).await {
 				Ok(_) => {
@@ -190,9 +220,12 @@ impl StatefulJob for FileCopierJobInit {
 								.await
 								// Using the ? here because we don't want to increase the completed task
 								// count in case of file system errors
-								.map_err(|e| FileIOError::from((new_path, e)))?;
+								.map_err(|e| FileIOError::from((&new_path, e)))?;
 
-							Ok(().into())
+							preserve_attributes(&source_file_data.full_path, &new_path, false)
+								.await;
+
+							Ok(None.into())
 						}
 
 						Err(FileSystemJobsError::FailedToFindAvailableName(path)) => {
@@ -218,7 +251,9 @@ impl StatefulJob for FileCopierJobInit {
 						// count in case of file system errors
 						.map_err(|e| FileIOError::from((target_full_path, e)))?;
 
-					Ok(().into())
+					preserve_attributes(&source_file_data.full_path, target_full_path, false).await;
+
+					Ok(None.into())
 				}
 				Err(e) => Err(FileIOError::from((target_full_path, e)).into()),
 			}
@@ -229,12 +264,38 @@ impl StatefulJob for FileCopierJobInit {
 		&self,
 		ctx: &WorkerContext,
 		_data: &Option<Self::Data>,
-		_run_metadata: &Self::RunMetadata,
+		run_metadata: &Self::RunMetadata,
 	) -> JobResult {
 		let init = self;
 
+		for (source, target) in &run_metadata.copied_directories {
+			if let Err(e) = copy_permissions(source, target) {
+				warn!(
+					"Failed to restore permissions of copied directory: {:#?}",
+					FileIOError::from((target, e))
+				);
+			}
+		}
+
 		invalidate_query!(ctx.library, "search.paths");
 
 		Ok(Some(json!({ "init": init })))
 	}
 }
+
+/// `fs::copy` only keeps the permission bits of files, so we also copy their owner and extended
+/// attributes, and everything for directories. The copy itself already succeeded by then, so
+/// failing to do so is only logged.
+async fn preserve_attributes(source: &Path, target: &Path, keep_writable: bool) {
+	let (source, target) = (source.to_path_buf(), target.to_path_buf());
+
+	match spawn_blocking(move || {
+		copy_attributes(&source, &target, keep_writable).map_err(|e| FileIOError::from((target, e)))
+	})
+	.await
+	{
+		Ok(Ok(())) => {}
+		Ok(Err(e)) => warn!("Failed to preserve attributes of copied file: {e:#?}"),
+		Err(e) => warn!("Failed to join attributes copy task: {e:#?}"),
+	}
+}
@@ -115,6 +115,8 @@ impl StatefulJob for FileCutterJobInit {
 						full_output.display()
 					);
 
+					// Renaming keeps the same inode, so permissions, owner and extended attributes
+					// all come along
 					fs::rename(&file_data.full_path, &full_output)
 						.await
 						.map_err(|e| FileIOError::from((&file_data.full_path, e)))?;
@@ -1,5 +1,6 @@
 pub mod media_data_extractor;
 pub mod media_processor;
+pub mod perceptual_hash;
 pub mod thumbnail;
 
 pub use media_processor::MediaProcessorJobInit;
@@ -24,6 +25,12 @@ pub fn media_data_image_to_query(
 			copyright::set(mdi.copyright),
 			exif_version::set(mdi.exif_version),
 			epoch_time::set(mdi.date_taken.map(|x| x.unix_timestamp())),
+			resolution_width::set(Some(mdi.resolution.width)),
+			resolution_height::set(Some(mdi.resolution.height)),
+			camera_make::set(mdi.camera_data.device_make),
+			camera_model::set(mdi.camera_data.device_model),
+			latitude::set(mdi.location.as_ref().map(|l| l.coordinates().0)),
+			longitude::set(mdi.location.as_ref().map(|l| l.coordinates().1)),
 		],
 	})
 }
@@ -44,6 +51,12 @@ pub fn media_data_image_to_query_params(
 			option_sync_db_entry!(mdi.description, description),
 			option_sync_db_entry!(mdi.copyright, copyright),
 			option_sync_db_entry!(mdi.exif_version, exif_version),
+			option_sync_db_entry!(Some(mdi.resolution.width), resolution_width),
+			option_sync_db_entry!(Some(mdi.resolution.height), resolution_height),
+			option_sync_db_entry!(mdi.camera_data.device_make, camera_make),
+			option_sync_db_entry!(mdi.camera_data.device_model, camera_model),
+			option_sync_db_entry!(mdi.location.as_ref().map(|l| l.coordinates().0), latitude),
+			option_sync_db_entry!(mdi.location.as_ref().map(|l| l.coordinates().1), longitude),
 		],
 	)
 	.into_iter()
@@ -0,0 +1,167 @@
+use sd_prisma::prisma::{file_path, object, PrismaClient};
+
+use image::{imageops::FilterType, DynamicImage};
+use itertools::Itertools;
+use prisma_client_rust::{not, QueryError};
+
+/// Distance up to which two images are usually the same picture, after being resized,
+/// recompressed or slightly edited
+pub const DEFAULT_MAX_DISTANCE: u32 = 10;
+
+/// How many `cas_id`s we look up in a single query
+const CAS_IDS_CHUNK_SIZE: usize = 1000;
+
+/// Computes the difference hash (dHash) of an image: it's shrunk to 9x8 grayscale pixels and
+/// each of the 64 bits tells if a pixel is brighter than its right neighbour.
+///
+/// As it only depends on the gradients of the image, it survives resizing, recompression and
+/// small color changes, so we can compute it from the thumbnails instead of the originals.
+pub fn dhash(img: &DynamicImage) -> u64 {
+	let pixels = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+
+	let mut hash = 0;
+	for y in 0..8 {
+		for x in 0..8 {
+			hash <<= 1;
+			if pixels.get_pixel(x, y)[0] > pixels.get_pixel(x + 1, y)[0] {
+				hash |= 1;
+			}
+		}
+	}
+
+	hash
+}
+
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+	(a ^ b).count_ones()
+}
+
+pub fn to_db(hash: u64) -> Vec<u8> {
+	hash.to_be_bytes().to_vec()
+}
+
+pub fn from_db(bytes: &[u8]) -> Option<u64> {
+	bytes.try_into().ok().map(u64::from_be_bytes)
+}
+
+/// Which of these `cas_id`s belong to objects that still don't have a perceptual hash
+pub async fn cas_ids_missing_hash(
+	db: &PrismaClient,
+	cas_ids: impl IntoIterator<Item = String>,
+) -> Result<Vec<String>, QueryError> {
+	db._batch(
+		cas_ids
+			.into_iter()
+			.chunks(CAS_IDS_CHUNK_SIZE)
+			.into_iter()
+			.map(|cas_ids| {
+				db.file_path()
+					.find_many(vec![
+						file_path::cas_id::in_vec(cas_ids.collect()),
+						file_path::object::is(vec![object::perceptual_hash::equals(None)]),
+					])
+					.select(file_path::select!({ cas_id }))
+			})
+			.collect::<Vec<_>>(),
+	)
+	.await
+	.map(|file_paths| {
+		file_paths
+			.into_iter()
+			.flatten()
+			.filter_map(|file_path| file_path.cas_id)
+			.collect()
+	})
+}
+
+/// Saves the hashes on the objects of each `cas_id`. They're derived from our local thumbnails,
+/// so they're written straight to the database instead of going through sync.
+pub async fn save(db: &PrismaClient, hashes: Vec<(String, u64)>) -> Result<(), QueryError> {
+	db._batch(
+		hashes
+			.into_iter()
+			.map(|(cas_id, hash)| {
+				db.object().update_many(
+					vec![object::file_paths::some(vec![file_path::cas_id::equals(
+						Some(cas_id),
+					)])],
+					vec![object::perceptual_hash::set(Some(to_db(hash)))],
+				)
+			})
+			.collect::<Vec<_>>(),
+	)
+	.await
+	.map(|_| ())
+}
+
+/// Finds the objects whose images are within `max_distance` of the image of `object_id`,
+/// the most similar first. Returns `None` if that object doesn't have a perceptual hash.
+pub async fn find_similar(
+	db: &PrismaClient,
+	object_id: object::id::Type,
+	max_distance: u32,
+) -> Result<Option<Vec<(object::id::Type, u32)>>, QueryError> {
+	let Some(target) = db
+		.object()
+		.find_unique(object::id::equals(object_id))
+		.select(object::select!({ perceptual_hash }))
+		.exec()
+		.await?
+		.and_then(|object| object.perceptual_hash)
+		.and_then(|bytes| from_db(&bytes))
+	else {
+		return Ok(None);
+	};
+
+	// Hamming distance can't be indexed by SQLite, but 8 bytes per image are cheap to scan
+	let mut similar = db
+		.object()
+		.find_many(vec![
+			not![object::id::equals(object_id)],
+			not![object::perceptual_hash::equals(None)],
+		])
+		.select(object::select!({ id perceptual_hash }))
+		.exec()
+		.await?
+		.into_iter()
+		.filter_map(|object| {
+			let distance = hamming_distance(target, from_db(&object.perceptual_hash?)?);
+			(distance <= max_distance).then_some((object.id, distance))
+		})
+		.collect::<Vec<_>>();
+
+	similar.sort_by_key(|(_, distance)| *distance);
+
+	Ok(Some(similar))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use image::{Rgb, RgbImage};
+
+	fn gradient(width: u32, height: u32, inverted: bool) -> DynamicImage {
+		DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+			let value = (x * 192 / width + y * 63 / height) as u8;
+			let value = if inverted { 255 - value } else { value };
+			Rgb([value, value / 2, 255 - value])
+		}))
+	}
+
+	#[test]
+	fn dhash_survives_resizing() {
+		let original = dhash(&gradient(640, 480, false));
+
+		assert!(hamming_distance(original, dhash(&gradient(160, 120, false))) <= 4);
+		assert!(hamming_distance(original, dhash(&gradient(640, 480, true))) > 32);
+	}
+
+	#[test]
+	fn hash_round_trips_through_db() {
+		let hash = 0x0123_4567_89ab_cdef;
+
+		assert_eq!(from_db(&to_db(hash)), Some(hash));
+		assert_eq!(from_db(&[1, 2, 3]), None);
+	}
+}
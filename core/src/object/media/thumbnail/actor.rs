@@ -321,6 +321,7 @@ impl Thumbnailer {
 				path,
 				in_background: false,
 				should_regenerate: false,
+				compute_perceptual_hash: false,
 				kind,
 			},
 			self.reporter.clone(),
@@ -1,13 +1,13 @@
-use crate::api::CoreEvent;
+use crate::{api::CoreEvent, object::media::perceptual_hash};
 
 use sd_file_ext::extensions::{DocumentExtension, ImageExtension};
 use sd_images::{format_image, scale_dimensions, ConvertableExtension};
 use sd_media_metadata::image::Orientation;
-use sd_prisma::prisma::location;
+use sd_prisma::prisma::{location, PrismaClient};
 use sd_utils::error::FileIOError;
 
 use std::{
-	collections::VecDeque,
+	collections::{HashSet, VecDeque},
 	ffi::OsString,
 	ops::Deref,
 	path::{Path, PathBuf},
@@ -92,6 +92,7 @@ pub(super) async fn batch_processor(
 		},
 		kind,
 	): (BatchToProcess, ThumbnailKind),
+	maybe_db: Option<Arc<PrismaClient>>,
 	generated_ephemeral_thumbs_file_names_tx: chan::Sender<Vec<OsString>>,
 	ProcessorControlChannels {
 		stop_rx,
@@ -145,6 +146,25 @@ pub(super) async fn batch_processor(
 		(None, None)
 	};
 
+	// Images that got their thumbnails before we had perceptual hashes get them from the
+	// existing thumbnails, so we don't need to regenerate anything
+	let (missing_hashes, maybe_hashes_tx, maybe_hashes_rx) = if let Some(db) = &maybe_db {
+		let missing_hashes =
+			perceptual_hash::cas_ids_missing_hash(db, queue.iter().map(|args| args.cas_id.clone()))
+				.await
+				.unwrap_or_else(|e| {
+					error!("Failed to fetch objects missing perceptual hashes: {e:#?}");
+					vec![]
+				})
+				.into_iter()
+				.collect::<HashSet<_>>();
+
+		let (tx, rx) = chan::bounded(batch_size);
+		(missing_hashes, Some(tx), Some(rx))
+	} else {
+		(HashSet::new(), None, None)
+	};
+
 	let maybe_stopped_tx = if let RaceOutputs::Stop(stopped_tx) = (
 		async {
 			let mut join_handles = Vec::with_capacity(batch_size);
@@ -161,12 +181,15 @@ pub(super) async fn batch_processor(
 					path,
 				} = queue.pop_front().expect("queue is not empty");
 
+				let compute_perceptual_hash = missing_hashes.contains(&cas_id);
+
 				// As we got a permit, then there is available CPU to process this thumbnail
 				join_handles.push(spawn({
 					let reporter = reporter.clone();
 					let thumbnails_directory = thumbnails_directory.as_ref().clone();
 					let report_progress_tx = batch_report_progress_tx.clone();
 					let maybe_cas_ids_tx = maybe_cas_ids_tx.clone();
+					let maybe_hashes_tx = maybe_hashes_tx.clone();
 
 					async move {
 						let res = timeout(THIRTY_SECS, async {
@@ -178,15 +201,23 @@ pub(super) async fn batch_processor(
 									path: &path,
 									in_background,
 									should_regenerate,
+									compute_perceptual_hash,
 									kind,
 								},
 								reporter,
 							)
 							.await
-							.map(|cas_id| {
-								// this send_blocking never blocks as we have a bounded channel with
+							.map(|(cas_id, maybe_hash)| {
+								// these send_blocking never block as we have bounded channels with
 								// the same capacity as the batch size, so there is always a space
 								// in the queue
+								if let (Some(hashes_tx), Some(hash)) = (maybe_hashes_tx, maybe_hash)
+								{
+									if hashes_tx.send_blocking((cas_id.clone(), hash)).is_err() {
+										warn!("No one to listen to generated perceptual hash");
+									}
+								}
+
 								if let Some(cas_ids_tx) = maybe_cas_ids_tx {
 									if cas_ids_tx
 										.send_blocking(OsString::from(format!("{}.webp", cas_id)))
@@ -236,6 +267,10 @@ pub(super) async fn batch_processor(
 				cas_ids_tx.close();
 			}
 
+			if let Some(hashes_tx) = &maybe_hashes_tx {
+				hashes_tx.close();
+			}
+
 			trace!("Processed batch with {batch_size} thumbnails");
 
 			RaceOutputs::Processed
@@ -280,6 +315,10 @@ pub(super) async fn batch_processor(
 			cas_ids_tx.close();
 		}
 
+		if let Some(hashes_tx) = &maybe_hashes_tx {
+			hashes_tx.close();
+		}
+
 		Some(stopped_tx)
 	} else {
 		None
@@ -295,6 +334,15 @@ pub(super) async fn batch_processor(
 		}
 	}
 
+	if let (Some(db), Some(hashes_rx)) = (maybe_db, maybe_hashes_rx) {
+		let hashes = hashes_rx.collect::<Vec<_>>().await;
+		if !hashes.is_empty() {
+			if let Err(e) = perceptual_hash::save(&db, hashes).await {
+				error!("Failed to save perceptual hashes: {e:#?}");
+			}
+		}
+	}
+
 	if let Some(stopped_tx) = maybe_stopped_tx {
 		stopped_tx.send(()).ok();
 	} else {
@@ -310,6 +358,8 @@ pub(super) struct ThumbData<'ext, P: AsRef<Path>> {
 	pub path: P,
 	pub in_background: bool,
 	pub should_regenerate: bool,
+	/// Only used for images, as other kinds of thumbnails don't tell much about their contents
+	pub compute_perceptual_hash: bool,
 	pub kind: ThumbnailKind,
 }
 
@@ -321,10 +371,11 @@ pub(super) async fn generate_thumbnail(
 		path,
 		in_background,
 		should_regenerate,
+		compute_perceptual_hash,
 		kind,
 	}: ThumbData<'_, impl AsRef<Path>>,
 	reporter: broadcast::Sender<CoreEvent>,
-) -> Result<String, ThumbnailerError> {
+) -> Result<(String, Option<u64>), ThumbnailerError> {
 	let path = path.as_ref();
 	trace!("Generating thumbnail for {}", path.display());
 
@@ -349,12 +400,25 @@ pub(super) async fn generate_thumbnail(
 			"Skipping thumbnail generation for {} because it already exists",
 			path.display()
 		);
-		return Ok(cas_id);
+
+		let maybe_hash = if compute_perceptual_hash && ImageExtension::from_str(extension).is_ok() {
+			hash_existing_thumbnail(&output_path)
+				.await
+				.map_err(|e| error!("Failed to compute perceptual hash from thumbnail: {e:#?}"))
+				.ok()
+		} else {
+			None
+		};
+
+		return Ok((cas_id, maybe_hash));
 	}
 
+	let mut maybe_hash = None;
+
 	if let Ok(extension) = ImageExtension::from_str(extension) {
 		if can_generate_thumbnail_for_image(&extension) {
-			generate_image_thumbnail(&path, &output_path).await?;
+			let hash = generate_image_thumbnail(&path, &output_path).await?;
+			maybe_hash = compute_perceptual_hash.then_some(hash);
 		}
 	} else if let Ok(extension) = DocumentExtension::from_str(extension) {
 		if can_generate_thumbnail_for_document(&extension) {
@@ -388,16 +452,18 @@ pub(super) async fn generate_thumbnail(
 
 	trace!("Generated thumbnail for {}", path.display());
 
-	Ok(cas_id)
+	Ok((cas_id, maybe_hash))
 }
 
+/// Generates the thumbnail and returns the perceptual hash of the image, as it's cheap to compute
+/// while we already have the decoded image at hand
 async fn generate_image_thumbnail(
 	file_path: impl AsRef<Path>,
 	output_path: impl AsRef<Path>,
-) -> Result<(), ThumbnailerError> {
+) -> Result<u64, ThumbnailerError> {
 	let file_path = file_path.as_ref().to_path_buf();
 
-	let webp = spawn_blocking(move || -> Result<_, ThumbnailerError> {
+	let (webp, hash) = spawn_blocking(move || -> Result<_, ThumbnailerError> {
 		let mut img = format_image(&file_path).map_err(|e| ThumbnailerError::SdImages {
 			path: file_path.clone().into_boxed_path(),
 			error: e,
@@ -437,7 +503,10 @@ async fn generate_image_thumbnail(
 		// Type WebPMemory is !Send, which makes the Future in this function !Send,
 		// this make us `deref` to have a `&[u8]` and then `to_owned` to make a Vec<u8>
 		// which implies on a unwanted clone...
-		Ok(encoder.encode(TARGET_QUALITY).deref().to_owned())
+		Ok((
+			encoder.encode(TARGET_QUALITY).deref().to_owned(),
+			perceptual_hash::dhash(&img),
+		))
 	})
 	.await??;
 
@@ -456,8 +525,27 @@ async fn generate_image_thumbnail(
 
 	fs::write(output_path, &webp)
 		.await
-		.map_err(|e| FileIOError::from((output_path, e)))
-		.map_err(Into::into)
+		.map_err(|e| FileIOError::from((output_path, e)))?;
+
+	Ok(hash)
+}
+
+/// The thumbnail is already a resized and correctly oriented copy of the image,
+/// so it's enough to get the perceptual hash without decoding the original again
+async fn hash_existing_thumbnail(
+	thumbnail_path: impl AsRef<Path>,
+) -> Result<u64, ThumbnailerError> {
+	let thumbnail_path = thumbnail_path.as_ref().to_path_buf();
+
+	spawn_blocking(move || {
+		format_image(&thumbnail_path)
+			.map(|img| perceptual_hash::dhash(&img))
+			.map_err(|e| ThumbnailerError::SdImages {
+				path: thumbnail_path.into_boxed_path(),
+				error: e,
+			})
+	})
+	.await?
 }
 
 #[cfg(feature = "ffmpeg")]
@@ -144,9 +144,17 @@ pub(super) async fn worker(
 						continue;
 					};
 
+					// Only indexed thumbnails have objects to store perceptual hashes on
+					let maybe_db = if let ThumbnailKind::Indexed(library_id) = &batch_and_kind.1 {
+						databases.get(library_id).cloned()
+					} else {
+						None
+					};
+
 					spawn(batch_processor(
 						thumbnails_directory.clone(),
 						batch_and_kind,
+						maybe_db,
 						generated_ephemeral_thumbnails_tx.clone(),
 						ProcessorControlChannels {
 							stop_rx: stop_older_processing_rx.clone(),
@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 use specta::Type;
 
 pub mod cas;
+pub mod content_index;
 pub mod file_identifier;
 pub mod fs;
 pub mod media;
@@ -38,6 +38,9 @@ pub struct ObjectValidatorJobData {
 pub struct ObjectValidatorJobInit {
 	pub location: location::Data,
 	pub sub_path: Option<PathBuf>,
+	/// Only validates these file paths of the location, like the copies of possible duplicates
+	#[serde(default)]
+	pub file_path_ids: Option<Vec<file_path::id::Type>>,
 }
 
 impl Hash for ObjectValidatorJobInit {
@@ -46,6 +49,9 @@ impl Hash for ObjectValidatorJobInit {
 		if let Some(ref sub_path) = self.sub_path {
 			sub_path.hash(state);
 		}
+		if let Some(ref file_path_ids) = self.file_path_ids {
+			file_path_ids.hash(state);
+		}
 	}
 }
 
@@ -112,11 +118,14 @@ impl StatefulJob for ObjectValidatorJobInit {
 					file_path::is_dir::equals(Some(false)),
 					file_path::integrity_checksum::equals(None),
 				],
-				[maybe_sub_iso_file_path.and_then(|iso_sub_path| {
-					iso_sub_path
-						.materialized_path_for_children()
-						.map(file_path::materialized_path::starts_with)
-				})],
+				[
+					maybe_sub_iso_file_path.and_then(|iso_sub_path| {
+						iso_sub_path
+							.materialized_path_for_children()
+							.map(file_path::materialized_path::starts_with)
+					}),
+					init.file_path_ids.clone().map(file_path::id::in_vec),
+				],
 			))
 			.select(file_path_for_object_validator::select())
 			.exec()
@@ -178,7 +178,7 @@ impl InitConfig {
 				.create(node, &library)
 				.await?
 				{
-					scan_location(node, &library, location).await?;
+					scan_location(node, &library, location, false).await?;
 				} else {
 					warn!(
 						"Debug init error: location '{}' was not found after being created!",
@@ -0,0 +1,391 @@
+//! Volumes are saved in the database of every library, so we know which volume each location of
+//! this node is on, even while it's unplugged. They're recognized by [`Volume::identifier`], as
+//! their names and mount points may change every time they're plugged in.
+
+use crate::{
+	invalidate_query,
+	job::Job,
+	library::Library,
+	location::{relink_location, relinker::LocationRelinkerJobInit},
+	Node,
+};
+
+use sd_prisma::prisma::{location, volume};
+use sd_utils::from_bytes_to_uuid;
+
+use std::{
+	collections::HashMap,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+
+use chrono::Utc;
+use tracing::{debug, error, info, warn};
+
+use super::{Volume, VolumeError};
+
+/// A saved volume that's mounted right now
+#[derive(Debug)]
+struct MountedVolume {
+	identifier: String,
+	mount_points: Vec<PathBuf>,
+	/// Where the volume was mounted before, if it's now mounted somewhere else
+	previous_mount_point: Option<PathBuf>,
+	/// Whether it wasn't mounted the last time we looked
+	newly_mounted: bool,
+	is_system: bool,
+}
+
+/// Saves the volumes of this node to the library, then brings its locations up to date with them:
+/// locations get bound to the volume they're on, locations on volumes that are gone are marked
+/// offline, and locations on volumes mounted somewhere else are re-pointed to their new path.
+pub(super) async fn update_library(node: &Arc<Node>, library: &Arc<Library>, volumes: &[Volume]) {
+	let mounted = match save_volumes(library, volumes).await {
+		Ok(mounted) => mounted,
+		Err(e) => {
+			error!("Failed to save volumes to the library: {e:#?}");
+			return;
+		}
+	};
+
+	if let Err(e) = update_locations(node, library, volumes, &mounted).await {
+		error!("Failed to update the locations of the library with its volumes: {e:#?}");
+	}
+
+	// Locations moved onto another volume are found by their metadata files instead, and only
+	// proposed to the user to relink. We leave system volumes out, as searching them takes a
+	// while and nothing was plugged in there
+	let roots = mounted
+		.into_values()
+		.filter(|volume| volume.newly_mounted && !volume.is_system)
+		.flat_map(|volume| volume.mount_points)
+		.collect::<Vec<_>>();
+
+	if !roots.is_empty() {
+		if let Err(e) = find_moved_locations(node, library, roots).await {
+			error!("Failed to search newly mounted volumes for moved locations: {e:#?}");
+		}
+	}
+}
+
+async fn save_volumes(
+	library: &Library,
+	volumes: &[Volume],
+) -> Result<HashMap<volume::id::Type, MountedVolume>, VolumeError> {
+	let db = &library.db;
+
+	let mut saved = db
+		.volume()
+		.find_many(vec![])
+		.exec()
+		.await?
+		.into_iter()
+		.filter_map(|volume| {
+			volume
+				.identifier
+				.clone()
+				.map(|identifier| (identifier, volume))
+		})
+		.collect::<HashMap<_, _>>();
+
+	let mut mounted = HashMap::with_capacity(volumes.len());
+
+	for volume in volumes {
+		// Without an identifier we couldn't tell it apart from the next volume mounted there
+		let Some(identifier) = volume.identifier() else {
+			continue;
+		};
+
+		let Some(mount_point) = volume
+			.mount_points
+			.first()
+			.and_then(|mount_point| mount_point.to_str())
+		else {
+			warn!(
+				"Skipping volume without a valid mount point: {}",
+				volume.name
+			);
+			continue;
+		};
+
+		let params = vec![
+			volume::total_bytes_capacity::set(volume.total_capacity.to_string()),
+			volume::total_bytes_available::set(volume.available_capacity.to_string()),
+			volume::disk_type::set(Some(volume.disk_type.to_string())),
+			volume::filesystem::set(volume.file_system.clone()),
+			volume::is_system::set(volume.is_root_filesystem),
+			volume::is_mounted::set(true),
+			volume::date_modified::set(Utc::now().into()),
+		];
+
+		let (id, previous_mount_point, newly_mounted) = match saved.remove(&identifier) {
+			Some(saved_volume) => {
+				db.volume()
+					.update(
+						volume::id::equals(saved_volume.id),
+						[
+							vec![
+								volume::name::set(volume.name.clone()),
+								volume::mount_point::set(mount_point.to_string()),
+							],
+							params,
+						]
+						.concat(),
+					)
+					.exec()
+					.await?;
+
+				(
+					saved_volume.id,
+					(saved_volume.mount_point != mount_point)
+						.then(|| PathBuf::from(saved_volume.mount_point)),
+					!saved_volume.is_mounted,
+				)
+			}
+
+			None => {
+				let created = db
+					.volume()
+					.create(
+						volume.name.clone(),
+						mount_point.to_string(),
+						[
+							vec![volume::identifier::set(Some(identifier.clone()))],
+							params,
+						]
+						.concat(),
+					)
+					.exec()
+					.await?;
+
+				debug!("Saved new volume <id='{}'>: {}", created.id, volume.name);
+
+				(created.id, None, true)
+			}
+		};
+
+		mounted.insert(
+			id,
+			MountedVolume {
+				identifier,
+				mount_points: volume.mount_points.clone(),
+				previous_mount_point,
+				newly_mounted,
+				is_system: volume.is_root_filesystem,
+			},
+		);
+	}
+
+	// Whatever we didn't find isn't mounted anymore
+	let unmounted = saved
+		.into_values()
+		.filter(|volume| volume.is_mounted)
+		.map(|volume| volume.id)
+		.collect::<Vec<_>>();
+
+	if !unmounted.is_empty() {
+		db.volume()
+			.update_many(
+				vec![volume::id::in_vec(unmounted)],
+				vec![volume::is_mounted::set(false)],
+			)
+			.exec()
+			.await?;
+	}
+
+	Ok(mounted)
+}
+
+async fn update_locations(
+	node: &Arc<Node>,
+	library: &Arc<Library>,
+	volumes: &[Volume],
+	mounted: &HashMap<volume::id::Type, MountedVolume>,
+) -> Result<(), VolumeError> {
+	let instance_id = library.config().await.instance_id;
+
+	// TODO(N): This isn't gonna work with removable media and this will likely permanently break if the DB is restored from a backup.
+	let locations = library
+		.db
+		.location()
+		.find_many(vec![location::instance_id::equals(Some(instance_id))])
+		.exec()
+		.await?;
+
+	for location in locations {
+		let Some(path) = location.path.as_deref().map(Path::new) else {
+			continue;
+		};
+
+		let Some(volume_id) = location.volume_id else {
+			if let Some(volume_id) = volume_of(path, volumes, mounted) {
+				library
+					.db
+					.location()
+					.update(
+						location::id::equals(location.id),
+						vec![location::volume_id::set(Some(volume_id))],
+					)
+					.exec()
+					.await?;
+
+				debug!(
+					"Bound location <id='{}'> to volume <id='{volume_id}'>",
+					location.id
+				);
+			}
+
+			continue;
+		};
+
+		let Some(volume) = mounted.get(&volume_id) else {
+			// Its path may still be there, as the empty directory where the volume was mounted
+			let pub_id = from_bytes_to_uuid(&location.pub_id);
+			if node.locations.is_online(&pub_id).await {
+				node.locations.remove_online(&pub_id).await;
+				info!(
+					"Location <id='{}'> is offline, as its volume is gone",
+					location.id
+				);
+			}
+
+			continue;
+		};
+
+		if volume
+			.mount_points
+			.iter()
+			.any(|mount_point| path.starts_with(mount_point))
+		{
+			continue;
+		}
+
+		// The volume was mounted somewhere else
+		let (Some(previous_mount_point), Some(mount_point)) =
+			(&volume.previous_mount_point, volume.mount_points.first())
+		else {
+			continue;
+		};
+
+		if let Ok(relative_path) = path.strip_prefix(previous_mount_point) {
+			repoint_location(location.id, mount_point.join(relative_path), node, library).await;
+		}
+	}
+
+	Ok(())
+}
+
+/// Queues a search of `roots` for the offline locations of this node, only proposing to relink
+/// the ones found, as they may be backup copies of locations on drives unplugged right now
+async fn find_moved_locations(
+	node: &Arc<Node>,
+	library: &Arc<Library>,
+	roots: Vec<PathBuf>,
+) -> Result<(), VolumeError> {
+	let instance_id = library.config().await.instance_id;
+
+	let locations = library
+		.db
+		.location()
+		.find_many(vec![location::instance_id::equals(Some(instance_id))])
+		.select(location::select!({ pub_id }))
+		.exec()
+		.await?;
+
+	let mut has_offline_locations = false;
+	for location in locations {
+		if !node
+			.locations
+			.is_online(&from_bytes_to_uuid(&location.pub_id))
+			.await
+		{
+			has_offline_locations = true;
+			break;
+		}
+	}
+
+	if !has_offline_locations {
+		return Ok(());
+	}
+
+	debug!("Searching newly mounted volumes for moved locations: {roots:?}");
+
+	if let Err(e) = Job::new(LocationRelinkerJobInit {
+		roots,
+		max_depth: None,
+		relink: false,
+	})
+	.spawn(node, library)
+	.await
+	{
+		error!("Failed to queue search for moved locations: {e:#?}");
+	}
+
+	Ok(())
+}
+
+/// The saved volume where `path` is, going by the most specific mount point containing it among
+/// all volumes, so we don't bind locations on unsaved volumes to the volumes they're mounted in
+fn volume_of(
+	path: &Path,
+	volumes: &[Volume],
+	mounted: &HashMap<volume::id::Type, MountedVolume>,
+) -> Option<volume::id::Type> {
+	let identifier = volumes
+		.iter()
+		.flat_map(|volume| {
+			volume
+				.mount_points
+				.iter()
+				.map(move |mount_point| (volume, mount_point))
+		})
+		.filter(|(_, mount_point)| path.starts_with(mount_point))
+		.max_by_key(|(_, mount_point)| mount_point.components().count())
+		.and_then(|(volume, _)| volume.identifier())?;
+
+	mounted
+		.iter()
+		.find(|(_, volume)| volume.identifier == identifier)
+		.map(|(id, _)| *id)
+}
+
+/// Relinks the location through the metadata file at its new path, which also makes sure it's
+/// the same location, and restarts its watcher there
+async fn repoint_location(
+	location_id: location::id::Type,
+	new_path: PathBuf,
+	node: &Arc<Node>,
+	library: &Arc<Library>,
+) {
+	match relink_location(library, &new_path).await {
+		Ok(relinked_id) => {
+			if relinked_id != location_id {
+				warn!(
+					"Found another location where location <id='{location_id}'> was expected: \
+					<path='{}', found_id='{relinked_id}'>",
+					new_path.display()
+				);
+			} else {
+				info!(
+					"Location <id='{location_id}'> is now at {}, as its volume was mounted there",
+					new_path.display()
+				);
+			}
+
+			if let Err(e) = node.locations.remove(relinked_id, library.clone()).await {
+				error!("Failed to stop watching relinked location: {e:#?}");
+			}
+			if let Err(e) = node.locations.add(relinked_id, library.clone()).await {
+				error!("Failed to watch relinked location: {e:#?}");
+			}
+
+			invalidate_query!(library, "locations.list");
+			invalidate_query!(library, "locations.get");
+		}
+
+		Err(e) => error!(
+			"Failed to re-point location <id='{location_id}'> to {}: {e:#?}",
+			new_path.display()
+		),
+	}
+}
@@ -5,20 +5,27 @@ use sd_cache::Model;
 use std::{
 	fmt::Display,
 	hash::{Hash, Hasher},
-	path::PathBuf,
-	sync::OnceLock,
+	path::{Path, PathBuf},
 };
 
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DisplayFromStr};
 use specta::Type;
-use sysinfo::{DiskExt, System, SystemExt};
 use thiserror::Error;
-use tokio::sync::Mutex;
 use tracing::error;
 
+// sysinfo is only used where we don't read the mounts ourselves
+#[cfg(not(target_os = "linux"))]
+use std::sync::OnceLock;
+#[cfg(not(target_os = "linux"))]
+use sysinfo::{DiskExt, System, SystemExt};
+#[cfg(not(target_os = "linux"))]
+use tokio::sync::Mutex;
+
+mod library;
 pub mod watcher;
 
+#[cfg(not(target_os = "linux"))]
 fn sys_guard() -> &'static Mutex<System> {
 	static SYS: OnceLock<Mutex<System>> = OnceLock::new();
 	SYS.get_or_init(|| Mutex::new(System::new_all()))
@@ -56,6 +63,28 @@ pub struct Volume {
 	pub disk_type: DiskType,
 	pub file_system: Option<String>,
 	pub is_root_filesystem: bool,
+	/// The UUID of the file system, when the OS gives us one
+	pub uuid: Option<String>,
+	/// The label of the file system, when it has one
+	pub label: Option<String>,
+	/// The serial number of the device on Linux, or of the volume on Windows, for file systems
+	/// without a UUID
+	pub serial: Option<String>,
+}
+
+impl Volume {
+	/// How we recognize the volume wherever it's mounted, if we can. Only Linux, macOS and
+	/// Windows tell us, so elsewhere volumes are only known by their mount points.
+	pub fn identifier(&self) -> Option<String> {
+		self.uuid
+			.as_ref()
+			.map(|uuid| format!("uuid:{uuid}"))
+			.or_else(|| {
+				self.serial
+					.as_ref()
+					.map(|serial| format!("serial:{serial}"))
+			})
+	}
 }
 
 impl Model for Volume {
@@ -107,69 +136,58 @@ impl From<VolumeError> for rspc::Error {
 
 #[cfg(target_os = "linux")]
 pub async fn get_volumes() -> Vec<Volume> {
-	use std::{collections::HashMap, path::Path};
+	use std::collections::HashMap;
 
-	let mut sys = sys_guard().lock().await;
-	sys.refresh_disks_list();
+	let mounts = match tokio::fs::read_to_string("/proc/self/mountinfo").await {
+		Ok(mount_info) => parse_mount_info(&mount_info),
+		Err(e) => {
+			error!("Failed to read /proc/self/mountinfo: {e:#?}");
+			return vec![];
+		}
+	};
+
+	let uuids = read_device_links("/dev/disk/by-uuid").await;
+	let labels = read_device_links("/dev/disk/by-label").await;
+	let serials = read_device_links("/dev/disk/by-id").await;
 
 	let mut volumes: Vec<Volume> = Vec::new();
-	let mut path_to_volume_index = HashMap::new();
-	for disk in sys.disks() {
-		let disk_name = disk.name();
-		let mount_point = disk.mount_point().to_path_buf();
-		let file_system = String::from_utf8(disk.file_system().to_vec())
-			.map(|s| s.to_uppercase())
-			.ok();
-		let total_capacity = disk.total_space();
-		let available_capacity = disk.available_space();
-		let is_root_filesystem = mount_point.is_absolute() && mount_point.parent().is_none();
+	let mut device_to_volume_index = HashMap::new();
+	for mount in mounts {
+		let is_zfs = mount.file_system == "zfs";
 
-		let mut disk_path: PathBuf = PathBuf::from(disk_name);
-		if file_system.as_ref().map(|fs| fs == "ZFS").unwrap_or(false) {
-			// Use a custom path for ZFS disks to avoid conflicts with normal disks paths
-			disk_path = Path::new("zfs://").join(disk_path);
+		// Ignore non-devices mounts (overlay, fuse, tmpfs, etc.), and the images of snaps
+		if !(is_zfs || mount.source.starts_with("/dev")) || mount.file_system == "squashfs" {
+			continue;
+		}
+
+		let block_device = if is_zfs {
+			None
 		} else {
-			// Ignore non-devices disks (overlay, fuse, tmpfs, etc.)
-			if !disk_path.starts_with("/dev") {
-				continue;
-			}
+			block_device_name(&mount).await
+		};
 
-			// Ensure disk has a valid device path
-			let real_path = match tokio::fs::canonicalize(disk_name).await {
-				Err(real_path) => {
-					error!(
-						"Failed to canonicalize disk path {}: {:#?}",
-						disk_name.to_string_lossy(),
-						real_path
-					);
-					continue;
-				}
-				Ok(real_path) => real_path,
-			};
-
-			// Check if disk is a symlink to another disk
-			if real_path != disk_path {
-				// Disk is a symlink to another disk, assign it to the same volume
-				path_to_volume_index.insert(
-					real_path.into_os_string(),
-					path_to_volume_index
-						.get(disk_name)
-						.cloned()
-						.unwrap_or(path_to_volume_index.len()),
-				);
-			}
-		}
+		// Mounts of the same device, like bind mounts or btrfs subvolumes, are the same volume
+		let device = match &block_device {
+			Some(block_device) => block_device.clone(),
+			// Use a custom key for ZFS datasets to avoid conflicts with normal disks
+			None if is_zfs => format!("zfs://{}", mount.source),
+			None => mount.source.clone(),
+		};
+
+		let (total_capacity, available_capacity) =
+			capacity_of(&mount.mount_point).unwrap_or_default();
+		let is_root_filesystem = mount.mount_point == Path::new("/");
 
-		if let Some(volume_index) = path_to_volume_index.get(disk_name) {
-			// Disk already has a volume assigned, update it
+		if let Some(volume_index) = device_to_volume_index.get(&device) {
+			// Device already has a volume assigned, update it
 			let volume: &mut Volume = volumes
 				.get_mut(*volume_index)
 				.expect("Volume index is present so the Volume must be present too");
 
 			// Update mount point if not already present
 			let mount_points = &mut volume.mount_points;
-			if mount_point.iter().all(|p| *p != mount_point) {
-				mount_points.push(mount_point);
+			if mount_points.iter().all(|p| *p != mount.mount_point) {
+				mount_points.push(mount.mount_point);
 				let mount_points_to_check = mount_points.clone();
 				mount_points.retain(|candidate| {
 					!mount_points_to_check
@@ -194,36 +212,207 @@ pub async fn get_volumes() -> Vec<Volume> {
 			continue;
 		}
 
-		// Assign volume to disk path
-		path_to_volume_index.insert(disk_path.into_os_string(), volumes.len());
+		let (is_removable, is_rotational) = match &block_device {
+			Some(block_device) => block_device_flags(block_device).await,
+			None => (None, None),
+		};
 
-		let mut name = disk_name.to_string_lossy().to_string();
+		let mut name = mount.source;
 		if name.replace(char::REPLACEMENT_CHARACTER, "") == "" {
 			name = "Unknown".to_string()
 		}
 
+		device_to_volume_index.insert(device, volumes.len());
+
 		volumes.push(Volume {
 			name,
-			disk_type: if disk.is_removable() {
-				DiskType::Removable
-			} else {
-				match disk.kind() {
-					sysinfo::DiskKind::SSD => DiskType::SSD,
-					sysinfo::DiskKind::HDD => DiskType::HDD,
-					_ => DiskType::Removable,
-				}
-			},
-			file_system,
-			mount_points: vec![mount_point],
+			mount_points: vec![mount.mount_point],
 			total_capacity,
 			available_capacity,
+			disk_type: match (is_removable, is_rotational) {
+				(Some(true), _) => DiskType::Removable,
+				(_, Some(false)) => DiskType::SSD,
+				(_, Some(true)) => DiskType::HDD,
+				_ => DiskType::Removable,
+			},
+			file_system: Some(mount.file_system.to_uppercase()),
 			is_root_filesystem,
+			uuid: block_device
+				.as_ref()
+				.and_then(|block_device| uuids.get(block_device).cloned()),
+			label: block_device
+				.as_ref()
+				.and_then(|block_device| labels.get(block_device).cloned()),
+			serial: block_device.and_then(|block_device| serials.get(&block_device).cloned()),
 		});
 	}
 
 	volumes
 }
 
+/// The kernel name of the block device behind a mount, like `sda1`, `nvme0n1p2` or `dm-0`
+#[cfg(target_os = "linux")]
+async fn block_device_name(mount: &MountInfo) -> Option<String> {
+	use tokio::fs;
+
+	// Device paths are often links, like the ones in `/dev/mapper` and `/dev/disk`
+	if let Ok(device_path) = fs::canonicalize(&mount.source).await {
+		if let Some(name) = device_path.file_name().and_then(|name| name.to_str()) {
+			if fs::metadata(Path::new("/sys/class/block").join(name))
+				.await
+				.is_ok()
+			{
+				return Some(name.to_string());
+			}
+		}
+	}
+
+	// Sources like `/dev/root` don't exist, so we go by the device numbers, which btrfs doesn't
+	// report faithfully, and that's why they come second
+	fs::canonicalize(Path::new("/sys/dev/block").join(&mount.device))
+		.await
+		.ok()?
+		.file_name()
+		.and_then(|name| name.to_str())
+		.map(str::to_string)
+}
+
+/// Whether the disk of a block device is removable and rotational, as told by `/sys/block`
+#[cfg(target_os = "linux")]
+async fn block_device_flags(block_device: &str) -> (Option<bool>, Option<bool>) {
+	use tokio::fs;
+
+	async fn read_flag(path: PathBuf) -> Option<bool> {
+		match fs::read_to_string(path).await.ok()?.trim() {
+			"0" => Some(false),
+			"1" => Some(true),
+			_ => None,
+		}
+	}
+
+	let Ok(device_path) = fs::canonicalize(Path::new("/sys/class/block").join(block_device)).await
+	else {
+		return (None, None);
+	};
+
+	// Partitions are kept in the directory of their disk, which is the one with the flags
+	let disk_path = if fs::metadata(device_path.join("partition")).await.is_ok() {
+		device_path.parent().map(Path::to_path_buf)
+	} else {
+		Some(device_path)
+	};
+
+	let Some(disk) = disk_path.as_deref().and_then(Path::file_name) else {
+		return (None, None);
+	};
+
+	let disk_path = Path::new("/sys/block").join(disk);
+
+	(
+		read_flag(disk_path.join("removable")).await,
+		read_flag(disk_path.join("queue/rotational")).await,
+	)
+}
+
+/// Maps the kernel names of block devices to the names of their links in a `/dev/disk` directory,
+/// like their file system UUIDs in `by-uuid`, their labels in `by-label` or the serials of their
+/// devices in `by-id`. A device may have many links in `by-id`, so we keep the first by name, which
+/// doesn't change from one boot to the next.
+#[cfg(target_os = "linux")]
+async fn read_device_links(dir: impl AsRef<Path>) -> std::collections::HashMap<String, String> {
+	use std::collections::HashMap;
+
+	use tokio::fs;
+
+	let mut links = HashMap::new();
+
+	// Missing when there's no udev, or when no device has a label
+	let Ok(mut read_dir) = fs::read_dir(dir.as_ref()).await else {
+		return links;
+	};
+
+	while let Ok(Some(entry)) = read_dir.next_entry().await {
+		let Ok(device_path) = fs::canonicalize(entry.path()).await else {
+			continue;
+		};
+
+		if let (Some(link), Some(block_device)) = (
+			entry.file_name().to_str(),
+			device_path.file_name().and_then(|name| name.to_str()),
+		) {
+			let link = unescape_udev(link);
+			links
+				.entry(block_device.to_string())
+				.and_modify(|first: &mut String| {
+					if link < *first {
+						first.clone_from(&link);
+					}
+				})
+				.or_insert(link);
+		}
+	}
+
+	links
+}
+
+/// udev escapes whitespace and other unsafe characters of link names as `\xHH` sequences
+#[cfg(target_os = "linux")]
+fn unescape_udev(link: &str) -> String {
+	let mut bytes = Vec::with_capacity(link.len());
+	let mut rest = link.as_bytes();
+
+	while let Some((&byte, tail)) = rest.split_first() {
+		let escaped = (byte == b'\\' && tail.first() == Some(&b'x'))
+			.then(|| tail.get(1..3))
+			.flatten()
+			.and_then(|hex| std::str::from_utf8(hex).ok())
+			.and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+		match escaped {
+			Some(byte) => {
+				bytes.push(byte);
+				rest = &tail[3..];
+			}
+			None => {
+				bytes.push(byte);
+				rest = tail;
+			}
+		}
+	}
+
+	String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Total and available bytes of the file system mounted at `mount_point`
+#[cfg(target_os = "linux")]
+// The fields of `statvfs` are narrower than `u64` on 32 bits targets
+#[allow(clippy::unnecessary_cast)]
+fn capacity_of(mount_point: &Path) -> Option<(u64, u64)> {
+	use std::{ffi::CString, mem::MaybeUninit, os::unix::ffi::OsStrExt};
+
+	let path = CString::new(mount_point.as_os_str().as_bytes()).ok()?;
+	let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+
+	// SAFETY: `path` is a valid C string and `stat` is big enough for `statvfs` to fill it
+	if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+		error!(
+			"Failed to get the capacity of {}: {:#?}",
+			mount_point.display(),
+			std::io::Error::last_os_error()
+		);
+		return None;
+	}
+
+	// SAFETY: `statvfs` succeeded, so it filled `stat`
+	let stat = unsafe { stat.assume_init() };
+	let fragment_size = stat.f_frsize as u64;
+
+	Some((
+		stat.f_blocks as u64 * fragment_size,
+		stat.f_bavail as u64 * fragment_size,
+	))
+}
+
 #[cfg(target_os = "macos")]
 #[derive(Deserialize)]
 #[serde(rename_all = "kebab-case")]
@@ -245,6 +434,67 @@ struct HDIUtilInfo {
 	images: Vec<ImageInfo>,
 }
 
+#[cfg(target_os = "macos")]
+#[derive(Deserialize)]
+struct DiskUtilInfo {
+	#[serde(rename = "VolumeUUID")]
+	volume_uuid: Option<String>,
+	#[serde(rename = "VolumeName")]
+	volume_name: Option<String>,
+}
+
+/// The UUID and name of the volume mounted at `mount_point`, as `diskutil` sees them
+#[cfg(target_os = "macos")]
+async fn disk_util_info(mount_point: &Path) -> Option<DiskUtilInfo> {
+	let output = tokio::process::Command::new("diskutil")
+		.args(["info", "-plist"])
+		.arg(mount_point)
+		.output()
+		.await
+		.map_err(|err| error!("Failed to execute diskutil: {err:#?}"))
+		.ok()?;
+
+	if !output.status.success() {
+		error!("Command diskutil return error");
+		return None;
+	}
+
+	plist::from_bytes(&output.stdout)
+		.map_err(|err| error!("Failed to parse diskutil output: {err:#?}"))
+		.ok()
+}
+
+/// The serial number Windows gave the volume mounted at `mount_point` when formatting it
+#[cfg(windows)]
+async fn volume_serial_number(mount_point: &Path) -> Option<String> {
+	let mut caption = mount_point.to_str()?.to_string();
+
+	// Remove path separator from Disk letter
+	caption.pop();
+
+	let output = tokio::process::Command::new("cmd")
+		.args([
+			"/C",
+			&format!("wmic logicaldisk where Caption='{caption}' get VolumeSerialNumber /value"),
+		])
+		.output()
+		.await
+		.map_err(|err| error!("Failed to execute wmic: {err:#?}"))
+		.ok()?;
+
+	if !output.status.success() {
+		error!("Command wmic return error");
+		return None;
+	}
+
+	String::from_utf8_lossy(&output.stdout)
+		.lines()
+		.find_map(|line| line.trim().strip_prefix("VolumeSerialNumber="))
+		.map(str::trim)
+		.filter(|serial| !serial.is_empty())
+		.map(ToString::to_string)
+}
+
 #[cfg(not(target_os = "linux"))]
 pub async fn get_volumes() -> Vec<Volume> {
 	use futures::future;
@@ -378,6 +628,18 @@ pub async fn get_volumes() -> Vec<Volume> {
 			name = "Unknown".to_string()
 		}
 
+		#[cfg(target_os = "macos")]
+		let (uuid, label) = disk_util_info(&mount_point)
+			.await
+			.map_or((None, None), |info| (info.volume_uuid, info.volume_name));
+		#[cfg(not(target_os = "macos"))]
+		let (uuid, label) = (None, None);
+
+		#[cfg(windows)]
+		let serial = volume_serial_number(&mount_point).await;
+		#[cfg(not(windows))]
+		let serial = None;
+
 		Some(Volume {
 			name,
 			disk_type: if disk.is_removable() {
@@ -394,6 +656,9 @@ pub async fn get_volumes() -> Vec<Volume> {
 			total_capacity,
 			available_capacity,
 			is_root_filesystem,
+			uuid,
+			label,
+			serial,
 		})
 	}))
 	.await
@@ -402,40 +667,107 @@ pub async fn get_volumes() -> Vec<Volume> {
 	.collect::<Vec<Volume>>()
 }
 
-// pub async fn save_volume(library: &Library) -> Result<(), VolumeError> {
-// 	// enter all volumes associate with this client add to db
-// 	for volume in get_volumes() {
-// 		let params = vec![
-// 			disk_type::set(volume.disk_type.map(|t| t.to_string())),
-// 			filesystem::set(volume.file_system.clone()),
-// 			total_bytes_capacity::set(volume.total_capacity.to_string()),
-// 			total_bytes_available::set(volume.available_capacity.to_string()),
-// 		];
-
-// 		library
-// 			.db
-// 			.volume()
-// 			.upsert(
-// 				node_id_mount_point_name(
-// 					library.node_local_id,
-// 					volume.mount_point,
-// 					volume.name,
-// 				),
-// 				volume::create(
-// 					library.node_local_id,
-// 					volume.name,
-// 					volume.mount_point,
-// 					params.clone(),
-// 				),
-// 				params,
-// 			)
-// 			.exec()
-// 			.await?;
-// 	}
-// 	// cleanup: remove all unmodified volumes associate with this client
-
-// 	Ok(())
-// }
+/// The type of the file system where `path` is mounted, lowercased and as the OS names it,
+/// e.g. "ext4", "nfs4" or "fuse.sshfs" on Linux, and "apfs" or "smbfs" on macOS
+pub async fn file_system_of(path: impl AsRef<Path>) -> Option<String> {
+	let path = tokio::fs::canonicalize(path.as_ref())
+		.await
+		.unwrap_or_else(|_| path.as_ref().to_path_buf());
+
+	// sysinfo leaves network and FUSE mounts out on Linux, so we read the mount table ourselves
+	#[cfg(target_os = "linux")]
+	let mounts = match tokio::fs::read_to_string("/proc/self/mountinfo").await {
+		Ok(mount_info) => parse_mount_info(&mount_info)
+			.into_iter()
+			.map(|mount| (mount.mount_point, mount.file_system))
+			.collect::<Vec<_>>(),
+		Err(e) => {
+			error!("Failed to read /proc/self/mountinfo: {e:#?}");
+			return None;
+		}
+	};
+
+	#[cfg(not(target_os = "linux"))]
+	let mounts = {
+		let mut sys = sys_guard().lock().await;
+		sys.refresh_disks_list();
+
+		sys.disks()
+			.iter()
+			.map(|disk| {
+				(
+					disk.mount_point().to_path_buf(),
+					String::from_utf8_lossy(disk.file_system()).into_owned(),
+				)
+			})
+			.collect::<Vec<_>>()
+	};
+
+	mounts
+		.into_iter()
+		.filter(|(mount_point, _)| path.starts_with(mount_point))
+		.max_by_key(|(mount_point, _)| mount_point.components().count())
+		.map(|(_, file_system)| file_system.to_lowercase())
+}
+
+/// A line of `/proc/self/mountinfo`, documented at
+/// https://www.kernel.org/doc/Documentation/filesystems/proc.txt
+#[cfg(target_os = "linux")]
+#[derive(Debug, PartialEq, Eq)]
+struct MountInfo {
+	/// The `major:minor` numbers of the mounted device
+	device: String,
+	mount_point: PathBuf,
+	file_system: String,
+	/// What was mounted, usually a device path like `/dev/sda1`, or something file system specific
+	source: String,
+}
+
+#[cfg(target_os = "linux")]
+fn parse_mount_info(mount_info: &str) -> Vec<MountInfo> {
+	// Spaces, tabs, newlines and backslashes in mount points and sources are escaped as octal
+	// sequences
+	fn unescape(field: &str) -> String {
+		let mut unescaped = String::with_capacity(field.len());
+		let mut rest = field;
+
+		while let Some(idx) = rest.find('\\') {
+			unescaped.push_str(&rest[..idx]);
+			let escaped = rest.get(idx + 1..idx + 4);
+			match escaped.and_then(|code| u8::from_str_radix(code, 8).ok()) {
+				Some(byte) => {
+					unescaped.push(char::from(byte));
+					rest = &rest[idx + 4..];
+				}
+				None => {
+					unescaped.push('\\');
+					rest = &rest[idx + 1..];
+				}
+			}
+		}
+		unescaped.push_str(rest);
+
+		unescaped
+	}
+
+	mount_info
+		.lines()
+		.filter_map(|line| {
+			let fields = line.split(' ').collect::<Vec<_>>();
+			let device = fields.get(2)?;
+			let mount_point = fields.get(4)?;
+			// Optional fields go between the mount options and the separator
+			let separator = fields.iter().skip(6).position(|field| *field == "-")? + 6;
+
+			Some(MountInfo {
+				device: (*device).to_string(),
+				mount_point: PathBuf::from(unescape(mount_point)),
+				file_system: (*fields.get(separator + 1)?).to_string(),
+				source: unescape(fields.get(separator + 2)?),
+			})
+		})
+		.collect()
+}
 
 // #[test]
 // fn test_get_volumes() {
@@ -443,3 +775,48 @@ pub async fn get_volumes() -> Vec<Volume> {
 //   dbg!(&volumes);
 //   assert!(volumes.len() > 0);
 // }
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn mount_info() {
+		let mounts = parse_mount_info(
+			"22 1 8:2 / / rw,relatime shared:1 - ext4 /dev/sda2 rw\n\
+			45 22 0:40 / /mnt/my\\040share rw,relatime shared:25 master:3 - nfs4 server:/share rw\n\
+			50 22 0:45 / /home/user/remote rw,nosuid - fuse.sshfs user@host: rw\n\
+			malformed line",
+		);
+
+		assert_eq!(
+			mounts,
+			vec![
+				MountInfo {
+					device: "8:2".to_string(),
+					mount_point: PathBuf::from("/"),
+					file_system: "ext4".to_string(),
+					source: "/dev/sda2".to_string(),
+				},
+				MountInfo {
+					device: "0:40".to_string(),
+					mount_point: PathBuf::from("/mnt/my share"),
+					file_system: "nfs4".to_string(),
+					source: "server:/share".to_string(),
+				},
+				MountInfo {
+					device: "0:45".to_string(),
+					mount_point: PathBuf::from("/home/user/remote"),
+					file_system: "fuse.sshfs".to_string(),
+					source: "user@host:".to_string(),
+				},
+			]
+		);
+	}
+
+	#[test]
+	fn udev_links() {
+		assert_eq!(unescape_udev("My\\x20Drive"), "My Drive");
+		assert_eq!(unescape_udev("a\\xzz\\x2"), "a\\xzz\\x2");
+	}
+}
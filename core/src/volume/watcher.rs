@@ -1,11 +1,11 @@
-#[cfg(not(target_os = "linux"))]
-use crate::{invalidate_query, library::Library};
+use crate::{invalidate_query, library::Library, Node};
 
-#[cfg(not(target_os = "linux"))]
 use std::{collections::HashSet, sync::Arc};
 
+use super::library::update_library;
+
 #[cfg(not(target_os = "linux"))]
-pub fn spawn_volume_watcher(library: Arc<Library>) {
+pub fn spawn_volume_watcher(node: Arc<Node>, library: Arc<Library>) {
 	use tokio::{
 		spawn,
 		time::{interval, Duration},
@@ -14,15 +14,125 @@ pub fn spawn_volume_watcher(library: Arc<Library>) {
 	use super::get_volumes;
 	spawn(async move {
 		let mut interval = interval(Duration::from_secs(1));
-		let mut existing_volumes = get_volumes().await.into_iter().collect::<HashSet<_>>();
+		let volumes = get_volumes().await;
+		update_library(&node, &library, &volumes).await;
+		let mut existing_volumes = volumes.into_iter().collect::<HashSet<_>>();
 
 		loop {
 			interval.tick().await;
 
-			let current_volumes = get_volumes().await.into_iter().collect::<HashSet<_>>();
+			let volumes = get_volumes().await;
+			let current_volumes = volumes.iter().cloned().collect::<HashSet<_>>();
+
+			if existing_volumes != current_volumes {
+				existing_volumes = current_volumes;
+				update_library(&node, &library, &volumes).await;
+				invalidate_query!(&library, "volumes.list");
+			}
+		}
+	});
+}
+
+/// Changes in the mount table, shared by every library, so a single thread waits on them
+#[cfg(target_os = "linux")]
+static MOUNT_CHANGES: once_cell::sync::Lazy<tokio::sync::broadcast::Sender<()>> =
+	once_cell::sync::Lazy::new(|| {
+		use std::{fs::File, io, os::fd::AsRawFd, thread};
+
+		use tokio::sync::broadcast;
+		use tracing::error;
+
+		let (changes_tx, _) = broadcast::channel(16);
+
+		// The kernel flags `/proc/self/mountinfo` with an exceptional condition whenever the mount
+		// table changes, so instead of listing the volumes every so often, we wait on it with `poll`
+		let spawned = thread::Builder::new()
+			.name("volume-watcher".to_string())
+			.spawn({
+				let changes_tx = changes_tx.clone();
+				move || {
+					let mount_info = match File::open("/proc/self/mountinfo") {
+						Ok(mount_info) => mount_info,
+						Err(e) => {
+							error!("Failed to open /proc/self/mountinfo to watch volumes: {e:#?}");
+							return;
+						}
+					};
+
+					let mut poll_fd = libc::pollfd {
+						fd: mount_info.as_raw_fd(),
+						events: libc::POLLPRI,
+						revents: 0,
+					};
+
+					loop {
+						// SAFETY: `poll_fd` is a single `pollfd`, with a file descriptor that's kept
+						// open by `mount_info` for as long as we're polling it
+						if unsafe { libc::poll(&mut poll_fd, 1, -1) } < 0 {
+							let e = io::Error::last_os_error();
+							if e.kind() == io::ErrorKind::Interrupted {
+								continue;
+							}
+
+							error!("Failed to wait for changes in the mount table: {e:#?}");
+							return;
+						}
+
+						if poll_fd.revents & (libc::POLLPRI | libc::POLLERR) != 0 {
+							// Only fails while no library is loaded, which is fine
+							changes_tx.send(()).ok();
+						}
+					}
+				}
+			});
+
+		if let Err(e) = spawned {
+			error!("Failed to spawn the volume watcher thread: {e:#?}");
+		}
+
+		changes_tx
+	});
+
+#[cfg(target_os = "linux")]
+pub fn spawn_volume_watcher(node: Arc<Node>, library: Arc<Library>) {
+	use tokio::{
+		spawn,
+		sync::broadcast::error::{RecvError, TryRecvError},
+		time::{sleep, Duration},
+	};
+
+	use super::get_volumes;
+
+	let mut changes_rx = MOUNT_CHANGES.subscribe();
+
+	spawn(async move {
+		let volumes = get_volumes().await;
+		update_library(&node, &library, &volumes).await;
+		let mut existing_volumes = volumes.into_iter().collect::<HashSet<_>>();
+
+		// So we stop watching once the library is unloaded
+		let weak_library = Arc::downgrade(&library);
+		drop(library);
+
+		// Missed changes are still changes, we only care that the mount table changed
+		while !matches!(changes_rx.recv().await, Err(RecvError::Closed)) {
+			// Plugging a drive in usually mounts all of its partitions at once
+			sleep(Duration::from_millis(500)).await;
+			while !matches!(
+				changes_rx.try_recv(),
+				Err(TryRecvError::Empty | TryRecvError::Closed)
+			) {}
+
+			let Some(library) = weak_library.upgrade() else {
+				return;
+			};
+
+			let volumes = get_volumes().await;
+			let current_volumes = volumes.iter().cloned().collect::<HashSet<_>>();
 
 			if existing_volumes != current_volumes {
 				existing_volumes = current_volumes;
+				update_library(&node, &library, &volumes).await;
 				invalidate_query!(&library, "volumes.list");
 			}
 		}
@@ -0,0 +1,326 @@
+use std::{
+	collections::BTreeMap,
+	fs::{self, Metadata},
+	io,
+	path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// The extended attributes kept for a path, by name, with their values as lossy UTF-8 strings.
+/// Stored as a JSON object in the `xattrs` column of `file_path`s, or null when there are none.
+pub type Xattrs = BTreeMap<String, String>;
+
+/// Only the permission bits of `st_mode` are kept, as the file type is already known
+pub const PERMISSION_BITS: u32 = 0o7777;
+
+/// POSIX permission bits, owner and group of a path, which Windows doesn't have
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilePathPermissions {
+	pub mode: u32,
+	pub uid: u32,
+	pub gid: u32,
+}
+
+impl FilePathPermissions {
+	pub fn from_metadata(metadata: &Metadata) -> Option<Self> {
+		#[cfg(target_family = "unix")]
+		{
+			use std::os::unix::fs::MetadataExt;
+
+			Some(Self {
+				mode: metadata.mode() & PERMISSION_BITS,
+				uid: metadata.uid(),
+				gid: metadata.gid(),
+			})
+		}
+
+		#[cfg(target_family = "windows")]
+		{
+			let _ = metadata; // just to avoid warnings on Windows
+			None
+		}
+	}
+
+	pub fn from_db(mode: Option<i32>, uid: Option<i32>, gid: Option<i32>) -> Option<Self> {
+		Some(Self {
+			mode: mode?.try_into().ok()?,
+			uid: id_from_db(uid?),
+			gid: id_from_db(gid?),
+		})
+	}
+
+	pub fn to_db(self) -> (i32, i32, i32) {
+		// The mode never goes past `PERMISSION_BITS`, so it always fits
+		(self.mode as i32, id_to_db(self.uid), id_to_db(self.gid))
+	}
+}
+
+/// User and group ids are unsigned 32 bit integers, which we keep with the same bits in the
+/// signed `Int` columns, as 64 bit ones can't be sent to the frontend
+pub fn id_to_db(id: u32) -> i32 {
+	i32::from_ne_bytes(id.to_ne_bytes())
+}
+
+pub fn id_from_db(id: i32) -> u32 {
+	u32::from_ne_bytes(id.to_ne_bytes())
+}
+
+pub fn xattrs_to_db(xattrs: &Xattrs) -> Option<String> {
+	if xattrs.is_empty() {
+		None
+	} else {
+		serde_json::to_string(xattrs).ok()
+	}
+}
+
+pub fn xattrs_from_db(maybe_xattrs: Option<&str>) -> Xattrs {
+	maybe_xattrs
+		.and_then(|xattrs| serde_json::from_str(xattrs).ok())
+		.unwrap_or_default()
+}
+
+/// Parses the `xattr_allowlist` column of a location, a JSON array of extended attribute names,
+/// where a trailing `*` matches every attribute starting with what comes before it
+pub fn xattr_allowlist_from_db(maybe_allowlist: Option<&str>) -> Vec<String> {
+	maybe_allowlist
+		.and_then(|allowlist| serde_json::from_str(allowlist).ok())
+		.unwrap_or_default()
+}
+
+pub fn xattr_allowlist_to_db(allowlist: &[String]) -> Option<String> {
+	if allowlist.is_empty() {
+		None
+	} else {
+		serde_json::to_string(allowlist).ok()
+	}
+}
+
+fn is_allowed(name: &str, allowlist: &[String]) -> bool {
+	allowlist.iter().any(|allowed| {
+		allowed
+			.strip_suffix('*')
+			.map_or(allowed == name, |prefix| name.starts_with(prefix))
+	})
+}
+
+/// Reads the extended attributes of `path` which are in the allowlist. Symbolic links are only
+/// followed if `metadata` is of their target, so we read the same path the metadata came from.
+///
+/// Attributes we fail to read are skipped, as they're often restricted to privileged users.
+pub fn read_xattrs(path: impl AsRef<Path>, metadata: &Metadata, allowlist: &[String]) -> Xattrs {
+	#[cfg(target_family = "unix")]
+	{
+		use tracing::trace;
+
+		if allowlist.is_empty() || !xattr::SUPPORTED_PLATFORM {
+			return Xattrs::new();
+		}
+
+		let path = path.as_ref();
+		let follow = !metadata.is_symlink();
+
+		let names = if allowlist.iter().any(|allowed| allowed.ends_with('*')) {
+			match if follow {
+				xattr::list_deref(path)
+			} else {
+				xattr::list(path)
+			} {
+				Ok(names) => names
+					.filter_map(|name| name.into_string().ok())
+					.filter(|name| is_allowed(name, allowlist))
+					.collect(),
+				Err(e) => {
+					trace!(
+						"Failed to list extended attributes <path='{}'>: {e:#?}",
+						path.display()
+					);
+					return Xattrs::new();
+				}
+			}
+		} else {
+			allowlist.to_vec()
+		};
+
+		names
+			.into_iter()
+			.filter_map(|name| {
+				match if follow {
+					xattr::get_deref(path, &name)
+				} else {
+					xattr::get(path, &name)
+				} {
+					Ok(maybe_value) => maybe_value
+						.map(|value| (name, String::from_utf8_lossy(&value).into_owned())),
+					Err(e) => {
+						trace!(
+							"Failed to read extended attribute <path='{}', name='{name}'>: {e:#?}",
+							path.display()
+						);
+						None
+					}
+				}
+			})
+			.collect()
+	}
+
+	#[cfg(target_family = "windows")]
+	{
+		let _ = (path, metadata, allowlist); // just to avoid warnings on Windows
+		Xattrs::new()
+	}
+}
+
+/// Like `cp -p`, copies we couldn't give the owner of their source lose these bits, otherwise
+/// copying a setuid file would grant the privileges of whoever copied it
+#[cfg(target_family = "unix")]
+const SETUID_AND_SETGID: u32 = 0o6000;
+
+/// Gives `target` the permission bits, owner, group and every extended attribute of `source`,
+/// as far as we're allowed to, for copies to keep what the originals had.
+///
+/// Changing the owner usually needs privileges we don't have, so it's only attempted, and so are
+/// the extended attributes, as some of them are protected or not supported by the target volume.
+///
+/// With `keep_writable`, the target stays writable by its owner, as we still need to copy the
+/// children of directories into them, so [`copy_permissions`] must be called once they're done.
+pub fn copy_attributes(
+	source: impl AsRef<Path>,
+	target: impl AsRef<Path>,
+	keep_writable: bool,
+) -> io::Result<()> {
+	let (source, target) = (source.as_ref(), target.as_ref());
+
+	#[cfg(target_family = "unix")]
+	{
+		use std::os::unix::fs::{chown, MetadataExt, PermissionsExt};
+
+		use tracing::trace;
+
+		let metadata = fs::metadata(source)?;
+
+		let owner_copied = chown(target, Some(metadata.uid()), Some(metadata.gid()))
+			.map_err(|e| {
+				trace!(
+					"Failed to copy owner <source='{}', target='{}'>: {e:#?}",
+					source.display(),
+					target.display()
+				);
+			})
+			.is_ok();
+
+		let mut permissions = metadata.permissions();
+		if !owner_copied {
+			permissions.set_mode(permissions.mode() & !SETUID_AND_SETGID);
+		}
+		if keep_writable {
+			permissions.set_mode(permissions.mode() | 0o200);
+		}
+
+		// After changing the owner, as it can clear the setuid and setgid bits
+		fs::set_permissions(target, permissions)?;
+
+		if xattr::SUPPORTED_PLATFORM {
+			for name in xattr::list(source)? {
+				match xattr::get(source, &name) {
+					Ok(Some(value)) => {
+						if let Err(e) = xattr::set(target, &name, &value) {
+							trace!(
+								"Failed to copy extended attribute <target='{}', name='{}'>: {e:#?}",
+								target.display(),
+								name.to_string_lossy()
+							);
+						}
+					}
+					Ok(None) => {}
+					Err(e) => trace!(
+						"Failed to read extended attribute <source='{}', name='{}'>: {e:#?}",
+						source.display(),
+						name.to_string_lossy()
+					),
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	#[cfg(target_family = "windows")]
+	{
+		// Only the read-only flag is a permission here, which `std::fs::copy` already keeps for
+		// files, so we just copy it for directories too
+		let mut permissions = fs::metadata(source)?.permissions();
+		// Only clears the read-only flag on Windows, it doesn't make anything world writable
+		#[allow(clippy::permissions_set_readonly_false)]
+		if keep_writable {
+			permissions.set_readonly(false);
+		}
+
+		fs::set_permissions(target, permissions)
+	}
+}
+
+/// Gives `target` the permissions of `source`, like to make directories copied with
+/// [`copy_attributes`] read-only again once their children were copied into them.
+///
+/// The setuid and setgid bits are only kept if `target` has the same owner as `source`.
+pub fn copy_permissions(source: impl AsRef<Path>, target: impl AsRef<Path>) -> io::Result<()> {
+	let (source, target) = (source.as_ref(), target.as_ref());
+
+	let metadata = fs::metadata(source)?;
+	#[allow(unused_mut)] // Only mutated on unix
+	let mut permissions = metadata.permissions();
+
+	#[cfg(target_family = "unix")]
+	{
+		use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+		let target_metadata = fs::metadata(target)?;
+		if (metadata.uid(), metadata.gid()) != (target_metadata.uid(), target_metadata.gid()) {
+			permissions.set_mode(permissions.mode() & !SETUID_AND_SETGID);
+		}
+	}
+
+	fs::set_permissions(target, permissions)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_xattr_allowlist() {
+		let allowlist = xattr_allowlist_from_db(Some(r#"["user.xdg.origin.url","com.apple.*"]"#));
+
+		assert!(is_allowed("user.xdg.origin.url", &allowlist));
+		assert!(is_allowed("com.apple.quarantine", &allowlist));
+		assert!(!is_allowed("user.xdg.origin", &allowlist));
+		assert!(!is_allowed("security.selinux", &allowlist));
+
+		assert_eq!(
+			xattr_allowlist_from_db(xattr_allowlist_to_db(&allowlist).as_deref()),
+			allowlist
+		);
+		assert!(xattr_allowlist_to_db(&[]).is_none());
+	}
+
+	#[test]
+	fn test_permissions_db_round_trip() {
+		let permissions = FilePathPermissions {
+			mode: 0o4755,
+			uid: u32::MAX,
+			gid: 1000,
+		};
+
+		let (mode, uid, gid) = permissions.to_db();
+		assert_eq!(
+			FilePathPermissions::from_db(Some(mode), Some(uid), Some(gid)),
+			Some(permissions)
+		);
+		assert_eq!(
+			FilePathPermissions::from_db(None, Some(uid), Some(gid)),
+			None
+		);
+	}
+}
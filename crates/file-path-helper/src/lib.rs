@@ -10,12 +10,15 @@ use std::{
 use chrono::{DateTime, Utc};
 use prisma_client_rust::QueryError;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use thiserror::Error;
 use tokio::{fs, io};
 use tracing::error;
 
+pub mod attributes;
 pub mod isolated_file_path_data;
 
+pub use attributes::{FilePathPermissions, Xattrs};
 pub use isolated_file_path_data::{
 	join_location_relative_path, push_location_relative_path, IsolatedFilePathData,
 	IsolatedFilePathDataParts,
@@ -79,8 +82,24 @@ file_path::select!(file_path_walker {
 	extension
 	date_modified
 	inode
+	device
 	size_in_bytes_bytes
 	hidden
+	fingerprint
+	mode
+	uid
+	gid
+	xattrs
+});
+file_path::select!(file_path_to_reconcile {
+	location_id
+	materialized_path
+	is_dir
+	name
+	extension
+	date_modified
+	date_indexed
+	size_in_bytes_bytes
 });
 file_path::select!(file_path_to_handle_custom_uri {
 	pub_id
@@ -121,13 +140,21 @@ file_path::select!(file_path_to_full_path {
 // File Path includes!
 file_path::include!(file_path_with_object { object });
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FilePathMetadata {
 	pub inode: u64,
+	/// Together with the inode, identifies the file, so hard links to it can be told apart
+	#[serde(default)]
+	pub device: u64,
 	pub size_in_bytes: u64,
 	pub created_at: DateTime<Utc>,
 	pub modified_at: DateTime<Utc>,
 	pub hidden: bool,
+	#[serde(default)]
+	pub permissions: Option<FilePathPermissions>,
+	/// Only the extended attributes in the allowlist of the location
+	#[serde(default)]
+	pub xattrs: Xattrs,
 }
 
 pub fn path_is_hidden(path: impl AsRef<Path>, metadata: &Metadata) -> bool {
@@ -178,27 +205,69 @@ impl FilePathMetadata {
 	pub async fn from_path(
 		path: impl AsRef<Path>,
 		metadata: &Metadata,
+		xattr_allowlist: &[String],
 	) -> Result<Self, FilePathError> {
-		let inode = {
+		let (device, inode) = {
 			#[cfg(target_family = "unix")]
 			{
-				get_inode(metadata)
+				(get_device(metadata), get_inode(metadata))
 			}
 
 			#[cfg(target_family = "windows")]
 			{
-				get_inode_from_path(path.as_ref()).await?
+				get_device_and_inode_from_path(path.as_ref()).await?
 			}
 		};
 
 		Ok(Self {
 			inode,
+			device,
 			hidden: path_is_hidden(path.as_ref(), metadata),
 			size_in_bytes: metadata.len(),
 			created_at: metadata.created_or_now().into(),
 			modified_at: metadata.modified_or_now().into(),
+			permissions: FilePathPermissions::from_metadata(metadata),
+			xattrs: attributes::read_xattrs(path, metadata, xattr_allowlist),
 		})
 	}
+
+	/// Sync and database params setting the `mode`, `uid`, `gid` and `xattrs` columns, which are
+	/// cleared when we don't have them
+	pub fn attributes_params(
+		&self,
+	) -> Vec<((&'static str, serde_json::Value), file_path::SetParam)> {
+		use file_path::*;
+
+		let (maybe_mode, maybe_uid, maybe_gid) = self
+			.permissions
+			.map(FilePathPermissions::to_db)
+			.map_or((None, None, None), |(mode, uid, gid)| {
+				(Some(mode), Some(uid), Some(gid))
+			});
+		let maybe_xattrs = attributes::xattrs_to_db(&self.xattrs);
+
+		vec![
+			((mode::NAME, json!(maybe_mode)), mode::set(maybe_mode)),
+			((uid::NAME, json!(maybe_uid)), uid::set(maybe_uid)),
+			((gid::NAME, json!(maybe_gid)), gid::set(maybe_gid)),
+			(
+				(xattrs::NAME, json!(maybe_xattrs)),
+				xattrs::set(maybe_xattrs),
+			),
+		]
+	}
+
+	/// Checks the `mode`, `uid`, `gid` and `xattrs` columns of a `file_path` against this metadata
+	pub fn attributes_changed(
+		&self,
+		mode: Option<i32>,
+		uid: Option<i32>,
+		gid: Option<i32>,
+		xattrs: Option<&str>,
+	) -> bool {
+		FilePathPermissions::from_db(mode, uid, gid) != self.permissions
+			|| attributes::xattrs_from_db(xattrs) != self.xattrs
+	}
 }
 
 #[derive(Error, Debug)]
@@ -434,6 +503,40 @@ pub fn get_inode(metadata: &Metadata) -> u64 {
 	}
 }
 
+/// Windows gets the device along with the inode, from [`get_device_and_inode_from_path`]
+#[cfg(target_family = "unix")]
+pub fn get_device(metadata: &Metadata) -> u64 {
+	use std::os::unix::fs::MetadataExt;
+
+	metadata.dev()
+}
+
+/// The device and inode of a path, which on Windows are its volume serial number and file index
+#[allow(unused)]
+pub async fn get_device_and_inode_from_path(
+	path: impl AsRef<Path>,
+) -> Result<(u64, u64), FilePathError> {
+	#[cfg(target_family = "unix")]
+	{
+		let metadata = fs::metadata(path.as_ref())
+			.await
+			.map_err(|e| FileIOError::from((path, e)))?;
+
+		Ok((get_device(&metadata), get_inode(&metadata)))
+	}
+
+	#[cfg(target_family = "windows")]
+	{
+		use winapi_util::{file::information, Handle};
+
+		let info = Handle::from_path_any(path.as_ref())
+			.and_then(|ref handle| information(handle))
+			.map_err(|e| FileIOError::from((path, e)))?;
+
+		Ok((info.volume_serial_number(), info.file_index()))
+	}
+}
+
 #[allow(unused)]
 pub async fn get_inode_from_path(path: impl AsRef<Path>) -> Result<u64, FilePathError> {
 	#[cfg(target_family = "unix")]
@@ -65,6 +65,12 @@ pub fn inode_to_db(inode: u64) -> Vec<u8> {
 	inode.to_le_bytes().to_vec()
 }
 
+/// Numeric sizes are stored as doubles, as we can't send 64 bit integers to the frontend,
+/// they're exact up to 2^53 bytes (8 PiB) which is plenty for a single file or directory
+pub fn size_in_bytes_to_db(size: u64) -> f64 {
+	size as f64
+}
+
 #[derive(Error, Debug)]
 #[error("Missing field {0}")]
 pub struct MissingFieldError(&'static str);
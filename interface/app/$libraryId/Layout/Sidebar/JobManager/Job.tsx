@@ -5,6 +5,7 @@ import {
 	Icon,
 	Image,
 	Info,
+	MagnifyingGlass,
 	Scissors,
 	Trash
 } from '@phosphor-icons/react';
@@ -29,7 +30,8 @@ const JobIcon: Record<string, Icon> = {
 	file_copier: Copy,
 	file_deleter: Trash,
 	file_cutter: Scissors,
-	object_validator: Fingerprint
+	object_validator: Fingerprint,
+	location_relinker: MagnifyingGlass
 };
 
 function Job({ job, className, isChild, progress }: JobProps) {
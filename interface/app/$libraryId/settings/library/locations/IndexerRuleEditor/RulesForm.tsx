@@ -21,7 +21,10 @@ const ruleKinds: UnionToTuple<RuleKind> = [
 	'AcceptFilesByGlob',
 	'RejectFilesByGlob',
 	'AcceptIfChildrenDirectoriesArePresent',
-	'RejectIfChildrenDirectoriesArePresent'
+	'RejectIfChildrenDirectoriesArePresent',
+	'RejectIgnoredByGit',
+	'AcceptFilesByMetadata',
+	'RejectFilesByMetadata'
 ];
 const ruleKindEnum = z.enum(ruleKinds);
 
@@ -13,6 +13,7 @@ export type Procedures = {
         { key: "ephemeralFiles.getMediaData", input: string, result: ({ type: "Image" } & ImageMetadata) | ({ type: "Video" } & VideoMetadata) | ({ type: "Audio" } & AudioMetadata) | null } | 
         { key: "files.get", input: LibraryArgs<number>, result: { item: Reference<ObjectWithFilePaths2>; nodes: CacheNode[] } | null } | 
         { key: "files.getConvertableImageExtensions", input: never, result: string[] } | 
+        { key: "files.getHardLinks", input: LibraryArgs<number>, result: FilePath[] } | 
         { key: "files.getMediaData", input: LibraryArgs<number>, result: MediaMetadata } | 
         { key: "files.getPath", input: LibraryArgs<number>, result: string | null } | 
         { key: "invalidation.test-invalidate", input: never, result: number } | 
@@ -29,10 +30,13 @@ export type Procedures = {
         { key: "library.statistics", input: LibraryArgs<null>, result: StatisticsResponse } | 
         { key: "locations.get", input: LibraryArgs<number>, result: { item: Reference<Location>; nodes: CacheNode[] } | null } | 
         { key: "locations.getWithRules", input: LibraryArgs<number>, result: { item: Reference<LocationWithIndexerRule>; nodes: CacheNode[] } | null } | 
+        { key: "locations.indexer_rules.export", input: LibraryArgs<number[]>, result: string } | 
         { key: "locations.indexer_rules.get", input: LibraryArgs<number>, result: NormalisedResult<IndexerRule> } | 
         { key: "locations.indexer_rules.list", input: LibraryArgs<null>, result: NormalisedResults<IndexerRule> } | 
         { key: "locations.indexer_rules.listForLocation", input: LibraryArgs<number>, result: NormalisedResults<IndexerRule> } | 
+        { key: "locations.indexer_rules.parameters", input: LibraryArgs<number>, result: ([RuleKind, string[]])[] } | 
         { key: "locations.list", input: LibraryArgs<null>, result: NormalisedResults<Location> } | 
+        { key: "locations.state", input: LibraryArgs<number>, result: LocationState } | 
         { key: "locations.systemLocations", input: never, result: SystemLocations } | 
         { key: "models.image_detection.list", input: never, result: string[] } | 
         { key: "nodeState", input: never, result: NodeState } | 
@@ -42,12 +46,26 @@ export type Procedures = {
         { key: "notifications.get", input: never, result: Notification[] } | 
         { key: "p2p.state", input: never, result: JsonValue } | 
         { key: "preferences.get", input: LibraryArgs<null>, result: LibraryPreferences } | 
+        { key: "search.duplicates", input: LibraryArgs<DuplicatesArgs>, result: Duplicates } | 
+        { key: "search.federatedPaths", input: FederatedSearchArgs, result: FederatedSearchData } | 
+        { key: "search.mediaLocations", input: LibraryArgs<{ 
+/**
+ * Web map zoom level, from 0 (the whole world in a single tile) up to 22
+ */
+zoom: number; bounds?: BoundingBox | null; filters?: SearchFilterArgs[] }>, result: MediaLocationCluster[] } | 
         { key: "search.objects", input: LibraryArgs<ObjectSearchArgs>, result: SearchData<ExplorerItem> } | 
-        { key: "search.objectsCount", input: LibraryArgs<{ filters?: SearchFilterArgs[] }>, result: number } | 
+        { key: "search.objectsCount", input: LibraryArgs<{ filters?: SearchFilterArgs[]; query?: string | null }>, result: number } | 
+        { key: "search.parseQuery", input: LibraryArgs<string>, result: ParseQueryResult } | 
         { key: "search.paths", input: LibraryArgs<FilePathSearchArgs>, result: SearchData<ExplorerItem> } | 
-        { key: "search.pathsCount", input: LibraryArgs<{ filters?: SearchFilterArgs[] }>, result: number } | 
-        { key: "search.saved.get", input: LibraryArgs<number>, result: { id: number; pub_id: number[]; search: string | null; filters: string | null; name: string | null; icon: string | null; description: string | null; date_created: string | null; date_modified: string | null } | null } | 
+        { key: "search.pathsCount", input: LibraryArgs<{ filters?: SearchFilterArgs[]; query?: string | null }>, result: number } | 
+        { key: "search.saved.count", input: LibraryArgs<number>, result: number } | 
+        { key: "search.saved.get", input: LibraryArgs<number>, result: { id: number; pub_id: number[]; search: string | null; filters: string | null; query: string | null; name: string | null; icon: string | null; description: string | null; date_created: string | null; date_modified: string | null } | null } | 
         { key: "search.saved.list", input: LibraryArgs<null>, result: SavedSearch[] } | 
+        { key: "search.similarImages", input: LibraryArgs<{ objectId: number; 
+/**
+ * How many of the 64 bits of the perceptual hashes may differ
+ */
+maxDistance?: number | null }>, result: SimilarImages } | 
         { key: "sync.enabled", input: LibraryArgs<null>, result: boolean } | 
         { key: "sync.messages", input: LibraryArgs<null>, result: CRDTOperation[] } | 
         { key: "tags.get", input: LibraryArgs<number>, result: { item: Reference<Tag>; nodes: CacheNode[] } | null } | 
@@ -103,9 +121,12 @@ export type Procedures = {
         { key: "locations.addLibrary", input: LibraryArgs<LocationCreateArgs>, result: number | null } | 
         { key: "locations.create", input: LibraryArgs<LocationCreateArgs>, result: number | null } | 
         { key: "locations.delete", input: LibraryArgs<number>, result: null } | 
+        { key: "locations.findRelinks", input: LibraryArgs<LocationRelinkerJobInit>, result: null } | 
         { key: "locations.fullRescan", input: LibraryArgs<FullRescanArgs>, result: null } | 
         { key: "locations.indexer_rules.create", input: LibraryArgs<IndexerRuleCreateArgs>, result: null } | 
         { key: "locations.indexer_rules.delete", input: LibraryArgs<number>, result: null } | 
+        { key: "locations.indexer_rules.import", input: LibraryArgs<IndexerRuleSetImportArgs>, result: number[] } | 
+        { key: "locations.indexer_rules.update", input: LibraryArgs<IndexerRuleUpdateArgs>, result: null } | 
         { key: "locations.relink", input: LibraryArgs<string>, result: number } | 
         { key: "locations.subPathRescan", input: LibraryArgs<RescanArgs>, result: null } | 
         { key: "locations.update", input: LibraryArgs<LocationUpdateArgs>, result: null } | 
@@ -116,9 +137,14 @@ export type Procedures = {
         { key: "p2p.debugConnect", input: RemoteIdentity, result: string } | 
         { key: "p2p.spacedrop", input: SpacedropArgs, result: string } | 
         { key: "preferences.update", input: LibraryArgs<LibraryPreferences>, result: null } | 
-        { key: "search.saved.create", input: LibraryArgs<{ name: string; search?: string | null; filters?: string | null; description?: string | null; icon?: string | null }>, result: null } | 
+        { key: "search.saved.create", input: LibraryArgs<{ name: string; search?: string | null; filters?: string | null; 
+/**
+ * Compiled into more filters, so it must be a valid query
+ */
+query?: string | null; description?: string | null; icon?: string | null }>, result: null } | 
         { key: "search.saved.delete", input: LibraryArgs<number>, result: null } | 
         { key: "search.saved.update", input: LibraryArgs<[number, Args]>, result: null } | 
+        { key: "search.verifyDuplicates", input: LibraryArgs<DuplicatesFilters>, result: null } | 
         { key: "sync.enable", input: LibraryArgs<null>, result: null } | 
         { key: "tags.assign", input: LibraryArgs<{ targets: Target[]; tag_id: number; unassign: boolean }>, result: null } | 
         { key: "tags.create", input: LibraryArgs<TagCreateArgs>, result: Tag } | 
@@ -131,15 +157,18 @@ export type Procedures = {
         { key: "jobs.newThumbnail", input: LibraryArgs<null>, result: string[] } | 
         { key: "jobs.progress", input: LibraryArgs<null>, result: JobProgressEvent } | 
         { key: "library.actors", input: LibraryArgs<null>, result: { [key in string]: boolean } } | 
+        { key: "locations.indexer_rules.explain", input: LibraryArgs<IndexerRulesExplainArgs>, result: IndexerRulesExplainItem } | 
         { key: "locations.online", input: never, result: number[][] } | 
         { key: "locations.quickRescan", input: LibraryArgs<LightScanArgs>, result: null } | 
         { key: "notifications.listen", input: never, result: Notification } | 
         { key: "p2p.events", input: never, result: P2PEvent } | 
         { key: "search.ephemeralPaths", input: LibraryArgs<EphemeralPathSearchArgs>, result: EphemeralPathsResultItem } | 
+        { key: "search.ephemeralSearch", input: LibraryArgs<EphemeralSearchArgs>, result: EphemeralPathsResultItem } | 
+        { key: "search.saved.live", input: LibraryArgs<number>, result: SavedSearchDiff } | 
         { key: "sync.newMessage", input: LibraryArgs<null>, result: null }
 };
 
-export type Args = { search?: string | null; filters?: string | null; name?: string | null; icon?: string | null; description?: string | null }
+export type Args = { search?: string | null; filters?: string | null; query?: string | null; name?: string | null; icon?: string | null; description?: string | null }
 
 export type AudioMetadata = { duration: number | null; audio_codec: string | null }
 
@@ -152,6 +181,11 @@ export type BackendFeature = "filesOverP2P" | "cloudSync"
 
 export type Backup = ({ id: string; timestamp: string; library_id: string; library_name: string }) & { path: string }
 
+/**
+ * An area on the map, in degrees. `west` is greater than `east` when the area crosses the antimeridian.
+ */
+export type BoundingBox = { north: number; south: number; east: number; west: number }
+
 export type BuildInfo = { version: string; commit: string }
 
 export type CRDTOperation = { instance: string; timestamp: number; id: string; model: string; record_id: JsonValue; data: CRDTOperationData }
@@ -190,6 +224,11 @@ export type Composite =
  */
 "Live"
 
+/**
+ * The filters and order described by a textual query, ready to be used in `search.paths` or `search.objects`
+ */
+export type CompiledQuery = { filters: SearchFilterArgs[]; order: FilePathOrder | null }
+
 export type ConvertImageArgs = { location_id: number; file_path_id: number; delete_src: boolean; desired_extension: ConvertableExtension; quality_percentage: number | null }
 
 export type ConvertableExtension = "bmp" | "dib" | "ff" | "gif" | "ico" | "jpg" | "jpeg" | "png" | "pnm" | "qoi" | "tga" | "icb" | "vda" | "vst" | "tiff" | "tif" | "hif" | "heif" | "heifs" | "heic" | "heics" | "avif" | "avci" | "avcs" | "svg" | "svgz" | "pdf" | "webp"
@@ -208,16 +247,99 @@ export type DiskType = "SSD" | "HDD" | "Removable"
 
 export type DoubleClickAction = "openFile" | "quickPreview"
 
+export type DuplicateGroup = { objectId: number; 
+/**
+ * Size of a single copy
+ */
+sizeInBytes: number; 
+/**
+ * Space freed by keeping a single copy
+ */
+reclaimableBytes: number; items: Reference<ExplorerItem>[] }
+
+export type Duplicates = { 
+/**
+ * Groups with the most reclaimable space first
+ */
+groups: DuplicateGroup[]; 
+/**
+ * Space freed across all groups, not only the ones in this page
+ */
+reclaimableBytes: number; 
+/**
+ * Copies left out when verifying integrity, as they don't have a checksum yet
+ */
+unverified: number; nodes: CacheNode[]; 
+/**
+ * `None` when there are no more pages
+ */
+cursor: number | null }
+
+export type DuplicatesArgs = ({ 
+/**
+ * Only objects of these kinds, any kind when empty
+ */
+kinds?: number[]; 
+/**
+ * Only file paths in these locations, all locations when empty
+ */
+locations?: number[]; minSizeInBytes?: number | null }) & { take: number; 
+/**
+ * The cursor returned with the previous page
+ */
+cursor?: number | null; 
+/**
+ * `cas_id` only samples big files, so this splits copies by their `integrity_checksum`
+ * and only reports the ones that really match. Copies without one are left out, so
+ * `search.verifyDuplicates` must compute them first.
+ */
+verifyIntegrity?: boolean }
+
+export type DuplicatesFilters = { 
+/**
+ * Only objects of these kinds, any kind when empty
+ */
+kinds?: number[]; 
+/**
+ * Only file paths in these locations, all locations when empty
+ */
+locations?: number[]; minSizeInBytes?: number | null }
+
 export type EditLibraryArgs = { id: string; name: LibraryName | null; description: MaybeUndefined<string> }
 
 export type EphemeralFileSystemOps = { sources: string[]; target_dir: string }
 
+export type EphemeralFilterArgs = { name: TextMatch } | { extension: InOrNotIn<string> } | { kind: InOrNotIn<number> } | 
+/**
+ * Only applies to files, as we don't know the sizes of directories without walking them
+ */
+{ sizeInBytes: Range<number> } | { createdAt: Range<string> } | { modifiedAt: Range<string> } | { hidden: boolean }
+
 export type EphemeralPathOrder = { field: "name"; value: SortOrder } | { field: "sizeInBytes"; value: SortOrder } | { field: "dateCreated"; value: SortOrder } | { field: "dateModified"; value: SortOrder }
 
-export type EphemeralPathSearchArgs = { path: string; withHiddenFiles: boolean; order?: EphemeralPathOrder | null }
+export type EphemeralPathSearchArgs = { path: string; withHiddenFiles: boolean; order?: EphemeralPathOrder | null; 
+/**
+ * Symbolic links are shown by themselves when missing
+ */
+symlinkPolicy?: SymlinkPolicy | null }
 
 export type EphemeralPathsResultItem = { entries: Reference<ExplorerItem>[]; errors: Error[]; nodes: CacheNode[] }
 
+export type EphemeralSearchArgs = { path: string; 
+/**
+ * How many levels of directories we walk into, where 1 only searches `path` itself.
+ * Unlimited when missing.
+ */
+maxDepth?: number | null; withHiddenFiles?: boolean; filters?: EphemeralFilterArgs[]; 
+/**
+ * Applied while walking, in the same way they're applied to locations
+ */
+indexerRulesIds?: number[]; 
+/**
+ * Symbolic links are skipped when missing
+ */
+symlinkPolicy?: SymlinkPolicy | null }
+
 export type EphemeralRenameFileArgs = { kind: EphemeralRenameKind }
 
 export type EphemeralRenameKind = { One: EphemeralRenameOne } | { Many: EphemeralRenameMany }
@@ -239,6 +361,35 @@ export type ExplorerLayout = "grid" | "list" | "media"
 
 export type ExplorerSettings<TOrder> = { layoutMode: ExplorerLayout | null; gridItemSize: number | null; gridGap: number | null; mediaColumns: number | null; mediaAspectSquare: boolean | null; mediaViewWithDescendants: boolean | null; openOnDoubleClick: DoubleClickAction | null; showBytesInGridView: boolean | null; colVisibility: { [key in string]: boolean } | null; colSizes: { [key in string]: number } | null; order?: TOrder | null; showHiddenFiles?: boolean }
 
+/**
+ * How many items of each library were already returned in previous pages
+ */
+export type FederatedCursor = { [key in string]: number }
+
+export type FederatedSearchArgs = { take: number; order?: FilePathOrder | null; 
+/**
+ * Applied as is to every library, so filters by id (like locations or tags) rarely
+ * make sense here, prefer `query` which is resolved against each library
+ */
+filters?: SearchFilterArgs[]; 
+/**
+ * A textual query, compiled into more filters and an order when none was given.
+ * Libraries without the tags or labels it names are skipped
+ */
+query?: string | null; 
+/**
+ * The cursor returned with the previous page
+ */
+cursor?: FederatedCursor | null }
+
+export type FederatedSearchData = { items: FederatedSearchItem[]; nodes: CacheNode[]; 
+/**
+ * `None` when there are no more pages
+ */
+cursor: FederatedCursor | null }
+
+export type FederatedSearchItem = { libraryId: string; item: Reference<ExplorerItem> }
+
 export type Feedback = { message: string; emoji: number }
 
 export type FileCopierJobInit = { source_location_id: number; target_location_id: number; sources_file_path_ids: number[]; target_location_relative_directory_path: string }
@@ -249,21 +400,50 @@ export type FileDeleterJobInit = { location_id: number; file_path_ids: number[]
 
 export type FileEraserJobInit = { location_id: number; file_path_ids: number[]; passes: string }
 
-export type FilePath = { id: number; pub_id: number[]; is_dir: boolean | null; cas_id: string | null; integrity_checksum: string | null; location_id: number | null; materialized_path: string | null; name: string | null; extension: string | null; hidden: boolean | null; size_in_bytes: string | null; size_in_bytes_bytes: number[] | null; inode: number[] | null; object_id: number | null; key_id: number | null; date_created: string | null; date_modified: string | null; date_indexed: string | null }
+export type FilePath = { id: number; pub_id: number[]; is_dir: boolean | null; cas_id: string | null; integrity_checksum: string | null; location_id: number | null; materialized_path: string | null; name: string | null; extension: string | null; hidden: boolean | null; size_in_bytes: string | null; size_in_bytes_bytes: number[] | null; size_in_bytes_number: number | null; inode: number[] | null; device: number[] | null; fingerprint: number[] | null; object_id: number | null; key_id: number | null; hard_link_of_id: number | null; mode: number | null; uid: number | null; gid: number | null; xattrs: string | null; date_created: string | null; date_modified: string | null; date_indexed: string | null }
 
 export type FilePathCursor = { isDir: boolean; variant: FilePathCursorVariant }
 
 export type FilePathCursorVariant = "none" | { name: CursorOrderItem<string> } | { sizeInBytes: SortOrder } | { dateCreated: CursorOrderItem<string> } | { dateModified: CursorOrderItem<string> } | { dateIndexed: CursorOrderItem<string> } | { object: FilePathObjectCursor }
 
-export type FilePathFilterArgs = { locations: InOrNotIn<number> } | { path: { location_id: number; path: string; include_descendants: boolean } } | { name: TextMatch } | { extension: InOrNotIn<string> } | { createdAt: Range<string> } | { modifiedAt: Range<string> } | { indexedAt: Range<string> } | { hidden: boolean }
+export type FilePathFilterArgs = { locations: InOrNotIn<number> } | { path: { location_id: number; path: string; include_descendants: boolean } } | { name: TextMatch } | { extension: InOrNotIn<string> } | { createdAt: Range<string> } | { modifiedAt: Range<string> } | { indexedAt: Range<string> } | { hidden: boolean } | 
+/**
+ * Applies to directories too, as their sizes are the sum of their contents
+ */
+{ sizeInBytes: Range<number> } | 
+/**
+ * Full-text search over the contents of text, code, config and document files
+ */
+{ content: string } | 
+/**
+ * Paths whose POSIX permission bits include all of the given ones, like `0o111` for
+ * files everyone can execute
+ */
+{ permissions: number } | 
+/**
+ * POSIX owner user ids
+ */
+{ owner: InOrNotIn<number> } | 
+/**
+ * POSIX owner group ids
+ */
+{ group: InOrNotIn<number> } | 
+/**
+ * Paths with an extended attribute of this name, among the ones in their location's allowlist
+ */
+{ xattr: string }
 
 export type FilePathObjectCursor = { dateAccessed: CursorOrderItem<string> } | { kind: CursorOrderItem<number> }
 
 export type FilePathOrder = { field: "name"; value: SortOrder } | { field: "sizeInBytes"; value: SortOrder } | { field: "dateCreated"; value: SortOrder } | { field: "dateModified"; value: SortOrder } | { field: "dateIndexed"; value: SortOrder } | { field: "object"; value: ObjectOrder }
 
-export type FilePathSearchArgs = { take?: number | null; orderAndPagination?: OrderAndPagination<number, FilePathOrder, FilePathCursor> | null; filters?: SearchFilterArgs[]; groupDirectories?: boolean }
+export type FilePathSearchArgs = { take?: number | null; orderAndPagination?: OrderAndPagination<number, FilePathOrder, FilePathCursor> | null; filters?: SearchFilterArgs[]; 
+/**
+ * A textual query, compiled into more filters and an order when none was given
+ */
+query?: string | null; groupDirectories?: boolean }
 
-export type FilePathWithObject = { id: number; pub_id: number[]; is_dir: boolean | null; cas_id: string | null; integrity_checksum: string | null; location_id: number | null; materialized_path: string | null; name: string | null; extension: string | null; hidden: boolean | null; size_in_bytes: string | null; size_in_bytes_bytes: number[] | null; inode: number[] | null; object_id: number | null; key_id: number | null; date_created: string | null; date_modified: string | null; date_indexed: string | null; object: { id: number; pub_id: number[]; kind: number | null; key_id: number | null; hidden: boolean | null; favorite: boolean | null; important: boolean | null; note: string | null; date_created: string | null; date_accessed: string | null } | null }
+export type FilePathWithObject = { id: number; pub_id: number[]; is_dir: boolean | null; cas_id: string | null; integrity_checksum: string | null; location_id: number | null; materialized_path: string | null; name: string | null; extension: string | null; hidden: boolean | null; size_in_bytes: string | null; size_in_bytes_bytes: number[] | null; size_in_bytes_number: number | null; inode: number[] | null; device: number[] | null; fingerprint: number[] | null; object_id: number | null; key_id: number | null; hard_link_of_id: number | null; mode: number | null; uid: number | null; gid: number | null; xattrs: string | null; date_created: string | null; date_modified: string | null; date_indexed: string | null; object: { id: number; pub_id: number[]; kind: number | null; key_id: number | null; hidden: boolean | null; favorite: boolean | null; important: boolean | null; note: string | null; date_created: string | null; date_accessed: string | null; size_in_bytes: number | null; perceptual_hash: number[] | null } | null }
 
 export type Flash = { 
 /**
@@ -312,7 +492,11 @@ export type FlashMode =
 
 export type FromPattern = { pattern: string; replace_all: boolean }
 
-export type FullRescanArgs = { location_id: number; reidentify_objects: boolean }
+export type FullRescanArgs = { location_id: number; reidentify_objects: boolean; 
+/**
+ * Walks every directory, even the ones that didn't change since the last scan
+ */
+force_full?: boolean }
 
 export type GenerateLabelsForLocationArgs = { id: number; path: string; regenerate?: boolean }
 
@@ -339,9 +523,55 @@ export type IndexerRule = { id: number; pub_id: number[]; name: string | null; d
  * 
  * In case of `RuleKind::AcceptIfChildrenDirectoriesArePresent` or `RuleKind::RejectIfChildrenDirectoriesArePresent` the
  * `parameters` field must be a vector of strings containing the names of the directories.
+ * 
+ * In case of `RuleKind::RejectIgnoredByGit` the `parameters` field can contain the names of the ignore files
+ * to honour, or be empty to honour `.gitignore` and `.ignore` files.
+ * 
+ * In case of `RuleKind::AcceptFilesByMetadata` or `RuleKind::RejectFilesByMetadata` the `parameters` field
+ * must be a vector of conditions like `size > 20GiB`, check [`MetadataCondition`] for the syntax.
  */
 export type IndexerRuleCreateArgs = { name: string; dry_run: boolean; rules: ([RuleKind, string[]])[] }
 
+/**
+ * `IndexerRuleSetImportArgs` is the argument received from the client using rspc to import an
+ * [`IndexerRuleSet`] from the contents of its file.
+ * 
+ * Rules are upserted by name, so importing a rule set again replaces the rules of the ones
+ * imported before, but system indexer rules can't be replaced. Imported rules are also attached
+ * to the locations in `location_ids`.
+ */
+export type IndexerRuleSetImportArgs = { contents: string; location_ids?: number[] }
+
+/**
+ * `IndexerRuleUpdateArgs` is the argument received from the client using rspc to update an indexer rule,
+ * where `rules` has the same format as in [`IndexerRuleCreateArgs`] and replaces all rules when present.
+ * 
+ * System indexer rules are seeded again on every library load, so they can't be updated.
+ */
+export type IndexerRuleUpdateArgs = { id: number; name: string | null; rules: ([RuleKind, string[]])[] | null }
+
+/**
+ * `IndexerRulesExplainArgs` is the argument received from the client using rspc to find out which
+ * indexer rules accept or reject each path, without indexing anything.
+ * 
+ * A location is walked with its own rules, unless `indexer_rules_ids` or `rules` are given, and
+ * `path` can be used to walk only a sub path of it. Without a location, `path` can be any directory.
+ * The rules in `rules` are in the same format as `IndexerRuleCreateArgs`, to preview them before
+ * creating them.
+ */
+export type IndexerRulesExplainArgs = { location_id?: number | null; path?: string | null; indexer_rules_ids?: number[]; rules?: ([RuleKind, string[]])[]; 
+/**
+ * How many levels of directories we walk into, where 1 only explains the entries of the
+ * walked directory itself. Unlimited when missing.
+ */
+max_depth?: number | null; 
+/**
+ * Skips accepted paths, to only find out why files are missing
+ */
+only_rejected?: boolean }
+
+export type IndexerRulesExplainItem = { decisions: RuleDecision[]; errors: Error[] }
+
 export type InvalidateOperationEvent = { type: "single"; data: SingleInvalidateOperationEvent } | { type: "all" }
 
 export type JobGroup = { id: string; action: string | null; status: JobStatus; created_at: string; jobs: JobReport[] }
@@ -401,7 +631,7 @@ export type LightScanArgs = { location_id: number; sub_path: string }
 
 export type Listener2 = { id: string; name: string; addrs: string[] }
 
-export type Location = { id: number; pub_id: number[]; name: string | null; path: string | null; total_capacity: number | null; available_capacity: number | null; size_in_bytes: number[] | null; is_archived: boolean | null; generate_preview_media: boolean | null; sync_preview_media: boolean | null; hidden: boolean | null; date_created: string | null; instance_id: number | null }
+export type Location = { id: number; pub_id: number[]; name: string | null; path: string | null; total_capacity: number | null; available_capacity: number | null; size_in_bytes: number[] | null; is_archived: boolean | null; generate_preview_media: boolean | null; sync_preview_media: boolean | null; hidden: boolean | null; symlink_policy: number | null; xattr_allowlist: string | null; watcher_mode: number | null; watcher_poll_interval: number | null; date_created: string | null; instance_id: number | null; volume_id: number | null }
 
 /**
  * `LocationCreateArgs` is the argument received from the client using `rspc` to create a new location.
@@ -410,8 +640,29 @@ export type Location = { id: number; pub_id: number[]; name: string | null; path
  */
 export type LocationCreateArgs = { path: string; dry_run: boolean; indexer_rules_ids: number[] }
 
+export type LocationRelinkerJobInit = { 
+/**
+ * Directories to search for the locations of the library
+ */
+roots: string[]; 
+/**
+ * How many directories deep to search under each root
+ */
+max_depth?: number | null; 
+/**
+ * Relinks the offline locations found in a single place, instead of only proposing it
+ */
+relink: boolean }
+
 export type LocationSettings = { explorer: ExplorerSettings<FilePathOrder> }
 
+export type LocationState = { online: boolean; 
+/**
+ * Watcher events received but not fully handled yet, which are replayed when the
+ * location is watched again if the node stops before handling them
+ */
+watcher_backlog: number }
+
 /**
  * `LocationUpdateArgs` is the argument received from the client using `rspc` to update a location.
  * It contains the id of the location to be updated, possible a name to change the current location's name
@@ -420,14 +671,40 @@ export type LocationSettings = { explorer: ExplorerSettings<FilePathOrder> }
  * It is important to note that only the indexer rule ids in this vector will be used from now on.
  * Old rules that aren't in this vector will be purged.
  */
-export type LocationUpdateArgs = { id: number; name: string | null; generate_preview_media: boolean | null; sync_preview_media: boolean | null; hidden: boolean | null; indexer_rules_ids: number[]; path: string | null }
+export type LocationUpdateArgs = { id: number; name: string | null; generate_preview_media: boolean | null; sync_preview_media: boolean | null; hidden: boolean | null; indexer_rules_ids: number[]; path: string | null; 
+/**
+ * Only applied to new paths found from now on, a rescan applies it to the whole location
+ */
+symlink_policy?: SymlinkPolicy | null; 
+/**
+ * Names of the extended attributes to keep for each path, where a trailing `*` matches any
+ * name starting with what comes before it. Like the symlink policy, a rescan is needed to
+ * apply it to the paths already indexed
+ */
+xattr_allowlist?: string[] | null; watcher_mode?: WatcherMode | null; 
+/**
+ * Seconds between the scans of the location when it's polled, at least 5
+ */
+watcher_poll_interval?: number | null }
 
-export type LocationWithIndexerRule = { id: number; pub_id: number[]; name: string | null; path: string | null; total_capacity: number | null; available_capacity: number | null; size_in_bytes: number[] | null; is_archived: boolean | null; generate_preview_media: boolean | null; sync_preview_media: boolean | null; hidden: boolean | null; date_created: string | null; instance_id: number | null; indexer_rules: Reference<IndexerRule>[] }
+export type LocationWithIndexerRule = { id: number; pub_id: number[]; name: string | null; path: string | null; total_capacity: number | null; available_capacity: number | null; size_in_bytes: number[] | null; is_archived: boolean | null; generate_preview_media: boolean | null; sync_preview_media: boolean | null; hidden: boolean | null; symlink_policy: number | null; xattr_allowlist: string | null; watcher_mode: number | null; watcher_poll_interval: number | null; date_created: string | null; instance_id: number | null; volume_id: number | null; indexer_rules: Reference<IndexerRule>[] }
 
 export type MaybeUndefined<T> = null | T
 
+export type MediaDataFilterArgs = { captureDate: Range<string> } | { width: Range<number> } | { height: Range<number> } | { cameraMake: TextMatch } | { cameraModel: TextMatch } | { artist: TextMatch } | { copyright: TextMatch } | { boundingBox: BoundingBox } | { radius: { latitude: number; longitude: number; 
+/**
+ * In meters
+ */
+radius: number } }
+
 export type MediaDataOrder = { field: "epochTime"; value: SortOrder }
 
+export type MediaLocationCluster = { latitude: number; longitude: number; count: number; 
+/**
+ * Any object in this cluster, so the map has something to preview
+ */
+objectId: number }
+
 /**
  * This can be either naive with no TZ (`YYYY-MM-DD HH-MM-SS`) or UTC (`YYYY-MM-DD HH-MM-SS ±HHMM`),
  * where `±HHMM` is the timezone data. It may be negative if West of the Prime Meridian, or positive if East.
@@ -481,23 +758,27 @@ export type NotificationId = { type: "library"; id: [string, number] } | { type:
 
 export type NotificationKind = "info" | "success" | "error" | "warning"
 
-export type Object = { id: number; pub_id: number[]; kind: number | null; key_id: number | null; hidden: boolean | null; favorite: boolean | null; important: boolean | null; note: string | null; date_created: string | null; date_accessed: string | null }
+export type Object = { id: number; pub_id: number[]; kind: number | null; key_id: number | null; hidden: boolean | null; favorite: boolean | null; important: boolean | null; note: string | null; date_created: string | null; date_accessed: string | null; size_in_bytes: number | null; perceptual_hash: number[] | null }
 
-export type ObjectCursor = "none" | { dateAccessed: CursorOrderItem<string> } | { kind: CursorOrderItem<number> }
+export type ObjectCursor = "none" | { dateAccessed: CursorOrderItem<string> } | { kind: CursorOrderItem<number> } | { sizeInBytes: CursorOrderItem<number> }
 
-export type ObjectFilterArgs = { favorite: boolean } | { hidden: ObjectHiddenFilter } | { kind: InOrNotIn<number> } | { tags: InOrNotIn<number> } | { labels: InOrNotIn<number> } | { dateAccessed: Range<string> }
+export type ObjectFilterArgs = { favorite: boolean } | { hidden: ObjectHiddenFilter } | { kind: InOrNotIn<number> } | { tags: InOrNotIn<number> } | { labels: InOrNotIn<number> } | { dateAccessed: Range<string> } | { sizeInBytes: Range<number> } | { mediaData: MediaDataFilterArgs }
 
 export type ObjectHiddenFilter = "exclude" | "include"
 
-export type ObjectOrder = { field: "dateAccessed"; value: SortOrder } | { field: "kind"; value: SortOrder } | { field: "mediaData"; value: MediaDataOrder }
+export type ObjectOrder = { field: "dateAccessed"; value: SortOrder } | { field: "kind"; value: SortOrder } | { field: "sizeInBytes"; value: SortOrder } | { field: "mediaData"; value: MediaDataOrder }
 
-export type ObjectSearchArgs = { take: number; orderAndPagination?: OrderAndPagination<number, ObjectOrder, ObjectCursor> | null; filters?: SearchFilterArgs[] }
+export type ObjectSearchArgs = { take: number; orderAndPagination?: OrderAndPagination<number, ObjectOrder, ObjectCursor> | null; filters?: SearchFilterArgs[]; 
+/**
+ * A textual query, compiled into more filters and an order when none was given
+ */
+query?: string | null }
 
 export type ObjectValidatorArgs = { id: number; path: string }
 
-export type ObjectWithFilePaths = { id: number; pub_id: number[]; kind: number | null; key_id: number | null; hidden: boolean | null; favorite: boolean | null; important: boolean | null; note: string | null; date_created: string | null; date_accessed: string | null; file_paths: FilePath[] }
+export type ObjectWithFilePaths = { id: number; pub_id: number[]; kind: number | null; key_id: number | null; hidden: boolean | null; favorite: boolean | null; important: boolean | null; note: string | null; date_created: string | null; date_accessed: string | null; size_in_bytes: number | null; perceptual_hash: number[] | null; file_paths: FilePath[] }
 
-export type ObjectWithFilePaths2 = { id: number; pub_id: number[]; kind: number | null; key_id: number | null; hidden: boolean | null; favorite: boolean | null; important: boolean | null; note: string | null; date_created: string | null; date_accessed: string | null; file_paths: Reference<FilePath>[] }
+export type ObjectWithFilePaths2 = { id: number; pub_id: number[]; kind: number | null; key_id: number | null; hidden: boolean | null; favorite: boolean | null; important: boolean | null; note: string | null; date_created: string | null; date_accessed: string | null; size_in_bytes: number | null; perceptual_hash: number[] | null; file_paths: Reference<FilePath>[] }
 
 /**
  * Represents the operating system which the remote peer is running.
@@ -516,12 +797,18 @@ export type P2PDiscoveryState = "Everyone" | "ContactsOnly" | "Disabled"
  */
 export type P2PEvent = { type: "DiscoveredPeer"; identity: RemoteIdentity; metadata: PeerMetadata } | { type: "ExpiredPeer"; identity: RemoteIdentity } | { type: "ConnectedPeer"; identity: RemoteIdentity } | { type: "DisconnectedPeer"; identity: RemoteIdentity } | { type: "SpacedropRequest"; id: string; identity: RemoteIdentity; peer_name: string; files: string[] } | { type: "SpacedropProgress"; id: string; percent: number } | { type: "SpacedropTimedout"; id: string } | { type: "SpacedropRejected"; id: string }
 
+export type ParseQueryResult = { type: "ok"; value: CompiledQuery } | { type: "err"; value: QueryError[] }
+
 export type PeerMetadata = { name: string; operating_system: OperatingSystem | null; device_model: HardwareModel | null; version: string | null }
 
 export type PlusCode = string
 
 export type Port = null | number
 
+export type QueryError = { kind: QueryErrorKind; span: Span }
+
+export type QueryErrorKind = { type: "unterminatedQuote" } | { type: "emptyValue" } | { type: "unknownKey"; value: string } | { type: "unknownKind"; value: string } | { type: "invalidDate"; value: string } | { type: "invalidBool"; value: string } | { type: "invalidSize"; value: string } | { type: "invalidPermissions"; value: string } | { type: "invalidId"; value: string } | { type: "unknownSortField"; value: string } | { type: "duplicateSort" } | { type: "negationNotSupported"; value: string } | { type: "unknownTag"; value: string } | { type: "unknownLabel"; value: string }
+
 export type Range<T> = { from: T } | { to: T }
 
 /**
@@ -550,11 +837,48 @@ export type Resolution = { width: number; height: number }
 
 export type Response = { Start: { user_code: string; verification_url: string; verification_url_complete: string } } | "Complete" | { Error: string }
 
-export type RuleKind = "AcceptFilesByGlob" | "RejectFilesByGlob" | "AcceptIfChildrenDirectoriesArePresent" | "RejectIfChildrenDirectoriesArePresent"
+export type ResponsibleRule = { 
+/**
+ * Missing for rules that weren't created yet
+ */
+id: number | null; name: string; kind: RuleKind }
 
-export type SavedSearch = { id: number; pub_id: number[]; search: string | null; filters: string | null; name: string | null; icon: string | null; description: string | null; date_created: string | null; date_modified: string | null }
+export type RuleDecision = { path: string; is_dir: boolean; accepted: boolean; 
+/**
+ * The rule that rejected this path, or that accepted it by its children directories.
+ * Rejected paths without a rule are symlinks, which are never indexed.
+ * 
+ * Directories rejected by accept rules are still walked, and are indexed anyway if any
+ * of their contents are accepted.
+ */
+rule: ResponsibleRule | null }
+
+export type RuleKind = "AcceptFilesByGlob" | "RejectFilesByGlob" | "AcceptIfChildrenDirectoriesArePresent" | "RejectIfChildrenDirectoriesArePresent" | "RejectIgnoredByGit" | "AcceptFilesByMetadata" | "RejectFilesByMetadata"
 
-export type SearchData<T> = { cursor: number[] | null; items: Reference<T>[]; nodes: CacheNode[] }
+export type SavedSearch = { id: number; pub_id: number[]; search: string | null; filters: string | null; query: string | null; name: string | null; icon: string | null; description: string | null; date_created: string | null; date_modified: string | null }
+
+/**
+ * Changes to the file paths matching a saved search, so it can be shown as a live folder
+ */
+export type SavedSearchDiff = { 
+/**
+ * File paths that started matching the search
+ */
+added: Reference<ExplorerItem>[]; 
+/**
+ * Ids of the file paths that stopped matching the search
+ */
+removed: number[]; nodes: CacheNode[]; 
+/**
+ * How many file paths match the search after this diff
+ */
+count: number }
+
+export type SearchData<T> = { cursor: number[] | null; items: Reference<T>[]; nodes: CacheNode[]; 
+/**
+ * Highlighted excerpts of the matched contents by item id, when searching with a content filter
+ */
+content_snippets: { [key in number]: string } }
 
 export type SearchFilterArgs = { filePath: FilePathFilterArgs } | { object: ObjectFilterArgs }
 
@@ -562,6 +886,16 @@ export type SetFavoriteArgs = { id: number; favorite: boolean }
 
 export type SetNoteArgs = { id: number; note: string | null }
 
+export type SimilarImages = { 
+/**
+ * The most similar first
+ */
+items: Reference<ExplorerItem>[]; nodes: CacheNode[]; 
+/**
+ * Hamming distance to the perceptual hash of the searched image by object id
+ */
+distances: { [key in number]: number } }
+
 export type SingleInvalidateOperationEvent = { 
 /**
  * This fields are intentionally private.
@@ -570,12 +904,23 @@ key: string; arg: JsonValue; result: JsonValue | null }
 
 export type SortOrder = "Asc" | "Desc"
 
+/**
+ * Positions are counted in chars instead of bytes, so the frontend can use them to highlight the query
+ */
+export type Span = { start: number; end: number }
+
 export type SpacedropArgs = { identity: RemoteIdentity; file_path: string[] }
 
 export type Statistics = { id: number; date_captured: string; total_object_count: number; library_db_size: string; total_bytes_used: string; total_bytes_capacity: string; total_unique_bytes: string; total_bytes_free: string; preview_media_bytes: string }
 
 export type StatisticsResponse = { statistics: Statistics | null }
 
+/**
+ * How symbolic links found inside a location are handled, stored in the `symlink_policy` column
+ * of the `location` table, and honoured by the indexer, the watcher and non indexed walks alike.
+ */
+export type SymlinkPolicy = "Ignore" | "IndexLink" | "Follow"
+
 export type SystemLocations = { desktop: string | null; documents: string | null; downloads: string | null; pictures: string | null; music: string | null; videos: string | null }
 
 export type Tag = { id: number; pub_id: number[]; name: string | null; color: string | null; is_hidden: boolean | null; date_created: string | null; date_modified: string | null }
@@ -596,4 +941,23 @@ export type UpdateThumbnailerPreferences = { background_processing_percentage: n
 
 export type VideoMetadata = { duration: number | null; video_codec: string | null; audio_codec: string | null }
 
-export type Volume = { name: string; mount_points: string[]; total_capacity: string; available_capacity: string; disk_type: DiskType; file_system: string | null; is_root_filesystem: boolean }
+export type Volume = { name: string; mount_points: string[]; total_capacity: string; available_capacity: string; disk_type: DiskType; file_system: string | null; is_root_filesystem: boolean; 
+/**
+ * The UUID of the file system, when the OS gives us one
+ */
+uuid: string | null; 
+/**
+ * The label of the file system, when it has one
+ */
+label: string | null; 
+/**
+ * The serial number of the device on Linux, or of the volume on Windows, for file systems
+ * without a UUID
+ */
+serial: string | null }
+
+/**
+ * How a location is watched for changes, stored in the `watcher_mode` column of the `location`
+ * table, along with the interval between scans in `watcher_poll_interval` when polling.
+ */
+export type WatcherMode = "Auto" | "Native" | "Polling"
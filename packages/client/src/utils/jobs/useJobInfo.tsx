@@ -205,6 +205,23 @@ export function useJobInfo(job: JobReport, realtimeUpdate: JobProgressEvent | nu
 				} ${plural(completedTaskCount, 'object')}`,
 				textItems: [[{ text: job.status }]]
 			};
+		case 'location_relinker':
+			return {
+				...data,
+				name: `${isQueued ? 'Search' : isRunning ? 'Searching' : 'Searched'} for moved locations`,
+				textItems: [
+					[
+						{
+							text: isRunning
+								? realtimeUpdate?.message
+								: `${formatNumber(output?.searched_directories)} ${plural(
+										output?.searched_directories,
+										'folder'
+								  )} searched`
+						}
+					]
+				]
+			};
 		default:
 			return {
 				...data,